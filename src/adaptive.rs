@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::stats::LoadTestStats;
+
+// how often the level is looked at, each time over the responses since
+const ADJUST_EVERY: Duration = Duration::from_secs(1);
+// fewer responses than this in an interval say too little about its p95,
+// the level is held
+const MIN_RESPONSES: u64 = 10;
+// the share of the workers kept when the p95 goes over the target
+const DECREASE: f64 = 0.75;
+
+// --adaptive --target-p95: the workers sending grow by one every interval
+// the p95 stays within the target and shrink by a quarter every interval it
+// doesn't, the level they settle around being what the target can carry
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Adaptive {
+    #[serde(rename = "target_p95_secs", serialize_with = "crate::units::serialize_secs")]
+    pub target_p95: Duration,
+    // the level it starts at, --concurrency
+    pub start: usize,
+    pub min: usize,
+    pub max: usize,
+}
+
+// one interval of the run
+#[derive(Debug, Clone, Serialize)]
+pub struct Level {
+    pub second: u64,
+    // the workers sending during it
+    pub concurrency: usize,
+    pub requests_per_second: f64,
+    // none with too few responses to tell
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p95_ms: Option<f64>,
+}
+
+// the level the workers go by and how it moved
+#[derive(Debug, Default)]
+pub struct Levels {
+    adaptive: OnceLock<Adaptive>,
+    current: AtomicUsize,
+    history: Mutex<Vec<Level>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdaptiveSummary {
+    pub target_p95_ms: f64,
+    pub final_concurrency: usize,
+    pub mean_concurrency: f64,
+    pub peak_concurrency: usize,
+    // the busiest interval that held the target, what the run measured the
+    // capacity at that latency to be; none if no interval did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capacity_rps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capacity_concurrency: Option<usize>,
+    pub levels: Vec<Level>,
+}
+
+impl Levels {
+    // the workers with an id below it send
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    fn adjust(&self, adaptive: &Adaptive, second: u64, requests: u64, p95: Option<Duration>) {
+        let level = self.current();
+        let next = match p95 {
+            Some(p95) if p95 <= adaptive.target_p95 => level + 1,
+            Some(_) => (level as f64 * DECREASE).floor() as usize,
+            None => level,
+        };
+        self.current.store(next.clamp(adaptive.min, adaptive.max), Ordering::SeqCst);
+        self.history.lock().unwrap().push(Level {
+            second,
+            concurrency: level,
+            requests_per_second: requests as f64 / ADJUST_EVERY.as_secs_f64(),
+            p95_ms: p95.map(|p95| p95.as_secs_f64() * 1000.0),
+        });
+    }
+
+    // none without --adaptive
+    pub fn summary(&self) -> Option<AdaptiveSummary> {
+        let adaptive = self.adaptive.get()?;
+        let levels = self.history.lock().unwrap().clone();
+        let target_ms = adaptive.target_p95.as_secs_f64() * 1000.0;
+        let capacity = levels
+            .iter()
+            .filter(|level| level.p95_ms.is_some_and(|p95| p95 <= target_ms))
+            .max_by(|a, b| a.requests_per_second.total_cmp(&b.requests_per_second));
+        Some(AdaptiveSummary {
+            target_p95_ms: target_ms,
+            final_concurrency: self.current(),
+            mean_concurrency: match levels.len() {
+                0 => self.current() as f64,
+                n => levels.iter().map(|level| level.concurrency as f64).sum::<f64>() / n as f64,
+            },
+            peak_concurrency: levels.iter().map(|level| level.concurrency).max().unwrap_or(self.current()),
+            capacity_rps: capacity.map(|level| level.requests_per_second),
+            capacity_concurrency: capacity.map(|level| level.concurrency),
+            levels,
+        })
+    }
+}
+
+// moves stats.concurrency until finished
+pub struct Controller {
+    task: JoinHandle<()>,
+}
+
+impl Controller {
+    pub fn start(adaptive: Adaptive, stats: Arc<LoadTestStats>) -> Self {
+        let levels = &stats.concurrency;
+        levels.current.store(adaptive.start.clamp(adaptive.min, adaptive.max), Ordering::SeqCst);
+        let _ = levels.adaptive.set(adaptive);
+        let task = tokio::spawn(async move {
+            let start = tokio::time::Instant::now();
+            let mut interval = tokio::time::interval_at(start + ADJUST_EVERY, ADJUST_EVERY);
+            let mut earlier = stats.latency.snapshot();
+            loop {
+                interval.tick().await;
+                let window = stats.latency.since(&earlier);
+                earlier = stats.latency.snapshot();
+                let p95 = (window.count() >= MIN_RESPONSES).then(|| Duration::from_micros(window.quantile(0.95)));
+                stats.concurrency.adjust(&adaptive, start.elapsed().saturating_sub(ADJUST_EVERY).as_secs(), window.count(), p95);
+            }
+        });
+        Controller { task }
+    }
+
+    pub fn finish(self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for Controller {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,234 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Sha512};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::placeholder::Pattern;
+use crate::sigv4::SigV4;
+
+// refresh tokens this long before they expire
+const REFRESH_MARGIN: Duration = Duration::from_secs(30);
+const DEFAULT_EXPIRY: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone)]
+pub enum Auth {
+    Bearer(String),
+    Basic(String),
+    OAuth2(OAuthConfig),
+    SigV4(SigV4),
+}
+
+impl Auth {
+    // "user:pass", a missing password is sent as empty
+    pub fn basic(credentials: &str) -> Self {
+        Auth::Basic(STANDARD.encode(credentials))
+    }
+
+    // authorization header value for static credentials
+    pub fn header_value(&self) -> Option<String> {
+        match self {
+            Auth::Bearer(token) => Some(format!("Bearer {}", token)),
+            Auth::Basic(encoded) => Some(format!("Basic {}", encoded)),
+            Auth::OAuth2(_) | Auth::SigV4(_) => None,
+        }
+    }
+}
+
+// never echo credentials into reports
+impl Serialize for Auth {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Auth::Bearer(_) => "bearer",
+            Auth::Basic(_) => "basic",
+            Auth::OAuth2(_) => "oauth2-client-credentials",
+            Auth::SigV4(_) => "aws-sigv4",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OAuthConfig {
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug)]
+struct Token {
+    value: String,
+    refresh_at: Instant,
+}
+
+// client-credentials tokens, fetched on first use and refreshed shortly
+// before they expire; the lock makes concurrent workers share one refresh
+#[derive(Debug)]
+pub struct TokenProvider {
+    config: OAuthConfig,
+    client: reqwest::Client,
+    token: Mutex<Option<Token>>,
+}
+
+impl TokenProvider {
+    pub fn new(config: OAuthConfig, client: reqwest::Client) -> Self {
+        TokenProvider {
+            config,
+            client,
+            token: Mutex::new(None),
+        }
+    }
+
+    pub async fn token(&self) -> Result<String, reqwest::Error> {
+        let mut token = self.token.lock().await;
+        if let Some(current) = token.as_ref() {
+            if Instant::now() < current.refresh_at {
+                return Ok(current.value.clone());
+            }
+        }
+
+        let fresh = self.fetch().await?;
+        let value = fresh.value.clone();
+        *token = Some(fresh);
+        Ok(value)
+    }
+
+    async fn fetch(&self) -> Result<Token, reqwest::Error> {
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", self.config.client_id.as_str()),
+            ("client_secret", self.config.client_secret.as_str()),
+        ];
+        if let Some(scope) = &self.config.scope {
+            form.push(("scope", scope));
+        }
+
+        let response: TokenResponse = self
+            .client
+            .post(&self.config.token_url)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let expires_in = response.expires_in.map(Duration::from_secs).unwrap_or(DEFAULT_EXPIRY);
+        Ok(Token {
+            value: response.access_token,
+            refresh_at: Instant::now() + expires_in.saturating_sub(REFRESH_MARGIN),
+        })
+    }
+}
+
+// a raw request header, used for api keys and --header
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+impl Header {
+    // "Name:value"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, value) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("expected Name:value, got: {}", spec))?;
+        Ok(Header {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+// names only, values may well be secrets
+impl Serialize for Header {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+// a header whose value has {{...}} placeholders, filled in per request
+#[derive(Debug, Clone)]
+pub struct HeaderTemplate {
+    pub name: HeaderName,
+    pub pattern: Pattern,
+}
+
+impl HeaderTemplate {
+    pub fn parse(header: &Header) -> Result<Self, String> {
+        Ok(HeaderTemplate {
+            name: HeaderName::try_from(&header.name).map_err(|_| format!("invalid header name: {}", header.name))?,
+            pattern: Pattern::parse(&header.value).map_err(|e| format!("header {}: {}", header.name, e))?,
+        })
+    }
+}
+
+impl Serialize for HeaderTemplate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignAlgorithm {
+    HmacSha256,
+    HmacSha512,
+}
+
+// --sign algorithm:secret:Header, an hmac of the body as sent, hex encoded
+#[derive(Debug, Clone)]
+pub struct BodySignature {
+    pub algorithm: SignAlgorithm,
+    secret: String,
+    pub header: HeaderName,
+}
+
+impl BodySignature {
+    // the secret may hold colons itself, the header name can't
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let usage = || format!("--sign {}: expected hmac-sha256:SECRET:Header", spec);
+        let (algorithm, rest) = spec.split_once(':').ok_or_else(usage)?;
+        let (secret, header) = rest.rsplit_once(':').ok_or_else(usage)?;
+        let algorithm = match algorithm {
+            "hmac-sha256" => SignAlgorithm::HmacSha256,
+            "hmac-sha512" => SignAlgorithm::HmacSha512,
+            other => return Err(format!("--sign: unknown algorithm {}, expected hmac-sha256 or hmac-sha512", other)),
+        };
+        let header = HeaderName::try_from(header.trim()).map_err(|_| format!("--sign: invalid header name: {}", header))?;
+        Ok(BodySignature { algorithm, secret: secret.to_string(), header })
+    }
+
+    pub fn value(&self, body: &[u8]) -> HeaderValue {
+        let key = self.secret.as_bytes();
+        let digest = match self.algorithm {
+            SignAlgorithm::HmacSha256 => Hmac::<Sha256>::new_from_slice(key)
+                .map(|mac| mac.chain_update(body).finalize().into_bytes().to_vec()),
+            SignAlgorithm::HmacSha512 => Hmac::<Sha512>::new_from_slice(key)
+                .map(|mac| mac.chain_update(body).finalize().into_bytes().to_vec()),
+        }
+        .expect("hmac takes any key length");
+        let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        HeaderValue::from_str(&hex).expect("hex is a valid header value")
+    }
+}
+
+// the algorithm and header, never the secret
+impl Serialize for BodySignature {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let algorithm = match self.algorithm {
+            SignAlgorithm::HmacSha256 => "hmac-sha256",
+            SignAlgorithm::HmacSha512 => "hmac-sha512",
+        };
+        serializer.serialize_str(&format!("{}:{}", algorithm, self.header))
+    }
+}
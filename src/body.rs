@@ -0,0 +1,387 @@
+use flate2::write::{GzEncoder, ZlibEncoder};
+use futures_util::{stream, StreamExt};
+use prost::Message;
+use prost_reflect::{DynamicMessage, MessageDescriptor};
+use rand::{Rng, RngCore};
+use reqwest::header::CONTENT_LENGTH;
+use reqwest::multipart::{Form, Part};
+use reqwest::{Body, Request};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::units::{parse_size, serialize_secs};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyFormat {
+    #[default]
+    Json,
+    Form,
+    Multipart,
+    Xml,
+    // set by --message, see Protobuf
+    Protobuf,
+    // set by --body-bytes, see RandomBytes
+    Bytes,
+}
+
+impl FromStr for BodyFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(BodyFormat::Json),
+            "form" => Ok(BodyFormat::Form),
+            "multipart" => Ok(BodyFormat::Multipart),
+            "xml" => Ok(BodyFormat::Xml),
+            other => Err(format!("unknown body format: {}", other)),
+        }
+    }
+}
+
+// --compress: the content-encoding json bodies are sent with; deflate is
+// the zlib format http means by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Gzip,
+    Deflate,
+}
+
+impl Compression {
+    pub fn encode(&self, bytes: &[u8]) -> Vec<u8> {
+        let level = flate2::Compression::default();
+        // writing into a vec can't fail
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), level);
+                let _ = encoder.write_all(bytes);
+                encoder.finish().unwrap_or_default()
+            }
+            Compression::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), level);
+                let _ = encoder.write_all(bytes);
+                encoder.finish().unwrap_or_default()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Compression::Gzip => "gzip",
+            Compression::Deflate => "deflate",
+        })
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(Compression::Gzip),
+            "deflate" => Ok(Compression::Deflate),
+            other => Err(format!("unknown compression: {}", other)),
+        }
+    }
+}
+
+// --message: bodies sent as this protobuf message, the generated object
+// read as its canonical json mapping
+#[derive(Debug, Clone, Serialize)]
+pub struct Protobuf {
+    pub message: String,
+    #[serde(skip)]
+    descriptor: MessageDescriptor,
+}
+
+impl Protobuf {
+    pub fn load(message: &str, protos: &[String], includes: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let pool = crate::grpc::descriptor_pool(protos, includes)?;
+        let descriptor = pool
+            .get_message_by_name(message)
+            .ok_or_else(|| format!("--message {}: no such message in the --proto files", message))?;
+        Ok(Protobuf { message: message.to_string(), descriptor })
+    }
+
+    pub fn encode(&self, payload: &Value) -> Result<Vec<u8>, String> {
+        DynamicMessage::deserialize(self.descriptor.clone(), payload)
+            .map(|message| message.encode_to_vec())
+            .map_err(|e| format!("payload doesn't fit {}: {}", self.message, e))
+    }
+}
+
+// --body-format xml: the generated object as the children of a `root`
+// element, keys starting with `attribute_prefix` as attributes of their
+// element and "#text" as its text; arrays repeat their element
+#[derive(Debug, Clone, Serialize)]
+pub struct Xml {
+    pub root: String,
+    pub attribute_prefix: String,
+}
+
+impl Xml {
+    pub fn render(&self, value: &Value) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+        match value {
+            // the items of a bare array need a name of their own
+            Value::Array(items) => {
+                let _ = write!(out, "<{}>", self.root);
+                for item in items {
+                    self.element(&mut out, "item", item);
+                }
+                let _ = write!(out, "</{}>", self.root);
+            }
+            other => self.element(&mut out, &self.root, other),
+        }
+        out
+    }
+
+    fn element(&self, out: &mut String, name: &str, value: &Value) {
+        let name = xml_name(name);
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    self.element(out, &name, item);
+                }
+            }
+            Value::Object(map) => {
+                let _ = write!(out, "<{}", name);
+                for (key, value) in map {
+                    if let Some(attribute) = key.strip_prefix(self.attribute_prefix.as_str()).filter(|_| !self.attribute_prefix.is_empty()) {
+                        let _ = write!(out, " {}=\"{}\"", xml_name(attribute), escape_xml(&xml_text(value)));
+                    }
+                }
+                let children: Vec<_> = map
+                    .iter()
+                    .filter(|(key, _)| self.attribute_prefix.is_empty() || !key.starts_with(self.attribute_prefix.as_str()))
+                    .collect();
+                if children.is_empty() {
+                    out.push_str("/>");
+                    return;
+                }
+                out.push('>');
+                for (key, value) in children {
+                    match key.as_str() {
+                        "#text" => out.push_str(&escape_xml(&xml_text(value))),
+                        _ => self.element(out, key, value),
+                    }
+                }
+                let _ = write!(out, "</{}>", name);
+            }
+            Value::Null => {
+                let _ = write!(out, "<{}/>", name);
+            }
+            scalar => {
+                let _ = write!(out, "<{}>{}</{}>", name, escape_xml(&xml_text(scalar)), name);
+            }
+        }
+    }
+}
+
+// json keys can be anything, element and attribute names can't
+fn xml_name(key: &str) -> String {
+    let mut name: String = key
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':') { c } else { '_' })
+        .collect();
+    if !name.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+fn xml_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// --body-bytes: bodies of random bytes instead of a generated object, each
+// between `min` and `max` long, for upload and object-storage endpoints
+#[derive(Debug, Clone, Serialize)]
+pub struct RandomBytes {
+    pub min: usize,
+    pub max: usize,
+    pub content_type: String,
+}
+
+impl RandomBytes {
+    // "1MB" for a fixed size or "1MB..10MB" for one drawn per request
+    pub fn parse(spec: &str, content_type: String) -> Result<Self, String> {
+        let (min, max) = match spec.split_once("..") {
+            Some((min, max)) => (parse_size(min)?, parse_size(max)?),
+            None => {
+                let size = parse_size(spec)?;
+                (size, size)
+            }
+        };
+        if min > max {
+            return Err(format!("--body-bytes {}: the smallest size comes first", spec));
+        }
+        Ok(RandomBytes { min, max, content_type })
+    }
+
+    pub fn size(&self, rng: &mut impl Rng) -> usize {
+        rng.gen_range(self.min..=self.max)
+    }
+
+    pub fn generate(&self, rng: &mut impl RngCore) -> Vec<u8> {
+        let mut bytes = vec![0u8; self.size(rng)];
+        rng.fill_bytes(&mut bytes);
+        bytes
+    }
+}
+
+impl fmt::Display for RandomBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.min == self.max {
+            true => write!(f, "{} random bytes", self.min),
+            false => write!(f, "{} to {} random bytes", self.min, self.max),
+        }
+    }
+}
+
+// the random-bytes part of multipart bodies
+#[derive(Debug, Clone, Serialize)]
+pub struct FilePart {
+    pub field: String,
+    pub size: usize,
+}
+
+// --body-size: the top-level string field that grows until a generated
+// body serializes to about `size` bytes
+#[derive(Debug, Clone, Serialize)]
+pub struct Padding {
+    pub field: String,
+    pub size: usize,
+}
+
+impl Padding {
+    // sized as json, form and multipart bodies come out close to it; a
+    // string already in the field is kept and extended, bodies that are
+    // already big enough stay as they are
+    pub fn pad(&self, value: &mut Value) -> Result<(), String> {
+        let Value::Object(map) = value else {
+            return Err("--body-size needs the template to make a json object".into());
+        };
+        let mut text = match map.remove(&self.field) {
+            Some(Value::String(s)) => s,
+            _ => String::new(),
+        };
+        map.insert(self.field.clone(), Value::String(String::new()));
+        let len = serde_json::to_vec(&*map).map(|bytes| bytes.len()).unwrap_or(0) + text.len();
+        if len < self.size {
+            text.push_str(&"x".repeat(self.size - len));
+        }
+        map.insert(self.field.clone(), Value::String(text));
+        Ok(())
+    }
+}
+
+// --stream-body: the body as it would be sent, but chunked, `chunk_size`
+// bytes at a time with `chunk_delay` before every chunk after the first,
+// the way a slow client uploads
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamBody {
+    pub chunk_size: usize,
+    #[serde(rename = "chunk_delay_secs", serialize_with = "serialize_secs")]
+    pub chunk_delay: Duration,
+}
+
+impl StreamBody {
+    // a body that's already a stream, multipart or an aborted one, is left
+    // as it is
+    pub fn apply(&self, request: &mut Request) {
+        let Some(bytes) = request.body().and_then(|body| body.as_bytes()) else { return };
+        let chunks: Vec<Vec<u8>> = bytes.chunks(self.chunk_size).map(<[u8]>::to_vec).collect();
+        let delay = self.chunk_delay;
+        let stream = stream::iter(chunks.into_iter().enumerate()).then(move |(index, chunk)| async move {
+            if index > 0 {
+                tokio::time::sleep(delay).await;
+            }
+            Ok::<_, io::Error>(chunk)
+        });
+        *request.body_mut() = Some(Body::wrap_stream(stream));
+        // sent with transfer-encoding: chunked instead
+        request.headers_mut().remove(CONTENT_LENGTH);
+    }
+}
+
+impl fmt::Display for StreamBody {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "chunks of {} bytes every {:?}", self.chunk_size, self.chunk_delay)
+    }
+}
+
+// form fields for a generated object, nested keys in the usual bracket
+// style: {"user": {"tags": ["a"]}} becomes user[tags][]=a
+pub fn flatten(value: &Value) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten_into(key.clone(), value, &mut fields);
+            }
+        }
+        other => flatten_into("value".to_string(), other, &mut fields),
+    }
+    fields
+}
+
+fn flatten_into(key: String, value: &Value, fields: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            for (child, value) in map {
+                flatten_into(format!("{}[{}]", key, child), value, fields);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                flatten_into(format!("{}[]", key), item, fields);
+            }
+        }
+        Value::String(s) => fields.push((key, s.clone())),
+        Value::Null => fields.push((key, String::new())),
+        other => fields.push((key, other.to_string())),
+    }
+}
+
+pub fn multipart(value: &Value, file: Option<(&FilePart, &[u8])>) -> Form {
+    let mut form = Form::new();
+    for (key, value) in flatten(value) {
+        form = form.text(key, value);
+    }
+    if let Some((part, bytes)) = file {
+        let bytes = Part::bytes(bytes.to_vec())
+            .file_name("random.bin")
+            .mime_str("application/octet-stream")
+            .expect("static mime type");
+        form = form.part(part.field.clone(), bytes);
+    }
+    form
+}
@@ -0,0 +1,51 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// a handful of early failures shouldn't end the run
+const MIN_REQUESTS: u64 = 20;
+
+// trips once the error rate over the last `window` goes above `threshold`
+#[derive(Debug)]
+pub struct Breaker {
+    threshold: f64,
+    window: Duration,
+    start: Instant,
+    // (second since start, requests, errors), oldest first
+    buckets: Mutex<VecDeque<(u64, u64, u64)>>,
+}
+
+impl Breaker {
+    pub fn new(threshold: f64, window: Duration) -> Self {
+        Breaker {
+            threshold,
+            window,
+            start: Instant::now(),
+            buckets: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    // records one result, true when the breaker trips
+    pub fn record(&self, failed: bool) -> bool {
+        let now = self.start.elapsed();
+        let second = now.as_secs();
+        let oldest = now.saturating_sub(self.window).as_secs();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        while buckets.front().is_some_and(|(s, _, _)| *s < oldest) {
+            buckets.pop_front();
+        }
+        match buckets.back_mut() {
+            Some((s, requests, errors)) if *s == second => {
+                *requests += 1;
+                *errors += failed as u64;
+            }
+            _ => buckets.push_back((second, 1, failed as u64)),
+        }
+
+        let (requests, errors) = buckets
+            .iter()
+            .fold((0, 0), |(r, e), (_, requests, errors)| (r + requests, e + errors));
+        requests >= MIN_REQUESTS && errors as f64 / requests as f64 > self.threshold
+    }
+}
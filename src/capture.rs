@@ -0,0 +1,232 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_LENGTH, HOST, TRANSFER_ENCODING};
+use reqwest::{StatusCode, Version};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::requestlog::now_ms;
+use crate::sample::{self, Sent, HIDDEN};
+
+// exchanges buffered before workers start waiting on the writer
+const CHANNEL_CAPACITY: usize = 1024;
+const BLOCK: usize = 512;
+
+// --capture capture.tar.gz: a sampled share of the exchanges, each written
+// as a pair of http/1.1 messages rebuilt from what reqwest and hyper hand
+// back rather than the bytes on the wire: an h2 exchange reads as 1.1, the
+// response body is the decoded one, and the Accept the client would add is
+// filled in. Credential headers are hidden unless --keep-secrets. Any tar
+// opens it, three files per exchange:
+//
+//   000042.request   000042.response   000042.json
+//
+// the last with the timing, the connection's addresses and any error
+#[derive(Debug, Clone)]
+pub struct Capture {
+    tx: mpsc::Sender<Vec<(String, Vec<u8>)>>,
+    rate: f64,
+    // what the client adds to every request, --header, auth and api key
+    defaults: Arc<HeaderMap>,
+    keep_secrets: bool,
+}
+
+#[derive(Debug)]
+pub struct Exchange {
+    pub sequence: u64,
+    pub latency_ms: f64,
+    pub failed: bool,
+    pub local_addr: Option<SocketAddr>,
+    pub remote_addr: Option<SocketAddr>,
+    pub request: Sent,
+    pub response: Option<Response>,
+    // no response, or one whose body broke off
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub version: Version,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+}
+
+#[derive(Serialize)]
+struct Meta<'a> {
+    sequence: u64,
+    finished_at_ms: u64,
+    latency_ms: f64,
+    failed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_addr: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote_addr: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+    // a streamed body isn't kept, the .request file ends at the headers
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    streamed_body: bool,
+}
+
+impl Capture {
+    // the writer task finishes once every Capture clone has been dropped,
+    // returning how many exchanges it wrote
+    pub fn create(path: &str, rate: f64, defaults: HeaderMap, keep_secrets: bool) -> io::Result<(Self, JoinHandle<io::Result<u64>>)> {
+        let file = File::create(path)?;
+        let (tx, mut rx) = mpsc::channel::<Vec<(String, Vec<u8>)>>(CHANNEL_CAPACITY);
+        let writer = tokio::task::spawn_blocking(move || {
+            let mut out = GzEncoder::new(BufWriter::new(file), Compression::default());
+            let mut written = 0;
+            while let Some(files) = rx.blocking_recv() {
+                let mtime = now_ms() / 1000;
+                for (name, data) in files {
+                    write_entry(&mut out, &name, &data, mtime)?;
+                }
+                written += 1;
+            }
+            // a tar ends in two empty blocks
+            out.write_all(&[0; BLOCK * 2])?;
+            out.finish()?.flush()?;
+            Ok(written)
+        });
+        let capture = Capture { tx, rate, defaults: Arc::new(defaults), keep_secrets };
+        Ok((capture, writer))
+    }
+
+    // whether the next exchange is one of the sampled ones
+    pub fn wants(&self) -> bool {
+        self.rate >= 1.0 || rand::thread_rng().gen_bool(self.rate.max(0.0))
+    }
+
+    pub async fn record(&self, exchange: Exchange) {
+        let name = format!("{:06}", exchange.sequence);
+        let mut files = vec![(format!("{}.request", name), self.request_bytes(&exchange.request))];
+        if let Some(response) = &exchange.response {
+            files.push((format!("{}.response", name), response_bytes(response, self.keep_secrets)));
+        }
+        let meta = Meta {
+            sequence: exchange.sequence,
+            finished_at_ms: now_ms(),
+            latency_ms: exchange.latency_ms,
+            failed: exchange.failed,
+            local_addr: exchange.local_addr,
+            remote_addr: exchange.remote_addr,
+            error: exchange.error.as_deref(),
+            streamed_body: exchange.request.body.is_none(),
+        };
+        let mut json = serde_json::to_vec_pretty(&meta).unwrap_or_default();
+        json.push(b'\n');
+        files.push((format!("{}.json", name), json));
+        // the writer only goes away with the run
+        let _ = self.tx.send(files).await;
+    }
+
+    // with the default headers the client put in, the request as written
+    // to the connection
+    fn request_bytes(&self, sent: &Sent) -> Vec<u8> {
+        let url = reqwest::Url::parse(&sent.url).ok();
+        let target = url.as_ref().map_or(sent.url.clone(), |url| match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        });
+        let mut headers = HeaderMap::new();
+        let host = url.as_ref().and_then(|url| match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => Some(format!("{}:{}", host, port)),
+            (host, None) => host.map(str::to_string),
+            (None, _) => None,
+        });
+        if let Some(host) = host.and_then(|host| HeaderValue::from_str(&host).ok()) {
+            headers.insert(HOST, host);
+        }
+        // the request's own headers win over the client's
+        for (name, value) in self.defaults.iter().filter(|(name, _)| !sent.headers.contains_key(*name)) {
+            headers.append(name, value.clone());
+        }
+        for (name, value) in &sent.headers {
+            headers.append(name, value.clone());
+        }
+        if !headers.contains_key(ACCEPT) {
+            headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+        }
+        match &sent.body {
+            Some(body) if !body.is_empty() => {
+                headers.insert(CONTENT_LENGTH, body.len().into());
+            }
+            Some(_) => {}
+            None => {
+                headers.insert(TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+            }
+        }
+        let mut bytes = format!("{} {} HTTP/1.1\r\n", sent.method, target).into_bytes();
+        write_headers(&mut bytes, &headers, self.keep_secrets);
+        bytes.extend_from_slice(sent.body.as_deref().unwrap_or_default());
+        bytes
+    }
+}
+
+fn response_bytes(response: &Response, keep_secrets: bool) -> Vec<u8> {
+    let mut bytes = format!("{:?} {}\r\n", response.version, response.status).into_bytes();
+    write_headers(&mut bytes, &response.headers, keep_secrets);
+    let body = response.body.as_deref().unwrap_or_default();
+    // hyper took the chunks apart, a single one holds the whole body again
+    let chunked = response.headers.get(TRANSFER_ENCODING).is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"chunked"));
+    match chunked {
+        true => {
+            if !body.is_empty() {
+                bytes.extend_from_slice(format!("{:x}\r\n", body.len()).as_bytes());
+                bytes.extend_from_slice(body);
+                bytes.extend_from_slice(b"\r\n");
+            }
+            bytes.extend_from_slice(b"0\r\n\r\n");
+        }
+        false => bytes.extend_from_slice(body),
+    }
+    bytes
+}
+
+fn write_headers(bytes: &mut Vec<u8>, headers: &HeaderMap, keep_secrets: bool) {
+    for (name, value) in headers {
+        bytes.extend_from_slice(name.as_str().as_bytes());
+        bytes.extend_from_slice(b": ");
+        match !keep_secrets && sample::is_secret(name, value) {
+            true => bytes.extend_from_slice(HIDDEN.as_bytes()),
+            false => bytes.extend_from_slice(value.as_bytes()),
+        }
+        bytes.extend_from_slice(b"\r\n");
+    }
+    bytes.extend_from_slice(b"\r\n");
+}
+
+// a ustar header, then the data padded to a whole block
+fn write_entry(out: &mut impl Write, name: &str, data: &[u8], mtime: u64) -> io::Result<()> {
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut header[100..108], 0o644);
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    octal(&mut header[124..136], data.len() as u64);
+    octal(&mut header[136..148], mtime);
+    // the checksum counts its own field as spaces
+    header[148..156].fill(b' ');
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    let checksum: u64 = header.iter().map(|&b| b as u64).sum();
+    header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+    out.write_all(&header)?;
+    out.write_all(data)?;
+    out.write_all(&[0; BLOCK][..(BLOCK - data.len() % BLOCK) % BLOCK])
+}
+
+// zero padded, ending in a nul
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}\0", value, width = field.len() - 1);
+    field.copy_from_slice(digits.as_bytes());
+}
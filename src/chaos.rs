@@ -0,0 +1,108 @@
+use rand::Rng;
+use reqwest::header::{HeaderValue, CONTENT_LENGTH};
+use reqwest::{Body, Request};
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use crate::units::parse_duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChaosKind {
+    // holds the request back this long before sending it
+    Delay(Duration),
+    // sends half the body and then breaks the request off
+    Abort,
+    // sends the request a second time, same body and headers, right after
+    // the first or this long after it with other requests in between
+    Duplicate(Option<Duration>),
+    // sends the first half of the body as if it were all of it
+    Malformed,
+}
+
+// --chaos kind:percent[:arg], e.g. delay:5%:200ms, abort:1%, duplicate:2%,
+// duplicate:2%:500ms, malformed:3%; the requests it hits are counted on
+// their own, not in the run's totals
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chaos {
+    pub kind: ChaosKind,
+    // share of the requests, 0 to 1
+    pub rate: f64,
+}
+
+impl Chaos {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.splitn(3, ':');
+        let (kind, rate, arg) = (parts.next().unwrap_or_default(), parts.next(), parts.next());
+        let rate = rate.ok_or_else(|| format!("--chaos {}: expected kind:percent, e.g. abort:1%", spec))?;
+        let percent: f64 = rate
+            .trim_end_matches('%')
+            .parse()
+            .map_err(|_| format!("--chaos {}: {} isn't a percentage", spec, rate))?;
+        if !(percent > 0.0 && percent <= 100.0) {
+            return Err(format!("--chaos {}: the percentage must be above 0 and at most 100", spec));
+        }
+        let kind = match (kind, arg) {
+            ("delay", Some(delay)) => ChaosKind::Delay(parse_duration(delay)?),
+            ("delay", None) => return Err(format!("--chaos {}: delay needs a duration, e.g. delay:5%:200ms", spec)),
+            ("abort", None) => ChaosKind::Abort,
+            ("duplicate", after) => ChaosKind::Duplicate(after.map(parse_duration).transpose()?),
+            ("malformed", None) => ChaosKind::Malformed,
+            ("abort" | "malformed", Some(_)) => return Err(format!("--chaos {}: {} takes no argument", spec, kind)),
+            _ => return Err(format!("--chaos {}: expected delay, abort, duplicate or malformed", spec)),
+        };
+        Ok(Chaos { kind, rate: percent / 100.0 })
+    }
+
+    // abort and malformed need a body to break
+    pub fn needs_body(&self) -> bool {
+        matches!(self.kind, ChaosKind::Abort | ChaosKind::Malformed)
+    }
+}
+
+// the first of `chaos` a request draws, each with its own rate
+pub fn pick(chaos: &[Chaos], rng: &mut impl Rng) -> Option<usize> {
+    chaos.iter().position(|chaos| rng.gen_bool(chaos.rate))
+}
+
+// replaces the body with its first half, under the same content-length for
+// an abort so the server is left waiting on the rest
+pub fn break_body(request: &mut Request, kind: ChaosKind) {
+    let Some(bytes) = request.body().and_then(|body| body.as_bytes()) else { return };
+    let half = bytes[..bytes.len() / 2].to_vec();
+    match kind {
+        ChaosKind::Malformed => *request.body_mut() = Some(Body::from(half)),
+        ChaosKind::Abort => {
+            let length = HeaderValue::from(bytes.len());
+            let parts: [Result<Vec<u8>, io::Error>; 2] = [Ok(half), Err(io::Error::other("aborted by --chaos"))];
+            *request.body_mut() = Some(Body::wrap_stream(futures_util::stream::iter(parts)));
+            request.headers_mut().insert(CONTENT_LENGTH, length);
+        }
+        ChaosKind::Delay(_) | ChaosKind::Duplicate(_) => {}
+    }
+}
+
+impl fmt::Display for ChaosKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChaosKind::Delay(delay) => write!(f, "delay {:?}", delay),
+            ChaosKind::Abort => write!(f, "abort"),
+            ChaosKind::Duplicate(None) => write!(f, "duplicate"),
+            ChaosKind::Duplicate(Some(after)) => write!(f, "duplicate after {:?}", after),
+            ChaosKind::Malformed => write!(f, "malformed"),
+        }
+    }
+}
+
+impl fmt::Display for Chaos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}%)", self.kind, self.rate * 100.0)
+    }
+}
+
+impl Serialize for Chaos {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
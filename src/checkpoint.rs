@@ -0,0 +1,96 @@
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::config::LoadConfig;
+use crate::report::Report;
+use crate::stats::LoadTestStats;
+use crate::timeseries::Point;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckpointConfig {
+    pub path: String,
+    #[serde(rename = "interval_secs", serialize_with = "crate::units::serialize_secs")]
+    pub interval: Duration,
+}
+
+// what a checkpoint file holds: the report as it would have been had the
+// run ended when it was written, and the seconds of the time series so far
+#[derive(Debug, Serialize)]
+struct Checkpoint {
+    written_at: String,
+    // false while the run is still going
+    finished: bool,
+    report: Report,
+    timeseries: Vec<Point>,
+}
+
+// --checkpoint: rewrites the file every interval, so a long run that
+// crashes or gets killed still leaves its results up to the last one
+pub struct Checkpoints {
+    config: LoadConfig,
+    stats: Arc<LoadTestStats>,
+    task: JoinHandle<()>,
+}
+
+impl Checkpoints {
+    pub fn start(config: &LoadConfig, stats: Arc<LoadTestStats>) -> Option<Self> {
+        let interval = config.checkpoint.as_ref()?.interval;
+        let (writer, writing) = (config.clone(), stats.clone());
+        let task = tokio::spawn(async move {
+            let start = tokio::time::Instant::now() + interval;
+            let mut ticks = tokio::time::interval_at(start, interval);
+            loop {
+                ticks.tick().await;
+                writing.timeseries.flush();
+                write(&writer, &writing, false).await;
+            }
+        });
+        Some(Checkpoints { config: config.clone(), stats, task })
+    }
+
+    // a last one with everything in it once the run is over
+    pub async fn finish(self) {
+        self.task.abort();
+        self.stats.timeseries.finish();
+        write(&self.config, &self.stats, true).await;
+    }
+}
+
+// a run that ends in an error stops writing them too
+impl Drop for Checkpoints {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// to a temporary file first and renamed over the last checkpoint, so the
+// file is never half written even if the process dies mid-write
+async fn write(config: &LoadConfig, stats: &LoadTestStats, finished: bool) {
+    let Some(checkpoint) = &config.checkpoint else { return };
+    let contents = Checkpoint {
+        written_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        finished,
+        report: Report::new(stats, config),
+        timeseries: stats.timeseries.points_from(0),
+    };
+    let json = match serde_json::to_vec_pretty(&contents) {
+        Ok(json) => json,
+        Err(e) => {
+            warn!(error = %e, "checkpoint couldn't be serialized");
+            return;
+        }
+    };
+    let partial = format!("{}.tmp", checkpoint.path);
+    let written = async {
+        tokio::fs::write(&partial, json).await?;
+        tokio::fs::rename(&partial, &checkpoint.path).await
+    };
+    match written.await {
+        Ok(()) => debug!(path = %checkpoint.path, finished, "checkpoint written"),
+        Err(e) => warn!(path = %checkpoint.path, error = %e, "checkpoint couldn't be written"),
+    }
+}
@@ -0,0 +1,165 @@
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::jsonpath;
+use crate::script::Script;
+
+#[derive(Debug, Clone)]
+pub enum Assertion {
+    Status(Vec<u16>),
+    JsonEquals(String, Value),
+    JsonExists(String),
+    BodyMatches(Regex),
+    MaxLatency(Duration),
+    // check(res) of a --script
+    Script(Arc<Script>),
+}
+
+// what a check gets to look at once a response has arrived
+pub struct Observed<'a> {
+    pub status: u16,
+    // none for grpc
+    pub headers: Option<&'a HeaderMap>,
+    pub body: Option<&'a str>,
+    pub json: Option<&'a Value>,
+    pub latency: Duration,
+}
+
+impl Assertion {
+    pub fn status(codes: &str) -> Result<Self, String> {
+        let codes = codes
+            .split(',')
+            .map(|c| c.trim().parse::<u16>().map_err(|_| format!("invalid status code: {}", c)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Assertion::Status(codes))
+    }
+
+    // `$.path=value`, value is parsed as json and falls back to a plain string
+    pub fn json_equals(spec: &str) -> Result<Self, String> {
+        let (path, value) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("expected <path>=<value>, got: {}", spec))?;
+        let value = serde_json::from_str(value.trim())
+            .unwrap_or_else(|_| Value::String(value.trim().to_string()));
+        Ok(Assertion::JsonEquals(path.trim().to_string(), value))
+    }
+
+    pub fn json_exists(path: &str) -> Self {
+        Assertion::JsonExists(path.trim().to_string())
+    }
+
+    pub fn body_matches(pattern: &str) -> Result<Self, String> {
+        Regex::new(pattern)
+            .map(Assertion::BodyMatches)
+            .map_err(|e| format!("invalid body regex: {}", e))
+    }
+
+    pub fn max_latency(ms: u64) -> Self {
+        Assertion::MaxLatency(Duration::from_millis(ms))
+    }
+
+    pub fn needs_body(&self) -> bool {
+        matches!(
+            self,
+            Assertion::JsonEquals(..) | Assertion::JsonExists(_) | Assertion::BodyMatches(_) | Assertion::Script(_)
+        )
+    }
+
+    pub fn check(&self, observed: &Observed) -> bool {
+        match self {
+            Assertion::Status(codes) => codes.contains(&observed.status),
+            Assertion::JsonEquals(path, expected) => observed
+                .json
+                .and_then(|json| jsonpath::select(json, path))
+                .is_some_and(|actual| actual == expected),
+            Assertion::JsonExists(path) => observed
+                .json
+                .and_then(|json| jsonpath::select(json, path))
+                .is_some(),
+            Assertion::BodyMatches(re) => observed.body.is_some_and(|body| re.is_match(body)),
+            Assertion::MaxLatency(max) => observed.latency <= *max,
+            Assertion::Script(script) => script.check(observed),
+        }
+    }
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Assertion::Status(codes) => {
+                let codes: Vec<String> = codes.iter().map(|c| c.to_string()).collect();
+                write!(f, "status in [{}]", codes.join(","))
+            }
+            Assertion::JsonEquals(path, value) => write!(f, "{} == {}", path, value),
+            Assertion::JsonExists(path) => write!(f, "{} exists", path),
+            Assertion::BodyMatches(re) => write!(f, "body =~ /{}/", re.as_str()),
+            Assertion::MaxLatency(max) => write!(f, "latency <= {}ms", max.as_millis()),
+            Assertion::Script(script) => write!(f, "check() in {}", script.path()),
+        }
+    }
+}
+
+impl serde::Serialize for Assertion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub assertion: Assertion,
+    pub passed: AtomicU64,
+    pub failed: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+pub struct Checks {
+    checks: Vec<Check>,
+}
+
+impl Checks {
+    pub fn new(assertions: Vec<Assertion>) -> Self {
+        let checks = assertions
+            .into_iter()
+            .map(|assertion| Check {
+                assertion,
+                passed: AtomicU64::new(0),
+                failed: AtomicU64::new(0),
+            })
+            .collect();
+        Checks { checks }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty()
+    }
+
+    pub fn needs_body(&self) -> bool {
+        self.checks.iter().any(|c| c.assertion.needs_body())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter()
+    }
+
+    // returns (passed, failed) for this response
+    pub fn run(&self, observed: &Observed) -> (u64, u64) {
+        let mut passed = 0;
+        let mut failed = 0;
+        for check in &self.checks {
+            if check.assertion.check(observed) {
+                check.passed.fetch_add(1, Ordering::SeqCst);
+                passed += 1;
+            } else {
+                check.failed.fetch_add(1, Ordering::SeqCst);
+                failed += 1;
+            }
+        }
+        (passed, failed)
+    }
+}
@@ -0,0 +1,1310 @@
+use clap::{Arg, ArgAction, Command};
+
+use arctic::history;
+use arctic::units::{parse_duration, parse_size};
+
+// what `arctic repl` reads at its prompt
+const REPL_COMMANDS: &str = "at the prompt: next [n] shows the next payloads, show <seq> the payload of request <seq>, send sends the payload shown last and prints the exchange, run starts the full run with these flags, quit leaves; templates are read again before each payload, so directives can be edited in between";
+// how a running load test is held and inspected from outside
+const SIGNALS: &str = "while a test runs, SIGUSR1 pauses and resumes it and SIGUSR2 prints the summary so far, unless --tui or --json";
+
+pub fn command() -> Command {
+    load_args(
+        Command::new("arctic")
+            .version("0.1.0")
+            .about("sends randomized json to api endpoints")
+            .after_help(SIGNALS)
+            .args_conflicts_with_subcommands(true)
+            .subcommand(run_command())
+            .subcommand(find_max_command())
+            .subcommand(matrix_command())
+            .subcommand(openapi_command())
+            .subcommand(worker_command())
+            .subcommand(ws_command())
+            .subcommand(sse_command())
+            .subcommand(compare_command())
+            .subcommand(history_command())
+            .subcommand(merge_command())
+            .subcommand(repl_command())
+            .arg(
+                Arg::new("verbose")
+                    .short('v')
+                    .long("verbose")
+                    .action(ArgAction::Count)
+                    .global(true)
+                    .help("log debug events, -vv for trace events such as every request")
+            )
+            .arg(
+                Arg::new("quiet")
+                    .short('q')
+                    .long("quiet")
+                    .action(ArgAction::SetTrue)
+                    .global(true)
+                    .conflicts_with("verbose")
+                    .help("print only the summary and errors, no banner, spinner or warnings")
+            )
+            .arg(
+                Arg::new("no-progress")
+                    .long("no-progress")
+                    .action(ArgAction::SetTrue)
+                    .global(true)
+                    .help("print progress as a plain line every 10 seconds instead of a spinner redrawn in place; the default when stdout isn't a terminal, as in ci logs")
+            )
+            .arg(
+                Arg::new("log-format")
+                    .long("log-format")
+                    .value_parser(["text", "json"])
+                    .default_value("text")
+                    .global(true)
+                    .help("format of the events logged to stderr, json for one object per line")
+            ),
+    )
+}
+
+// the flags of a load test, shared by `arctic ...` and `arctic run ...`
+fn load_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help("toml or yaml file describing the run, flags override its values [default: ./arctic.toml if present]")
+        )
+        .arg(
+            Arg::new("endpoint")
+                .short('e')
+                .long("endpoint")
+                .action(ArgAction::Append)
+                .help("target url, repeat for several and append @weight to skew the mix and \" key=value\" tags to group its stats; {{uuid}}, {{int 1 50}} and friends are filled in per request")
+        )
+        .arg(
+            Arg::new("mix")
+                .long("mix")
+                .conflicts_with_all(["targets", "har", "grpc", "method"])
+                .help("weighted requests against the --endpoint as base url, e.g. \"GET /items:80,POST /items:20\"; each entry gets its own stats")
+        )
+        .arg(
+            Arg::new("targets")
+                .long("targets")
+                .help("file with one \"<url> [weight] [key=value ...]\" per line")
+        )
+        .arg(
+            Arg::new("har")
+                .long("har")
+                .conflicts_with_all(["graphql", "grpc"])
+                .help("browser-exported har file whose requests are replayed as recorded, each as its own endpoint")
+        )
+        .arg(
+            Arg::new("har-host")
+                .long("har-host")
+                .help("send the har requests to this scheme://host[:port] instead of the recorded one")
+        )
+        .arg(
+            Arg::new("time")
+                .short('t')
+                .long("time")
+                .value_parser(clap::value_parser!(u64))
+                .help("test duration in seconds, defaults to the length of --stages")
+        )
+        .arg(
+            Arg::new("warmup")
+                .long("warmup")
+                .value_parser(parse_duration)
+                .help("send traffic for this long (e.g. 10s) before measuring; warm-up requests don't count towards any stats or limits")
+        )
+        .arg(
+            Arg::new("drain-timeout")
+                .long("drain-timeout")
+                .value_parser(parse_duration)
+                .default_value("30s")
+                .help("once the run ends, wait this long for the requests still in flight; those that haven't finished by then are counted as abandoned, not failed")
+        )
+        .arg(
+            Arg::new("requests")
+                .short('n')
+                .long("requests")
+                .value_parser(clap::value_parser!(u64))
+                .help("stop after this many requests, whichever of --time/--requests hits first")
+        )
+        .arg(
+            Arg::new("data")
+                .short('d')
+                .long("data")
+                .action(ArgAction::Append)
+                .help("json template the payloads are generated from; repeat with :weight, e.g. create.json:3, to mix several, or - to send the json lines read from stdin as they arrive")
+        )
+        .arg(
+            Arg::new("generator")
+                .long("generator")
+                .help("where payloads come from: template, corpus or script; by default corpus with --corpus, script with a --script generate(ctx) and no --data, template otherwise")
+        )
+        .arg(
+            Arg::new("corpus")
+                .long("corpus")
+                .conflicts_with("data")
+                .help("jsonl file of recorded bodies to replay instead of generating payloads, one per line")
+        )
+        .arg(
+            Arg::new("corpus-order")
+                .long("corpus-order")
+                .default_value("cycle")
+                .help("replay the corpus in file order (cycle), pick lines at random, cycle a permutation fixed by --seed (shuffle), or shuffle only within runs of N lines so they arrive slightly out of order (shuffle:N)")
+        )
+        .arg(
+            Arg::new("feed")
+                .long("feed")
+                .help("csv file with a header line, each request fills {{csv.<column>}} placeholders from one row")
+        )
+        .arg(
+            Arg::new("feed-order")
+                .long("feed-order")
+                .value_parser(["cycle", "random"])
+                .default_value("cycle")
+                .help("take the feed rows round-robin or at random")
+        )
+        .arg(
+            Arg::new("graphql")
+                .long("graphql")
+                .help("graphql query or mutation file, --data then templates its variables")
+        )
+        .arg(
+            Arg::new("graphql-operation")
+                .long("graphql-operation")
+                .requires("graphql")
+                .help("operationName to send when the graphql file has several operations")
+        )
+        .arg(
+            Arg::new("method")
+                .short('X')
+                .long("method")
+                .default_value("POST")
+                .help("http method; GET, HEAD and OPTIONS requests are sent without a body")
+        )
+        .arg(
+            Arg::new("body-format")
+                .long("body-format")
+                .value_parser(["json", "form", "multipart", "xml"])
+                .default_value("json")
+                .conflicts_with_all(["graphql", "grpc"])
+                .help("send the generated object as json, urlencoded form fields, multipart/form-data or xml")
+        )
+        .arg(
+            Arg::new("xml-root")
+                .long("xml-root")
+                .default_value("root")
+                .help("element --body-format xml wraps the generated object in")
+        )
+        .arg(
+            Arg::new("xml-attribute-prefix")
+                .long("xml-attribute-prefix")
+                .default_value("@")
+                .help("keys starting with this become attributes of their element with --body-format xml, \"#text\" keys its text")
+        )
+        .arg(
+            Arg::new("file-size")
+                .long("file-size")
+                .value_parser(parse_size)
+                .help("add a file part of this many random bytes to multipart bodies, e.g. 64KiB")
+        )
+        .arg(
+            Arg::new("file-field")
+                .long("file-field")
+                .default_value("file")
+                .help("form field name of the --file-size part")
+        )
+        .arg(
+            Arg::new("body-bytes")
+                .long("body-bytes")
+                .conflicts_with_all(["data", "corpus", "grpc", "graphql", "body-format", "message", "body-size"])
+                .help("send bodies of random bytes instead of a generated object, this size or a size drawn from a range, e.g. 1MB or 1MB..10MB")
+        )
+        .arg(
+            Arg::new("body-bytes-type")
+                .long("body-bytes-type")
+                .default_value("application/octet-stream")
+                .help("content-type of the --body-bytes bodies")
+        )
+        .arg(
+            Arg::new("validate-payloads")
+                .long("validate-payloads")
+                .conflicts_with_all(["body-bytes"])
+                .help("json schema every body is checked against before it's sent; those that don't match are counted, and skipped or fixed up as --invalid-payloads says")
+        )
+        .arg(
+            Arg::new("invalid-payloads")
+                .long("invalid-payloads")
+                .requires("validate-payloads")
+                .default_value("skip")
+                .help("what becomes of a body that doesn't match the --validate-payloads schema: skip, not sending it, or fix, clamping numbers, cutting strings and arrays to length and dropping unknown properties, then sending it if that's enough")
+        )
+        .arg(
+            Arg::new("body-size")
+                .long("body-size")
+                .value_parser(parse_size)
+                .conflicts_with_all(["grpc", "corpus"])
+                .help("pad generated bodies to about this size, e.g. 64KB, by growing a string field")
+        )
+        .arg(
+            Arg::new("body-size-field")
+                .long("body-size-field")
+                .default_value("padding")
+                .help("top-level field --body-size grows, added when the template lacks it")
+        )
+        .arg(
+            Arg::new("stream-body")
+                .long("stream-body")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["grpc"])
+                .help("send bodies chunked, a --chunk-size at a time with --chunk-delay in between, like a slow client")
+        )
+        .arg(
+            Arg::new("chunk-size")
+                .long("chunk-size")
+                .value_parser(parse_size)
+                .default_value("16KB")
+                .requires("stream-body")
+                .help("bytes per chunk of a --stream-body")
+        )
+        .arg(
+            Arg::new("chunk-delay")
+                .long("chunk-delay")
+                .value_parser(parse_duration)
+                .default_value("10ms")
+                .requires("stream-body")
+                .help("wait between the chunks of a --stream-body")
+        )
+        .arg(
+            Arg::new("compress")
+                .long("compress")
+                .value_parser(["gzip", "deflate"])
+                .conflicts_with_all(["grpc"])
+                .help("compress json bodies and send them with this content-encoding")
+        )
+        .arg(
+            Arg::new("grpc")
+                .long("grpc")
+                .conflicts_with("graphql")
+                .help("call this unary grpc method, package.Service/Method, instead of posting json")
+        )
+        .arg(
+            Arg::new("proto")
+                .long("proto")
+                .action(ArgAction::Append)
+                .help(".proto file or binary descriptor set (protoc --descriptor_set_out) declaring the --grpc service or the --message; server reflection finds the --grpc service without one")
+        )
+        .arg(
+            Arg::new("message")
+                .long("message")
+                .requires("proto")
+                .conflicts_with_all(["grpc", "graphql", "body-format"])
+                .help("send http bodies as this protobuf message, e.g. my.pkg.Request, encoded from the generated object as application/x-protobuf")
+        )
+        .arg(
+            Arg::new("proto-include")
+                .long("proto-include")
+                .action(ArgAction::Append)
+                .requires("proto")
+                .help("import path for --proto files, defaults to their directory")
+        )
+        .arg(
+            Arg::new("concurrency")
+                .short('c')
+                .long("concurrency")
+                .value_parser(clap::value_parser!(usize))
+                .help("number of concurrent workers (max in-flight requests with --rate)")
+        )
+        .arg(
+            Arg::new("rate")
+                .long("rate")
+                .value_parser(clap::value_parser!(f64))
+                .conflicts_with("stages")
+                .help("target request rate per second")
+        )
+        .arg(
+            Arg::new("stages")
+                .long("stages")
+                .help("ramp load through stages, e.g. 30s:10,2m:100,30s:0")
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .conflicts_with_all(["stages", "rate"])
+                .help("a preset load shape, spike:<target>:<duration> or soak:<target>:<duration>, e.g. spike:1000rps:10s or soak:50rps:4h; a target without rps counts workers. spike holds a tenth of the target for 30s, jumps to the target for the duration, then drops back to the tenth for another 30s; soak ramps up over a tenth of the duration, at most a minute, and holds the target, with a summary every 10m unless --summary-every says otherwise")
+        )
+        .arg(
+            Arg::new("adaptive")
+                .long("adaptive")
+                .action(ArgAction::SetTrue)
+                .requires("target-p95")
+                .conflicts_with_all(["stages", "rate", "profile"])
+                .help("move the concurrency to hold the p95 near --target-p95, one more worker each second it's within and a quarter fewer each second it's over, starting at --concurrency; reports the level over time and the throughput the target allows")
+        )
+        .arg(
+            Arg::new("target-p95")
+                .long("target-p95")
+                .value_parser(parse_duration)
+                .requires("adaptive")
+                .help("p95 latency --adaptive aims for, e.g. 200ms")
+        )
+        .arg(
+            Arg::new("max-concurrency")
+                .long("max-concurrency")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("256")
+                .requires("adaptive")
+                .help("the most workers --adaptive goes up to")
+        )
+        .arg(
+            Arg::new("summary-every")
+                .long("summary-every")
+                .value_parser(parse_duration)
+                .help("print a summary of the last interval this often (e.g. 10m) to spot drift during long runs; soak profiles default to 10m")
+        )
+        .arg(
+            Arg::new("stage-unit")
+                .long("stage-unit")
+                .value_parser(["concurrency", "rps"])
+                .default_value("concurrency")
+                .help("whether stage targets are worker counts or request rates")
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(clap::value_parser!(u64))
+                .help("seed for payload generation, replays the exact payload sequence of a run")
+        )
+        .arg(
+            Arg::new("shard")
+                .long("shard")
+                .value_name("N/COUNT")
+                .help("run shard N of COUNT, e.g. 3/10, as one of COUNT parallel jobs given the same flags and --seed: it takes its share of the rate, concurrency and --requests, and every COUNT-th request of the payload stream, so the jobs add up to one run; `arctic merge` combines their --output reports")
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .visible_alias("request-timeout")
+                .value_parser(parse_duration)
+                .help("give up on a request after this long, e.g. 2s or 500ms; timed out requests fail and are counted on their own, out of the latencies")
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("0")
+                .help("retry transport errors, 429 and 5xx responses this many times; a status --expect-status lists isn't retried")
+        )
+        .arg(
+            Arg::new("think-time")
+                .long("think-time")
+                .value_parser(parse_duration)
+                .conflicts_with("rate")
+                .help("pause each virtual user this long (e.g. 200ms) after every request, like a real client would")
+        )
+        .arg(
+            Arg::new("think-jitter")
+                .long("think-jitter")
+                .value_parser(parse_duration)
+                .requires("think-time")
+                .help("vary every --think-time pause by up to this much either way")
+        )
+        .arg(
+            Arg::new("retry-backoff")
+                .long("retry-backoff")
+                .value_parser(parse_duration)
+                .default_value("100ms")
+                .help("delay before the first retry, doubled on every further attempt")
+        )
+        .arg(
+            Arg::new("respect-retry-after")
+                .long("respect-retry-after")
+                .action(ArgAction::SetTrue)
+                .help("on a 429 or 503 with Retry-After the worker waits that long before its next request or retry, and a rate skips the slots it missed")
+        )
+        .arg(
+            Arg::new("max-retry-after")
+                .long("max-retry-after")
+                .value_parser(parse_duration)
+                .default_value("60s")
+                .requires("respect-retry-after")
+                .help("longest Retry-After a worker waits out")
+        )
+        .arg(
+            Arg::new("chaos")
+                .long("chaos")
+                .action(ArgAction::Append)
+                .conflicts_with("grpc")
+                .help("break a share of the requests on purpose, kind:percent[:arg] with delay:5%:200ms, abort:1%, duplicate:2% (or duplicate:2%:500ms to resend it later, out of order) or malformed:3%; counted apart from the totals, repeatable")
+        )
+        .arg(
+            Arg::new("header")
+                .short('H')
+                .long("header")
+                .action(ArgAction::Append)
+                .help("extra request header, Name:value, repeatable; {{...}} placeholders in the value are filled in per request")
+        )
+        .arg(
+            Arg::new("idempotency-key")
+                .long("idempotency-key")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["grpc"])
+                .help("send Idempotency-Key: {{uuid}}, a fresh key on every request")
+        )
+        .arg(
+            Arg::new("request-id")
+                .long("request-id")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["grpc"])
+                .help("send X-Request-Id: {{uuid}}, a fresh id on every request; --log-requests records it")
+        )
+        .arg(
+            Arg::new("user-agents")
+                .long("user-agents")
+                .value_name("FILE")
+                .conflicts_with_all(["grpc", "header-sets"])
+                .help("rotate the User-Agent through the file's lines, blank lines and # comments skipped")
+        )
+        .arg(
+            Arg::new("header-sets")
+                .long("header-sets")
+                .value_name("FILE")
+                .conflicts_with_all(["grpc"])
+                .help("rotate through sets of headers, one json object per line, e.g. {\"User-Agent\":\"...\",\"Accept-Language\":\"de-DE\"}")
+        )
+        .arg(
+            Arg::new("rotate-per")
+                .long("rotate-per")
+                .value_parser(["request", "user"])
+                .help("with --user-agents or --header-sets, draw one per request or keep one per virtual user [default: request]")
+        )
+        .arg(
+            Arg::new("bearer")
+                .long("bearer")
+                .conflicts_with_all(["basic", "oauth-token-url"])
+                .help("send Authorization: Bearer <token>")
+        )
+        .arg(
+            Arg::new("basic")
+                .long("basic")
+                .conflicts_with("oauth-token-url")
+                .help("send basic auth, user:pass")
+        )
+        .arg(
+            Arg::new("aws-sigv4")
+                .long("aws-sigv4")
+                .conflicts_with_all(["bearer", "basic", "oauth-token-url", "grpc"])
+                .help("sign every request with aws signature v4, credentials from AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY or the AWS_PROFILE profile's keys, read once at the start and never refreshed; sso, credential_process, role_arn profiles and instance or container credentials aren't supported, export temporary ones that outlast the run")
+        )
+        .arg(
+            Arg::new("api-key-header")
+                .long("api-key-header")
+                .help("send an api key header, Name:value")
+        )
+        .arg(
+            Arg::new("sign")
+                .long("sign")
+                .conflicts_with_all(["grpc"])
+                .help("algorithm:secret:Header, send an hmac of each body as sent in this header, hex encoded; hmac-sha256 or hmac-sha512")
+        )
+        .arg(
+            Arg::new("http-version")
+                .long("http-version")
+                .value_parser(["1.1", "2"])
+                .conflicts_with_all(["grpc"])
+                .help("speak only this http version instead of negotiating one, 2 without tls is h2c")
+        )
+        .arg(
+            Arg::new("max-redirects")
+                .long("max-redirects")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10")
+                .conflicts_with_all(["grpc"])
+                .help("follow at most this many redirects per request, more fail the request; 0 doesn't follow any")
+        )
+        .arg(
+            Arg::new("no-follow-redirects")
+                .long("no-follow-redirects")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["max-redirects", "grpc"])
+                .help("hand 3xx responses back as they are, same as --max-redirects 0")
+        )
+        .arg(
+            Arg::new("max-idle-per-host")
+                .long("max-idle-per-host")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with_all(["grpc"])
+                .help("keep at most this many idle connections per host for reuse")
+        )
+        .arg(
+            Arg::new("pool-idle-timeout")
+                .long("pool-idle-timeout")
+                .value_parser(parse_duration)
+                .conflicts_with_all(["grpc"])
+                .help("close connections idle for this long, e.g. 30s; 90s when not given")
+        )
+        .arg(
+            Arg::new("preconnect")
+                .long("preconnect")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with_all(["grpc"])
+                .help("open this many connections to every endpoint host before the run starts, so early latencies aren't handshakes; http/2 multiplexes over one")
+        )
+        .arg(
+            Arg::new("dns-refresh")
+                .long("dns-refresh")
+                .value_parser(parse_duration)
+                .conflicts_with_all(["grpc"])
+                .help("look endpoint hosts up again once their addresses are this old, e.g. 60s, spreading new connections over the records and moving off ones that went away; for weighted dns and failover drills")
+        )
+        .arg(
+            Arg::new("disable-keepalive")
+                .long("disable-keepalive")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["max-idle-per-host", "pool-idle-timeout", "preconnect", "grpc"])
+                .help("open a new connection for every request")
+        )
+        .arg(
+            Arg::new("cookies")
+                .long("cookies")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["grpc"])
+                .help("keep cookies between requests, a separate jar per virtual user so sessions stay apart")
+        )
+        .arg(
+            Arg::new("setup")
+                .long("setup")
+                .conflicts_with_all(["grpc"])
+                .help("request every virtual user sends once before its first one, e.g. \"POST https://api/login\"; a bare url is a POST")
+        )
+        .arg(
+            Arg::new("setup-data")
+                .long("setup-data")
+                .requires("setup")
+                .help("json template of the --setup body, rendered with the user's number as its sequence")
+        )
+        .arg(
+            Arg::new("setup-extract")
+                .long("setup-extract")
+                .action(ArgAction::Append)
+                .requires("setup")
+                .help("name=$.json.path or name=header:<name> taken from the --setup response, used as {{vu.<name>}} in that user's urls, headers and templates")
+        )
+        .arg(
+            Arg::new("scenario")
+                .long("scenario")
+                .conflicts_with_all(["mix", "targets", "har", "grpc", "chaos", "data", "corpus", "body-bytes"])
+                .help("yaml file of steps every virtual user walks in turn: request, sleep, if and loop, e.g. to poll for a job until it's done; a /path is relative to the single --endpoint")
+        )
+        .arg(
+            Arg::new("global-setup")
+                .long("global-setup")
+                .help("request sent once before the run and left out of its stats, e.g. \"POST https://api/tenants\"; a bare url is a POST")
+        )
+        .arg(
+            Arg::new("global-setup-data")
+                .long("global-setup-data")
+                .requires("global-setup")
+                .help("json template of the --global-setup body")
+        )
+        .arg(
+            Arg::new("global-setup-extract")
+                .long("global-setup-extract")
+                .action(ArgAction::Append)
+                .requires("global-setup")
+                .help("name=$.json.path or name=header:<name> taken from the --global-setup response, used as {{run.<name>}} in the run's and --teardown's requests")
+        )
+        .arg(
+            Arg::new("teardown")
+                .long("teardown")
+                .help("request sent once after the run, even a cut short one, e.g. \"DELETE https://api/tenants/{{run.id}}\"")
+        )
+        .arg(
+            Arg::new("teardown-data")
+                .long("teardown-data")
+                .requires("teardown")
+                .help("json template of the --teardown body")
+        )
+        .arg(
+            Arg::new("traceparent")
+                .long("traceparent")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["grpc"])
+                .help("send a w3c traceparent header with a fresh trace id on every request")
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .conflicts_with_all(["grpc"])
+                .help("send all traffic through this http://, https://, socks5:// or socks5h:// proxy")
+        )
+        .arg(
+            Arg::new("proxy-user")
+                .long("proxy-user")
+                .requires("proxy")
+                .help("user:password for the --proxy")
+        )
+        .arg(
+            Arg::new("cacert")
+                .long("cacert")
+                .help("pem file with ca certificates to trust on top of the system ones")
+        )
+        .arg(
+            Arg::new("cert")
+                .long("cert")
+                .requires("key")
+                .help("pem client certificate for mutual tls, with --key")
+        )
+        .arg(
+            Arg::new("key")
+                .long("key")
+                .requires("cert")
+                .help("pkcs#8 pem private key of the --cert")
+        )
+        .arg(
+            Arg::new("insecure")
+                .short('k')
+                .long("insecure")
+                .action(ArgAction::SetTrue)
+                .help("don't verify server certificates or hostnames")
+        )
+        .arg(
+            Arg::new("probe-full-handshakes")
+                .long("probe-full-handshakes")
+                .action(ArgAction::SetTrue)
+                .help("have the --timing probes do a full tls handshake every time; by default every other one resumes the session before it, to time both and show whether the server resumes. The load's own connections always do full handshakes, the client keeps no sessions")
+        )
+        .arg(
+            Arg::new("resolve")
+                .long("resolve")
+                .action(ArgAction::Append)
+                .help("host:address or host:port:address, connect to this address for the host while keeping it in sni and the host header; repeatable")
+        )
+        .arg(
+            Arg::new("host-header")
+                .long("host-header")
+                .conflicts_with_all(["grpc"])
+                .help("send this Host header, and sni, while still connecting to the endpoints' host, e.g. an internal address or load balancer")
+        )
+        .arg(
+            Arg::new("cache-bust")
+                .long("cache-bust")
+                .num_args(0..=1)
+                .default_missing_value("_cb")
+                .value_name("PARAM")
+                .conflicts_with_all(["grpc"])
+                .help("get past caches and cdns to the origin: a random query parameter, _cb unless named, on every request, and Cache-Control and Pragma no-cache headers")
+        )
+        .arg(
+            Arg::new("sni")
+                .long("sni")
+                .conflicts_with_all(["grpc"])
+                .help("server name for tls, the certificate is checked against it [default: the --host-header name]")
+        )
+        .arg(
+            Arg::new("ipv4")
+                .short('4')
+                .long("ipv4")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["ipv6", "grpc"])
+                .help("only connect to ipv4 addresses")
+        )
+        .arg(
+            Arg::new("ipv6")
+                .short('6')
+                .long("ipv6")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["grpc"])
+                .help("only connect to ipv6 addresses")
+        )
+        .arg(
+            Arg::new("oauth-token-url")
+                .long("oauth-token-url")
+                .requires_all(["oauth-client-id", "oauth-client-secret"])
+                .help("fetch and refresh a token with the oauth2 client-credentials grant")
+        )
+        .arg(
+            Arg::new("oauth-client-id")
+                .long("oauth-client-id")
+                .requires("oauth-token-url")
+        )
+        .arg(
+            Arg::new("oauth-client-secret")
+                .long("oauth-client-secret")
+                .requires("oauth-token-url")
+        )
+        .arg(
+            Arg::new("oauth-scope")
+                .long("oauth-scope")
+                .requires("oauth-token-url")
+        )
+        .arg(
+            Arg::new("assert-status")
+                .long("assert-status")
+                .help("comma separated status codes a response must have")
+        )
+        .arg(
+            Arg::new("expect-status")
+                .long("expect-status")
+                .conflicts_with("grpc")
+                .help("comma separated status codes or classes the requests succeed with, e.g. 200,201,409 or 2xx,409; any other response, a 2xx left out included, counts as failed; the statuses listed are never retried, so --expect-status 503 takes a 503 as it comes")
+        )
+        .arg(
+            Arg::new("assert-json")
+                .long("assert-json")
+                .action(ArgAction::Append)
+                .help("jsonpath that must equal a value, e.g. $.status=ok")
+        )
+        .arg(
+            Arg::new("assert-json-exists")
+                .long("assert-json-exists")
+                .action(ArgAction::Append)
+                .help("jsonpath that must exist in the response body")
+        )
+        .arg(
+            Arg::new("assert-body")
+                .long("assert-body")
+                .action(ArgAction::Append)
+                .help("regex the response body must match")
+        )
+        .arg(
+            Arg::new("max-latency")
+                .long("max-latency")
+                .value_parser(clap::value_parser!(u64))
+                .help("maximum acceptable response time in milliseconds")
+        )
+        .arg(
+            Arg::new("threshold")
+                .long("threshold")
+                .action(ArgAction::Append)
+                .help("pass/fail criterion checked after the run, e.g. p95<250ms or error_rate<0.01, or for one group with an endpoint:, tag: or template: scope, e.g. \"tag:checkout p99<500ms\"; any failure exits with status 2")
+        )
+        .arg(
+            Arg::new("metric")
+                .long("metric")
+                .action(ArgAction::Append)
+                .help("number to read out of every json response, e.g. queue_depth=$.queue_depth; its min, mean and max go in the summary, the report and each second of the time series")
+        )
+        .arg(
+            Arg::new("abort-on-error-rate")
+                .long("abort-on-error-rate")
+                .value_parser(clap::value_parser!(f64))
+                .help("end the run early, exiting with status 3, once more than this fraction of requests fail within --abort-window")
+        )
+        .arg(
+            Arg::new("abort-window")
+                .long("abort-window")
+                .value_parser(parse_duration)
+                .default_value("10s")
+                .help("rolling window the --abort-on-error-rate is measured over")
+        )
+        .arg(
+            Arg::new("timing")
+                .long("timing")
+                .action(ArgAction::SetTrue)
+                .help("break latency down into dns, connect, tls, ttfb and download; reads every response body, and times connection setup on a probe connection per origin each second")
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["corpus"])
+                .help("reload the --data template whenever the file or a fragment it $includes changes, keeping the stats so far; a template that doesn't load is skipped")
+        )
+        .arg(
+            Arg::new("script")
+                .long("script")
+                .help("rhai file with generate(ctx), before_request(req) and check(res) hooks for payloads, requests and checks the flags can't express")
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("write a machine readable summary to this file")
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .num_args(0..=1)
+                .default_missing_value("5")
+                .value_parser(clap::value_parser!(u64))
+                .help("print the first N requests (5 without a value) as they would be sent, to --output if given, and exit")
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(["json", "csv"])
+                .help("format of --output, inferred from the file extension by default")
+        )
+        .arg(
+            Arg::new("report")
+                .long("report")
+                .help("write a self-contained html report with charts to this file")
+        )
+        .arg(
+            Arg::new("junit")
+                .long("junit")
+                .help("write the thresholds and response checks to this file as junit xml test cases, for ci test reports")
+        )
+        .arg(
+            Arg::new("history")
+                .long("history")
+                .num_args(0..=1)
+                .default_missing_value(history::DEFAULT_PATH)
+                .help("append the run's summary to this sqlite file (~/.arctic/history.sqlite without a value), keyed by its config, for `arctic history`")
+        )
+        .arg(
+            Arg::new("timeseries")
+                .long("timeseries")
+                .help("write per-second metrics to this file, ndjson or csv by extension")
+        )
+        .arg(
+            Arg::new("log-requests")
+                .long("log-requests")
+                .help("write every request/response pair to this ndjson file")
+        )
+        .arg(
+            Arg::new("log-body-limit")
+                .long("log-body-limit")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1024")
+                .help("truncate logged response bodies to this many bytes")
+        )
+        .arg(
+            Arg::new("sample-responses")
+                .long("sample-responses")
+                .value_parser(clap::value_parser!(usize))
+                .help("keep a random sample of N full request/response pairs, failures first, and write them to --sample-dir")
+        )
+        .arg(
+            Arg::new("sample-dir")
+                .long("sample-dir")
+                .default_value("samples")
+                .help("directory --sample-responses writes one file per exchange to")
+        )
+        .arg(
+            Arg::new("capture")
+                .long("capture")
+                .help("write a sampled share of the exchanges to this .tar.gz, each request and response as an http/1.1 message rebuilt from what the client saw, h2 included, the response body decoded, with a .json of its timing and connection next to them")
+        )
+        .arg(
+            Arg::new("capture-rate")
+                .long("capture-rate")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.01")
+                .help("share of the requests --capture keeps, 1 for all of them")
+        )
+        .arg(
+            Arg::new("keep-secrets")
+                .long("keep-secrets")
+                .action(ArgAction::SetTrue)
+                .help("write Authorization, Cookie, API key and other credential headers into --capture and --sample-responses as they were sent, rather than as <hidden>")
+        )
+        .arg(
+            Arg::new("tui")
+                .long("tui")
+                .action(ArgAction::SetTrue)
+                .help("show a live dashboard instead of the spinner")
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["tui", "dry-run"])
+                .help("print nothing on stdout but the report, as one line of json once the run is over, for scripts to pipe; add --quiet to keep stderr to errors")
+        )
+        .arg(
+            Arg::new("statsd")
+                .long("statsd")
+                .help("push request counts and latencies every second to this host:port over udp, in statsd format")
+        )
+        .arg(
+            Arg::new("statsd-prefix")
+                .long("statsd-prefix")
+                .default_value("arctic")
+                .requires("statsd")
+                .help("name prefix of the --statsd metrics")
+        )
+        .arg(
+            Arg::new("statsd-tag")
+                .long("statsd-tag")
+                .action(ArgAction::Append)
+                .requires("statsd")
+                .help("dogstatsd tag such as env:staging to attach to every --statsd metric; repeatable")
+        )
+        .arg(
+            Arg::new("influx")
+                .long("influx")
+                .help("send per-second metrics as influx line protocol to this write url, e.g. http://influx:8086/write?db=loadtests, or append them to this file")
+        )
+        .arg(
+            Arg::new("influx-measurement")
+                .long("influx-measurement")
+                .default_value("arctic")
+                .requires("influx")
+                .help("measurement the --influx points go under")
+        )
+        .arg(
+            Arg::new("influx-tag")
+                .long("influx-tag")
+                .action(ArgAction::Append)
+                .requires("influx")
+                .help("key=value tag to attach to every --influx point; repeatable")
+        )
+        .arg(
+            Arg::new("checkpoint")
+                .long("checkpoint")
+                .help("rewrite this json file with the report and time series so far every --checkpoint-interval, so a run that dies still leaves its results")
+        )
+        .arg(
+            Arg::new("checkpoint-interval")
+                .long("checkpoint-interval")
+                .value_parser(parse_duration)
+                .default_value("60s")
+                .requires("checkpoint")
+                .help("how often --checkpoint is rewritten")
+        )
+        .arg(
+            Arg::new("otlp")
+                .long("otlp")
+                .conflicts_with_all(["grpc"])
+                .help("export a client span per request to this otlp/http collector, such as http://localhost:4318; implies --traceparent")
+        )
+        .arg(
+            Arg::new("prometheus-port")
+                .long("prometheus-port")
+                .value_parser(clap::value_parser!(u16))
+                .help("serve live prometheus metrics on this port at /metrics")
+        )
+}
+
+fn run_command() -> Command {
+    load_args(Command::new("run").about("runs a load test, the same as leaving out the subcommand").after_help(SIGNALS))
+        .arg(
+            Arg::new("workers")
+                .long("workers")
+                .value_delimiter(',')
+                .conflicts_with_all(["dry-run", "shard"])
+                .help("comma separated host[:port] list of `arctic worker`s to split the load across, this process only coordinates and merges their stats")
+        )
+}
+
+fn repl_command() -> Command {
+    load_args(Command::new("repl").about("tries a template out one payload and one request at a time before running it").after_help(REPL_COMMANDS))
+}
+
+fn find_max_command() -> Command {
+    load_args(Command::new("find-max").about("raises the request rate step by step to find the highest one the target sustains"))
+        .arg(
+            Arg::new("start-rate")
+                .long("start-rate")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("10")
+                .help("requests per second of the first step")
+        )
+        .arg(
+            Arg::new("rate-step")
+                .long("rate-step")
+                .value_parser(clap::value_parser!(f64))
+                .help("requests per second added after every step that held [default: the start rate]")
+        )
+        .arg(
+            Arg::new("max-rate")
+                .long("max-rate")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("10000")
+                .help("stop stepping up past this rate, a target that holds every step ends the search there")
+        )
+        .arg(
+            Arg::new("step-duration")
+                .long("step-duration")
+                .value_parser(parse_duration)
+                .default_value("10s")
+                .help("how long each step holds its rate")
+        )
+        .arg(
+            Arg::new("max-error-rate")
+                .long("max-error-rate")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("1")
+                .help("percent of failed requests a step may have and still hold")
+        )
+        .arg(
+            Arg::new("max-p99")
+                .long("max-p99")
+                .value_parser(parse_duration)
+                .help("p99 latency a step may have and still hold, e.g. 500ms")
+        )
+        .arg(
+            Arg::new("refine")
+                .long("refine")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("3")
+                .help("steps spent bisecting between the last rate that held and the first that didn't")
+        )
+}
+
+pub fn matrix_command() -> Command {
+    load_args(
+        Command::new("matrix")
+            .about("runs every combination of the --vary values one after the other and compares them in a table")
+            .after_help("every other option applies to each run, the files a run writes numbered after it, --report report.html giving report-1.html, report-2.html and so on; --output gets the table, csv or json by its extension"),
+    )
+    .arg(
+        Arg::new("vary")
+            .long("vary")
+            .action(ArgAction::Append)
+            .required(true)
+            .help("a run option and the values to try it at, e.g. concurrency=10,50,100 or body-size=1KB,100KB; repeat for more, true and false switch a flag on and off")
+    )
+}
+
+fn openapi_command() -> Command {
+    load_args(Command::new("openapi").about("runs a weighted mix of the operations of an openapi document").after_help(SIGNALS))
+        .arg(
+            Arg::new("spec")
+                .long("spec")
+                .required(true)
+                .conflicts_with_all(["graphql", "grpc"])
+                .help("openapi 3 or swagger 2 document in yaml or json; path and required query parameters are filled in per request and json bodies generated from their schemas")
+        )
+        .arg(
+            Arg::new("operation")
+                .long("operation")
+                .action(ArgAction::Append)
+                .help("operationId or \"METHOD /path\" to run, repeat for several and append :weight to skew the mix [default: every operation]")
+        )
+        .arg(
+            Arg::new("base-url")
+                .long("base-url")
+                .help("send the operations to this url instead of the document's first server")
+        )
+}
+
+fn worker_command() -> Command {
+    Command::new("worker")
+        .about("waits for `arctic run --workers` to hand it a share of a load test")
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .default_value("0.0.0.0:7878")
+                .help("address to accept the coordinator on")
+        )
+}
+
+fn compare_command() -> Command {
+    Command::new("compare")
+        .about("diffs two json reports and fails on regressions")
+        .arg(
+            Arg::new("baseline")
+                .required(true)
+                .help("report of the reference run, as written by --output")
+        )
+        .arg(
+            Arg::new("current")
+                .required(true)
+                .help("report of the run to check")
+        )
+        .arg(
+            Arg::new("max-regression")
+                .long("max-regression")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("10")
+                .help("percent any metric may get worse by (percentage points for the error rate) before exiting with status 2")
+        )
+}
+
+fn history_command() -> Command {
+    Command::new("history")
+        .about("lists the runs --history recorded, or diffs two of them")
+        .arg(
+            Arg::new("db")
+                .long("db")
+                .default_value(history::DEFAULT_PATH)
+                .help("sqlite file the runs were recorded in")
+        )
+        .arg(
+            Arg::new("config-key")
+                .long("config-key")
+                .help("only runs of this config key, or of keys starting with it")
+        )
+        .arg(
+            Arg::new("last")
+                .long("last")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20")
+                .help("how many of the latest runs to list")
+        )
+        .arg(
+            Arg::new("compare")
+                .long("compare")
+                .num_args(1..=2)
+                .value_parser(clap::value_parser!(i64))
+                .conflicts_with_all(["config-key", "last"])
+                .help("diff two runs by id, baseline first, like `arctic compare`; a single id is diffed against the run of its config before it")
+        )
+        .arg(
+            Arg::new("max-regression")
+                .long("max-regression")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("10")
+                .requires("compare")
+                .help("percent any metric may get worse by (percentage points for the error rate) before exiting with status 2")
+        )
+}
+
+fn merge_command() -> Command {
+    Command::new("merge")
+        .about("adds the json reports of every --shard of a run up into the report of the whole run")
+        .arg(
+            Arg::new("reports")
+                .required(true)
+                .num_args(1..)
+                .help("the --output of each shard, e.g. shard*.json; the run's flags are taken from them, so its template and config files have to be where the shards found them")
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("write the merged summary to this file")
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(["json", "csv"])
+                .help("format of --output, inferred from the file extension by default")
+        )
+        .arg(
+            Arg::new("report")
+                .long("report")
+                .help("write the merged html report to this file")
+        )
+        .arg(
+            Arg::new("junit")
+                .long("junit")
+                .help("write the thresholds and response checks of the whole run to this file as junit xml")
+        )
+        .arg(
+            Arg::new("history")
+                .long("history")
+                .num_args(0..=1)
+                .default_missing_value(history::DEFAULT_PATH)
+                .help("append the merged run to this sqlite file (~/.arctic/history.sqlite without a value), for `arctic history`")
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("print nothing on stdout but the merged report, as one line of json")
+        )
+}
+
+fn sse_command() -> Command {
+    Command::new("sse")
+        .about("holds server-sent event streams open and times the events on them")
+        .arg(
+            Arg::new("endpoint")
+                .short('e')
+                .long("endpoint")
+                .required(true)
+                .help("http:// or https:// url of the event stream")
+        )
+        .arg(
+            Arg::new("time")
+                .short('t')
+                .long("time")
+                .value_parser(clap::value_parser!(u64))
+                .required(true)
+                .help("test duration in seconds")
+        )
+        .arg(
+            Arg::new("concurrency")
+                .short('c')
+                .long("concurrency")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1")
+                .help("number of streams kept open")
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_parser(parse_duration)
+                .help("give up on a stream's response headers after this long [default: 10s]")
+        )
+        .arg(
+            Arg::new("header")
+                .short('H')
+                .long("header")
+                .action(ArgAction::Append)
+                .help("extra request header, Name:value, repeatable")
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("write a json summary to this file")
+        )
+}
+
+fn ws_command() -> Command {
+    Command::new("ws")
+        .about("floods a websocket endpoint with randomized json messages")
+        .arg(
+            Arg::new("endpoint")
+                .short('e')
+                .long("endpoint")
+                .required(true)
+                .help("ws:// or wss:// url")
+        )
+        .arg(
+            Arg::new("data")
+                .short('d')
+                .long("data")
+                .required(true)
+                .help("json template the messages are generated from")
+        )
+        .arg(
+            Arg::new("time")
+                .short('t')
+                .long("time")
+                .value_parser(clap::value_parser!(u64))
+                .required(true)
+                .help("test duration in seconds")
+        )
+        .arg(
+            Arg::new("concurrency")
+                .short('c')
+                .long("concurrency")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1")
+                .help("number of connections kept open")
+        )
+        .arg(
+            Arg::new("rate")
+                .long("rate")
+                .value_parser(clap::value_parser!(f64))
+                .help("messages per second over all connections, defaults to one per connection")
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(clap::value_parser!(u64))
+                .help("seed for message generation")
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_parser(parse_duration)
+                .help("give up on connecting after this long [default: 10s]")
+        )
+        .arg(
+            Arg::new("header")
+                .short('H')
+                .long("header")
+                .action(ArgAction::Append)
+                .help("extra handshake header, Name:value, repeatable")
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("write a json summary to this file")
+        )
+}
@@ -0,0 +1,302 @@
+use reqwest::cookie::Jar;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::redirect::Policy;
+use reqwest::{Client, ClientBuilder, Proxy, Request, Response, Url};
+use serde::Serialize;
+use futures_util::future::join_all;
+use hyper::client::connect::HttpInfo;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::config::LoadConfig;
+use crate::dns::{DnsRefresh, Resolver};
+use crate::units::serialize_opt_secs;
+
+// --http-version, left unset the protocol is whatever alpn negotiates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum HttpVersion {
+    #[serde(rename = "1.1")]
+    Http1,
+    #[serde(rename = "2")]
+    Http2,
+}
+
+impl FromStr for HttpVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.1" => Ok(HttpVersion::Http1),
+            "2" => Ok(HttpVersion::Http2),
+            // reqwest's quic support is unstable and not part of this build
+            "3" => Err("http/3 isn't supported by this build, the http version is 1.1 or 2".to_string()),
+            other => Err(format!("unknown http version: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            HttpVersion::Http1 => "1.1",
+            HttpVersion::Http2 => "2",
+        };
+        f.write_str(name)
+    }
+}
+
+// --proxy, with the credentials of --proxy-user folded into the url
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    url: Url,
+}
+
+impl ProxyConfig {
+    // http://, https://, socks5:// or socks5h:// to resolve names on the
+    // proxy; `user` is "user:pass" and wins over credentials in the url
+    pub fn parse(url: &str, user: Option<&str>) -> Result<Self, String> {
+        let mut url = Url::parse(url).map_err(|e| format!("invalid --proxy {}: {}", url, e))?;
+        if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+            return Err(format!("unsupported proxy scheme: {}", url.scheme()));
+        }
+        if let Some(user) = user {
+            let (name, password) = user.split_once(':').unwrap_or((user, ""));
+            let _ = url.set_username(name);
+            let _ = url.set_password(Some(password));
+        }
+        Ok(ProxyConfig { url })
+    }
+
+    fn proxy(&self) -> Result<Proxy, reqwest::Error> {
+        Proxy::all(self.url.clone())
+    }
+}
+
+// the url without credentials
+impl fmt::Display for ProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut url = self.url.clone();
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+        f.write_str(url.as_str().trim_end_matches('/'))
+    }
+}
+
+impl Serialize for ProxyConfig {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+// reqwest's own default for --pool-idle-timeout
+const DEFAULT_POOL_IDLE: Duration = Duration::from_secs(90);
+// address pairs kept before the ones idle too long are dropped
+const TRACKED_CONNECTIONS: usize = 4096;
+
+tokio::task_local! {
+    // redirects followed for the request being sent
+    static HOPS: Cell<u64>;
+}
+
+// reqwest follows redirects out of sight, the policy counts the hops of
+// whichever request the task is executing
+fn redirect_policy(max: usize) -> Policy {
+    if max == 0 {
+        return Policy::none();
+    }
+    Policy::custom(move |attempt| {
+        if attempt.previous().len() > max {
+            return attempt.error(format!("more than {} redirects", max));
+        }
+        let _ = HOPS.try_with(|hops| hops.set(hops.get() + 1));
+        attempt.follow()
+    })
+}
+
+// sends `request`, along with the number of redirects it took
+pub async fn execute(client: &Client, request: Request) -> (reqwest::Result<Response>, u64) {
+    HOPS.scope(Cell::new(0), async {
+        let response = client.execute(request).await;
+        (response, HOPS.with(Cell::get))
+    })
+    .await
+}
+
+// tls and proxy settings every client of a load test shares
+fn base(config: &LoadConfig) -> Result<ClientBuilder, Box<dyn std::error::Error>> {
+    let mut builder = config.tls.apply(Client::builder())?;
+    if config.tls.host_override.is_some() {
+        builder = builder.dns_resolver(resolver(config, None));
+    }
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(proxy.proxy()?);
+    }
+    Ok(builder)
+}
+
+fn resolver(config: &LoadConfig, dns: Option<&Arc<DnsRefresh>>) -> Arc<Resolver> {
+    Arc::new(Resolver { dns: dns.cloned(), host_override: config.tls.host_override.clone(), family: config.tls.family })
+}
+
+// `dns` resolves the names with --dns-refresh; `jar` keeps the cookies of
+// one virtual user, over the clients it's given
+pub fn build(config: &LoadConfig, dns: Option<&Arc<DnsRefresh>>, jar: Option<&Arc<Jar>>) -> Result<Client, Box<dyn std::error::Error>> {
+    let mut builder = base(config)?.redirect(redirect_policy(config.max_redirects));
+    builder = match jar {
+        Some(jar) => builder.cookie_provider(jar.clone()),
+        None => builder.cookie_store(config.cookies),
+    };
+    if let Some(dns) = dns {
+        builder = builder.dns_resolver(resolver(config, Some(dns)));
+    }
+    if let Some(timeout) = config.timeout {
+        builder = builder.timeout(timeout);
+    }
+    match config.http_version {
+        Some(HttpVersion::Http1) => builder = builder.http1_only(),
+        // h2c on plain http, h2 without falling back over tls
+        Some(HttpVersion::Http2) => builder = builder.http2_prior_knowledge(),
+        None => {}
+    }
+
+    if let Some(max) = config.pool.max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    if let Some(idle) = config.pool.idle_timeout {
+        builder = builder.pool_idle_timeout(idle);
+    }
+    if !config.pool.keepalive {
+        builder = builder.pool_max_idle_per_host(0);
+    }
+
+    Ok(builder.default_headers(default_headers(config)?).build()?)
+}
+
+// what the client sends with every request: --header, auth and api key
+pub fn default_headers(config: &LoadConfig) -> Result<HeaderMap, Box<dyn std::error::Error>> {
+    let mut headers = HeaderMap::new();
+    for header in &config.headers {
+        headers.insert(HeaderName::from_bytes(header.name.as_bytes())?, HeaderValue::from_str(&header.value)?);
+    }
+    if let Some(value) = config.auth.as_ref().and_then(|auth| auth.header_value()) {
+        let mut value = HeaderValue::from_str(&value)?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+    if let Some(key) = &config.api_key {
+        let mut value = HeaderValue::from_str(&key.value)?;
+        value.set_sensitive(true);
+        headers.insert(HeaderName::from_bytes(key.name.as_bytes())?, value);
+    }
+    Ok(headers)
+}
+
+// --max-idle-per-host, --pool-idle-timeout, --disable-keepalive and
+// --preconnect
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolConfig {
+    pub max_idle_per_host: Option<usize>,
+    #[serde(rename = "idle_timeout_secs", serialize_with = "serialize_opt_secs")]
+    pub idle_timeout: Option<Duration>,
+    pub keepalive: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preconnect: Option<usize>,
+}
+
+impl PoolConfig {
+    // how long an unused connection stays open, none when none are kept
+    fn idle(&self) -> Option<Duration> {
+        match self.keepalive && self.max_idle_per_host != Some(0) {
+            true => Some(self.idle_timeout.unwrap_or(DEFAULT_POOL_IDLE)),
+            false => None,
+        }
+    }
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig { max_idle_per_host: None, idle_timeout: None, keepalive: true, preconnect: None }
+    }
+}
+
+// tells new connections from reused ones by the local and remote address
+// hyper hands back with each response; a pair unseen for longer than the
+// pool keeps connections idle is a new connection on a recycled port
+#[derive(Debug)]
+pub struct ConnectionTracker {
+    idle: Option<Duration>,
+    seen: Mutex<HashMap<(SocketAddr, SocketAddr), Instant>>,
+}
+
+impl ConnectionTracker {
+    pub fn new(pool: &PoolConfig) -> Self {
+        ConnectionTracker { idle: pool.idle(), seen: Mutex::new(HashMap::new()) }
+    }
+
+    // whether `response` came over a connection opened for it, none when
+    // hyper didn't say
+    pub fn is_new(&self, response: &Response) -> Option<bool> {
+        let info = response.extensions().get::<HttpInfo>()?;
+        let Some(idle) = self.idle else { return Some(true) };
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+        let new = seen
+            .insert((info.local_addr(), info.remote_addr()), now)
+            .is_none_or(|last| now.duration_since(last) > idle);
+        if seen.len() > TRACKED_CONNECTIONS {
+            seen.retain(|_, last| now.duration_since(*last) <= idle);
+        }
+        Some(new)
+    }
+}
+
+// --preconnect: `count` HEAD requests at once to each of `origins`, spread
+// over `clients` (one per virtual user with --cookies), so that many
+// connections sit in the pools with their handshakes done when the
+// measured window opens; what comes back doesn't matter. Returns how many
+// connections were opened
+pub async fn preconnect(clients: &[&Client], origins: &[String], count: usize, connections: &ConnectionTracker) -> usize {
+    let requests = origins.iter().flat_map(|origin| {
+        (0..count).map(move |index| {
+            let client = clients[index % clients.len()];
+            async move {
+                match client.head(origin).send().await {
+                    Ok(response) => connections.is_new(&response).unwrap_or(true),
+                    Err(e) => {
+                        debug!(origin = %origin, error = %e, "preconnect failed");
+                        false
+                    }
+                }
+            }
+        })
+    });
+    join_all(requests).await.into_iter().filter(|&opened| opened).count()
+}
+
+// the same tls and proxy settings without the default headers, for side
+// requests such as fetching oauth tokens
+pub fn build_bare(config: &LoadConfig) -> Result<Client, Box<dyn std::error::Error>> {
+    Ok(base(config)?.build()?)
+}
+
+// the same static headers, for clients that aren't reqwest based
+pub fn header_pairs(config: &LoadConfig) -> Vec<(String, String)> {
+    let mut pairs: Vec<(String, String)> = config
+        .headers
+        .iter()
+        .map(|h| (h.name.clone(), h.value.clone()))
+        .collect();
+    if let Some(value) = config.auth.as_ref().and_then(|auth| auth.header_value()) {
+        pairs.push((AUTHORIZATION.to_string(), value));
+    }
+    if let Some(key) = &config.api_key {
+        pairs.push((key.name.clone(), key.value.clone()));
+    }
+    pairs
+}
@@ -0,0 +1,117 @@
+use serde_json::Value;
+use std::fs;
+use std::io::IsTerminal;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Better {
+    Higher,
+    Lower,
+}
+
+// (label, json pointer into a report, which direction is an improvement)
+const METRICS: &[(&str, &str, Better)] = &[
+    ("requests/s", "/requests_per_second", Better::Higher),
+    ("error rate", "", Better::Lower),
+    ("latency mean", "/latency_ms/mean", Better::Lower),
+    ("latency p50", "/latency_ms/p50", Better::Lower),
+    ("latency p90", "/latency_ms/p90", Better::Lower),
+    ("latency p95", "/latency_ms/p95", Better::Lower),
+    ("latency p99", "/latency_ms/p99", Better::Lower),
+    ("latency max", "/latency_ms/max", Better::Lower),
+];
+
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub metric: &'static str,
+    pub baseline: f64,
+    pub current: f64,
+    // relative change in percent, percentage points for the error rate
+    pub change: f64,
+    pub improved: bool,
+    pub regressed: bool,
+}
+
+// two json reports written by --output; `max_regression` is the percentage
+// any metric may get worse by
+pub fn compare(baseline: &Value, current: &Value, max_regression: f64) -> Result<Vec<Change>, String> {
+    let mut changes = Vec::new();
+    for &(metric, pointer, better) in METRICS {
+        let (baseline, current) = match pointer.is_empty() {
+            true => (error_rate(baseline)?, error_rate(current)?),
+            false => (number(baseline, pointer)?, number(current, pointer)?),
+        };
+        let change = match metric {
+            "error rate" => (current - baseline) * 100.0,
+            _ if baseline == 0.0 => 0.0,
+            _ => (current - baseline) / baseline * 100.0,
+        };
+        let worse = match better {
+            Better::Higher => -change,
+            Better::Lower => change,
+        };
+        changes.push(Change {
+            metric,
+            baseline,
+            current,
+            change,
+            improved: worse < 0.0,
+            regressed: worse > max_regression,
+        });
+    }
+    Ok(changes)
+}
+
+pub fn load(path: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(serde_json::from_str(&text).map_err(|e| format!("{}: not a json report: {}", path, e))?)
+}
+
+pub fn print_table(changes: &[Change]) {
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    println!("{:<14} {:>12} {:>12} {:>10}", "metric", "baseline", "current", "change");
+    for c in changes {
+        let unit = if c.metric == "error rate" { "pp" } else { "%" };
+        let change = format!("{:+.1}{}", c.change, unit);
+        let (start, end) = match (color, c.regressed, c.improved) {
+            (true, true, _) => (RED, RESET),
+            (true, false, true) => (GREEN, RESET),
+            _ => ("", ""),
+        };
+        let mark = if c.regressed { "  regressed" } else { "" };
+        println!(
+            "{}{:<14} {:>12} {:>12} {:>10}{}{}",
+            start,
+            c.metric,
+            format_value(c.metric, c.baseline),
+            format_value(c.metric, c.current),
+            change,
+            mark,
+            end
+        );
+    }
+}
+
+fn format_value(metric: &str, value: f64) -> String {
+    match metric {
+        "error rate" => format!("{:.2}%", value * 100.0),
+        "requests/s" => format!("{:.2}", value),
+        _ => format!("{:.2}ms", value),
+    }
+}
+
+fn number(report: &Value, pointer: &str) -> Result<f64, String> {
+    report
+        .pointer(pointer)
+        .and_then(Value::as_f64)
+        .ok_or_else(|| format!("report has no {}", pointer.trim_start_matches('/').replace('/', ".")))
+}
+
+fn error_rate(report: &Value) -> Result<f64, String> {
+    let total = number(report, "/total_requests")?;
+    let failed = number(report, "/failed_requests")?;
+    Ok(if total > 0.0 { failed / total } else { 0.0 })
+}
@@ -0,0 +1,1174 @@
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::adaptive::Adaptive;
+use crate::auth::{Auth, BodySignature, Header, HeaderTemplate, OAuthConfig};
+use crate::body::{BodyFormat, Compression, FilePart, Padding, Protobuf, RandomBytes, StreamBody, Xml};
+use crate::chaos::Chaos;
+use crate::checkpoint::CheckpointConfig;
+use crate::checks::Assertion;
+use crate::client::{HttpVersion, PoolConfig, ProxyConfig};
+use crate::graphql::GraphQl;
+use crate::grpc::GrpcConfig;
+use crate::har;
+use crate::influx::InfluxConfig;
+use crate::metrics::MetricSpec;
+use crate::openapi::{self, Selector};
+use crate::corpus::CorpusOrder;
+use crate::feed::FeedOrder;
+use crate::failure::ExpectedStatus;
+use crate::fingerprint::{Fingerprints, Rotate};
+use crate::generator::TemplateSpec;
+use crate::configfile::{FileConfig, DEFAULT_PATH as DEFAULT_CONFIG_PATH};
+use crate::profile::{LoadProfile, Preset, PresetKind, StageUnit};
+use crate::report::OutputFormat;
+use crate::scenario::Scenario;
+use crate::setup::Setup;
+use crate::schema::{Invalid, Schema};
+use crate::script::Script;
+use crate::sigv4::SigV4;
+use crate::statsd::StatsdConfig;
+use crate::target::{self, Target};
+use crate::threshold::Threshold;
+use crate::tls::{HostOverride, IpFamily, Resolve, TlsOptions};
+use crate::units::{serialize_opt_secs, serialize_secs};
+
+// in-flight limit for rate based runs when --concurrency isn't given
+const DEFAULT_RATE_WORKERS: usize = 100;
+// how often a soak profile prints a summary without --summary-every
+const SOAK_SUMMARY_EVERY: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadConfig {
+    pub endpoints: Vec<Target>,
+    pub duration: Option<u64>,
+    pub requests: Option<u64>,
+    #[serde(rename = "warmup_secs", serialize_with = "serialize_opt_secs")]
+    pub warmup: Option<Duration>,
+    // once the run ends, how long the requests still in flight get to
+    // finish before they're abandoned
+    #[serde(rename = "drain_timeout_secs", serialize_with = "serialize_secs")]
+    pub drain_timeout: Duration,
+    // the payload backend --generator named, see generator::Registry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generator: Option<String>,
+    // --data templates, none when bodies come from a corpus or a har
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub data: Vec<TemplateSpec>,
+    // --data -: payloads read off stdin as they arrive, the run ends with it
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stdin: bool,
+    pub corpus: Option<String>,
+    pub corpus_order: CorpusOrder,
+    pub feed: Option<String>,
+    pub feed_order: FeedOrder,
+    pub method: String,
+    pub body_format: BodyFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protobuf: Option<Protobuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xml: Option<Xml>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub random_bytes: Option<RandomBytes>,
+    // the query parameter --cache-bust sets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_bust: Option<String>,
+    // --validate-payloads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_schema: Option<Schema>,
+    pub file: Option<FilePart>,
+    pub padding: Option<Padding>,
+    // --stream-body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_body: Option<StreamBody>,
+    pub compress: Option<Compression>,
+    pub graphql: Option<GraphQl>,
+    pub grpc: Option<GrpcConfig>,
+    pub seed: u64,
+    pub concurrency: Option<usize>,
+    pub stages: Option<String>,
+    // the --profile the stages came from
+    pub preset: Option<String>,
+    pub profile: LoadProfile,
+    // --adaptive, the concurrency moves to hold a p95
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adaptive: Option<Adaptive>,
+    #[serde(rename = "timeout_secs", serialize_with = "serialize_opt_secs")]
+    pub timeout: Option<Duration>,
+    pub retries: u32,
+    #[serde(rename = "retry_backoff_secs", serialize_with = "serialize_secs")]
+    pub retry_backoff: Duration,
+    // --respect-retry-after, the longest Retry-After waited out
+    #[serde(rename = "max_retry_after_secs", serialize_with = "serialize_opt_secs")]
+    pub retry_after: Option<Duration>,
+    // --chaos, in the order requests draw them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chaos: Vec<Chaos>,
+    pub headers: Vec<Header>,
+    // the headers with placeholders, rendered per request
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub header_templates: Vec<HeaderTemplate>,
+    // --user-agents or --header-sets
+    pub fingerprints: Option<Fingerprints>,
+    pub auth: Option<Auth>,
+    #[serde(rename = "api_key_header")]
+    pub api_key: Option<Header>,
+    // --sign, an hmac of each body in a header
+    pub sign: Option<BodySignature>,
+    pub tls: TlsOptions,
+    pub http_version: Option<HttpVersion>,
+    // 0 leaves redirects to the caller as they are
+    pub max_redirects: usize,
+    pub pool: PoolConfig,
+    // --dns-refresh, how old a host's addresses get before it's looked up again
+    #[serde(rename = "dns_refresh_secs", serialize_with = "serialize_opt_secs")]
+    pub dns_refresh: Option<Duration>,
+    pub proxy: Option<ProxyConfig>,
+    // a cookie jar per virtual user
+    pub cookies: bool,
+    // --setup, the request that logs each virtual user in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setup: Option<Setup>,
+    // --scenario, the steps every virtual user walks; its requests are the endpoints
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scenario: Option<Scenario>,
+    // sent once before and after the run, outside of it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub global_setup: Option<Setup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub teardown: Option<Setup>,
+    // a w3c traceparent header on every http request
+    pub traceparent: bool,
+    // --expect-status, the statuses an http response succeeds with instead
+    // of anything below 400
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expect_status: Option<ExpectedStatus>,
+    pub assertions: Vec<Assertion>,
+    pub thresholds: Vec<Threshold>,
+    // --metric numbers read out of the json responses
+    pub metrics: Vec<MetricSpec>,
+    pub abort_on_error_rate: Option<f64>,
+    #[serde(rename = "abort_window_secs", serialize_with = "serialize_secs")]
+    pub abort_window: Duration,
+    pub timing: bool,
+    // pause after each request of a virtual user, give or take the jitter
+    #[serde(rename = "think_time_secs", serialize_with = "serialize_opt_secs")]
+    pub think_time: Option<Duration>,
+    #[serde(rename = "think_jitter_secs", serialize_with = "serialize_secs")]
+    pub think_jitter: Duration,
+    // reload the --data template when it changes
+    pub watch: bool,
+    // --script, its check() is also one of the assertions
+    #[serde(skip)]
+    pub script: Option<Arc<Script>>,
+    #[serde(skip)]
+    pub output: Option<String>,
+    #[serde(skip)]
+    pub format: OutputFormat,
+    #[serde(skip)]
+    pub report: Option<String>,
+    #[serde(skip)]
+    pub junit: Option<String>,
+    // --history, the sqlite file the run is appended to
+    #[serde(skip)]
+    pub history: Option<String>,
+    #[serde(skip)]
+    pub timeseries: Option<String>,
+    #[serde(skip)]
+    pub prometheus_port: Option<u16>,
+    #[serde(skip)]
+    pub statsd: Option<StatsdConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub influx: Option<InfluxConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<CheckpointConfig>,
+    // otlp/http collector the client spans go to
+    #[serde(skip)]
+    pub otlp: Option<String>,
+    #[serde(skip)]
+    pub summary_every: Option<Duration>,
+    #[serde(skip)]
+    pub tui: bool,
+    // --json, the report on one line of stdout and nothing else there
+    #[serde(skip)]
+    pub json: bool,
+    #[serde(skip)]
+    pub log_requests: Option<String>,
+    #[serde(skip)]
+    pub log_body_limit: usize,
+    // keep this many exchanges, failures first, and write them to sample_dir
+    #[serde(skip)]
+    pub sample_responses: Option<usize>,
+    #[serde(skip)]
+    pub sample_dir: String,
+    // --capture, the archive a --capture-rate share of the exchanges goes to
+    #[serde(skip)]
+    pub capture: Option<String>,
+    #[serde(skip)]
+    pub capture_rate: f64,
+    // credential headers written as they were rather than hidden
+    #[serde(skip)]
+    pub keep_secrets: bool,
+    #[serde(skip)]
+    pub shard: Option<Shard>,
+    // print this many rendered requests instead of running
+    #[serde(skip)]
+    pub dry_run: Option<u64>,
+}
+
+// one worker's slice of a distributed run, or the --shard of this one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Shard {
+    // "3/10", the third of ten, counting from 1
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let usage = || format!("invalid shard {}: expected <n>/<count>, e.g. 3/10", s);
+        let (index, count) = s.trim().split_once('/').ok_or_else(usage)?;
+        let (index, count): (u64, u64) = (index.trim().parse().map_err(|_| usage())?, count.trim().parse().map_err(|_| usage())?);
+        if count == 0 {
+            return Err(format!("invalid shard {}: there's at least one shard", s));
+        }
+        if index == 0 || index > count {
+            return Err(format!("invalid shard {}: expected 1/{} to {}/{}", s, count, count, count));
+        }
+        Ok(Shard { index: index - 1, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index + 1, self.count)
+    }
+}
+
+impl LoadConfig {
+    // a single endpoint hit by one worker until the run is stopped; the
+    // builder methods below cover the common knobs, everything else is a
+    // public field
+    pub fn new(endpoint: &str) -> Self {
+        LoadConfig {
+            endpoints: vec![Target { url: endpoint.to_string(), weight: 1.0, method: None, tags: BTreeMap::new(), replay: None, operation: None }],
+            duration: None,
+            requests: None,
+            warmup: None,
+            drain_timeout: Duration::from_secs(30),
+            generator: None,
+            data: Vec::new(),
+            stdin: false,
+            corpus: None,
+            corpus_order: CorpusOrder::Cycle,
+            feed: None,
+            feed_order: FeedOrder::Cycle,
+            method: "POST".to_string(),
+            body_format: BodyFormat::Json,
+            protobuf: None,
+            xml: None,
+            random_bytes: None,
+            payload_schema: None,
+            cache_bust: None,
+            file: None,
+            padding: None,
+            stream_body: None,
+            compress: None,
+            graphql: None,
+            grpc: None,
+            seed: rand::random(),
+            concurrency: None,
+            stages: None,
+            preset: None,
+            profile: LoadProfile::constant(StageUnit::Concurrency, 1.0),
+            adaptive: None,
+            timeout: None,
+            retries: 0,
+            retry_backoff: Duration::from_millis(100),
+            retry_after: None,
+            chaos: Vec::new(),
+            headers: Vec::new(),
+            header_templates: Vec::new(),
+            fingerprints: None,
+            auth: None,
+            sign: None,
+            api_key: None,
+            tls: TlsOptions::default(),
+            http_version: None,
+            max_redirects: 10,
+            pool: PoolConfig::default(),
+            dns_refresh: None,
+            proxy: None,
+            cookies: false,
+            setup: None,
+            scenario: None,
+            global_setup: None,
+            teardown: None,
+            traceparent: false,
+            expect_status: None,
+            assertions: Vec::new(),
+            thresholds: Vec::new(),
+            metrics: Vec::new(),
+            abort_on_error_rate: None,
+            abort_window: Duration::from_secs(10),
+            timing: false,
+            think_time: None,
+            think_jitter: Duration::ZERO,
+            watch: false,
+            script: None,
+            output: None,
+            format: OutputFormat::default(),
+            report: None,
+            junit: None,
+            history: None,
+            timeseries: None,
+            prometheus_port: None,
+            statsd: None,
+            influx: None,
+            checkpoint: None,
+            otlp: None,
+            summary_every: None,
+            tui: false,
+            json: false,
+            log_requests: None,
+            log_body_limit: 1024,
+            sample_responses: None,
+            sample_dir: "samples".to_string(),
+            capture: None,
+            capture_rate: 0.01,
+            keep_secrets: false,
+            shard: None,
+            dry_run: None,
+        }
+    }
+
+    pub fn duration(mut self, secs: u64) -> Self {
+        self.duration = Some(secs);
+        self
+    }
+
+    pub fn requests(mut self, requests: u64) -> Self {
+        self.requests = Some(requests);
+        self
+    }
+
+    // makes this run worker `index` of `count`: the load is split and the
+    // workers take turns on sequence numbers, so together they send what
+    // one run would have
+    pub fn share(mut self, index: usize, count: usize) -> Self {
+        self.profile = self.profile.share(index, count);
+        self.shard = Some(Shard { index: index as u64, count: count as u64 });
+        self
+    }
+
+    // unmeasured traffic before the run proper
+    pub fn warmup(mut self, warmup: Duration) -> Self {
+        self.warmup = Some(warmup);
+        self
+    }
+
+    // how long requests in flight at the end may take, see drain_timeout
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    // per phase latencies, see timing::Phases
+    pub fn timing(mut self) -> Self {
+        self.timing = true;
+        self
+    }
+
+    // a pause of `think` +/- up to `jitter` after each request of a worker
+    pub fn think_time(mut self, think: Duration, jitter: Duration) -> Self {
+        self.think_time = Some(think);
+        self.think_jitter = jitter;
+        self
+    }
+
+    // fixed worker count, or the in-flight cap for rate based runs
+    pub fn concurrency(mut self, workers: usize) -> Self {
+        self.concurrency = Some(workers);
+        if self.profile.unit == StageUnit::Concurrency && self.stages.is_none() {
+            self.profile = LoadProfile::constant(StageUnit::Concurrency, workers as f64);
+        }
+        self
+    }
+
+    pub fn rate(mut self, rps: f64) -> Self {
+        self.stages = None;
+        self.profile = LoadProfile::constant(StageUnit::Rps, rps);
+        self
+    }
+
+    pub fn stages(mut self, spec: &str, unit: StageUnit) -> Result<Self, String> {
+        self.profile = LoadProfile::parse(spec, unit)?;
+        self.stages = Some(spec.to_string());
+        if self.duration.is_none() {
+            self.duration = Some(self.profile.total_duration().as_secs_f64().ceil() as u64);
+        }
+        Ok(self)
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn assert(mut self, assertion: Assertion) -> Self {
+        self.assertions.push(assertion);
+        self
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = match matches.get_one::<String>("config") {
+            Some(path) => FileConfig::load(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => FileConfig::load(DEFAULT_CONFIG_PATH)?,
+            None => FileConfig::default(),
+        };
+
+        let output = pick(matches, "output", file.output.clone());
+        let format = match pick::<String>(matches, "format", file.format.clone()) {
+            Some(format) => format.parse()?,
+            None => output
+                .as_deref()
+                .map(OutputFormat::from_path)
+                .unwrap_or_default(),
+        };
+
+        let (specs, targets, har) = if ["endpoint", "targets", "har"].iter().any(|id| explicit(matches, id)) {
+            (
+                matches.get_many::<String>("endpoint").unwrap_or_default().cloned().collect(),
+                matches.get_one::<String>("targets").cloned(),
+                matches.get_one::<String>("har").cloned(),
+            )
+        } else {
+            (
+                file.endpoint.iter().chain(&file.endpoints).cloned().collect::<Vec<_>>(),
+                file.targets.clone(),
+                file.har.clone(),
+            )
+        };
+        let mut endpoints = specs
+            .iter()
+            .map(|spec| Target::parse(spec))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(path) = targets {
+            endpoints.extend(Target::load_file(&path)?);
+        }
+        if let Some(path) = har {
+            endpoints.extend(har::load(&path, pick(matches, "har-host", file.har_host.clone()).as_deref())?);
+        }
+        // only `arctic openapi` has a --spec
+        if let Some(path) = matches.try_get_one::<String>("spec").ok().flatten() {
+            let operations = matches
+                .get_many::<String>("operation")
+                .unwrap_or_default()
+                .map(|spec| Selector::parse(spec))
+                .collect::<Result<Vec<_>, _>>()?;
+            endpoints.extend(openapi::load(path, matches.get_one::<String>("base-url").map(String::as_str), &operations)?);
+        }
+        if let Some(mix) = pick::<String>(matches, "mix", file.mix.clone()) {
+            endpoints = match endpoints.as_slice() {
+                [base] if base.replay.is_none() && base.operation.is_none() => base.mix(&mix)?,
+                _ => return Err("--mix needs a single --endpoint, the base url of its paths".into()),
+            };
+        }
+        let scenario = match pick::<String>(matches, "scenario", file.scenario.clone()) {
+            Some(_) if pick::<String>(matches, "grpc", file.grpc.clone()).is_some() => {
+                return Err("--scenario sends http requests, it doesn't combine with --grpc".into())
+            }
+            Some(path) => {
+                let base = match endpoints.as_slice() {
+                    [] => None,
+                    [base] if base.replay.is_none() && base.operation.is_none() => Some(base.url.clone()),
+                    _ => return Err("--scenario takes a single --endpoint, the base url of its paths".into()),
+                };
+                let (scenario, steps) = Scenario::load(&path, base.as_deref())?;
+                endpoints = steps;
+                Some(scenario)
+            }
+            None => None,
+        };
+        if endpoints.is_empty() {
+            return Err("no endpoints given, use --endpoint, --targets, --har or a config file".into());
+        }
+        let script = match pick::<String>(matches, "script", file.script.clone()) {
+            Some(path) => Some(Arc::new(Script::load(&path)?)),
+            None => None,
+        };
+        let generator = pick::<String>(matches, "generator", file.generator.clone());
+        // other backends make payloads without a template
+        let templated = match &generator {
+            Some(name) => name == "template",
+            None => !script.as_ref().is_some_and(|script| script.has_generate()),
+        };
+        // --data and --corpus replace each other like --stages and --rate
+        let (data, corpus) = if explicit(matches, "data") || explicit(matches, "corpus") {
+            (
+                matches.get_many::<String>("data").unwrap_or_default().cloned().collect(),
+                matches.get_one::<String>("corpus").cloned(),
+            )
+        } else {
+            (file.data.iter().chain(&file.templates).cloned().collect::<Vec<_>>(), file.corpus.clone())
+        };
+        let mut data = data.iter().map(|spec| TemplateSpec::parse(spec)).collect::<Result<Vec<_>, _>>()?;
+        let random_bytes = pick::<String>(matches, "body-bytes", file.body_bytes.clone());
+        if random_bytes.is_some() && (!data.is_empty() || corpus.is_some()) {
+            return Err("--body-bytes sends random bytes, it doesn't combine with --data or --corpus".into());
+        }
+        let stdin = data.iter().any(|spec| spec.path == "-");
+        if stdin {
+            if data.len() > 1 {
+                return Err("--data - reads every payload from stdin, it can't be mixed with templates".into());
+            }
+            if corpus.is_some() {
+                return Err("--data - and --corpus can't both be given".into());
+            }
+            data.clear();
+        }
+        if scenario.is_some() && (stdin || random_bytes.is_some()) {
+            return Err("--scenario steps bring their own bodies, they don't combine with --data - or --body-bytes".into());
+        }
+        match (data.is_empty(), &corpus) {
+            (false, Some(_)) => return Err("data and corpus can't both be given".into()),
+            (false, None) => {}
+            // recorded requests bring their own bodies
+            (true, Some(_)) => {}
+            (true, None) if stdin || random_bytes.is_some() || !templated || endpoints.iter().all(|t| t.replay.is_some() || t.operation.is_some()) => {}
+            (true, None) => return Err("no template given, use --data, --corpus or a config file".into()),
+        }
+        let proxy_user = pick::<String>(matches, "proxy-user", file.proxy_user.clone());
+        let otlp = pick::<String>(matches, "otlp", file.otlp.clone());
+        let corpus_order: CorpusOrder = pick::<String>(matches, "corpus-order", file.corpus_order.clone())
+            .unwrap()
+            .parse()?;
+
+        // --stages, --rate and --profile replace each other, so any one on
+        // the command line shadows all of them from the file
+        let (stages, rate, preset) = if ["stages", "rate", "profile"].iter().any(|id| explicit(matches, id)) {
+            (
+                matches.get_one::<String>("stages").cloned(),
+                matches.get_one::<f64>("rate").copied(),
+                matches.get_one::<String>("profile").cloned(),
+            )
+        } else {
+            (file.stages.clone(), file.rate, file.profile.clone())
+        };
+        let parsed_preset = preset.as_deref().map(Preset::parse).transpose()?;
+        let stages = parsed_preset.map(|preset| preset.stages()).or(stages);
+        let concurrency = pick(matches, "concurrency", file.concurrency);
+        let profile = match (&stages, rate) {
+            (Some(spec), _) => {
+                let unit: StageUnit = match parsed_preset {
+                    Some(preset) => preset.unit,
+                    None => pick::<String>(matches, "stage-unit", file.stage_unit.clone()).unwrap().parse()?,
+                };
+                LoadProfile::parse(spec, unit)?
+            }
+            (None, Some(rate)) => LoadProfile::constant(StageUnit::Rps, rate),
+            (None, None) => LoadProfile::constant(StageUnit::Concurrency, concurrency.unwrap_or(1) as f64),
+        };
+        let adaptive = match pick(matches, "adaptive", file.adaptive).unwrap() {
+            true => {
+                if stages.is_some() || rate.is_some() {
+                    return Err("--adaptive sets the concurrency itself, it doesn't combine with --rate, --stages or --profile".into());
+                }
+                let adaptive = Adaptive {
+                    target_p95: pick(matches, "target-p95", file.target_p95).ok_or("--adaptive needs a --target-p95")?,
+                    start: concurrency.unwrap_or(1),
+                    min: 1,
+                    max: pick(matches, "max-concurrency", file.max_concurrency).unwrap(),
+                };
+                if adaptive.target_p95.is_zero() {
+                    return Err("--target-p95 must be above zero".into());
+                }
+                if adaptive.start > adaptive.max {
+                    return Err(format!("--concurrency {} is above --max-concurrency {}", adaptive.start, adaptive.max).into());
+                }
+                Some(adaptive)
+            }
+            false => None,
+        };
+        let requests = pick(matches, "requests", file.requests);
+        let duration = match (pick(matches, "time", file.time), &stages) {
+            (Some(time), _) => Some(time),
+            (None, Some(_)) => Some(profile.total_duration().as_secs_f64().ceil() as u64),
+            (None, None) => None,
+        };
+        // a dry run sends nothing to bound, find-max bounds each step itself
+        let find_max = matches.try_contains_id("step-duration").unwrap_or(false);
+        // stdin running dry ends a --data - run
+        if duration.is_none() && requests.is_none() && !explicit(matches, "dry-run") && !find_max && !stdin {
+            return Err("one of --time, --requests or --stages is required".into());
+        }
+
+        // an arrival rate sets the pacing itself
+        let think_time = pick(matches, "think-time", file.think_time);
+        if think_time.is_some() && profile.unit == StageUnit::Rps {
+            return Err("--think-time paces virtual users, it doesn't combine with a rate".into());
+        }
+        if scenario.is_some() && profile.unit == StageUnit::Rps {
+            return Err("--scenario passes take as long as their steps do, they don't combine with a rate; set the virtual users with --concurrency".into());
+        }
+
+        let auth_flags = ["bearer", "basic", "oauth-token-url", "aws-sigv4"];
+        let auth = match auth_flags.iter().any(|id| explicit(matches, id)) {
+            true => parse_auth(matches)?,
+            false => file.auth()?,
+        };
+
+        let mut headers = Vec::new();
+        for (name, value) in &file.headers {
+            headers.push(Header { name: name.clone(), value: value.clone() });
+        }
+        for spec in matches.get_many::<String>("header").unwrap_or_default() {
+            let header = Header::parse(spec)?;
+            headers.retain(|h: &Header| !h.name.eq_ignore_ascii_case(&header.name));
+            headers.push(header);
+        }
+        // a header given by name wins over the built-in value
+        let builtin = [
+            ("idempotency-key", file.idempotency_key, "Idempotency-Key"),
+            ("request-id", file.request_id, "X-Request-Id"),
+        ];
+        for (id, from_file, name) in builtin {
+            if pick(matches, id, from_file).unwrap() && !headers.iter().any(|h| h.name.eq_ignore_ascii_case(name)) {
+                headers.push(Header { name: name.to_string(), value: "{{uuid}}".to_string() });
+            }
+        }
+        let (templated, headers): (Vec<Header>, Vec<Header>) = headers.into_iter().partition(|h| h.value.contains("{{"));
+        let header_templates = templated.iter().map(HeaderTemplate::parse).collect::<Result<Vec<_>, _>>()?;
+        if !header_templates.is_empty() && pick::<String>(matches, "grpc", file.grpc.clone()).is_some() {
+            return Err("headers with placeholders are rendered per http request, they don't combine with --grpc".into());
+        }
+        let rotate = Rotate::parse(&pick(matches, "rotate-per", file.rotate_per.clone()).unwrap_or_else(|| "request".to_string()))?;
+        let fingerprints = match (
+            pick::<String>(matches, "user-agents", file.user_agents.clone()),
+            pick::<String>(matches, "header-sets", file.header_sets.clone()),
+        ) {
+            (Some(_), Some(_)) => return Err("--user-agents and --header-sets don't combine, put the User-Agent into the header sets".into()),
+            (Some(path), None) => Some(Fingerprints::user_agents(&path, rotate)?),
+            (None, Some(path)) => Some(Fingerprints::header_sets(&path, rotate)?),
+            (None, None) => None,
+        };
+        if fingerprints.is_some() && pick::<String>(matches, "grpc", file.grpc.clone()).is_some() {
+            return Err("--user-agents and --header-sets are http headers, they don't combine with --grpc".into());
+        }
+
+        let chaos = pick_many(matches, "chaos", &file.chaos)
+            .iter()
+            .map(|spec| Chaos::parse(spec))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !chaos.is_empty() && pick::<String>(matches, "grpc", file.grpc.clone()).is_some() {
+            return Err("--chaos breaks http requests, it doesn't combine with --grpc".into());
+        }
+        if !chaos.is_empty() && scenario.is_some() {
+            return Err("--chaos breaks single requests, it doesn't combine with --scenario".into());
+        }
+        let setup = match pick::<String>(matches, "setup", file.setup.clone()) {
+            Some(_) if pick::<String>(matches, "grpc", file.grpc.clone()).is_some() => {
+                return Err("--setup sends an http request, it doesn't combine with --grpc".into())
+            }
+            Some(spec) => Some(Setup::parse(
+                "setup",
+                &spec,
+                pick(matches, "setup-data", file.setup_data.clone()),
+                &pick_many(matches, "setup-extract", &file.setup_extract),
+            )?),
+            None => None,
+        };
+        let global_setup = pick::<String>(matches, "global-setup", file.global_setup.clone())
+            .map(|spec| {
+                let data = pick(matches, "global-setup-data", file.global_setup_data.clone());
+                Setup::parse("global-setup", &spec, data, &pick_many(matches, "global-setup-extract", &file.global_setup_extract))
+            })
+            .transpose()?;
+        let teardown = pick::<String>(matches, "teardown", file.teardown.clone())
+            .map(|spec| Setup::parse("teardown", &spec, pick(matches, "teardown-data", file.teardown_data.clone()), &[]))
+            .transpose()?;
+        let influx = pick::<String>(matches, "influx", file.influx.clone()).map(|target| InfluxConfig {
+            target,
+            measurement: pick(matches, "influx-measurement", file.influx_measurement.clone()).unwrap(),
+            tags: pick_many(matches, "influx-tag", &file.influx_tags),
+        });
+        if let Some(influx) = &influx {
+            influx.check()?;
+        }
+        let checkpoint = pick::<String>(matches, "checkpoint", file.checkpoint.clone()).map(|path| CheckpointConfig {
+            path,
+            interval: pick(matches, "checkpoint-interval", file.checkpoint_interval).unwrap(),
+        });
+        if checkpoint.as_ref().is_some_and(|checkpoint| checkpoint.interval.is_zero()) {
+            return Err("--checkpoint-interval must be above zero".into());
+        }
+        let sample_responses = pick(matches, "sample-responses", file.sample_responses);
+        if sample_responses.is_some() && pick::<String>(matches, "grpc", file.grpc.clone()).is_some() {
+            return Err("--sample-responses keeps http exchanges, it doesn't combine with --grpc".into());
+        }
+        let capture = pick(matches, "capture", file.capture.clone());
+        if capture.is_some() && pick::<String>(matches, "grpc", file.grpc.clone()).is_some() {
+            return Err("--capture keeps http exchanges, it doesn't combine with --grpc".into());
+        }
+        let capture_rate = pick(matches, "capture-rate", file.capture_rate).unwrap();
+        if !(capture_rate > 0.0 && capture_rate <= 1.0) {
+            return Err("--capture-rate is the share of the requests to capture, above 0 and at most 1".into());
+        }
+        let shard = pick::<String>(matches, "shard", file.shard.clone()).map(|shard| Shard::parse(&shard)).transpose()?;
+        // every shard draws from the same payload stream, so they all need
+        // to be handed the same seed
+        if shard.is_some() && pick(matches, "seed", file.seed).is_none() {
+            return Err("--shard needs a --seed shared by every shard, their payloads are cut from one seeded stream".into());
+        }
+
+        let abort_on_error_rate = pick(matches, "abort-on-error-rate", file.abort_on_error_rate);
+        if abort_on_error_rate.is_some_and(|rate| !(0.0..1.0).contains(&rate)) {
+            return Err("--abort-on-error-rate must be at least 0 and below 1".into());
+        }
+        let metrics: Vec<MetricSpec> = pick_many(matches, "metric", &file.metric)
+            .iter()
+            .map(|spec| MetricSpec::parse(spec))
+            .collect::<Result<_, _>>()?;
+        if let Some((_, metric)) = metrics.iter().enumerate().find(|(i, m)| metrics[..*i].iter().any(|other| other.name == m.name)) {
+            return Err(format!("--metric {}: given twice", metric.name).into());
+        }
+        let method = pick::<String>(matches, "method", file.method.clone())
+            .unwrap()
+            .to_ascii_uppercase();
+        reqwest::Method::from_bytes(method.as_bytes()).map_err(|_| format!("invalid http method: {}", method))?;
+        let mut body_format: BodyFormat = pick::<String>(matches, "body-format", file.body_format.clone())
+            .unwrap()
+            .parse()?;
+        let protos = pick_many(matches, "proto", &file.proto);
+        let mut includes = pick_many(matches, "proto-include", &file.proto_include);
+        if includes.is_empty() {
+            includes = proto_dirs(&protos);
+        }
+        let grpc = pick::<String>(matches, "grpc", file.grpc.clone());
+        let protobuf = match pick::<String>(matches, "message", file.message.clone()) {
+            Some(_) if grpc.is_some() => return Err("--message is for http bodies, --grpc finds its own".into()),
+            Some(_) if body_format != BodyFormat::Json => {
+                return Err("--message bodies are protobuf, they don't combine with --body-format".into())
+            }
+            Some(_) if protos.is_empty() => {
+                return Err("--message needs the --proto files or descriptor set that declare it".into())
+            }
+            Some(message) => Some(Protobuf::load(&message, &protos, &includes)?),
+            None if grpc.is_none() && !protos.is_empty() => {
+                return Err("--proto declares the --grpc service or the --message, give one of them".into())
+            }
+            None => None,
+        };
+        if protobuf.is_some() {
+            body_format = BodyFormat::Protobuf;
+        }
+        let random_bytes = match random_bytes {
+            Some(_) if grpc.is_some() => return Err("--body-bytes is for http bodies, it doesn't combine with --grpc".into()),
+            Some(_) if body_format != BodyFormat::Json => {
+                return Err("--body-bytes bodies are raw bytes, they don't combine with --body-format or --message".into())
+            }
+            Some(spec) => {
+                let content_type = pick(matches, "body-bytes-type", file.body_bytes_type.clone()).unwrap();
+                Some(RandomBytes::parse(&spec, content_type)?)
+            }
+            None => None,
+        };
+        if random_bytes.is_some() {
+            body_format = BodyFormat::Bytes;
+        }
+        let payload_schema = match pick::<String>(matches, "validate-payloads", file.validate_payloads.clone()) {
+            Some(_) if random_bytes.is_some() => return Err("--body-bytes bodies aren't json, there's nothing for --validate-payloads to check".into()),
+            Some(path) => {
+                let invalid = Invalid::parse(&pick::<String>(matches, "invalid-payloads", file.invalid_payloads.clone()).unwrap())?;
+                Some(Schema::load(&path, invalid)?)
+            }
+            None => None,
+        };
+        let xml = (body_format == BodyFormat::Xml).then(|| Xml {
+            root: pick(matches, "xml-root", file.xml_root.clone()).unwrap(),
+            attribute_prefix: pick(matches, "xml-attribute-prefix", file.xml_attribute_prefix.clone()).unwrap(),
+        });
+        let file_part = match pick(matches, "file-size", file.file_size) {
+            Some(size) if size > 0 => {
+                if body_format != BodyFormat::Multipart {
+                    return Err("--file-size needs --body-format multipart".into());
+                }
+                Some(FilePart {
+                    field: pick(matches, "file-field", file.file_field.clone()).unwrap(),
+                    size,
+                })
+            }
+            _ => None,
+        };
+        let compress: Option<Compression> = pick::<String>(matches, "compress", file.compress.clone())
+            .map(|c| c.parse())
+            .transpose()?;
+        if compress.is_some() && body_format != BodyFormat::Json {
+            return Err("--compress needs --body-format json".into());
+        }
+        let sign = pick::<String>(matches, "sign", file.sign.clone())
+            .map(|spec| BodySignature::parse(&spec))
+            .transpose()?;
+        // multipart bodies are streamed, there's nothing to sign up front
+        if sign.is_some() && body_format == BodyFormat::Multipart {
+            return Err("--sign doesn't combine with --body-format multipart".into());
+        }
+        let summary_every = pick(matches, "summary-every", file.summary_every);
+        if summary_every.is_some_and(|every| every.is_zero()) {
+            return Err("--summary-every must be above zero".into());
+        }
+
+        let stream_body = pick(matches, "stream-body", file.stream_body).unwrap().then(|| StreamBody {
+            chunk_size: pick(matches, "chunk-size", file.chunk_size).unwrap(),
+            chunk_delay: pick(matches, "chunk-delay", file.chunk_delay).unwrap(),
+        });
+        if stream_body.as_ref().is_some_and(|stream| stream.chunk_size == 0) {
+            return Err("--chunk-size must be above zero".into());
+        }
+        // multipart bodies are streamed already
+        if stream_body.is_some() && body_format == BodyFormat::Multipart {
+            return Err("--stream-body doesn't combine with --body-format multipart".into());
+        }
+        if stream_body.is_some() && pick::<String>(matches, "grpc", file.grpc.clone()).is_some() {
+            return Err("--stream-body doesn't combine with --grpc".into());
+        }
+
+        let tls = TlsOptions {
+            cacert: pick(matches, "cacert", file.cacert.clone()),
+            cert: pick(matches, "cert", file.cert.clone()),
+            key: pick(matches, "key", file.key.clone()),
+            insecure: pick(matches, "insecure", file.insecure).unwrap(),
+            probe_full_handshakes: pick(matches, "probe-full-handshakes", file.probe_full_handshakes).unwrap(),
+            resolve: pick_many(matches, "resolve", &file.resolve)
+                .iter()
+                .map(|spec| Resolve::parse(spec))
+                .collect::<Result<_, _>>()?,
+            family: match (explicit(matches, "ipv4"), explicit(matches, "ipv6")) {
+                (true, _) => Some(IpFamily::Ipv4),
+                (_, true) => Some(IpFamily::Ipv6),
+                _ => file.ip_family.as_deref().map(str::parse).transpose()?,
+            },
+            host_override: HostOverride::new(
+                pick(matches, "host-header", file.host_header.clone()),
+                pick(matches, "sni", file.sni.clone()),
+                endpoints.iter().map(|target| target.url.as_str()),
+            )?,
+        };
+        if tls.host_override.is_some() && pick::<String>(matches, "grpc", file.grpc.clone()).is_some() {
+            return Err("--host-header and --sni don't combine with --grpc".into());
+        }
+        let setup = setup.map(|setup| setup.host_override(tls.host_override.clone()));
+        let global_setup = global_setup.map(|setup| setup.host_override(tls.host_override.clone()));
+        let teardown = teardown.map(|setup| setup.host_override(tls.host_override.clone()));
+        // an explicit -H Host wins
+        let mut headers = headers;
+        if let Some(host) = tls.host_override.as_ref().and_then(HostOverride::header) {
+            if !headers.iter().any(|h| h.name.eq_ignore_ascii_case("host")) {
+                headers.push(Header { name: "Host".to_string(), value: host.to_string() });
+            }
+        }
+        let cache_bust = pick::<String>(matches, "cache-bust", file.cache_bust.clone());
+        if cache_bust.is_some() {
+            if pick::<String>(matches, "grpc", file.grpc.clone()).is_some() {
+                return Err("--cache-bust is for http, it doesn't combine with --grpc".into());
+            }
+            for (name, value) in [("Cache-Control", "no-cache"), ("Pragma", "no-cache")] {
+                if !headers.iter().any(|h| h.name.eq_ignore_ascii_case(name)) {
+                    headers.push(Header { name: name.to_string(), value: value.to_string() });
+                }
+            }
+        }
+        tls.check(endpoints.iter().map(|target| target.url.as_str()))?;
+
+        let mut config = LoadConfig {
+            endpoints,
+            duration,
+            requests,
+            warmup: pick(matches, "warmup", file.warmup),
+            drain_timeout: pick(matches, "drain-timeout", file.drain_timeout).unwrap(),
+            generator,
+            data,
+            stdin,
+            corpus,
+            corpus_order,
+            feed: pick(matches, "feed", file.feed.clone()),
+            feed_order: pick::<String>(matches, "feed-order", file.feed_order.clone())
+                .unwrap()
+                .parse()?,
+            method,
+            body_format,
+            protobuf,
+            xml,
+            random_bytes,
+            payload_schema,
+            cache_bust,
+            file: file_part,
+            stream_body,
+            padding: pick(matches, "body-size", file.body_size).filter(|size| *size > 0).map(|size| Padding {
+                field: pick(matches, "body-size-field", file.body_size_field.clone()).unwrap(),
+                size,
+            }),
+            compress,
+            graphql: match pick::<String>(matches, "graphql", file.graphql.clone()) {
+                Some(path) => Some(GraphQl::load(
+                    &path,
+                    pick(matches, "graphql-operation", file.graphql_operation.clone()),
+                )?),
+                None => None,
+            },
+            grpc: grpc.map(|method| GrpcConfig { method, protos, includes }),
+            seed: pick(matches, "seed", file.seed).unwrap_or_else(rand::random),
+            concurrency,
+            stages,
+            preset,
+            profile,
+            adaptive,
+            timeout: pick(matches, "timeout", file.timeout),
+            retries: pick(matches, "retries", file.retries).unwrap(),
+            retry_backoff: pick(matches, "retry-backoff", file.retry_backoff).unwrap(),
+            retry_after: pick(matches, "respect-retry-after", file.respect_retry_after)
+                .unwrap()
+                .then(|| pick(matches, "max-retry-after", file.max_retry_after).unwrap()),
+            chaos,
+            headers,
+            header_templates,
+            fingerprints,
+            auth,
+            api_key: pick::<String>(matches, "api-key-header", file.api_key_header.clone())
+                .map(|spec| Header::parse(&spec))
+                .transpose()?,
+            sign,
+            tls,
+            http_version: pick::<String>(matches, "http-version", file.http_version.clone())
+                .map(|v| v.parse())
+                .transpose()?,
+            max_redirects: match explicit(matches, "no-follow-redirects") {
+                true => 0,
+                false => pick(matches, "max-redirects", file.max_redirects).unwrap(),
+            },
+            pool: PoolConfig {
+                max_idle_per_host: pick(matches, "max-idle-per-host", file.max_idle_per_host),
+                idle_timeout: pick(matches, "pool-idle-timeout", file.pool_idle_timeout),
+                keepalive: !pick(matches, "disable-keepalive", file.disable_keepalive).unwrap(),
+                preconnect: pick(matches, "preconnect", file.preconnect).filter(|&count| count > 0),
+            },
+            dns_refresh: pick(matches, "dns-refresh", file.dns_refresh).filter(|refresh| !refresh.is_zero()),
+            proxy: pick::<String>(matches, "proxy", file.proxy.clone())
+                .map(|url| ProxyConfig::parse(&url, proxy_user.as_deref()))
+                .transpose()?,
+            cookies: pick(matches, "cookies", file.cookies).unwrap(),
+            setup,
+            scenario,
+            global_setup,
+            teardown,
+            // spans nobody can join with the server's would be of little use
+            traceparent: pick(matches, "traceparent", file.traceparent).unwrap() || otlp.is_some(),
+            expect_status: pick(matches, "expect-status", file.expect_status.clone())
+                .map(|spec| ExpectedStatus::parse(&spec))
+                .transpose()?,
+            assertions: parse_assertions(matches, &file, script.as_ref())?,
+            thresholds: pick_many(matches, "threshold", &file.threshold)
+                .iter()
+                .map(|spec| Threshold::parse(spec))
+                .collect::<Result<_, _>>()?,
+            metrics,
+            abort_on_error_rate,
+            abort_window: pick(matches, "abort-window", file.abort_window).unwrap(),
+            timing: pick(matches, "timing", file.timing).unwrap(),
+            think_time,
+            think_jitter: pick(matches, "think-jitter", file.think_jitter).unwrap_or_default(),
+            watch: pick(matches, "watch", file.watch).unwrap(),
+            script,
+            output,
+            format,
+            report: pick(matches, "report", file.report.clone()),
+            junit: pick(matches, "junit", file.junit.clone()),
+            history: pick(matches, "history", file.history.clone()),
+            timeseries: pick(matches, "timeseries", file.timeseries.clone()),
+            prometheus_port: pick(matches, "prometheus-port", file.prometheus_port),
+            influx,
+            checkpoint,
+            statsd: pick::<String>(matches, "statsd", file.statsd.clone()).map(|addr| StatsdConfig {
+                addr,
+                prefix: pick(matches, "statsd-prefix", file.statsd_prefix.clone()).unwrap(),
+                tags: pick_many(matches, "statsd-tag", &file.statsd_tags),
+            }),
+            otlp,
+            summary_every: summary_every
+                .or((parsed_preset.map(|p| p.kind) == Some(PresetKind::Soak)).then_some(SOAK_SUMMARY_EVERY)),
+            tui: pick(matches, "tui", file.tui).unwrap(),
+            json: pick(matches, "json", file.json).unwrap(),
+            log_requests: pick(matches, "log-requests", file.log_requests.clone()),
+            log_body_limit: pick(matches, "log-body-limit", file.log_body_limit).unwrap(),
+            sample_responses,
+            sample_dir: pick(matches, "sample-dir", file.sample_dir.clone()).unwrap(),
+            capture,
+            capture_rate,
+            keep_secrets: pick(matches, "keep-secrets", file.keep_secrets).unwrap(),
+            shard: None,
+            dry_run: matches.get_one::<u64>("dry-run").copied(),
+        };
+        if let Some(shard) = shard {
+            if config.stdin {
+                return Err("--data - reads this machine's stdin, it can't be split into --shard runs".into());
+            }
+            if config.global_setup.is_some() || config.teardown.is_some() {
+                return Err("--global-setup and --teardown run once, they can't be split into --shard runs".into());
+            }
+            let users = config.profile.peak();
+            config = config.share(shard.index as usize, shard.count as usize);
+            // the virtual users are dealt out, with fewer than shards some get none
+            if config.profile.unit == StageUnit::Concurrency && config.profile.peak() < 1.0 {
+                return Err(format!("--shard {} gets none of the {} virtual users, the {} shards need --concurrency {} or more", shard, users, shard.count, shard.count).into());
+            }
+        }
+        for threshold in &config.thresholds {
+            threshold.check_scope(&config)?;
+        }
+        if config.pool.preconnect.is_some() {
+            if !config.pool.keepalive || config.pool.max_idle_per_host == Some(0) {
+                return Err("--preconnect needs connections kept for reuse".into());
+            }
+            if config.grpc.is_some() {
+                return Err("--preconnect doesn't combine with --grpc".into());
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn endpoint_urls(&self) -> Vec<String> {
+        self.endpoints.iter().map(|t| t.url.clone()).collect()
+    }
+
+    pub fn endpoint_labels(&self) -> Vec<String> {
+        self.endpoints.iter().map(Target::label).collect()
+    }
+
+    // the --data paths, when there's more than one to tell apart
+    pub fn template_labels(&self) -> Vec<String> {
+        match self.data.len() {
+            0 | 1 => Vec::new(),
+            _ => self.data.iter().map(|spec| spec.path.clone()).collect(),
+        }
+    }
+
+    // one group per --chaos, the requests it broke
+    pub fn chaos_labels(&self) -> Vec<String> {
+        self.chaos.iter().map(|chaos| chaos.kind.to_string()).collect()
+    }
+
+    // groups requests are also counted under: the endpoints' key=value tags,
+    // then one stage=N per stage when there's more than one
+    pub fn tag_labels(&self) -> Vec<String> {
+        let mut labels = target::tag_labels(&self.endpoints);
+        if self.profile.stages.len() > 1 {
+            labels.extend((1..=self.profile.stages.len()).map(|stage| format!("stage={}", stage)));
+        }
+        labels
+    }
+
+    // size of the worker pool the runner spawns
+    pub fn workers(&self) -> usize {
+        if let Some(adaptive) = &self.adaptive {
+            return adaptive.max;
+        }
+        match self.profile.unit {
+            StageUnit::Concurrency => (self.profile.peak().ceil() as usize).max(1),
+            StageUnit::Rps => self.concurrency.unwrap_or(DEFAULT_RATE_WORKERS).max(1),
+        }
+    }
+}
+
+fn parse_auth(matches: &ArgMatches) -> Result<Option<Auth>, String> {
+    let arg = |name: &str| matches.get_one::<String>(name).cloned();
+
+    if let Some(token) = arg("bearer") {
+        return Ok(Some(Auth::Bearer(token)));
+    }
+    if let Some(credentials) = arg("basic") {
+        return Ok(Some(Auth::basic(&credentials)));
+    }
+    if let Some(spec) = arg("aws-sigv4") {
+        return Ok(Some(Auth::SigV4(SigV4::parse(&spec)?)));
+    }
+    Ok(arg("oauth-token-url").map(|token_url| {
+        Auth::OAuth2(OAuthConfig {
+            token_url,
+            client_id: arg("oauth-client-id").unwrap_or_default(),
+            client_secret: arg("oauth-client-secret").unwrap_or_default(),
+            scope: arg("oauth-scope"),
+        })
+    }))
+}
+
+fn parse_assertions(
+    matches: &ArgMatches,
+    file: &FileConfig,
+    script: Option<&Arc<Script>>,
+) -> Result<Vec<Assertion>, Box<dyn std::error::Error>> {
+    let mut assertions = Vec::new();
+
+    if let Some(codes) = pick::<String>(matches, "assert-status", file.assert_status.clone()) {
+        assertions.push(Assertion::status(&codes)?);
+    }
+    for spec in pick_many(matches, "assert-json", &file.assert_json) {
+        assertions.push(Assertion::json_equals(&spec)?);
+    }
+    for path in pick_many(matches, "assert-json-exists", &file.assert_json_exists) {
+        assertions.push(Assertion::json_exists(&path));
+    }
+    for pattern in pick_many(matches, "assert-body", &file.assert_body) {
+        assertions.push(Assertion::body_matches(&pattern)?);
+    }
+    if let Some(ms) = pick(matches, "max-latency", file.max_latency) {
+        assertions.push(Assertion::max_latency(ms));
+    }
+    if let Some(script) = script.filter(|script| script.has_check()) {
+        assertions.push(Assertion::Script(script.clone()));
+    }
+
+    Ok(assertions)
+}
+
+fn proto_dirs(protos: &[String]) -> Vec<String> {
+    let mut dirs: Vec<String> = Vec::new();
+    for proto in protos {
+        let dir = match Path::new(proto).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_string_lossy().into_owned(),
+            _ => ".".to_string(),
+        };
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+// typed on the command line rather than filled in from a default
+fn explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches
+        .value_source(id)
+        .is_some_and(|source| source != ValueSource::DefaultValue)
+}
+
+// command line, then config file, then the flag's default
+fn pick<T: Clone + Send + Sync + 'static>(matches: &ArgMatches, id: &str, file: Option<T>) -> Option<T> {
+    if explicit(matches, id) {
+        return matches.get_one::<T>(id).cloned();
+    }
+    file.or_else(|| matches.get_one::<T>(id).cloned())
+}
+
+// repeatable flags replace the file's list rather than adding to it
+fn pick_many(matches: &ArgMatches, id: &str, file: &[String]) -> Vec<String> {
+    match explicit(matches, id) {
+        true => matches.get_many::<String>(id).unwrap_or_default().cloned().collect(),
+        false => file.to_vec(),
+    }
+}
@@ -0,0 +1,215 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::auth::{Auth, OAuthConfig};
+use crate::sigv4::SigV4;
+use crate::units::deserialize_opt_duration;
+
+// picked up from the working directory when --config isn't given
+pub const DEFAULT_PATH: &str = "arctic.toml";
+
+// a whole run described in toml or yaml; keys mirror the long flag names,
+// and any flag given on the command line wins over the file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FileConfig {
+    pub endpoint: Option<String>,
+    pub endpoints: Vec<String>,
+    pub targets: Option<String>,
+    pub har: Option<String>,
+    pub har_host: Option<String>,
+    #[serde(alias = "duration")]
+    pub time: Option<u64>,
+    pub requests: Option<u64>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub warmup: Option<Duration>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub drain_timeout: Option<Duration>,
+    pub generator: Option<String>,
+    #[serde(alias = "template")]
+    pub data: Option<String>,
+    // several weighted templates, "create.json:3"
+    pub templates: Vec<String>,
+    pub corpus: Option<String>,
+    pub corpus_order: Option<String>,
+    pub feed: Option<String>,
+    pub feed_order: Option<String>,
+    pub graphql: Option<String>,
+    pub graphql_operation: Option<String>,
+    pub method: Option<String>,
+    pub body_format: Option<String>,
+    pub file_size: Option<usize>,
+    pub file_field: Option<String>,
+    pub body_bytes: Option<String>,
+    pub body_bytes_type: Option<String>,
+    pub cache_bust: Option<String>,
+    pub mix: Option<String>,
+    pub validate_payloads: Option<String>,
+    pub invalid_payloads: Option<String>,
+    pub xml_root: Option<String>,
+    pub xml_attribute_prefix: Option<String>,
+    pub body_size: Option<usize>,
+    pub body_size_field: Option<String>,
+    pub stream_body: Option<bool>,
+    pub chunk_size: Option<usize>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub chunk_delay: Option<Duration>,
+    pub compress: Option<String>,
+    pub grpc: Option<String>,
+    pub proto: Vec<String>,
+    pub message: Option<String>,
+    pub proto_include: Vec<String>,
+    pub concurrency: Option<usize>,
+    pub rate: Option<f64>,
+    pub stages: Option<String>,
+    pub adaptive: Option<bool>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub target_p95: Option<Duration>,
+    pub max_concurrency: Option<usize>,
+    pub stage_unit: Option<String>,
+    pub profile: Option<String>,
+    pub seed: Option<u64>,
+    #[serde(alias = "request-timeout", deserialize_with = "deserialize_opt_duration")]
+    pub timeout: Option<Duration>,
+    pub retries: Option<u32>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub retry_backoff: Option<Duration>,
+    pub respect_retry_after: Option<bool>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub max_retry_after: Option<Duration>,
+    pub chaos: Vec<String>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub think_time: Option<Duration>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub think_jitter: Option<Duration>,
+    pub headers: BTreeMap<String, String>,
+    pub bearer: Option<String>,
+    pub basic: Option<String>,
+    pub aws_sigv4: Option<String>,
+    pub api_key_header: Option<String>,
+    pub sign: Option<String>,
+    pub oauth_token_url: Option<String>,
+    pub oauth_client_id: Option<String>,
+    pub oauth_client_secret: Option<String>,
+    pub oauth_scope: Option<String>,
+    pub cacert: Option<String>,
+    pub cert: Option<String>,
+    pub key: Option<String>,
+    pub insecure: Option<bool>,
+    pub probe_full_handshakes: Option<bool>,
+    pub resolve: Vec<String>,
+    pub host_header: Option<String>,
+    pub sni: Option<String>,
+    pub ip_family: Option<String>,
+    pub http_version: Option<String>,
+    pub proxy: Option<String>,
+    pub proxy_user: Option<String>,
+    pub cookies: Option<bool>,
+    pub setup: Option<String>,
+    pub scenario: Option<String>,
+    pub setup_data: Option<String>,
+    pub setup_extract: Vec<String>,
+    pub global_setup: Option<String>,
+    pub global_setup_data: Option<String>,
+    pub global_setup_extract: Vec<String>,
+    pub teardown: Option<String>,
+    pub teardown_data: Option<String>,
+    pub max_redirects: Option<usize>,
+    pub max_idle_per_host: Option<usize>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub pool_idle_timeout: Option<Duration>,
+    pub disable_keepalive: Option<bool>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub dns_refresh: Option<Duration>,
+    pub preconnect: Option<usize>,
+    pub idempotency_key: Option<bool>,
+    pub request_id: Option<bool>,
+    pub user_agents: Option<String>,
+    pub header_sets: Option<String>,
+    pub rotate_per: Option<String>,
+    pub traceparent: Option<bool>,
+    pub watch: Option<bool>,
+    pub script: Option<String>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub summary_every: Option<Duration>,
+    pub assert_status: Option<String>,
+    pub expect_status: Option<String>,
+    pub assert_json: Vec<String>,
+    pub assert_json_exists: Vec<String>,
+    pub assert_body: Vec<String>,
+    pub max_latency: Option<u64>,
+    pub threshold: Vec<String>,
+    pub metric: Vec<String>,
+    pub abort_on_error_rate: Option<f64>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub abort_window: Option<Duration>,
+    pub timing: Option<bool>,
+    pub output: Option<String>,
+    pub format: Option<String>,
+    pub report: Option<String>,
+    pub junit: Option<String>,
+    pub history: Option<String>,
+    pub timeseries: Option<String>,
+    pub log_requests: Option<String>,
+    pub log_body_limit: Option<usize>,
+    pub sample_responses: Option<usize>,
+    pub sample_dir: Option<String>,
+    pub capture: Option<String>,
+    pub capture_rate: Option<f64>,
+    pub keep_secrets: Option<bool>,
+    pub shard: Option<String>,
+    pub tui: Option<bool>,
+    pub json: Option<bool>,
+    pub prometheus_port: Option<u16>,
+    pub statsd: Option<String>,
+    pub statsd_prefix: Option<String>,
+    pub statsd_tags: Vec<String>,
+    pub influx: Option<String>,
+    pub influx_measurement: Option<String>,
+    pub influx_tags: Vec<String>,
+    pub checkpoint: Option<String>,
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub checkpoint_interval: Option<Duration>,
+    pub otlp: Option<String>,
+}
+
+impl FileConfig {
+    // yaml for .yaml/.yml, toml for anything else
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let yaml = matches!(
+            Path::new(path).extension().and_then(|ext| ext.to_str()),
+            Some("yaml" | "yml")
+        );
+        let config = if yaml {
+            serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?
+        } else {
+            toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?
+        };
+        Ok(config)
+    }
+
+    // same precedence as the flags: bearer, then basic, then sigv4, then oauth
+    pub fn auth(&self) -> Result<Option<Auth>, String> {
+        if let Some(token) = &self.bearer {
+            return Ok(Some(Auth::Bearer(token.clone())));
+        }
+        if let Some(credentials) = &self.basic {
+            return Ok(Some(Auth::basic(credentials)));
+        }
+        if let Some(spec) = &self.aws_sigv4 {
+            return Ok(Some(Auth::SigV4(SigV4::parse(spec)?)));
+        }
+        Ok(self.oauth_token_url.as_ref().map(|token_url| {
+            Auth::OAuth2(OAuthConfig {
+                token_url: token_url.clone(),
+                client_id: self.oauth_client_id.clone().unwrap_or_default(),
+                client_secret: self.oauth_client_secret.clone().unwrap_or_default(),
+                scope: self.oauth_scope.clone(),
+            })
+        }))
+    }
+}
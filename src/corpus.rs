@@ -0,0 +1,102 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CorpusOrder {
+    // line after line, starting over at the end
+    #[default]
+    Cycle,
+    // any line, independently per request
+    Random,
+    // cycle through a seeded permutation of the lines
+    Shuffle,
+    // shuffle:N, lines shuffled within each run of N, so they arrive out of
+    // order by less than N, as a queue with redeliveries would hand them out
+    ShuffleWithin(usize),
+}
+
+impl FromStr for CorpusOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cycle" => Ok(CorpusOrder::Cycle),
+            "random" => Ok(CorpusOrder::Random),
+            "shuffle" => Ok(CorpusOrder::Shuffle),
+            other => match other.strip_prefix("shuffle:").map(str::parse::<usize>) {
+                Some(Ok(window)) if window > 1 => Ok(CorpusOrder::ShuffleWithin(window)),
+                Some(_) => Err(format!("corpus order {}: expected shuffle:N with N above 1", other)),
+                None => Err(format!("unknown corpus order: {}, expected cycle, random, shuffle or shuffle:N", other)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for CorpusOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CorpusOrder::Cycle => write!(f, "cycle"),
+            CorpusOrder::Random => write!(f, "random"),
+            CorpusOrder::Shuffle => write!(f, "shuffle"),
+            CorpusOrder::ShuffleWithin(window) => write!(f, "shuffle:{}", window),
+        }
+    }
+}
+
+impl Serialize for CorpusOrder {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// recorded bodies, one json document per line, replayed instead of
+// generated ones
+#[derive(Debug, Clone)]
+pub struct Corpus {
+    entries: Vec<Value>,
+    order: CorpusOrder,
+}
+
+impl Corpus {
+    pub fn new(mut entries: Vec<Value>, order: CorpusOrder, seed: u64) -> Result<Self, String> {
+        if entries.is_empty() {
+            return Err("corpus is empty".to_string());
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        match order {
+            CorpusOrder::Shuffle => entries.shuffle(&mut rng),
+            CorpusOrder::ShuffleWithin(window) => entries.chunks_mut(window).for_each(|run| run.shuffle(&mut rng)),
+            CorpusOrder::Cycle | CorpusOrder::Random => {}
+        }
+        Ok(Corpus { entries, order })
+    }
+
+    // blank lines are skipped
+    pub fn load(path: &str, order: CorpusOrder, seed: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut entries = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(line).map_err(|e| format!("{}:{}: {}", path, number + 1, e))?;
+            entries.push(entry);
+        }
+        Ok(Corpus::new(entries, order, seed).map_err(|e| format!("{}: {}", path, e))?)
+    }
+
+    pub fn entry<R: Rng + ?Sized>(&self, sequence: u64, rng: &mut R) -> &Value {
+        match self.order {
+            CorpusOrder::Random => &self.entries[rng.gen_range(0..self.entries.len())],
+            CorpusOrder::Cycle | CorpusOrder::Shuffle | CorpusOrder::ShuffleWithin(_) => {
+                &self.entries[(sequence % self.entries.len() as u64) as usize]
+            }
+        }
+    }
+}
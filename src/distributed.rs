@@ -0,0 +1,461 @@
+use arctic::checks::Checks;
+use arctic::configfile::{FileConfig, DEFAULT_PATH as DEFAULT_CONFIG_PATH};
+use arctic::generator::TemplateSpec;
+use arctic::profile::StageUnit;
+use arctic::report::Report;
+use arctic::stats::{LoadTestStats, StatsSnapshot, StopReason};
+use arctic::{LoadConfig, Runner};
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::{Component, Path};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::time::sleep;
+use tracing::{error, info};
+
+use crate::cli;
+
+// relative path to contents
+type Files = BTreeMap<String, String>;
+
+const DEFAULT_PORT: u16 = 7878;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+const PLAIN_PROGRESS_EVERY: Duration = Duration::from_secs(10);
+
+// flags naming files the workers get a copy of
+const FILE_ARGS: &[&str] = &[
+    "config", "targets", "har", "data", "corpus", "feed", "script", "graphql", "proto", "cacert", "cert", "key",
+];
+// flags only the coordinator acts on
+const COORDINATOR_ARGS: &[&str] = &["workers", "output", "format", "report", "sample-dir", "seed"];
+
+// one json object per line, in both directions; externally tagged, an
+// internally tagged enum can't read back the stats' integer map keys
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Message {
+    // coordinator to worker: the flags to run with, the files they name,
+    // which share of the load to take and the seed of the whole run, the
+    // worker's requests cut from its stream as a --shard's are
+    Job {
+        args: Vec<String>,
+        files: Files,
+        index: usize,
+        count: usize,
+        seed: u64,
+    },
+    Start,
+    Stop,
+    // worker to coordinator
+    Ready,
+    Progress { stats: StatsSnapshot },
+    Done { stats: StatsSnapshot },
+    Failed { error: String },
+}
+
+struct Connection {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        let (reader, writer) = stream.into_split();
+        Connection { lines: BufReader::new(reader).lines(), writer }
+    }
+
+    async fn send(&mut self, message: &Message) -> Result<(), Box<dyn std::error::Error>> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    // None once the other side hung up
+    async fn receive(&mut self) -> Result<Option<Message>, Box<dyn std::error::Error>> {
+        match self.lines.next_line().await? {
+            Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+// `arctic worker`: takes one run at a time from whichever coordinator connects
+pub async fn serve(listen: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(listen).await?;
+    info!(address = %listener.local_addr()?, "worker listening");
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        info!(%peer, "coordinator connected");
+        match work(Connection::new(stream)).await {
+            Ok(()) => info!("run finished"),
+            Err(e) => error!(error = %e, "run failed"),
+        }
+    }
+}
+
+async fn work(mut connection: Connection) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(Message::Job { args, files, index, count, seed }) = connection.receive().await? else {
+        return Err("expected a job from the coordinator".into());
+    };
+    let runner = match prepare(args, files, index, count, seed) {
+        Ok(runner) => runner,
+        Err(e) => {
+            connection.send(&Message::Failed { error: e.to_string() }).await?;
+            return Err(e);
+        }
+    };
+    connection.send(&Message::Ready).await?;
+    match connection.receive().await? {
+        Some(Message::Start) => {}
+        _ => return Err("coordinator left before the start".into()),
+    }
+
+    let stats = runner.stats();
+    let stop = runner.stop_handle();
+    // the clock started when the runner was built, not when the coordinator said go
+    stats.restart();
+    let run = runner.run();
+    tokio::pin!(run);
+    let mut progress = tokio::time::interval(PROGRESS_INTERVAL);
+    let mut stopping = false;
+    loop {
+        tokio::select! {
+            result = &mut run => {
+                let message = match result {
+                    Ok(_) => Message::Done { stats: stats.snapshot() },
+                    Err(e) => Message::Failed { error: e.to_string() },
+                };
+                return connection.send(&message).await;
+            }
+            _ = progress.tick() => connection.send(&Message::Progress { stats: stats.snapshot() }).await?,
+            message = connection.receive(), if !stopping => match message? {
+                Some(Message::Stop) => {
+                    stop.store(true, Ordering::SeqCst);
+                    stopping = true;
+                }
+                // dropping the run cancels whatever is in flight
+                None => return Err("coordinator hung up".into()),
+                Some(other) => return Err(format!("unexpected message from the coordinator: {:?}", other).into()),
+            },
+        }
+    }
+}
+
+// lays the coordinator's files out in a scratch directory, so relative paths
+// in the flags and the config file find them, and builds this worker's share
+fn prepare(
+    args: Vec<String>,
+    files: Files,
+    index: usize,
+    count: usize,
+    seed: u64,
+) -> Result<Runner, Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("arctic-worker-{}", std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    for (path, contents) in &files {
+        let local = dir.join(relative(path));
+        if let Some(parent) = local.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(local, contents)?;
+    }
+    std::env::set_current_dir(&dir)?;
+
+    let matches = cli::command().try_get_matches_from(std::iter::once("arctic".to_string()).chain(args))?;
+    let config = LoadConfig::from_matches(&matches)?.seed(seed).share(index, count);
+    let generator = crate::generator(&config)?;
+    info!(worker = index + 1, of = count, endpoints = config.endpoint_urls().join(", "), "share loaded");
+    Ok(Runner::new(config, generator))
+}
+
+// `arctic run --workers`: hands every worker a share of the load, starts them
+// together and merges what they report into one summary
+pub async fn coordinate(matches: &ArgMatches, workers: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = LoadConfig::from_matches(matches)?;
+    if config.stdin {
+        return Err("--data - reads this machine's stdin, it can't be split across --workers".into());
+    }
+    if config.adaptive.is_some() {
+        return Err("--adaptive follows the p95 of one machine's requests, it can't be split across --workers".into());
+    }
+    if config.scenario.is_some() {
+        return Err("--scenario steps read their data files on this machine, it can't be split across --workers".into());
+    }
+    if config.global_setup.is_some() || config.teardown.is_some() {
+        return Err("--global-setup and --teardown run once, they can't be split across --workers".into());
+    }
+    // what would watch or write down the requests of this machine's run,
+    // which the coordinator doesn't have
+    let local = [
+        (config.tui, "--tui"),
+        (config.timeseries.is_some(), "--timeseries"),
+        (config.prometheus_port.is_some(), "--prometheus-port"),
+        (config.log_requests.is_some(), "--log-requests"),
+        (config.sample_responses.is_some(), "--sample-responses"),
+    ];
+    if let Some((_, flag)) = local.iter().find(|(given, _)| *given) {
+        return Err(format!("{} follows the requests of one machine, it doesn't work with --workers", flag).into());
+    }
+    let count = workers.len();
+    // a virtual user is on one machine, fewer than one each leaves some idle
+    if config.profile.unit == StageUnit::Concurrency && (config.profile.peak().round() as usize) < count {
+        return Err(format!("{} virtual users can't be shared out across {} --workers, give at least one each", config.profile.peak().round(), count).into());
+    }
+    let (args, files) = job(matches)?;
+    let quiet = matches.get_flag("quiet") || config.json;
+    if !quiet {
+        crate::print_config(&config);
+        println!("workers: {}", workers.join(", "));
+    }
+
+    let mut connections = Vec::new();
+    for (index, worker) in workers.iter().enumerate() {
+        let address = match worker.contains(':') {
+            true => worker.clone(),
+            false => format!("{}:{}", worker, DEFAULT_PORT),
+        };
+        let stream = TcpStream::connect(&address)
+            .await
+            .map_err(|e| format!("{}: {}", address, e))?;
+        let mut connection = Connection::new(stream);
+        let job = Message::Job { args: args.clone(), files: files.clone(), index, count, seed: config.seed };
+        connection.send(&job).await?;
+        connections.push((address, connection));
+    }
+    // every worker has its share loaded before any of them starts
+    for (address, connection) in &mut connections {
+        match connection.receive().await? {
+            Some(Message::Ready) => {}
+            Some(Message::Failed { error }) => return Err(format!("{}: {}", address, error).into()),
+            _ => return Err(format!("{}: hung up before it was ready", address).into()),
+        }
+    }
+    for (_, connection) in &mut connections {
+        connection.send(&Message::Start).await?;
+    }
+
+    let stats = LoadTestStats::new(Checks::new(config.assertions.clone()), &config.metrics, &config.endpoint_labels(), &config.tag_labels(), &config.template_labels(), &config.chaos_labels());
+    let latest = Mutex::new(vec![StatsSnapshot::default(); count]);
+    let (stop, _) = watch::channel(false);
+    let followers = connections
+        .into_iter()
+        .enumerate()
+        .map(|(index, (address, connection))| follow(index, address, connection, &latest, &stop));
+    let results = tokio::select! {
+        results = join_all(followers) => results,
+        _ = progress(&config, &stats, &latest, quiet) => unreachable!(),
+    };
+    crate::logging::spinner(false);
+    if !quiet {
+        match crate::logging::plain() {
+            true => println!("Finished!"),
+            false => println!("\rFinished!{:30}", ""),
+        }
+    }
+    for result in results {
+        result?;
+    }
+
+    let snapshots = latest.into_inner().unwrap();
+    // only the first reason sticks, a tripped breaker anywhere is the one to report
+    if snapshots.iter().any(|s| s.stop_reason == Some(StopReason::ErrorRate)) {
+        stats.stop(StopReason::ErrorRate);
+    }
+    for snapshot in &snapshots {
+        stats.merge(snapshot);
+    }
+    let report = Report::new(&stats, &config);
+    crate::finish(&config, &report, &stats)
+}
+
+// keeps the latest stats of one worker until it's done
+async fn follow(
+    index: usize,
+    address: String,
+    mut connection: Connection,
+    latest: &Mutex<Vec<StatsSnapshot>>,
+    stop: &watch::Sender<bool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stopping = stop.subscribe();
+    loop {
+        tokio::select! {
+            message = connection.receive() => match message.map_err(|e| format!("{}: {}", address, e))? {
+                Some(Message::Progress { stats }) => latest.lock().unwrap()[index] = stats,
+                Some(Message::Done { stats }) => {
+                    // one worker tripping --abort-on-error-rate ends the run everywhere
+                    if stats.stop_reason == Some(StopReason::ErrorRate) {
+                        stop.send_replace(true);
+                    }
+                    latest.lock().unwrap()[index] = stats;
+                    return Ok(());
+                }
+                Some(Message::Failed { error }) => {
+                    stop.send_replace(true);
+                    return Err(format!("{}: {}", address, error).into());
+                }
+                _ => {
+                    stop.send_replace(true);
+                    return Err(format!("{}: connection lost", address).into());
+                }
+            },
+            _ = stopping.changed() => connection.send(&Message::Stop).await?,
+        }
+    }
+}
+
+// a running total on the terminal unless `quiet`, never returns
+async fn progress(config: &LoadConfig, stats: &LoadTestStats, latest: &Mutex<Vec<StatsSnapshot>>, quiet: bool) {
+    // a line of its own now and then where there's no terminal to redraw one in
+    let plain = crate::logging::plain();
+    if !quiet {
+        println!();
+        crate::logging::spinner(!plain);
+    }
+    // the workers' own stats leave the warm-up out, so does the merged clock
+    if let Some(warmup) = config.warmup {
+        if !quiet {
+            print!("{}warming up...", if plain { "" } else { "\r" });
+            stdout().flush().ok();
+        }
+        sleep(warmup).await;
+        if !quiet && plain {
+            println!();
+        }
+    }
+    stats.restart();
+    if quiet {
+        return std::future::pending().await;
+    }
+    let every = if plain { PLAIN_PROGRESS_EVERY } else { PROGRESS_INTERVAL };
+    loop {
+        let (requests, errors) = latest
+            .lock()
+            .unwrap()
+            .iter()
+            .fold((0, 0), |(r, e), s| (r + s.total_requests, e + s.error_count));
+        match plain {
+            true => println!("running... {} requests, {} errors", requests, errors),
+            false => print!("\rrunning... {} requests, {} errors", requests, errors),
+        }
+        stdout().flush().ok();
+        sleep(every).await;
+    }
+}
+
+// the flags given on the command line as a worker's argv, file paths
+// pointed at the copies sent along
+fn job(matches: &ArgMatches) -> Result<(Vec<String>, Files), Box<dyn std::error::Error>> {
+    let mut args = Vec::new();
+    let mut files = Files::new();
+    for arg in cli::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        if COORDINATOR_ARGS.contains(&id) || matches.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let flag = format!("--{}", arg.get_long().unwrap_or(id));
+        if !arg.get_action().takes_values() {
+            args.push(flag);
+            continue;
+        }
+        for value in matches.get_raw(id).into_iter().flatten() {
+            let value = value.to_str().ok_or_else(|| format!("{}: not valid utf-8", flag))?;
+            let value = match id {
+                "proto-include" => copy_dir(value, &mut files)?,
+                // the weight stays out of the path
+                "data" => {
+                    let spec = TemplateSpec::parse(value)?;
+                    TemplateSpec { path: copy(&spec.path, &mut files)?, ..spec }.to_string()
+                }
+                _ if FILE_ARGS.contains(&id) => copy(value, &mut files)?,
+                _ => value.to_string(),
+            };
+            // = keeps values that start with a dash from reading as flags
+            args.push(format!("{}={}", flag, value));
+        }
+    }
+
+    // and whatever the config file points at
+    let config = match matches.get_one::<String>("config") {
+        Some(path) => Some(path.clone()),
+        None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+            copy(DEFAULT_CONFIG_PATH, &mut files)?;
+            Some(DEFAULT_CONFIG_PATH.to_string())
+        }
+        None => None,
+    };
+    if let Some(path) = config {
+        let file = FileConfig::load(&path)?;
+        let paths = [
+            &file.targets,
+            &file.har,
+            &file.corpus,
+            &file.feed,
+            &file.script,
+            &file.graphql,
+            &file.cacert,
+            &file.cert,
+            &file.key,
+        ];
+        let templates = file.data.iter().chain(&file.templates).map(|spec| TemplateSpec::parse(spec).map(|spec| spec.path));
+        let templates = templates.collect::<Result<Vec<_>, _>>()?;
+        for path in paths.into_iter().flatten().chain(&file.proto).chain(&templates) {
+            // ones a flag replaced may well not exist
+            if Path::new(path).is_file() {
+                copy(path, &mut files)?;
+            }
+        }
+        for dir in &file.proto_include {
+            copy_dir(dir, &mut files)?;
+        }
+    }
+    Ok((args, files))
+}
+
+// queues a file for the workers, returns the path they'll find it at
+fn copy(path: &str, files: &mut Files) -> Result<String, Box<dyn std::error::Error>> {
+    let local = relative(path);
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    files.insert(local.clone(), contents);
+    Ok(local)
+}
+
+// every .proto file below an include directory
+fn copy_dir(dir: &str, files: &mut Files) -> Result<String, Box<dyn std::error::Error>> {
+    for entry in fs::read_dir(dir).map_err(|e| format!("{}: {}", dir, e))? {
+        let path = entry?.path();
+        let Some(path) = path.to_str() else { continue };
+        if Path::new(path).is_dir() {
+            copy_dir(path, files)?;
+        } else if path.ends_with(".proto") {
+            copy(path, files)?;
+        }
+    }
+    Ok(relative(dir))
+}
+
+// absolute and parent components dropped, so a copy can't land outside the
+// worker's scratch directory
+fn relative(path: &str) -> String {
+    Path::new(path)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
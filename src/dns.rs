@@ -0,0 +1,181 @@
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::stats::LoadTestStats;
+use crate::tls::{HostOverride, IpFamily};
+
+// --dns-refresh: a name is looked up again once its addresses are older
+// than the interval, rather than the run holding on to the connections it
+// made to whatever the name pointed at first. New connections take the
+// addresses in turn so they spread over every record, and when the records
+// change each worker moves to a client built anew, so its next request
+// opens a connection to where the name points now
+#[derive(Debug)]
+pub struct DnsRefresh {
+    interval: Duration,
+    stats: Arc<LoadTestStats>,
+    hosts: Mutex<HashMap<String, Cached>>,
+    // how often the records of any host changed, and what each worker saw
+    // of that at its last request
+    changes: AtomicU64,
+    seen: Vec<AtomicU64>,
+}
+
+#[derive(Debug)]
+struct Cached {
+    // sorted, to tell a change from a shuffle
+    addrs: Vec<IpAddr>,
+    resolved: Instant,
+    // the address the next connection starts with
+    next: usize,
+}
+
+impl DnsRefresh {
+    pub fn new(interval: Duration, workers: usize, stats: Arc<LoadTestStats>) -> Arc<Self> {
+        Arc::new(DnsRefresh {
+            interval,
+            stats,
+            hosts: Mutex::new(HashMap::new()),
+            changes: AtomicU64::new(0),
+            seen: (0..workers).map(|_| AtomicU64::new(0)).collect(),
+        })
+    }
+
+    // whether the records changed since `worker` last asked, so it should
+    // let go of its connection
+    pub fn changed_for(&self, worker: usize) -> bool {
+        let changes = self.changes.load(Ordering::SeqCst);
+        self.seen.get(worker).is_some_and(|seen| seen.swap(changes, Ordering::SeqCst) != changes)
+    }
+
+    async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>, String> {
+        match self.cached(host, false) {
+            Some(addrs) => Ok(addrs),
+            None => self.resolve(host).await,
+        }
+    }
+
+    // looked up now, whatever the cache holds
+    async fn resolve(&self, host: &str) -> Result<Vec<IpAddr>, String> {
+        self.stats.dns_lookups.fetch_add(1, Ordering::SeqCst);
+        let resolved = tokio::net::lookup_host((host, 0)).await.map(|addrs| {
+            let mut addrs: Vec<IpAddr> = addrs.map(|addr| addr.ip()).collect();
+            addrs.sort();
+            addrs.dedup();
+            addrs
+        });
+        let addrs = match resolved {
+            Ok(addrs) if !addrs.is_empty() => addrs,
+            // a failover drill may take the name away for a while, the
+            // addresses it had are better than none
+            failed => {
+                let error = failed.map_or_else(|e| e.to_string(), |_| "no addresses".to_string());
+                return match self.cached(host, true) {
+                    Some(addrs) => {
+                        warn!(host, error = %error, "dns refresh failed, keeping the addresses from before");
+                        Ok(addrs)
+                    }
+                    None => Err(format!("{}: {}", host, error)),
+                };
+            }
+        };
+        let mut hosts = self.hosts.lock().unwrap();
+        let cached = hosts.entry(host.to_string()).or_insert_with(|| Cached { addrs: Vec::new(), resolved: Instant::now(), next: 0 });
+        if !cached.addrs.is_empty() && cached.addrs != addrs {
+            info!(host, before = ?cached.addrs, now = ?addrs, "dns records changed");
+            self.changes.fetch_add(1, Ordering::SeqCst);
+            self.stats.dns_changes.fetch_add(1, Ordering::SeqCst);
+        }
+        cached.addrs = addrs;
+        cached.resolved = Instant::now();
+        Ok(rotate(cached))
+    }
+
+    // the addresses while they're fresh, or regardless with `stale`, in
+    // the order the next connection tries them
+    fn cached(&self, host: &str, stale: bool) -> Option<Vec<IpAddr>> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let cached = hosts.get_mut(host).filter(|cached| stale || cached.resolved.elapsed() < self.interval)?;
+        (!cached.addrs.is_empty()).then(|| rotate(cached))
+    }
+}
+
+fn rotate(cached: &mut Cached) -> Vec<IpAddr> {
+    let mut addrs = cached.addrs.clone();
+    let first = cached.next % addrs.len();
+    addrs.rotate_left(first);
+    cached.next = cached.next.wrapping_add(1);
+    addrs
+}
+
+// looks every host up again each interval until finished: connections kept
+// alive don't ask for addresses, so nothing else would see the records change
+pub struct Refresher {
+    task: JoinHandle<()>,
+}
+
+impl Refresher {
+    pub fn start(dns: Arc<DnsRefresh>) -> Self {
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + dns.interval, dns.interval);
+            loop {
+                interval.tick().await;
+                let hosts: Vec<String> = dns.hosts.lock().unwrap().keys().cloned().collect();
+                for host in hosts {
+                    if let Err(e) = dns.resolve(&host).await {
+                        warn!(host, error = %e, "dns refresh failed");
+                    }
+                }
+            }
+        });
+        Refresher { task }
+    }
+
+    pub fn finish(self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for Refresher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// what the clients resolve names with: --dns-refresh's cache, or the
+// system's lookup without it; the --sni name is looked up as the endpoints'
+// host, and only addresses of --ipv4 or --ipv6 are kept
+pub struct Resolver {
+    pub dns: Option<Arc<DnsRefresh>>,
+    pub host_override: Option<HostOverride>,
+    pub family: Option<IpFamily>,
+}
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let dns = self.dns.clone();
+        let family = self.family;
+        let host = self.host_override.as_ref().map_or(name.as_str(), |o| o.lookup_name(name.as_str())).to_string();
+        Box::pin(async move {
+            let ips = match (host.parse::<IpAddr>(), dns) {
+                (Ok(ip), _) => vec![ip],
+                (Err(_), Some(dns)) => dns.lookup(&host).await?,
+                (Err(_), None) => tokio::net::lookup_host((host.as_str(), 0)).await?.map(|addr| addr.ip()).collect(),
+            };
+            // the connector puts the url's port in
+            let addrs: Vec<SocketAddr> = ips.into_iter().filter(|ip| family.is_none_or(|f| f.matches(ip))).map(|ip| SocketAddr::new(ip, 0)).collect();
+            if addrs.is_empty() {
+                return Err(format!("{}: no addresses of the --ipv4/--ipv6 family", host).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
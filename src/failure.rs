@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error as _;
+use std::fmt;
+use std::sync::Mutex;
+use tokio_native_tls::native_tls;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TransportError {
+    Timeout,
+    ConnectionRefused,
+    Dns,
+    Tls,
+    Connect,
+    // the response broke off or couldn't be decoded after its headers
+    Body,
+    // http/2 connection shut down or stream refused by the server
+    GoAway,
+    StreamReset,
+    // more hops than --max-redirects allows
+    Redirects,
+    Other,
+}
+
+impl TransportError {
+    pub const ALL: [TransportError; 10] = [
+        TransportError::Timeout,
+        TransportError::ConnectionRefused,
+        TransportError::Dns,
+        TransportError::Tls,
+        TransportError::Connect,
+        TransportError::Body,
+        TransportError::GoAway,
+        TransportError::StreamReset,
+        TransportError::Redirects,
+        TransportError::Other,
+    ];
+
+    pub fn classify(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            return TransportError::Timeout;
+        }
+        let mut source = error.source();
+        while let Some(cause) = source {
+            if let Some(h2) = cause.downcast_ref::<h2::Error>() {
+                if h2.is_go_away() {
+                    return TransportError::GoAway;
+                }
+                if h2.is_reset() {
+                    return TransportError::StreamReset;
+                }
+            }
+            source = cause.source();
+        }
+        if error.is_redirect() {
+            return TransportError::Redirects;
+        }
+        if error.is_body() || error.is_decode() {
+            return TransportError::Body;
+        }
+        if !error.is_connect() {
+            return TransportError::Other;
+        }
+
+        let mut source = error.source();
+        while let Some(cause) = source {
+            if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+                match io.kind() {
+                    std::io::ErrorKind::ConnectionRefused => return TransportError::ConnectionRefused,
+                    std::io::ErrorKind::TimedOut => return TransportError::Timeout,
+                    _ => {}
+                }
+            }
+            if cause.downcast_ref::<native_tls::Error>().is_some() {
+                return TransportError::Tls;
+            }
+            let message = cause.to_string();
+            if message.contains("dns error") || message.contains("failed to lookup address") {
+                return TransportError::Dns;
+            }
+            source = cause.source();
+        }
+        TransportError::Connect
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    // errors of the http/2 framing rather than of the connection
+    pub fn is_protocol(self) -> bool {
+        matches!(self, TransportError::GoAway | TransportError::StreamReset)
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TransportError::Timeout => "timeout",
+            TransportError::ConnectionRefused => "connection refused",
+            TransportError::Dns => "dns",
+            TransportError::Tls => "tls",
+            TransportError::Connect => "connect",
+            TransportError::Body => "body",
+            TransportError::GoAway => "goaway",
+            TransportError::StreamReset => "stream reset",
+            TransportError::Redirects => "too many redirects",
+            TransportError::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+// "2xx", "4xx", ...
+pub fn status_class(status: u16) -> String {
+    format!("{}xx", status / 100)
+}
+
+// --expect-status 200,201,409: the statuses a response may have and still
+// count as successful, codes or classes such as 2xx; any other status fails
+// the request, a 2xx left out of the list as much as a 500
+#[derive(Debug, Clone, Serialize)]
+#[serde(into = "String")]
+pub struct ExpectedStatus {
+    codes: Vec<u16>,
+    // 2 for 2xx
+    classes: Vec<u16>,
+}
+
+impl ExpectedStatus {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut expected = ExpectedStatus { codes: Vec::new(), classes: Vec::new() };
+        for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let invalid = || format!("--expect-status: {}: expected a status code such as 201 or a class such as 2xx", part);
+            match part.to_ascii_lowercase().strip_suffix("xx") {
+                Some(class) => match class.parse::<u16>() {
+                    Ok(class) if (1..=5).contains(&class) => expected.classes.push(class),
+                    _ => return Err(invalid()),
+                },
+                None => match part.parse::<u16>() {
+                    Ok(code) if (100..=599).contains(&code) => expected.codes.push(code),
+                    _ => return Err(invalid()),
+                },
+            }
+        }
+        if expected.codes.is_empty() && expected.classes.is_empty() {
+            return Err("--expect-status: expected a list of status codes, e.g. 200,201,409".to_string());
+        }
+        Ok(expected)
+    }
+
+    pub fn allows(&self, status: u16) -> bool {
+        self.codes.contains(&status) || self.classes.contains(&(status / 100))
+    }
+}
+
+impl fmt::Display for ExpectedStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let codes = self.codes.iter().map(u16::to_string);
+        let classes = self.classes.iter().map(|class| format!("{}xx", class));
+        f.write_str(&codes.chain(classes).collect::<Vec<_>>().join(","))
+    }
+}
+
+impl From<ExpectedStatus> for String {
+    fn from(expected: ExpectedStatus) -> Self {
+        expected.to_string()
+    }
+}
+
+// failed requests bucketed by what went wrong, e.g. "connection refused" or
+// "503 Service Unavailable", each with the first message seen for it
+#[derive(Debug, Default)]
+pub struct ErrorBuckets {
+    buckets: Mutex<BTreeMap<String, ErrorBucket>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorBucket {
+    pub kind: String,
+    pub count: u64,
+    pub example: String,
+}
+
+impl ErrorBuckets {
+    // true for the first error of its kind
+    pub fn record(&self, kind: &str, message: &str) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        match buckets.get_mut(kind) {
+            Some(bucket) => {
+                bucket.count += 1;
+                false
+            }
+            None => {
+                let bucket = ErrorBucket { kind: kind.to_string(), count: 1, example: message.to_string() };
+                buckets.insert(kind.to_string(), bucket);
+                true
+            }
+        }
+    }
+
+    // most frequent first
+    pub fn top(&self, n: usize) -> Vec<ErrorBucket> {
+        let mut buckets: Vec<ErrorBucket> = self.buckets.lock().unwrap().values().cloned().collect();
+        buckets.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.kind.cmp(&b.kind)));
+        buckets.truncate(n);
+        buckets
+    }
+
+    pub fn snapshot(&self) -> Vec<ErrorBucket> {
+        self.buckets.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn merge(&self, other: &[ErrorBucket]) {
+        let mut buckets = self.buckets.lock().unwrap();
+        for bucket in other {
+            buckets
+                .entry(bucket.kind.clone())
+                .and_modify(|b| b.count += bucket.count)
+                .or_insert_with(|| bucket.clone());
+        }
+    }
+}
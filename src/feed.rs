@@ -0,0 +1,79 @@
+use rand::Rng;
+use serde::Serialize;
+use std::str::FromStr;
+
+// rows of a csv file with a header line, one row per request
+#[derive(Debug, Clone)]
+pub struct Feed {
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+    order: FeedOrder,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedOrder {
+    // row after row, starting over at the end
+    #[default]
+    Cycle,
+    Random,
+}
+
+impl FromStr for FeedOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cycle" => Ok(FeedOrder::Cycle),
+            "random" => Ok(FeedOrder::Random),
+            other => Err(format!("unknown feed order: {}", other)),
+        }
+    }
+}
+
+// the values of one row, looked up by column name
+#[derive(Debug, Clone, Copy)]
+pub struct Record<'a> {
+    columns: &'a [String],
+    values: &'a [String],
+}
+
+impl<'a> Record<'a> {
+    pub fn get(&self, column: &str) -> Option<&'a str> {
+        let index = self.columns.iter().position(|c| c == column)?;
+        self.values.get(index).map(String::as_str)
+    }
+}
+
+impl Feed {
+    pub fn load(path: &str, order: FeedOrder) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = csv::Reader::from_path(path).map_err(|e| format!("{}: {}", path, e))?;
+        let columns = reader
+            .headers()
+            .map_err(|e| format!("{}: {}", path, e))?
+            .iter()
+            .map(|c| c.trim().to_string())
+            .collect();
+        let mut rows = Vec::new();
+        for row in reader.records() {
+            let row = row.map_err(|e| format!("{}: {}", path, e))?;
+            rows.push(row.iter().map(str::to_string).collect());
+        }
+        if rows.is_empty() {
+            return Err(format!("{}: no rows below the header", path).into());
+        }
+        Ok(Feed { columns, rows, order })
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn record<R: Rng + ?Sized>(&self, sequence: u64, rng: &mut R) -> Record<'_> {
+        let index = match self.order {
+            FeedOrder::Cycle => (sequence % self.rows.len() as u64) as usize,
+            FeedOrder::Random => rng.gen_range(0..self.rows.len()),
+        };
+        Record { columns: &self.columns, values: &self.rows[index] }
+    }
+}
@@ -0,0 +1,160 @@
+use serde::Serialize;
+use std::io::{stdout, Write};
+use std::time::Duration;
+
+use crate::config::LoadConfig;
+use crate::generator::Generator;
+use crate::report::Report;
+use crate::runner::Runner;
+use crate::units::serialize_opt_secs;
+
+// `arctic find-max`: steps the rate up by `step` from `start` until a step
+// breaks a limit, then bisects between the last rate that held and the
+// first that didn't `refine` times
+#[derive(Debug, Clone, Serialize)]
+pub struct Search {
+    pub start: f64,
+    pub step: f64,
+    pub max_rate: f64,
+    #[serde(rename = "step_duration_secs")]
+    pub step_duration: u64,
+    // percent of failed requests a step may have
+    pub max_error_rate: f64,
+    #[serde(rename = "max_p99_secs", serialize_with = "serialize_opt_secs")]
+    pub max_p99: Option<Duration>,
+    pub refine: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Step {
+    pub rate: f64,
+    pub achieved_rps: f64,
+    pub error_rate: f64,
+    pub p99_ms: f64,
+    pub passed: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchReport {
+    // none when not even the lowest rate tried held
+    pub max_sustainable_rps: Option<f64>,
+    // stopped at --max-rate without breaking a limit
+    pub capped: bool,
+    pub steps: Vec<Step>,
+    pub search: Search,
+    pub config: LoadConfig,
+}
+
+impl Search {
+    pub async fn run(&self, config: &LoadConfig, generator: &Generator, quiet: bool) -> Result<SearchReport, Box<dyn std::error::Error>> {
+        let mut steps = Vec::new();
+        let (mut good, mut bad) = (None, None);
+        let mut rate = self.start;
+        let mut capped = false;
+
+        loop {
+            let step = self.step_at(config, generator, rate, steps.len() + 1, quiet).await?;
+            let passed = step.passed;
+            steps.push(step);
+            if !passed {
+                bad = Some(rate);
+                break;
+            }
+            good = Some(rate);
+            rate += self.step;
+            if rate > self.max_rate {
+                capped = true;
+                break;
+            }
+        }
+
+        if let Some(mut high) = bad {
+            let mut low = good.unwrap_or(0.0);
+            for _ in 0..self.refine {
+                let rate = (low + high) / 2.0;
+                let step = self.step_at(config, generator, rate, steps.len() + 1, quiet).await?;
+                match step.passed {
+                    true => {
+                        low = rate;
+                        good = Some(rate);
+                    }
+                    false => high = rate,
+                }
+                steps.push(step);
+            }
+        }
+
+        Ok(SearchReport {
+            max_sustainable_rps: good,
+            capped,
+            steps,
+            search: self.clone(),
+            config: config.clone(),
+        })
+    }
+
+    async fn step_at(
+        &self,
+        config: &LoadConfig,
+        generator: &Generator,
+        rate: f64,
+        number: usize,
+        quiet: bool,
+    ) -> Result<Step, Box<dyn std::error::Error>> {
+        if !quiet {
+            print!("step {}: {:.1} rps for {}s ... ", number, rate, self.step_duration);
+            stdout().flush().ok();
+        }
+        let config = config.clone().rate(rate).duration(self.step_duration);
+        let report = Runner::new(config, generator.clone()).run().await?;
+        let step = self.judge(rate, &report);
+        if !quiet {
+            println!(
+                "{:.1} rps, {:.2}% errors, p99 {:.2}ms: {}",
+                step.achieved_rps,
+                step.error_rate,
+                step.p99_ms,
+                if step.passed { "ok" } else { "over the limit" }
+            );
+        }
+        Ok(step)
+    }
+
+    fn judge(&self, rate: f64, report: &Report) -> Step {
+        let error_rate = match report.total_requests {
+            0 => 0.0,
+            n => report.failed_requests as f64 / n as f64 * 100.0,
+        };
+        let p99_ms = report.latency_ms.p99;
+        let p99_ok = self.max_p99.is_none_or(|max| p99_ms <= max.as_secs_f64() * 1000.0);
+        Step {
+            rate,
+            achieved_rps: report.requests_per_second,
+            error_rate,
+            p99_ms,
+            passed: report.total_requests > 0 && error_rate <= self.max_error_rate && p99_ok,
+        }
+    }
+}
+
+impl SearchReport {
+    pub fn print(&self) {
+        println!("\n===== Find Max Summary =====");
+        println!("steps run:           {}", self.steps.len());
+        match self.max_sustainable_rps {
+            Some(rps) if self.capped => println!("max sustainable rate: {:.1} rps (reached --max-rate)", rps),
+            Some(rps) => println!("max sustainable rate: {:.1} rps", rps),
+            None => println!("max sustainable rate: none, {:.1} rps already broke a limit", self.search.start),
+        }
+        let mut limits = vec![format!("errors <= {}%", self.search.max_error_rate)];
+        if let Some(p99) = self.search.max_p99 {
+            limits.push(format!("p99 <= {:?}", p99));
+        }
+        println!("limits:              {}", limits.join(", "));
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
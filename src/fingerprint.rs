@@ -0,0 +1,106 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::header::{HeaderName, HeaderValue, USER_AGENT};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+
+// how often a client changes its fingerprint
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rotate {
+    // a fresh one drawn for every request
+    Request,
+    // one per virtual user, kept for all of its requests
+    User,
+}
+
+impl Rotate {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "request" => Ok(Rotate::Request),
+            "user" => Ok(Rotate::User),
+            _ => Err(format!("--rotate-per {}: expected request or user", name)),
+        }
+    }
+}
+
+// --user-agents, one User-Agent per line, or --header-sets, one json object
+// of headers per line such as a browser's User-Agent with the
+// Accept-Language that goes with it; requests go out with one of them so a
+// target's per-client rate limits and caches see many clients
+#[derive(Debug, Clone, Serialize)]
+pub struct Fingerprints {
+    pub path: String,
+    pub rotate: Rotate,
+    pub entries: usize,
+    #[serde(skip)]
+    sets: Vec<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl Fingerprints {
+    pub fn user_agents(path: &str, rotate: Rotate) -> Result<Self, String> {
+        let sets = lines(path)?
+            .into_iter()
+            .map(|(number, line)| {
+                let value = HeaderValue::try_from(line).map_err(|_| format!("{}:{}: not a valid User-Agent", path, number))?;
+                Ok(vec![(USER_AGENT, value)])
+            })
+            .collect::<Result<_, String>>()?;
+        Self::new(path, rotate, sets)
+    }
+
+    pub fn header_sets(path: &str, rotate: Rotate) -> Result<Self, String> {
+        let sets = lines(path)?
+            .into_iter()
+            .map(|(number, line)| {
+                let at = || format!("{}:{}", path, number);
+                let Ok(Value::Object(set)) = serde_json::from_str(&line) else {
+                    return Err(format!("{}: expected a json object of header names to values", at()));
+                };
+                set.into_iter()
+                    .map(|(name, value)| {
+                        let value = match value {
+                            Value::String(s) => s,
+                            other => other.to_string(),
+                        };
+                        Ok((
+                            HeaderName::try_from(&name).map_err(|_| format!("{}: invalid header name {}", at(), name))?,
+                            HeaderValue::try_from(value).map_err(|_| format!("{}: invalid value for {}", at(), name))?,
+                        ))
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, String>>()?;
+        Self::new(path, rotate, sets)
+    }
+
+    fn new(path: &str, rotate: Rotate, sets: Vec<Vec<(HeaderName, HeaderValue)>>) -> Result<Self, String> {
+        if sets.is_empty() {
+            return Err(format!("{}: no entries", path));
+        }
+        Ok(Fingerprints { path: path.to_string(), rotate, entries: sets.len(), sets })
+    }
+
+    // the headers the request goes out with; the rng is only drawn from when
+    // they rotate per request
+    pub fn pick(&self, user: usize, rng: &mut StdRng) -> &[(HeaderName, HeaderValue)] {
+        let index = match self.rotate {
+            Rotate::Request => rng.gen_range(0..self.sets.len()),
+            Rotate::User => user % self.sets.len(),
+        };
+        &self.sets[index]
+    }
+}
+
+// the numbered lines of the file, blank ones and # comments left out
+fn lines(path: &str) -> Result<Vec<(usize, String)>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| (number, line.to_string()))
+        .collect())
+}
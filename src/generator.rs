@@ -0,0 +1,357 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use crate::config::LoadConfig;
+use crate::corpus::{Corpus, CorpusOrder};
+use crate::feed::Feed;
+use crate::placeholder::Context;
+use crate::script::Script;
+use crate::template::Template;
+
+// payloads from a backend, shaped like a template or replayed from a
+// corpus, reproducible from (seed, sequence)
+#[derive(Debug, Clone)]
+pub struct Generator {
+    backend: Arc<dyn Backend>,
+    feed: Option<Arc<Feed>>,
+    // generate(ctx) of a --script, which gets the payload the backend made
+    script: Option<Arc<Script>>,
+    seed: u64,
+}
+
+// where payloads come from; clones of a generator share theirs, so a reload
+// reaches every one of them
+pub trait Backend: fmt::Debug + Send + Sync {
+    // the payload of one request, with the index of the template it came
+    // from when the backend has several
+    fn generate(&self, context: &Context, rng: &mut dyn RngCore) -> (Option<usize>, Value);
+
+    // how many templates generate() picks from, none if it isn't templated
+    fn templates(&self) -> usize {
+        0
+    }
+
+    // the {{csv.<column>}} placeholders the payloads refer to
+    fn columns(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    // a payload of every shape generate() makes, for checks made before a run
+    fn samples(&self, context: &Context, rng: &mut dyn RngCore) -> Vec<Value> {
+        vec![self.generate(context, rng).1]
+    }
+
+    // swaps in the file at `path` on a --watch change; `check` gets the
+    // columns of the new one first and can refuse it
+    fn reload(&self, _path: &str, _check: &dyn Fn(&[String]) -> Result<(), String>) -> Result<(), Box<dyn std::error::Error>> {
+        Err("only a --data template can be reloaded".into())
+    }
+}
+
+// builds a backend for a run from the options it reads off the config
+pub type Factory = Box<dyn Fn(&LoadConfig) -> Result<Arc<dyn Backend>, Box<dyn std::error::Error>> + Send + Sync>;
+
+// the backends --generator can name; a library user registers their own
+// next to the built-in template, corpus and script ones
+pub struct Registry {
+    factories: BTreeMap<String, Factory>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry { factories: BTreeMap::new() };
+        registry.register("template", |config| match config.data.is_empty() {
+            // a har replay with nothing to generate
+            true => Ok(Arc::new(Templates::new(vec![None], vec![Template::compile(&Value::Null)?], &[1.0]))),
+            false => Ok(Arc::new(Templates::load(&config.data)?)),
+        });
+        registry.register("corpus", |config| {
+            let path = config.corpus.as_ref().ok_or("the corpus generator needs a --corpus")?;
+            Ok(Arc::new(Corpus::load(path, config.corpus_order, config.seed)?))
+        });
+        registry.register("script", |config| match &config.script {
+            Some(script) if script.has_generate() => Ok(Arc::new(Scripted(script.clone()))),
+            _ => Err("the script generator needs a --script with generate(ctx)".into()),
+        });
+        registry
+    }
+}
+
+impl Registry {
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&LoadConfig) -> Result<Arc<dyn Backend>, Box<dyn std::error::Error>> + Send + Sync + 'static,
+    ) {
+        self.factories.insert(name.into(), Box::new(factory));
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.factories.keys().map(String::as_str).collect()
+    }
+
+    // the generator --generator names, or the one the other flags imply:
+    // corpus with a --corpus, script with a generate(ctx) and no --data,
+    // template otherwise
+    pub fn generator(&self, config: &LoadConfig) -> Result<Generator, Box<dyn std::error::Error>> {
+        let scripted = config.script.as_ref().is_some_and(|script| script.has_generate());
+        let name = match &config.generator {
+            Some(name) => name.as_str(),
+            None if config.corpus.is_some() => "corpus",
+            None if scripted && config.data.is_empty() => "script",
+            None => "template",
+        };
+        let factory = self
+            .factories
+            .get(name)
+            .ok_or_else(|| format!("unknown generator {}, expected one of {}", name, self.names().join(", ")))?;
+        let mut generator = Generator::with_backend(factory(config)?, config.seed);
+        if let Some(path) = &config.feed {
+            generator = generator.with_feed(Feed::load(path, config.feed_order)?);
+        }
+        // the script backend already is generate(ctx)
+        match &config.script {
+            Some(script) if name != "script" => Ok(generator.with_script(script.clone())),
+            _ => Ok(generator),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Templates {
+    // the file each came from, none for one built from a schema
+    paths: Vec<Option<String>>,
+    templates: Vec<RwLock<Template>>,
+    weights: WeightedIndex<f64>,
+}
+
+impl Templates {
+    fn new(paths: Vec<Option<String>>, templates: Vec<Template>, weights: &[f64]) -> Self {
+        Templates {
+            paths,
+            templates: templates.into_iter().map(RwLock::new).collect(),
+            // weights are positive, see TemplateSpec::parse
+            weights: WeightedIndex::new(weights).unwrap(),
+        }
+    }
+
+    // each payload comes from one of the templates, picked by weight
+    fn load(specs: &[TemplateSpec]) -> Result<Self, Box<dyn std::error::Error>> {
+        let templates = specs.iter().map(|spec| load(&spec.path)).collect::<Result<_, _>>()?;
+        let paths = specs.iter().map(|spec| Some(spec.path.clone())).collect();
+        let weights: Vec<f64> = specs.iter().map(|spec| spec.weight).collect();
+        Ok(Templates::new(paths, templates, &weights))
+    }
+}
+
+impl Backend for Templates {
+    // a single template leaves the rng as it was for the payload
+    fn generate(&self, context: &Context, rng: &mut dyn RngCore) -> (Option<usize>, Value) {
+        let index = match self.templates.len() {
+            1 => 0,
+            _ => self.weights.sample(rng),
+        };
+        (Some(index), self.templates[index].read().unwrap().generate(rng, context))
+    }
+
+    fn templates(&self) -> usize {
+        self.templates.len()
+    }
+
+    fn columns(&self) -> Vec<String> {
+        let columns = self.templates.iter().flat_map(|t| t.read().unwrap().columns().into_iter().map(str::to_string).collect::<Vec<_>>());
+        columns.collect()
+    }
+
+    fn samples(&self, context: &Context, rng: &mut dyn RngCore) -> Vec<Value> {
+        self.templates.iter().map(|template| template.read().unwrap().generate(rng, context)).collect()
+    }
+
+    // keeps the current template if the new one doesn't compile
+    fn reload(&self, path: &str, check: &dyn Fn(&[String]) -> Result<(), String>) -> Result<(), Box<dyn std::error::Error>> {
+        let index = self
+            .paths
+            .iter()
+            .position(|p| p.as_deref() == Some(path))
+            .ok_or_else(|| format!("{}: not one of the templates", path))?;
+        let template = load(path)?;
+        let columns: Vec<String> = template.columns().into_iter().map(str::to_string).collect();
+        check(&columns)?;
+        *self.templates[index].write().unwrap() = template;
+        Ok(())
+    }
+}
+
+impl Backend for Corpus {
+    fn generate(&self, context: &Context, rng: &mut dyn RngCore) -> (Option<usize>, Value) {
+        (None, self.entry(context.sequence, rng).clone())
+    }
+}
+
+// generate(ctx) of a --script with no template underneath, ctx.body is null
+#[derive(Debug)]
+struct Scripted(Arc<Script>);
+
+impl Backend for Scripted {
+    fn generate(&self, context: &Context, _rng: &mut dyn RngCore) -> (Option<usize>, Value) {
+        (None, self.0.generate(context, Value::Null))
+    }
+}
+
+// one --data, "create.json" or "create.json:3"
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TemplateSpec {
+    pub path: String,
+    pub weight: f64,
+}
+
+impl TemplateSpec {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (path, weight): (&str, f64) = match spec.rsplit_once(':') {
+            Some((path, weight)) if weight.parse::<f64>().is_ok() => (path, weight.parse().unwrap()),
+            _ => (spec, 1.0),
+        };
+        if !(weight > 0.0 && weight.is_finite()) {
+            return Err(format!("template weight must be positive: {}", spec));
+        }
+        Ok(TemplateSpec { path: path.to_string(), weight })
+    }
+}
+
+impl fmt::Display for TemplateSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.weight == 1.0 {
+            true => f.write_str(&self.path),
+            false => write!(f, "{}:{}", self.path, self.weight),
+        }
+    }
+}
+
+impl Generator {
+    pub fn new(schema: &Value, seed: u64) -> Result<Self, String> {
+        let template = Template::compile(schema)?;
+        Ok(Generator::with_backend(Arc::new(Templates::new(vec![None], vec![template], &[1.0])), seed))
+    }
+
+    pub fn with_backend(backend: Arc<dyn Backend>, seed: u64) -> Self {
+        Generator { backend, feed: None, script: None, seed }
+    }
+
+    pub fn from_corpus(path: &str, order: CorpusOrder, seed: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Generator::with_backend(Arc::new(Corpus::load(path, order, seed)?), seed))
+    }
+
+    pub fn from_file(path: &str, seed: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        Generator::from_files(&[TemplateSpec { path: path.to_string(), weight: 1.0 }], seed)
+    }
+
+    pub fn from_files(specs: &[TemplateSpec], seed: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Generator::with_backend(Arc::new(Templates::load(specs)?), seed))
+    }
+
+    // rows for the {{csv.<column>}} placeholders
+    pub fn with_feed(mut self, feed: Feed) -> Self {
+        self.feed = Some(Arc::new(feed));
+        self
+    }
+
+    pub fn with_script(mut self, script: Arc<Script>) -> Self {
+        self.script = Some(script);
+        self
+    }
+
+    // fails on {{csv.<column>}} placeholders, in the payloads or `more`,
+    // that the feed can't fill in
+    pub fn check_columns<'a>(&self, more: impl IntoIterator<Item = &'a str>) -> Result<(), String> {
+        let mut columns: Vec<String> = more.into_iter().map(str::to_string).collect();
+        columns.extend(self.backend.columns());
+        self.check_feed(&columns)
+    }
+
+    fn check_feed(&self, columns: &[String]) -> Result<(), String> {
+        for column in columns {
+            match &self.feed {
+                None => return Err(format!("{{{{csv.{}}}}} needs a --feed", column)),
+                Some(feed) if !feed.columns().contains(column) => return Err(format!("the --feed has no column {}", column)),
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    // swaps in the template at `path` for every clone of this generator
+    pub fn reload(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.backend.reload(path, &|columns| self.check_feed(columns))
+    }
+
+    pub fn templates(&self) -> usize {
+        self.backend.templates()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rng(&self, sequence: u64) -> StdRng {
+        payload_rng(self.seed, sequence)
+    }
+
+    // the n-th payload of a run
+    pub fn generate(&self, sequence: u64) -> Value {
+        let mut rng = self.rng(sequence);
+        let context = self.context(sequence, &mut rng);
+        self.generate_with(&context, &mut rng).1
+    }
+
+    // the first payload of every shape the backend makes
+    pub fn samples(&self) -> Vec<Value> {
+        let mut rng = self.rng(0);
+        let context = self.context(0, &mut rng);
+        let samples = self.backend.samples(&context, &mut rng);
+        samples.into_iter().map(|body| self.scripted(&context, body)).collect()
+    }
+
+    // placeholders in the payload and the url of one request see the same
+    // feed row
+    pub fn context<R: Rng + ?Sized>(&self, sequence: u64, rng: &mut R) -> Context<'_> {
+        Context {
+            sequence,
+            seed: self.seed,
+            record: self.feed.as_ref().map(|feed| feed.record(sequence, rng)),
+            user: None,
+            run: None,
+        }
+    }
+
+    // the payload, with the index of the template it came from
+    pub fn generate_with<R: RngCore>(&self, context: &Context, rng: &mut R) -> (Option<usize>, Value) {
+        let (template, body) = self.backend.generate(context, rng);
+        (template, self.scripted(context, body))
+    }
+
+    fn scripted(&self, context: &Context, body: Value) -> Value {
+        match self.script.as_ref().filter(|script| script.has_generate()) {
+            Some(script) => script.generate(context, body),
+            None => body,
+        }
+    }
+}
+
+fn load(path: &str) -> Result<Template, Box<dyn std::error::Error>> {
+    Ok(Template::load(path)?)
+}
+
+// rng for the n-th payload of a run, so a payload only depends on (seed, n)
+// and not on which worker happened to generate it
+pub fn payload_rng(seed: u64, sequence: u64) -> StdRng {
+    let mut z = seed.wrapping_add(sequence.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    StdRng::seed_from_u64(z ^ (z >> 31))
+}
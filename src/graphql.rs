@@ -0,0 +1,37 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+
+// a query or mutation sent with generated variables
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphQl {
+    pub path: String,
+    #[serde(skip)]
+    pub query: String,
+    pub operation_name: Option<String>,
+}
+
+impl GraphQl {
+    pub fn load(path: &str, operation_name: Option<String>) -> Result<Self, Box<dyn std::error::Error>> {
+        let query = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(GraphQl { path: path.to_string(), query, operation_name })
+    }
+
+    // the standard {query, variables} request body
+    pub fn envelope(&self, variables: Value) -> Value {
+        let mut body = json!({ "query": self.query, "variables": variables });
+        if let Some(name) = &self.operation_name {
+            body["operationName"] = Value::String(name.clone());
+        }
+        body
+    }
+}
+
+// entries in the response's top level "errors" array; servers answer 200
+// for most of these, so the status alone doesn't tell
+pub fn error_count(response: &Value) -> usize {
+    match response.get("errors") {
+        Some(Value::Array(errors)) => errors.len(),
+        _ => 0,
+    }
+}
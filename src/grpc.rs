@@ -0,0 +1,247 @@
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor};
+use prost_types::{FileDescriptorProto, FileDescriptorSet};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::time::Duration;
+use tonic::codec::{BufferSettings, Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::{MetadataKey, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Status};
+use tonic_reflection::pb::v1::server_reflection_client::ServerReflectionClient;
+use tonic_reflection::pb::v1::server_reflection_request::MessageRequest;
+use tonic_reflection::pb::v1::server_reflection_response::MessageResponse;
+use tonic_reflection::pb::v1::ServerReflectionRequest;
+
+// grpc-status codes 0 through 16
+pub const CODES: usize = 17;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GrpcConfig {
+    // package.Service/Method
+    pub method: String,
+    // server reflection is used when no .proto files are given
+    pub protos: Vec<String>,
+    pub includes: Vec<String>,
+}
+
+impl GrpcConfig {
+    fn service_and_method(&self) -> Result<(&str, &str), String> {
+        self.method
+            .trim_start_matches('/')
+            .rsplit_once(['/', '.'])
+            .filter(|(service, method)| !service.is_empty() && !method.is_empty())
+            .ok_or_else(|| format!("expected package.Service/Method, got: {}", self.method))
+    }
+}
+
+// one channel per endpoint, cloned per call
+#[derive(Debug, Clone)]
+pub struct GrpcClient {
+    channel: Channel,
+    method: MethodDescriptor,
+    path: PathAndQuery,
+    metadata: Vec<(MetadataKey<tonic::metadata::Ascii>, MetadataValue<tonic::metadata::Ascii>)>,
+}
+
+impl GrpcClient {
+    // headers (including auth) become request metadata
+    pub async fn connect(
+        endpoint: &str,
+        config: &GrpcConfig,
+        timeout: Option<Duration>,
+        headers: &[(String, String)],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut builder = Endpoint::from_shared(endpoint.to_string())?;
+        if let Some(timeout) = timeout {
+            builder = builder.timeout(timeout);
+        }
+        let channel = builder.connect_lazy();
+
+        let (service, method) = config.service_and_method()?;
+        let pool = match config.protos.is_empty() {
+            true => reflect(channel.clone(), service).await?,
+            false => descriptor_pool(&config.protos, &config.includes)?,
+        };
+        let method = pool
+            .get_service_by_name(service)
+            .ok_or_else(|| format!("unknown grpc service: {}", service))?
+            .methods()
+            .find(|m| m.name() == method)
+            .ok_or_else(|| format!("unknown grpc method: {}/{}", service, method))?;
+        if method.is_client_streaming() || method.is_server_streaming() {
+            return Err(format!("only unary grpc methods are supported: {}", config.method).into());
+        }
+
+        let path = PathAndQuery::try_from(format!("/{}/{}", service, method.name()))?;
+        let metadata = headers
+            .iter()
+            .map(|(name, value)| {
+                Ok((MetadataKey::from_bytes(name.to_ascii_lowercase().as_bytes())?, value.parse()?))
+            })
+            .collect::<Result<_, Box<dyn std::error::Error>>>()?;
+        Ok(GrpcClient { channel, method, path, metadata })
+    }
+
+    // the payload is the request message in its canonical json mapping
+    pub fn message(&self, payload: &Value) -> Result<DynamicMessage, String> {
+        DynamicMessage::deserialize(self.method.input(), payload)
+            .map_err(|e| format!("payload doesn't fit {}: {}", self.method.input().full_name(), e))
+    }
+
+    // `token` is a fresh oauth2 bearer token, static auth is already in the metadata
+    pub async fn call(&self, message: DynamicMessage, token: Option<&str>) -> Result<Value, Status> {
+        let mut grpc = tonic::client::Grpc::new(self.channel.clone());
+        grpc.ready()
+            .await
+            .map_err(|e| Status::unavailable(format!("connect: {}", e)))?;
+
+        let mut request = Request::new(message);
+        for (key, value) in &self.metadata {
+            request.metadata_mut().insert(key.clone(), value.clone());
+        }
+        if let Some(token) = token {
+            let value = format!("Bearer {}", token)
+                .parse()
+                .map_err(|_| Status::unauthenticated("token isn't valid metadata"))?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        let codec = DynamicCodec(self.method.output());
+        let response = grpc.unary(request, self.path.clone(), codec).await?;
+        serde_json::to_value(response.get_ref()).map_err(|e| Status::internal(e.to_string()))
+    }
+}
+
+// --proto files: .proto sources compiled together, anything else read as
+// a binary descriptor set like protoc --descriptor_set_out writes
+pub fn descriptor_pool(protos: &[String], includes: &[String]) -> Result<DescriptorPool, Box<dyn std::error::Error>> {
+    let (sources, sets): (Vec<&String>, Vec<&String>) = protos.iter().partition(|path| path.ends_with(".proto"));
+    let mut pool = DescriptorPool::new();
+    for path in sets {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        pool.decode_file_descriptor_set(bytes.as_slice())
+            .map_err(|e| format!("{}: not a descriptor set: {}", path, e))?;
+    }
+    if !sources.is_empty() {
+        pool.add_file_descriptor_set(protox::compile(sources, includes)?)?;
+    }
+    Ok(pool)
+}
+
+pub fn code_name(code: Code) -> &'static str {
+    match code {
+        Code::Ok => "OK",
+        Code::Cancelled => "CANCELLED",
+        Code::Unknown => "UNKNOWN",
+        Code::InvalidArgument => "INVALID_ARGUMENT",
+        Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+        Code::NotFound => "NOT_FOUND",
+        Code::AlreadyExists => "ALREADY_EXISTS",
+        Code::PermissionDenied => "PERMISSION_DENIED",
+        Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+        Code::FailedPrecondition => "FAILED_PRECONDITION",
+        Code::Aborted => "ABORTED",
+        Code::OutOfRange => "OUT_OF_RANGE",
+        Code::Unimplemented => "UNIMPLEMENTED",
+        Code::Internal => "INTERNAL",
+        Code::Unavailable => "UNAVAILABLE",
+        Code::DataLoss => "DATA_LOSS",
+        Code::Unauthenticated => "UNAUTHENTICATED",
+    }
+}
+
+pub fn is_retryable(code: Code) -> bool {
+    matches!(code, Code::Unavailable | Code::ResourceExhausted)
+}
+
+// fetches the file declaring `service` plus whatever it imports
+async fn reflect(channel: Channel, service: &str) -> Result<DescriptorPool, Box<dyn std::error::Error>> {
+    let mut client = ServerReflectionClient::new(channel);
+    let mut files: Vec<FileDescriptorProto> = Vec::new();
+    let mut have = HashSet::new();
+    let mut requested = HashSet::new();
+    let mut pending = vec![MessageRequest::FileContainingSymbol(service.to_string())];
+
+    while let Some(request) = pending.pop() {
+        let request = ServerReflectionRequest { host: String::new(), message_request: Some(request) };
+        let mut responses = client
+            .server_reflection_info(tokio_stream::iter([request]))
+            .await
+            .map_err(|e| format!("grpc reflection: {}", e.message()))?
+            .into_inner();
+
+        while let Some(response) = responses.message().await? {
+            let encoded = match response.message_response {
+                Some(MessageResponse::FileDescriptorResponse(r)) => r.file_descriptor_proto,
+                Some(MessageResponse::ErrorResponse(e)) => {
+                    return Err(format!("grpc reflection: {}", e.error_message).into())
+                }
+                _ => continue,
+            };
+            for bytes in encoded {
+                let file = FileDescriptorProto::decode(bytes.as_slice())?;
+                if have.insert(file.name().to_string()) {
+                    files.push(file);
+                }
+            }
+        }
+
+        for file in &files {
+            for dependency in &file.dependency {
+                if !have.contains(dependency) && requested.insert(dependency.clone()) {
+                    pending.push(MessageRequest::FileByFilename(dependency.clone()));
+                }
+            }
+        }
+    }
+
+    Ok(DescriptorPool::from_file_descriptor_set(FileDescriptorSet { file: files })?)
+}
+
+// tonic codec for messages only known at runtime
+struct DynamicCodec(MessageDescriptor);
+
+impl Codec for DynamicCodec {
+    type Encode = DynamicMessage;
+    type Decode = DynamicMessage;
+    type Encoder = DynamicEncoder;
+    type Decoder = DynamicDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        DynamicEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        DynamicDecoder(self.0.clone())
+    }
+}
+
+struct DynamicEncoder;
+
+impl Encoder for DynamicEncoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        item.encode(dst).map_err(|e| Status::internal(e.to_string()))
+    }
+
+    fn buffer_settings(&self) -> BufferSettings {
+        BufferSettings::default()
+    }
+}
+
+struct DynamicDecoder(MessageDescriptor);
+
+impl Decoder for DynamicDecoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        DynamicMessage::decode(self.0.clone(), src)
+            .map(Some)
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+}
@@ -0,0 +1,106 @@
+use reqwest::{Method, Url};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::auth::Header;
+use crate::target::{Replay, Target};
+
+// request headers that belong to the recorded connection rather than the
+// request, reqwest sets its own
+const SKIPPED_HEADERS: &[&str] = &["host", "content-length", "connection", "keep-alive", "transfer-encoding"];
+
+#[derive(Deserialize)]
+struct Har {
+    log: Log,
+}
+
+#[derive(Deserialize)]
+struct Log {
+    entries: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    request: Request,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<NameValue>,
+    post_data: Option<PostData>,
+}
+
+#[derive(Deserialize)]
+struct NameValue {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostData {
+    #[serde(default)]
+    mime_type: String,
+    #[serde(default)]
+    text: String,
+}
+
+// one target per recorded request; `host` ("https://staging:8443") swaps
+// the scheme, host and port of every url
+pub fn load(path: &str, host: Option<&str>) -> Result<Vec<Target>, Box<dyn std::error::Error>> {
+    let har: Har = serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| format!("{}: {}", path, e))?;
+    let host = host
+        .map(|host| Url::parse(host).map_err(|e| format!("invalid --har-host {}: {}", host, e)))
+        .transpose()?;
+
+    let mut targets = Vec::new();
+    for (number, entry) in har.log.entries.into_iter().enumerate() {
+        let request = entry.request;
+        let method = Method::from_bytes(request.method.as_bytes())
+            .map_err(|_| format!("{}: entry {} has an invalid method: {}", path, number, request.method))?;
+        let mut url = Url::parse(&request.url).map_err(|e| format!("{}: entry {}: {}", path, number, e))?;
+        if let Some(host) = &host {
+            rewrite_host(&mut url, host).map_err(|_| format!("{}: can't move {} to {}", path, url, host))?;
+        }
+
+        let mut headers: Vec<Header> = request
+            .headers
+            .into_iter()
+            // http/2 pseudo headers like :authority
+            .filter(|h| !h.name.starts_with(':'))
+            .filter(|h| !SKIPPED_HEADERS.iter().any(|s| h.name.eq_ignore_ascii_case(s)))
+            .map(|h| Header { name: h.name, value: h.value })
+            .collect();
+        let body = request.post_data.map(|data| {
+            let has_type = headers.iter().any(|h| h.name.eq_ignore_ascii_case("content-type"));
+            if !has_type && !data.mime_type.is_empty() {
+                headers.push(Header { name: "Content-Type".to_string(), value: data.mime_type });
+            }
+            data.text
+        });
+
+        targets.push(Target {
+            url: url.to_string(),
+            weight: 1.0,
+            method: None,
+            tags: BTreeMap::new(),
+            replay: Some(Replay { method: method.to_string(), headers, body }),
+            operation: None,
+        });
+    }
+    if targets.is_empty() {
+        return Err(format!("{}: no entries", path).into());
+    }
+    Ok(targets)
+}
+
+fn rewrite_host(url: &mut Url, host: &Url) -> Result<(), ()> {
+    url.set_scheme(host.scheme())?;
+    url.set_host(host.host_str()).map_err(|_| ())?;
+    url.set_port(host.port())
+}
@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// log-linear buckets: exact below 32, then 32 sub-buckets per power of two,
+// which keeps the relative error of any reported value under ~3%
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const MAX_MAGNITUDE: u32 = 40;
+const BUCKETS: usize = SUB_BUCKETS * (MAX_MAGNITUDE - SUB_BUCKET_BITS + 2) as usize;
+
+// a histogram's contents, shipped between distributed workers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistogramSnapshot {
+    // non-empty buckets as (index, count)
+    pub buckets: Vec<(usize, u64)>,
+    pub count: u64,
+    pub sum: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+#[derive(Debug)]
+pub struct Histogram {
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Histogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, value: u64) {
+        self.buckets[bucket_index(value)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.min.fetch_min(value, Ordering::Relaxed);
+        self.max.fetch_max(value, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
+
+    pub fn min(&self) -> u64 {
+        match self.count() {
+            0 => 0,
+            _ => self.min.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    pub fn mean(&self) -> f64 {
+        match self.count() {
+            0 => 0.0,
+            n => self.sum() as f64 / n as f64,
+        }
+    }
+
+    // number of recorded values <= value, at bucket resolution
+    pub fn count_le(&self, value: u64) -> u64 {
+        self.buckets
+            .iter()
+            .enumerate()
+            .take_while(|(index, _)| bucket_upper(*index) <= value)
+            .map(|(_, bucket)| bucket.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    // `bins` log-scaled bins spanning min..=max as (upper edge, count), at
+    // bucket resolution
+    pub fn log_bins(&self, bins: usize) -> Vec<(u64, u64)> {
+        if self.count() == 0 {
+            return Vec::new();
+        }
+        let (min, max) = (self.min().max(1) as f64, self.max().max(1) as f64);
+        let ratio = (max / min).max(1.0001).powf(1.0 / bins as f64);
+        let mut below = self.count_le(min as u64 - 1);
+        (1..=bins)
+            .map(|i| {
+                let upper = (min * ratio.powi(i as i32)).ceil() as u64;
+                let le = self.count_le(upper);
+                let count = le - below.min(le);
+                below = le;
+                (upper, count)
+            })
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .enumerate()
+                .map(|(index, bucket)| (index, bucket.load(Ordering::Relaxed)))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+            count: self.count(),
+            sum: self.sum(),
+            min: self.min.load(Ordering::Relaxed),
+            max: self.max(),
+        }
+    }
+
+    // adds another histogram's values to this one
+    pub fn merge(&self, other: &HistogramSnapshot) {
+        for &(index, count) in &other.buckets {
+            if let Some(bucket) = self.buckets.get(index) {
+                bucket.fetch_add(count, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(other.count, Ordering::Relaxed);
+        self.sum.fetch_add(other.sum, Ordering::Relaxed);
+        self.min.fetch_min(other.min, Ordering::Relaxed);
+        self.max.fetch_max(other.max, Ordering::Relaxed);
+    }
+
+    // the values recorded after `earlier` was taken of this histogram, for
+    // quantiles and the mean; min and max stay those of the whole histogram
+    pub fn since(&self, earlier: &HistogramSnapshot) -> Histogram {
+        let since = Histogram::new();
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            since.buckets[index].store(bucket.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        for &(index, count) in &earlier.buckets {
+            since.buckets[index].fetch_sub(count.min(since.buckets[index].load(Ordering::Relaxed)), Ordering::Relaxed);
+        }
+        since.count.store(self.count().saturating_sub(earlier.count), Ordering::Relaxed);
+        since.sum.store(self.sum().saturating_sub(earlier.sum), Ordering::Relaxed);
+        since.min.store(self.min.load(Ordering::Relaxed), Ordering::Relaxed);
+        since.max.store(self.max(), Ordering::Relaxed);
+        since
+    }
+
+    // value at quantile q (0.0..=1.0), reported as the upper edge of its bucket
+    pub fn quantile(&self, q: f64) -> u64 {
+        let count = self.count();
+        if count == 0 {
+            return 0;
+        }
+
+        let target = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= target {
+                return bucket_upper(index).min(self.max());
+            }
+        }
+        self.max()
+    }
+}
+
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+    let magnitude = (63 - value.leading_zeros()).min(MAX_MAGNITUDE);
+    let value = value.min((1 << (MAX_MAGNITUDE + 1)) - 1);
+    let shift = magnitude - SUB_BUCKET_BITS;
+    let sub = ((value >> shift) as usize) - SUB_BUCKETS;
+    SUB_BUCKETS + shift as usize * SUB_BUCKETS + sub
+}
+
+fn bucket_upper(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    let shift = ((index - SUB_BUCKETS) / SUB_BUCKETS) as u32;
+    let sub = ((index - SUB_BUCKETS) % SUB_BUCKETS) as u64;
+    let lower = (SUB_BUCKETS as u64 + sub) << shift;
+    lower + (1 << shift) - 1
+}
@@ -0,0 +1,168 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::report::Report;
+
+// where --history and `arctic history` keep the runs unless told otherwise
+pub const DEFAULT_PATH: &str = "~/.arctic/history.sqlite";
+// how long a run waits for another one writing to the same file
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    started_at TEXT NOT NULL,
+    config_key TEXT NOT NULL,
+    endpoints TEXT NOT NULL,
+    duration_secs REAL NOT NULL,
+    total_requests INTEGER NOT NULL,
+    failed_requests INTEGER NOT NULL,
+    requests_per_second REAL NOT NULL,
+    latency_mean_ms REAL NOT NULL,
+    latency_p50_ms REAL NOT NULL,
+    latency_p95_ms REAL NOT NULL,
+    latency_p99_ms REAL NOT NULL,
+    report TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS runs_config_key ON runs (config_key, started_at);
+";
+
+// one past run as `arctic history` lists it
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub id: i64,
+    pub started_at: String,
+    pub config_key: String,
+    pub endpoints: String,
+    pub total_requests: u64,
+    pub failed_requests: u64,
+    pub requests_per_second: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+}
+
+// what runs of the same test have in common: the sha-256 of the config the
+// report shows, short, with the seed left out since it's random unless given
+pub fn config_key(report: &Report) -> String {
+    let mut config = serde_json::to_value(&report.config).unwrap_or_default();
+    if let Some(config) = config.as_object_mut() {
+        config.remove("seed");
+    }
+    let digest = Sha256::digest(config.to_string().as_bytes());
+    digest.iter().take(6).map(|b| format!("{:02x}", b)).collect()
+}
+
+// ~/ is the home directory
+pub fn resolve(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn open(path: &Path) -> Result<Connection, Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let db = Connection::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    db.busy_timeout(BUSY_TIMEOUT)?;
+    db.execute_batch(SCHEMA).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(db)
+}
+
+// appends the run, returns its id
+pub fn record(path: &str, report: &Report) -> Result<i64, Box<dyn std::error::Error>> {
+    let db = open(&resolve(path))?;
+    let latency = &report.latency_ms;
+    db.execute(
+        "INSERT INTO runs (started_at, config_key, endpoints, duration_secs, total_requests, failed_requests, requests_per_second,
+                           latency_mean_ms, latency_p50_ms, latency_p95_ms, latency_p99_ms, report)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            report.metadata.started_at,
+            config_key(report),
+            report.config.endpoint_labels().join(", "),
+            report.duration_secs,
+            report.total_requests as i64,
+            report.failed_requests as i64,
+            report.requests_per_second,
+            latency.mean,
+            latency.p50,
+            latency.p95,
+            latency.p99,
+            serde_json::to_string(report)?,
+        ],
+    )?;
+    Ok(db.last_insert_rowid())
+}
+
+// the latest `limit` runs, oldest first; `config` is a config key or the
+// start of one
+pub fn list(path: &str, config: Option<&str>, limit: usize) -> Result<Vec<Run>, Box<dyn std::error::Error>> {
+    let db = open(&resolve(path))?;
+    let mut query = db.prepare(
+        "SELECT id, started_at, config_key, endpoints, total_requests, failed_requests, requests_per_second, latency_p95_ms, latency_p99_ms
+         FROM runs WHERE config_key LIKE ?1 || '%' ORDER BY id DESC LIMIT ?2",
+    )?;
+    let runs = query.query_map(params![config.unwrap_or_default(), limit as i64], |row| {
+        Ok(Run {
+            id: row.get(0)?,
+            started_at: row.get(1)?,
+            config_key: row.get(2)?,
+            endpoints: row.get(3)?,
+            total_requests: row.get::<_, i64>(4)? as u64,
+            failed_requests: row.get::<_, i64>(5)? as u64,
+            requests_per_second: row.get(6)?,
+            p95_ms: row.get(7)?,
+            p99_ms: row.get(8)?,
+        })
+    })?;
+    let mut runs = runs.collect::<Result<Vec<_>, _>>()?;
+    runs.reverse();
+    Ok(runs)
+}
+
+// the json report of run `id`
+pub fn report(path: &str, id: i64) -> Result<Value, Box<dyn std::error::Error>> {
+    let db = open(&resolve(path))?;
+    let report: Option<String> = db.query_row("SELECT report FROM runs WHERE id = ?1", [id], |row| row.get(0)).optional()?;
+    let report = report.ok_or_else(|| format!("{}: no run {}", path, id))?;
+    Ok(serde_json::from_str(&report)?)
+}
+
+// the run of the same config before `id`
+pub fn previous(path: &str, id: i64) -> Result<i64, Box<dyn std::error::Error>> {
+    let db = open(&resolve(path))?;
+    let previous: Option<i64> = db
+        .query_row(
+            "SELECT id FROM runs WHERE config_key = (SELECT config_key FROM runs WHERE id = ?1) AND id < ?1 ORDER BY id DESC LIMIT 1",
+            [id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(previous.ok_or_else(|| format!("{}: no earlier run of the config of run {}", path, id))?)
+}
+
+pub fn print_table(runs: &[Run]) {
+    println!(
+        "{:>5}  {:<19}  {:<12}  {:>10}  {:>7}  {:>10}  {:>10}  {:>10}  endpoints",
+        "id", "started", "config", "requests", "errors", "req/s", "p95", "p99"
+    );
+    for run in runs {
+        let errors = match run.total_requests {
+            0 => 0.0,
+            n => run.failed_requests as f64 / n as f64 * 100.0,
+        };
+        // 2024-05-01T12:00:00.000Z as 2024-05-01 12:00:00
+        let started = run.started_at.get(..19).unwrap_or(&run.started_at).replace('T', " ");
+        println!(
+            "{:>5}  {:<19}  {:<12}  {:>10}  {:>6.2}%  {:>10.2}  {:>8.2}ms  {:>8.2}ms  {}",
+            run.id, started, run.config_key, run.total_requests, errors, run.requests_per_second, run.p95_ms, run.p99_ms, run.endpoints
+        );
+    }
+}
@@ -0,0 +1,573 @@
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::adaptive::Level;
+use crate::histogram::Histogram;
+use crate::report::{GroupSummary, Report};
+use crate::stats::{format_bytes, LoadTestStats};
+use crate::timeseries::Point;
+
+const LATENCY_BINS: usize = 30;
+const CHART_WIDTH: f64 = 760.0;
+const CHART_HEIGHT: f64 = 220.0;
+// room for the axis labels
+const MARGIN: f64 = 40.0;
+
+const STYLE: &str = "
+body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; color: #222; max-width: 860px; margin: 2em auto; padding: 0 1em; }
+h1 { font-size: 1.5em; margin-bottom: 0.2em; }
+h2 { font-size: 1.15em; margin-top: 2em; border-bottom: 1px solid #ddd; padding-bottom: 0.3em; }
+table { border-collapse: collapse; }
+td, th { padding: 0.25em 1.2em 0.25em 0; text-align: left; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+.muted { color: #777; }
+.pass { color: #1a7f37; }
+.fail { color: #cf222e; font-weight: bold; }
+svg text { font-size: 11px; fill: #555; }
+";
+
+// a single self-contained page: inline css and svg, nothing fetched
+pub fn write(path: &str, report: &Report, stats: &LoadTestStats) -> Result<(), Box<dyn std::error::Error>> {
+    stats.timeseries.finish();
+    let points = stats.timeseries.points_from(0);
+    fs::write(path, render(report, &stats.latency, &points))?;
+    Ok(())
+}
+
+pub fn render(report: &Report, latency: &Histogram, points: &[Point]) -> String {
+    let mut html = String::new();
+    let endpoints = report.config.endpoint_urls().join(", ");
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>arctic report</title>\n<style>{}</style>\n</head>\n<body>\n",
+        STYLE
+    );
+    let _ = writeln!(html, "<h1>arctic load test report</h1>");
+    let _ = writeln!(html, "<div class=\"muted\">{}</div>", escape(&endpoints));
+
+    html.push_str("<h2>Summary</h2>\n<table>\n");
+    let l = &report.latency_ms;
+    let mut rows = vec![
+        ("duration", format!("{:.2}s", report.duration_secs)),
+        ("stopped because", report.stop_reason.map(|r| r.to_string()).unwrap_or_else(|| "-".into())),
+        ("total requests", report.total_requests.to_string()),
+        ("successful requests", report.successful_requests.to_string()),
+        ("failed requests", report.failed_requests.to_string()),
+        ("timed out", report.timeouts.to_string()),
+        ("abandoned at the end", report.abandoned_requests.to_string()),
+        ("retries", report.retries.to_string()),
+        ("requests per second", format!("{:.2}", report.requests_per_second)),
+        ("latency min / mean", format!("{:.2}ms / {:.2}ms", l.min, l.mean)),
+        ("latency p50 / p90 / p95", format!("{:.2}ms / {:.2}ms / {:.2}ms", l.p50, l.p90, l.p95)),
+        ("latency p99 / max", format!("{:.2}ms / {:.2}ms", l.p99, l.max)),
+        ("seed", report.config.seed.to_string()),
+    ];
+    if let Some(s) = &report.service_time_ms {
+        rows.insert(10, ("service time p50 / p99", format!("{:.2}ms / {:.2}ms", s.p50, s.p99)));
+    }
+    if let Some(t) = &report.think_time_ms {
+        let seed = rows.len() - 1;
+        rows.insert(seed, ("think time mean / p99", format!("{:.2}ms / {:.2}ms", t.mean, t.p99)));
+    }
+    if report.bytes_sent > 0 || report.bytes_received > 0 {
+        let bytes = format!(
+            "{} ({:.2} MB/s) / {} ({:.2} MB/s)",
+            format_bytes(report.bytes_sent),
+            report.sent_mb_per_sec,
+            format_bytes(report.bytes_received),
+            report.received_mb_per_sec
+        );
+        rows.insert(7, ("bytes sent / received", bytes));
+    }
+    if let Some(s) = &report.response_size_bytes {
+        let sizes = format!("{} / {} / {}", format_bytes(s.min), format_bytes(s.mean as u64), format_bytes(s.max));
+        rows.insert(8, ("response size min / mean / max", sizes));
+    }
+    if let Some(c) = &report.compression {
+        let compression = format!(
+            "{}, {} from {} ({:.1}%)",
+            c.encoding,
+            format_bytes(c.compressed_bytes),
+            format_bytes(c.uncompressed_bytes),
+            c.ratio * 100.0
+        );
+        rows.insert(8, ("compression", compression));
+    }
+    if let Some(c) = &report.connections {
+        rows.insert(8, ("connections opened / reused", format!("{} / {}", c.opened, c.reused)));
+    }
+    if let Some(r) = &report.redirects {
+        let redirects = format!("{} attempts, {} hops, {} at most", r.redirected, r.hops, r.max_hops);
+        rows.insert(8, ("redirected", redirects));
+    }
+    if let Some(proxy) = &report.proxy {
+        rows.insert(6, ("via proxy", proxy.clone()));
+    }
+    if !report.protocols.is_empty() {
+        let protocols: Vec<String> = report.protocols.iter().map(|(p, count)| format!("{} ({})", p, count)).collect();
+        rows.insert(6, ("protocol", protocols.join(", ")));
+    }
+    for (name, value) in rows {
+        let _ = writeln!(html, "<tr><th>{}</th><td class=\"num\">{}</td></tr>", name, escape(&value));
+    }
+    html.push_str("</table>\n");
+
+    if !report.thresholds.is_empty() {
+        html.push_str("<h2>Thresholds</h2>\n<table>\n");
+        for t in &report.thresholds {
+            let (class, outcome) = if t.passed { ("pass", "passed") } else { ("fail", "FAILED") };
+            let _ = writeln!(
+                html,
+                "<tr><th>{}</th><td class=\"{}\">{}</td><td class=\"num\">{}</td></tr>",
+                escape(&t.threshold),
+                class,
+                outcome,
+                escape(&t.actual)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !report.phases.is_empty() {
+        html.push_str("<h2>Phases</h2>\n<table>\n<tr><th>phase</th><th>samples</th><th>mean</th><th>p50</th><th>p99</th><th>max</th></tr>\n");
+        for p in &report.phases {
+            let l = &p.latency_ms;
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{:.2}ms</td><td class=\"num\">{:.2}ms</td><td class=\"num\">{:.2}ms</td><td class=\"num\">{:.2}ms</td></tr>",
+                p.phase, p.samples, l.mean, l.p50, l.p99, l.max
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Latency distribution</h2>\n");
+    html.push_str(&bar_chart(&latency_bins(latency), "ms"));
+
+    html.push_str("<h2>Requests per second</h2>\n");
+    match points.is_empty() {
+        true => html.push_str("<p class=\"muted\">no completed seconds</p>\n"),
+        false => html.push_str(&line_chart(points)),
+    }
+
+    if let Some(adaptive) = &report.adaptive {
+        html.push_str("<h2>Adaptive concurrency</h2>\n<table>\n");
+        let capacity = match adaptive.capacity_rps.zip(adaptive.capacity_concurrency) {
+            Some((rps, workers)) => format!("{:.2} rps at {} workers", rps, workers),
+            None => "no second had its p95 within the target".into(),
+        };
+        let rows = [
+            ("target p95", format!("{:.2}ms", adaptive.target_p95_ms)),
+            ("workers at the end", adaptive.final_concurrency.to_string()),
+            ("workers mean / peak", format!("{:.1} / {}", adaptive.mean_concurrency, adaptive.peak_concurrency)),
+            ("capacity", capacity),
+        ];
+        for (name, value) in rows {
+            let _ = writeln!(html, "<tr><th>{}</th><td class=\"num\">{}</td></tr>", name, escape(&value));
+        }
+        html.push_str("</table>\n");
+        if !adaptive.levels.is_empty() {
+            html.push_str(&concurrency_chart(&adaptive.levels, adaptive.target_p95_ms));
+        }
+    }
+
+    if !report.response_metrics.is_empty() {
+        html.push_str("<h2>Response metrics</h2>\n<table>\n<tr><th>metric</th><th>responses</th><th>min</th><th>mean</th><th>max</th></tr>\n");
+        for (name, m) in &report.response_metrics {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{:.3}</td><td class=\"num\">{}</td></tr>",
+                escape(name), m.count, m.min, m.mean, m.max
+            );
+        }
+        html.push_str("</table>\n");
+        for name in report.response_metrics.keys().filter(|name| points.iter().any(|p| p.metrics.contains_key(*name))) {
+            html.push_str(&metric_chart(points, name));
+        }
+    }
+
+    html.push_str("<h2>Status codes</h2>\n");
+    let codes: Vec<(String, u64)> = report
+        .status_codes
+        .iter()
+        .map(|(code, count)| (code.to_string(), *count))
+        .chain(report.grpc_status.iter().map(|(code, count)| (code.clone(), *count)))
+        .chain(report.transport_errors.iter().map(|(kind, count)| (kind.clone(), *count)))
+        .collect();
+    match codes.is_empty() {
+        true => html.push_str("<p class=\"muted\">no responses</p>\n"),
+        false => {
+            let total = codes.iter().map(|(_, c)| c).sum::<u64>().max(1);
+            html.push_str("<table>\n");
+            for (code, count) in &codes {
+                let share = *count as f64 / total as f64;
+                let _ = writeln!(
+                    html,
+                    "<tr><th>{}</th><td class=\"num\">{}</td><td class=\"num\">{:.1}%</td>\
+                     <td><svg width=\"300\" height=\"12\"><rect width=\"{:.1}\" height=\"12\" fill=\"{}\"/></svg></td></tr>",
+                    escape(code),
+                    count,
+                    share * 100.0,
+                    share * 300.0,
+                    status_color(code)
+                );
+            }
+            html.push_str("</table>\n");
+        }
+    }
+
+    if !report.errors.is_empty() {
+        html.push_str("<h2>Errors</h2>\n<table>\n<tr><th>kind</th><th>count</th><th>first seen</th></tr>\n");
+        for bucket in &report.errors {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"num\">{}</td><td>{}</td></tr>",
+                escape(&bucket.kind),
+                bucket.count,
+                escape(&bucket.example)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    if report.endpoints.len() > 1 {
+        group_table(&mut html, "Endpoints", "endpoint", &report.endpoints);
+    }
+    if !report.tags.is_empty() {
+        group_table(&mut html, "Tags", "tag", &report.tags);
+    }
+    if !report.templates.is_empty() {
+        group_table(&mut html, "Templates", "template", &report.templates);
+    }
+    if !report.chaos.is_empty() {
+        group_table(&mut html, "Chaos (not in the totals)", "chaos", &report.chaos);
+    }
+
+    let m = &report.metadata;
+    html.push_str("<h2>Run</h2>\n<table>\n");
+    let rows = [
+        ("started", m.started_at.clone()),
+        ("finished", m.finished_at.clone()),
+        ("arctic", m.arctic_version.to_string()),
+        ("host", format!("{} ({} {}, {} cpus)", m.hostname.as_deref().unwrap_or("-"), m.os, m.arch, m.cpus)),
+        ("templates at", m.template_git.clone().unwrap_or_else(|| "-".into())),
+        ("config hash", m.config_hash.clone()),
+        ("command", m.args.join(" ")),
+    ];
+    for (name, value) in rows {
+        let _ = writeln!(html, "<tr><td>{}</td><td>{}</td></tr>", name, escape(&value));
+    }
+    if let Some(resources) = &report.load_generator {
+        let mut rows = Vec::new();
+        if let Some((mean, peak)) = resources.cpu_mean_percent.zip(resources.cpu_peak_percent) {
+            rows.push(("arctic cpu", format!("{:.0}% mean, {:.0}% peak", mean, peak)));
+        }
+        if let Some(memory) = resources.memory_peak_bytes {
+            rows.push(("arctic memory", format!("{} peak", format_bytes(memory))));
+        }
+        rows.push(("arctic timer lag", format!("{:.1}ms mean, {:.1}ms max", resources.scheduling_lag_mean_ms, resources.scheduling_lag_max_ms)));
+        for (name, value) in rows {
+            let _ = writeln!(html, "<tr><td>{}</td><td>{}</td></tr>", name, escape(&value));
+        }
+        for warning in &resources.warnings {
+            let _ = writeln!(html, "<tr><td>warning</td><td class=\"fail\">{}</td></tr>", escape(warning));
+        }
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn group_table(html: &mut String, title: &str, heading: &str, groups: &[GroupSummary]) {
+    let _ = writeln!(
+        html,
+        "<h2>{}</h2>\n<table>\n<tr><th>{}</th><th>requests</th><th>errors</th><th>p50</th><th>p99</th></tr>",
+        title, heading
+    );
+    for group in groups {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{:.2}ms</td><td class=\"num\">{:.2}ms</td></tr>",
+            escape(&group.name),
+            group.requests,
+            group.errors,
+            group.latency_ms.p50,
+            group.latency_ms.p99
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+// log-spaced bins from the fastest to the slowest response, in ms
+fn latency_bins(latency: &Histogram) -> Vec<(f64, u64)> {
+    latency
+        .log_bins(LATENCY_BINS)
+        .into_iter()
+        .map(|(upper, count)| (upper as f64 / 1000.0, count))
+        .collect()
+}
+
+fn bar_chart(bins: &[(f64, u64)], unit: &str) -> String {
+    let mut svg = svg_open();
+    let peak = bins.iter().map(|(_, c)| *c).max().unwrap_or(0).max(1) as f64;
+    let plot_width = CHART_WIDTH - 2.0 * MARGIN;
+    let plot_height = CHART_HEIGHT - 2.0 * MARGIN;
+    let width = plot_width / bins.len().max(1) as f64;
+    for (i, (upper, count)) in bins.iter().enumerate() {
+        let height = *count as f64 / peak * plot_height;
+        let x = MARGIN + i as f64 * width;
+        let _ = write!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#4a7bd0\"><title>up to {:.2}{}: {}</title></rect>",
+            x + 1.0,
+            MARGIN + plot_height - height,
+            (width - 2.0).max(1.0),
+            height,
+            upper,
+            unit,
+            count
+        );
+        // every fifth edge is labelled
+        if i % 5 == 4 || i + 1 == bins.len() {
+            let _ = write!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{:.1}{}</text>",
+                x + width,
+                CHART_HEIGHT - MARGIN + 15.0,
+                upper,
+                unit
+            );
+        }
+    }
+    axes(&mut svg, &format!("{}", peak as u64));
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn line_chart(points: &[Point]) -> String {
+    let mut svg = svg_open();
+    let peak = points.iter().map(|p| p.requests).max().unwrap_or(0).max(1) as f64;
+    let plot_width = CHART_WIDTH - 2.0 * MARGIN;
+    let plot_height = CHART_HEIGHT - 2.0 * MARGIN;
+    let last = points.last().map(|p| p.second).unwrap_or(0).max(1) as f64;
+    let xy = |second: u64, value: u64| {
+        (
+            MARGIN + second as f64 / last * plot_width,
+            MARGIN + plot_height - value as f64 / peak * plot_height,
+        )
+    };
+
+    for (color, errors) in [("#4a7bd0", false), ("#cf222e", true)] {
+        let path: Vec<String> = points
+            .iter()
+            .map(|p| {
+                let (x, y) = xy(p.second, if errors { p.errors } else { p.requests });
+                format!("{:.1},{:.1}", x, y)
+            })
+            .collect();
+        let _ = write!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>",
+            color,
+            path.join(" ")
+        );
+    }
+    for p in points {
+        let (x, y) = xy(p.second, p.requests);
+        let _ = write!(
+            svg,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"#4a7bd0\"><title>{}s: {} requests, {} errors, p99 {:.2}ms</title></circle>",
+            x, y, p.second, p.requests, p.errors, p.latency_p99_ms
+        );
+    }
+    let _ = write!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}s</text>",
+        CHART_WIDTH - MARGIN,
+        CHART_HEIGHT - MARGIN + 15.0,
+        last as u64
+    );
+    let _ = write!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">requests</text><text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" style=\"fill:#cf222e\">errors</text>",
+        CHART_WIDTH - MARGIN,
+        MARGIN - 20.0,
+        CHART_WIDTH - MARGIN,
+        MARGIN - 6.0
+    );
+    axes(&mut svg, &format!("{}", peak as u64));
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// the mean of a --metric each second against the p99 latency, each scaled
+// to its own peak
+fn metric_chart(points: &[Point], name: &str) -> String {
+    let mut svg = svg_open();
+    let metric = |p: &Point| p.metrics.get(name).map(|m| m.mean);
+    let peak = points.iter().filter_map(metric).fold(0.0, f64::max);
+    let latency_peak = points.iter().map(|p| p.latency_p99_ms).fold(0.0, f64::max);
+    let plot_width = CHART_WIDTH - 2.0 * MARGIN;
+    let plot_height = CHART_HEIGHT - 2.0 * MARGIN;
+    let last = points.last().map(|p| p.second).unwrap_or(0).max(1) as f64;
+    let xy = |second: u64, value: f64, peak: f64| {
+        let scaled = if peak > 0.0 { value / peak } else { 0.0 };
+        (MARGIN + second as f64 / last * plot_width, MARGIN + plot_height - scaled * plot_height)
+    };
+
+    for (color, latency) in [("#4a7bd0", false), ("#cf222e", true)] {
+        let path: Vec<String> = points
+            .iter()
+            .filter_map(|p| match latency {
+                true => Some(xy(p.second, p.latency_p99_ms, latency_peak)),
+                false => metric(p).map(|value| xy(p.second, value, peak)),
+            })
+            .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+            .collect();
+        let _ = write!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>",
+            color,
+            path.join(" ")
+        );
+    }
+    for p in points {
+        if let Some(value) = metric(p) {
+            let (x, y) = xy(p.second, value, peak);
+            let _ = write!(
+                svg,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"#4a7bd0\"><title>{}s: {} {:.3}, p99 {:.2}ms</title></circle>",
+                x, y, p.second, escape(name), value, p.latency_p99_ms
+            );
+        }
+    }
+    let _ = write!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}s</text>",
+        CHART_WIDTH - MARGIN,
+        CHART_HEIGHT - MARGIN + 15.0,
+        last as u64
+    );
+    let _ = write!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text><text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" style=\"fill:#cf222e\">p99 latency, peak {:.2}ms</text>",
+        CHART_WIDTH - MARGIN,
+        MARGIN - 20.0,
+        escape(name),
+        CHART_WIDTH - MARGIN,
+        MARGIN - 6.0,
+        latency_peak
+    );
+    axes(&mut svg, &format!("{:.3}", peak));
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// the --adaptive workers each second against the p95 they got, which is
+// scaled to twice the target so the target sits halfway up
+fn concurrency_chart(levels: &[Level], target_ms: f64) -> String {
+    let mut svg = svg_open();
+    let peak = levels.iter().map(|level| level.concurrency).max().unwrap_or(0).max(1) as f64;
+    let latency_peak = target_ms * 2.0;
+    let plot_width = CHART_WIDTH - 2.0 * MARGIN;
+    let plot_height = CHART_HEIGHT - 2.0 * MARGIN;
+    let last = levels.last().map(|level| level.second).unwrap_or(0).max(1) as f64;
+    let xy = |second: u64, value: f64, peak: f64| {
+        (MARGIN + second as f64 / last * plot_width, MARGIN + plot_height - (value / peak).min(1.0) * plot_height)
+    };
+
+    let (_, target_y) = xy(0, target_ms, latency_peak);
+    let _ = write!(
+        svg,
+        "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#cf222e\" stroke-dasharray=\"4\"/>",
+        MARGIN,
+        target_y,
+        CHART_WIDTH - MARGIN,
+        target_y
+    );
+    for (color, latency) in [("#4a7bd0", false), ("#cf222e", true)] {
+        let path: Vec<String> = levels
+            .iter()
+            .filter_map(|level| match latency {
+                true => level.p95_ms.map(|p95| xy(level.second, p95, latency_peak)),
+                false => Some(xy(level.second, level.concurrency as f64, peak)),
+            })
+            .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+            .collect();
+        let _ = write!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>",
+            color,
+            path.join(" ")
+        );
+    }
+    for level in levels {
+        let (x, y) = xy(level.second, level.concurrency as f64, peak);
+        let p95 = level.p95_ms.map_or("-".into(), |p95| format!("{:.2}ms", p95));
+        let _ = write!(
+            svg,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"#4a7bd0\"><title>{}s: {} workers, {:.0} rps, p95 {}</title></circle>",
+            x, y, level.second, level.concurrency, level.requests_per_second, p95
+        );
+    }
+    let _ = write!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}s</text>",
+        CHART_WIDTH - MARGIN,
+        CHART_HEIGHT - MARGIN + 15.0,
+        last as u64
+    );
+    let _ = write!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">workers</text><text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" style=\"fill:#cf222e\">p95 latency, target {:.2}ms dashed</text>",
+        CHART_WIDTH - MARGIN,
+        MARGIN - 20.0,
+        CHART_WIDTH - MARGIN,
+        MARGIN - 6.0,
+        target_ms
+    );
+    axes(&mut svg, &format!("{}", peak as u64));
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn svg_open() -> String {
+    format!(
+        "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+        CHART_WIDTH, CHART_HEIGHT, CHART_WIDTH, CHART_HEIGHT
+    )
+}
+
+// x and y axis with the y maximum written at the top
+fn axes(svg: &mut String, peak: &str) {
+    let bottom = CHART_HEIGHT - MARGIN;
+    let _ = write!(
+        svg,
+        "<line x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{b}\" stroke=\"#999\"/>\
+         <line x1=\"{m}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#999\"/>\
+         <text x=\"{t}\" y=\"{m}\" text-anchor=\"end\">{p}</text>\
+         <text x=\"{t}\" y=\"{b}\" text-anchor=\"end\">0</text>",
+        m = MARGIN,
+        b = bottom,
+        r = CHART_WIDTH - MARGIN,
+        t = MARGIN - 4.0,
+        p = escape(peak)
+    );
+}
+
+fn status_color(code: &str) -> &'static str {
+    match code.as_bytes().first() {
+        Some(b'2') | Some(b'O') => "#1a7f37",
+        Some(b'3') => "#4a7bd0",
+        Some(b'4') => "#d4a72c",
+        _ => "#cf222e",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,168 @@
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::stats::LoadTestStats;
+use crate::timeseries::Point;
+
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+// a database that doesn't answer holds up the next push, and the end of the
+// run, no longer than this
+const PUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+// --influx: an http write endpoint such as http://influx:8086/write?db=loadtests
+// or a file to append to, the measurement the points go under and the
+// key=value tags they carry
+#[derive(Debug, Clone, Serialize)]
+pub struct InfluxConfig {
+    pub target: String,
+    pub measurement: String,
+    pub tags: Vec<String>,
+}
+
+impl InfluxConfig {
+    pub fn check(&self) -> Result<(), String> {
+        match self.tags.iter().find(|tag| !tag.split_once('=').is_some_and(|(k, v)| !k.is_empty() && !v.is_empty())) {
+            Some(tag) => Err(format!("--influx-tag {}: expected key=value", tag)),
+            None => Ok(()),
+        }
+    }
+
+    fn is_http(&self) -> bool {
+        self.target.starts_with("http://") || self.target.starts_with("https://")
+    }
+}
+
+// the seconds of the time series as influx line protocol, sent once a
+// second as they close, so the usual grafana boards can follow a run
+pub struct Influx {
+    sink: Arc<Sink>,
+    task: JoinHandle<()>,
+}
+
+struct Sink {
+    config: InfluxConfig,
+    output: Output,
+    // how many points of the series went out already
+    written: Mutex<usize>,
+}
+
+enum Output {
+    Http(Client),
+    File(Mutex<File>),
+}
+
+impl Influx {
+    pub async fn start(config: &InfluxConfig, client: Client, stats: Arc<LoadTestStats>) -> Result<Self, Box<dyn std::error::Error>> {
+        let output = match config.is_http() {
+            true => Output::Http(client),
+            false => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&config.target)
+                    .await
+                    .map_err(|e| format!("--influx {}: {}", config.target, e))?;
+                Output::File(Mutex::new(file))
+            }
+        };
+        let sink = Arc::new(Sink { config: config.clone(), output, written: Mutex::new(0) });
+        let pusher = sink.clone();
+        let task = tokio::spawn(async move {
+            let start = tokio::time::Instant::now() + PUSH_INTERVAL;
+            let mut interval = tokio::time::interval_at(start, PUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                stats.timeseries.flush();
+                pusher.push(&stats).await;
+            }
+        });
+        Ok(Influx { sink, task })
+    }
+
+    // pushes the seconds still open when the run ended
+    pub async fn finish(self, stats: &LoadTestStats) {
+        self.task.abort();
+        stats.timeseries.finish();
+        self.sink.push(stats).await;
+    }
+}
+
+impl Sink {
+    async fn push(&self, stats: &LoadTestStats) {
+        let mut written = self.written.lock().await;
+        let points = stats.timeseries.points_from(*written);
+        if points.is_empty() {
+            return;
+        }
+        let lines = self.lines(&points);
+        // a database that's down mustn't disturb the run, those seconds are lost
+        let sent = match &self.output {
+            Output::Http(client) => client
+                .post(&self.config.target)
+                .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+                .timeout(PUSH_TIMEOUT)
+                .body(lines)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Output::File(file) => {
+                let mut file = file.lock().await;
+                async { file.write_all(lines.as_bytes()).await?; file.flush().await }.await.map_err(|e| e.to_string())
+            }
+        };
+        if let Err(e) = sent {
+            warn!(target = %self.config.target, error = %e, "influx write failed");
+        }
+        *written += points.len();
+    }
+
+    fn lines(&self, points: &[Point]) -> String {
+        let mut series = escape(&self.config.measurement, ", ");
+        for tag in &self.config.tags {
+            if let Some((key, value)) = tag.split_once('=') {
+                let _ = write!(series, ",{}={}", escape(key, ",= "), escape(value, ",= "));
+            }
+        }
+        let mut lines = String::new();
+        for point in points {
+            let _ = writeln!(
+                lines,
+                "{} requests={}i,errors={}i,latency_mean_ms={},latency_p50_ms={},latency_p90_ms={},latency_p99_ms={},latency_max_ms={} {}",
+                series,
+                point.requests,
+                point.errors,
+                point.latency_mean_ms,
+                point.latency_p50_ms,
+                point.latency_p90_ms,
+                point.latency_p99_ms,
+                point.latency_max_ms,
+                // nanoseconds, influx's default precision
+                point.timestamp * 1_000_000_000
+            );
+        }
+        lines
+    }
+}
+
+// a backslash in front of each of `special`
+fn escape(text: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,56 @@
+use serde_json::Value;
+use std::io::{self, BufRead};
+use std::thread;
+use tokio::sync::{mpsc, Mutex};
+use tracing::warn;
+
+// lines read ahead of the workers, the reader waits once this many are queued
+const BUFFER: usize = 1024;
+
+// --data -: payloads read off stdin, one json document per line, each sent
+// by the next free request as soon as it arrives
+#[derive(Debug)]
+pub struct Input {
+    payloads: Mutex<mpsc::Receiver<Value>>,
+}
+
+impl Input {
+    // reads on a thread of its own, stdin has no async reader that doesn't
+    // tie up a blocking task for the whole run
+    pub fn start() -> Self {
+        let (sender, receiver) = mpsc::channel(BUFFER);
+        thread::spawn(move || {
+            for payload in payloads(io::stdin().lock()) {
+                if sender.blocking_send(payload).is_err() {
+                    break;
+                }
+            }
+        });
+        Input { payloads: Mutex::new(receiver) }
+    }
+
+    // the next payload, none once stdin is closed and everything read is sent
+    pub async fn next(&self) -> Option<Value> {
+        self.payloads.lock().await.recv().await
+    }
+}
+
+// the json lines of `reader`; blank lines are skipped and lines that don't
+// parse are logged and left out rather than ending the run
+pub fn payloads(reader: impl BufRead) -> impl Iterator<Item = Value> {
+    reader.lines().enumerate().map_while(|(number, line)| match line {
+        Ok(line) => Some((number, line)),
+        Err(e) => {
+            warn!(error = %e, "stdin couldn't be read, no more payloads");
+            None
+        }
+    })
+    .filter(|(_, line)| !line.trim().is_empty())
+    .filter_map(|(number, line)| match serde_json::from_str(&line) {
+        Ok(payload) => Some(payload),
+        Err(e) => {
+            warn!(line = number + 1, error = %e, "stdin line isn't json, skipped");
+            None
+        }
+    })
+}
@@ -0,0 +1,54 @@
+use serde_json::Value;
+
+// minimal jsonpath subset: `$.a.b[0].c`, `a.b`, `$["key with dots"]`
+pub fn select<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = value;
+    for segment in parse(path)? {
+        current = match segment {
+            Segment::Key(key) => current.as_object()?.get(&key)?,
+            Segment::Index(index) => current.as_array()?.get(index)?,
+        };
+    }
+    Some(current)
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn parse(path: &str) -> Option<Vec<Segment>> {
+    let path = path.trim();
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    let mut segments = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            rest = after;
+            continue;
+        }
+
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            let inner = after[..end].trim();
+            if let Some(quoted) = inner
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .or_else(|| inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')))
+            {
+                segments.push(Segment::Key(quoted.to_string()));
+            } else {
+                segments.push(Segment::Index(inner.parse().ok()?));
+            }
+            rest = &after[end + 1..];
+            continue;
+        }
+
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        segments.push(Segment::Key(rest[..end].to_string()));
+        rest = &rest[end..];
+    }
+
+    Some(segments)
+}
@@ -0,0 +1,79 @@
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::body::escape_xml;
+use crate::report::Report;
+
+// --junit: the thresholds and response checks as junit test cases, one
+// suite each, for ci servers that show test reports but not load tests
+pub fn write(path: &str, report: &Report) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(path, render(report))?;
+    Ok(())
+}
+
+pub fn render(report: &Report) -> String {
+    // name and failure message of each case
+    let thresholds: Vec<(String, Option<String>)> = report
+        .thresholds
+        .iter()
+        .map(|t| (t.threshold.clone(), (!t.passed).then(|| format!("actual {}", t.actual))))
+        .collect();
+    let checks: Vec<(String, Option<String>)> = report
+        .checks
+        .iter()
+        .map(|check| {
+            let failure = (check.failed > 0)
+                .then(|| format!("{} of {} responses failed", check.failed, check.passed + check.failed));
+            (check.name.clone(), failure)
+        })
+        .collect();
+
+    let total = thresholds.len() + checks.len();
+    let failed = thresholds.iter().chain(&checks).filter(|(_, failure)| failure.is_some()).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"arctic\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        total, failed, report.duration_secs
+    );
+    for (suite, cases) in [("thresholds", &thresholds), ("checks", &checks)] {
+        if cases.is_empty() {
+            continue;
+        }
+        let failures = cases.iter().filter(|(_, failure)| failure.is_some()).count();
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"arctic.{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\" timestamp=\"{}\" hostname=\"{}\">",
+            suite,
+            cases.len(),
+            failures,
+            report.duration_secs,
+            escape_xml(&report.metadata.started_at),
+            escape_xml(report.metadata.hostname.as_deref().unwrap_or("localhost"))
+        );
+        // every case took the whole run to decide
+        for (name, failure) in cases {
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"arctic.{}\" time=\"{:.3}\"",
+                escape_xml(name),
+                suite,
+                report.duration_secs
+            );
+            match failure {
+                Some(message) => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                        escape_xml(message),
+                        escape_xml(&format!("{}: {}", name, message))
+                    );
+                }
+                None => xml.push_str("/>\n"),
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
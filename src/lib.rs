@@ -0,0 +1,79 @@
+//! Load testing with randomized payloads generated from a JSON template.
+//!
+//! ```no_run
+//! use arctic::{Generator, LoadConfig, Runner};
+//! use serde_json::json;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = LoadConfig::new("http://localhost:8000/users").requests(500).concurrency(8);
+//! let generator = Generator::new(&json!({"name": "", "age": {"$int": {"min": 0, "max": 120}}}), 42)?;
+//! let report = Runner::new(config, generator).run().await?;
+//! assert_eq!(report.failed_requests, 0);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod adaptive;
+pub mod auth;
+pub mod body;
+pub mod breaker;
+mod capture;
+pub mod chaos;
+pub mod checkpoint;
+pub mod checks;
+pub mod compare;
+mod client;
+pub mod config;
+pub mod configfile;
+pub mod corpus;
+mod dns;
+pub mod failure;
+pub mod feed;
+pub mod findmax;
+pub mod fingerprint;
+pub mod generator;
+pub mod graphql;
+pub mod grpc;
+pub mod har;
+pub mod histogram;
+pub mod history;
+pub mod html;
+pub mod influx;
+mod input;
+mod jsonpath;
+pub mod junit;
+pub mod matrix;
+pub mod metadata;
+pub mod metrics;
+pub mod monitor;
+pub mod openapi;
+pub mod placeholder;
+pub mod profile;
+pub mod prometheus;
+pub mod report;
+mod requestlog;
+pub mod runner;
+pub mod scenario;
+pub mod schema;
+mod sample;
+pub mod script;
+pub mod setup;
+pub mod sigv4;
+pub mod sse;
+pub mod stats;
+pub mod statsd;
+pub mod target;
+pub mod template;
+pub mod threshold;
+pub mod timeseries;
+pub mod timing;
+pub mod tls;
+pub mod trace;
+pub mod units;
+mod watch;
+pub mod ws;
+
+pub use config::LoadConfig;
+pub use generator::Generator;
+pub use report::Report;
+pub use runner::Runner;
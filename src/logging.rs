@@ -0,0 +1,90 @@
+use clap::ArgMatches;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::EnvFilter;
+
+// set while the spinner owns the current terminal line, events clear it first
+static SPINNER: AtomicBool = AtomicBool::new(false);
+// set while the tui owns the whole terminal
+static MUTED: AtomicBool = AtomicBool::new(false);
+// set when stdout isn't a terminal, as in ci logs, or with --no-progress:
+// progress goes out as a whole line now and then instead of one redrawn in
+// place
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+// events go to stderr as text or json lines; -v shows debug events, -vv
+// trace events and --quiet only errors. RUST_LOG, when set, replaces the
+// level flags entirely
+pub fn init(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    PLAIN.store(matches.get_flag("no-progress") || !io::stdout().is_terminal(), Ordering::SeqCst);
+    let level = match (matches.get_flag("quiet"), matches.get_count("verbose")) {
+        (true, _) => "error",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    };
+    // other crates only get a say through RUST_LOG
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(format!("warn,arctic={}", level)));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(io::stderr().is_terminal())
+        .with_writer(Console);
+    let installed = match matches.get_one::<String>("log-format").map(String::as_str) {
+        Some("json") => builder.json().try_init(),
+        _ => builder.try_init(),
+    };
+    Ok(installed.map_err(|e| e.to_string())?)
+}
+
+pub fn spinner(active: bool) {
+    SPINNER.store(active, Ordering::SeqCst);
+}
+
+pub fn plain() -> bool {
+    PLAIN.load(Ordering::SeqCst)
+}
+
+// makes room on stdout for a line of its own while the spinner is up
+pub fn clear_line() {
+    if SPINNER.load(Ordering::SeqCst) {
+        print!("\r\x1b[K");
+    }
+}
+
+pub fn mute(muted: bool) {
+    MUTED.store(muted, Ordering::SeqCst);
+}
+
+struct Console;
+
+impl<'a> MakeWriter<'a> for Console {
+    type Writer = ConsoleWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ConsoleWriter { cleared: false }
+    }
+}
+
+// one per event
+struct ConsoleWriter {
+    cleared: bool,
+}
+
+impl Write for ConsoleWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if MUTED.load(Ordering::SeqCst) {
+            return Ok(buf.len());
+        }
+        let mut stderr = io::stderr().lock();
+        if !self.cleared && SPINNER.load(Ordering::SeqCst) && stderr.is_terminal() {
+            stderr.write_all(b"\r\x1b[2K")?;
+        }
+        self.cleared = true;
+        stderr.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
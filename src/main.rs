@@ -1,209 +1,833 @@
-use clap::{Arg, Command};
-use rand::Rng;
-use reqwest::Error;
-use serde_json::{Value, Map, Number};
-use std::fs;
+mod cli;
+mod distributed;
+mod logging;
+mod merge;
+mod repl;
+mod tui;
+
+use arctic::body::BodyFormat;
+use arctic::findmax::Search;
+use arctic::matrix::{self, Cell, MatrixReport, Vary};
+use arctic::fingerprint::Rotate;
+use arctic::generator::Registry;
+use arctic::profile::StageUnit;
+use arctic::report::Report;
+use arctic::runner::Pause;
+use arctic::setup::Vars;
+use arctic::schema::Invalid;
+use arctic::stats::{clock, LoadTestStats, StopReason};
+use arctic::timeseries::TimeSeriesWriter;
+use arctic::sse::{self, SseConfig, SseStats};
+use arctic::ws::{self, WsConfig, WsStats};
+use arctic::{compare, history, html, junit, prometheus, threshold, Generator, LoadConfig, Runner};
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::time::sleep;
 use std::io::{stdout, Write};
+use tracing::{info, warn};
+
+// the run was cut short by --abort-on-error-rate
+const ABORTED_EXIT_CODE: i32 = 3;
+// at least one --threshold didn't hold
+const THRESHOLDS_FAILED_EXIT_CODE: i32 = 2;
+// `arctic compare` found a metric that got worse than allowed
+const REGRESSION_EXIT_CODE: i32 = 2;
+// the status line redraws this often, rates cover the last STATUS_WINDOW
+const STATUS_REFRESH: Duration = Duration::from_millis(250);
+const STATUS_WINDOW: Duration = Duration::from_secs(5);
+// without a terminal to redraw a line in, a line of its own this often
+const PLAIN_STATUS_EVERY: Duration = Duration::from_secs(10);
 
-#[derive(Debug)]
-struct LoadTestStats {
-    total_requests: AtomicU64,
-    success_count: AtomicU64,
-    error_count: AtomicU64,
-    total_duration: AtomicU64,
-    start_time: Instant,
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>>{
+    let matches = cli::command().get_matches();
+    logging::init(&matches)?;
+    match matches.subcommand() {
+        Some(("ws", matches)) => return run_ws(matches).await,
+        Some(("sse", matches)) => return run_sse(matches).await,
+        Some(("compare", matches)) => return run_compare(matches),
+        Some(("history", matches)) => return run_history(matches),
+        Some(("merge", matches)) => {
+            let (config, stats) = merge::merge(matches)?;
+            return finish(&config, &Report::new(&stats, &config), &stats);
+        }
+        Some(("repl", matches)) => {
+            let config = LoadConfig::from_matches(matches)?;
+            let generator = generator(&config)?;
+            return match repl::run(config, generator).await? {
+                repl::Exit::Run(run_vars) => load(matches, Some(run_vars)).await,
+                repl::Exit::Quit => Ok(()),
+            };
+        }
+        Some(("find-max", matches)) => return run_find_max(matches).await,
+        Some(("matrix", matches)) => return run_matrix(matches).await,
+        Some(("openapi", matches)) => return run_load(matches).await,
+        Some(("worker", matches)) => return distributed::serve(matches.get_one::<String>("listen").unwrap()).await,
+        Some(("run", matches)) => match matches.get_many::<String>("workers") {
+            Some(workers) => return distributed::coordinate(matches, workers.cloned().collect()).await,
+            None => return run_load(matches).await,
+        },
+        _ => {}
+    }
+    run_load(&matches).await
+}
+
+fn generator(config: &LoadConfig) -> Result<Generator, Box<dyn std::error::Error>> {
+    Registry::default().generator(config)
 }
 
-impl LoadTestStats {
-    fn new() -> Self {
-        LoadTestStats {
-            total_requests: AtomicU64::new(0),
-            success_count: AtomicU64::new(0),
-            error_count: AtomicU64::new(0),
-            total_duration: AtomicU64::new(0),
-            start_time: Instant::now(),
+async fn run_load(matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    load(matches, None).await
+}
+
+// a run, after arctic repl with the values of the --global-setup it did
+async fn load(matches: &clap::ArgMatches, run_vars: Option<Vars>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = LoadConfig::from_matches(matches)?;
+    let generator = generator(&config)?;
+    let mut runner = Runner::new(config.clone(), generator);
+    if let Some(run_vars) = run_vars {
+        runner = runner.after_global_setup(run_vars);
+    }
+    if let Some(count) = config.dry_run {
+        return dry_run(&runner, count, config.output.as_deref());
+    }
+    let stats = runner.stats();
+    let stop = runner.stop_handle();
+    // --quiet leaves only the summary on stdout, --json not even that
+    let quiet = matches.get_flag("quiet") || config.json;
+
+    if !quiet {
+        print_config(&config);
+    }
+
+    if let Some(port) = config.prometheus_port {
+        prometheus::serve(port, stats.clone()).await?;
+        if !quiet {
+            println!("metrics: http://0.0.0.0:{}/metrics", port);
         }
     }
 
-    fn print_summary(&self) {
-        let total = self.total_requests.load(Ordering::SeqCst);
-        let success = self.success_count.load(Ordering::SeqCst);
-        let errors = self.error_count.load(Ordering::SeqCst);
-        let total_duration = self.start_time.elapsed().as_secs_f64();
-        let avg_rps = total as f64 / total_duration;
+    let is_running = Arc::new(AtomicBool::new(true));
+    let status_is_running = is_running.clone();
+
+    // the dashboard owns the terminal, events would tear it
+    logging::mute(config.tui);
+    let tui_handle = config.tui.then(|| {
+        let (stats, config, is_running, stop) = (stats.clone(), config.clone(), is_running.clone(), stop.clone());
+        tokio::task::spawn_blocking(move || tui::run(stats, config, is_running, stop))
+    });
+    let show_status = !config.tui && !quiet;
 
-        let avg_response_time = 
-        if total > 0 {
-            self.total_duration.load(Ordering::SeqCst) as f64 / total as f64
+    let status_handle = show_status.then(|| {
+        tokio::spawn(status(stats.clone(), config.clone(), runner.pause_handle(), status_is_running))
+    });
+
+    let timeseries_handle = match &config.timeseries {
+        Some(path) => {
+            let mut writer = TimeSeriesWriter::create(path).await?;
+            let stats = stats.clone();
+            let is_running = is_running.clone();
+            Some(tokio::spawn(async move {
+                while is_running.load(Ordering::SeqCst) {
+                    sleep(Duration::from_secs(1)).await;
+                    stats.timeseries.flush();
+                    writer.write_new(&stats.timeseries).await?;
+                }
+                stats.timeseries.finish();
+                writer.write_new(&stats.timeseries).await
+            }))
         }
-        else {
-            0.0
-        };
+        None => None,
+    };
 
-        println!("\n===== Load Test Summary =====");
-        println!("total duration:      {:.2}s", total_duration);
-        println!("total requests:      {}", total);
-        println!("successful requests: {}", success);
-        println!("failed requests:     {}", errors);
-        println!("requests per second: {:.2}", avg_rps);
-        println!("avg response time:   {:.2}ms", avg_response_time);
+    let summary_handle = match config.summary_every {
+        Some(every) if !config.tui && !quiet => {
+            let stats = stats.clone();
+            Some(tokio::spawn(async move {
+                let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + every, every);
+                let mut earlier = stats.snapshot();
+                loop {
+                    interval.tick().await;
+                    logging::clear_line();
+                    stats.print_interval(&earlier, every);
+                    earlier = stats.snapshot();
+                }
+            }))
+        }
+        _ => None,
+    };
+
+    let control_handle = tokio::spawn(control(runner.pause_handle(), stats.clone(), !config.tui && !config.json));
+
+    let report = runner.run().await?;
+    is_running.store(false, Ordering::SeqCst);
+    if let Some(handle) = summary_handle {
+        handle.abort();
+    }
+    control_handle.abort();
+    if let Some(handle) = status_handle {
+        handle.await?;
     }
+    if let Some(handle) = tui_handle {
+        handle.await??;
+        logging::mute(false);
+    }
+    if let Some(handle) = timeseries_handle {
+        handle.await??;
+    }
+
+    finish(&config, &report, &stats)
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>>{
-    let matches = Command::new("arctic")
-        .version("0.1.0")
-        .about("sends randomized json to api endpoints")
-        .arg(
-            Arg::new("endpoint")
-                .short('e')
-                .long("endpoint")
-                .required(true)
-        )
-        .arg(
-            Arg::new("time")
-                .short('t')
-                .long("time")
-                .value_parser(clap::value_parser!(u64))
-                .required(true)
-        )
-        .arg(
-            Arg::new("data")
-                .short('d')
-                .long("data")
-                .required(true)
-        )
-        .get_matches();
-    
-    let endpoint = matches.get_one::<String>("endpoint").unwrap();
-    let duration = *matches.get_one::<u64>("time").unwrap();
-    let data_file = matches.get_one::<String>("data").unwrap();
-
-    let schema = read_json_file(data_file)?;
-    let start_time = std::time::Instant::now();
-    let stats = Arc::new(LoadTestStats::new());
+async fn run_find_max(matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    for flag in ["rate", "stages", "profile", "time", "requests", "adaptive"] {
+        if matches.value_source(flag) == Some(clap::parser::ValueSource::CommandLine) {
+            return Err(format!("find-max picks the rate and length of every step itself, --{} doesn't apply", flag).into());
+        }
+    }
+    let config = LoadConfig::from_matches(matches)?;
+    if config.scenario.is_some() {
+        return Err("find-max searches for a request rate, --scenario passes don't run at one".into());
+    }
+    // a search writes its own report, of the steps, and has no single run
+    // to show or judge
+    let unsupported = [
+        (config.dry_run.is_some(), "--dry-run"),
+        (config.tui, "--tui"),
+        (config.report.is_some(), "--report"),
+        (config.junit.is_some(), "--junit"),
+        (config.history.is_some(), "--history"),
+        (!config.thresholds.is_empty(), "--threshold"),
+    ];
+    if let Some((_, flag)) = unsupported.iter().find(|(given, _)| *given) {
+        return Err(format!("find-max doesn't take {}, its --output has the steps and the rate they found", flag).into());
+    }
+    let generator = generator(&config)?;
+    let start = *matches.get_one::<f64>("start-rate").unwrap();
+    let search = Search {
+        start,
+        step: matches.get_one::<f64>("rate-step").copied().unwrap_or(start),
+        max_rate: *matches.get_one::<f64>("max-rate").unwrap(),
+        step_duration: matches.get_one::<Duration>("step-duration").unwrap().as_secs().max(1),
+        max_error_rate: *matches.get_one::<f64>("max-error-rate").unwrap(),
+        max_p99: matches.get_one::<Duration>("max-p99").copied(),
+        refine: *matches.get_one::<u32>("refine").unwrap(),
+    };
+    if search.start <= 0.0 || search.step <= 0.0 {
+        return Err("--start-rate and --rate-step need to be above zero".into());
+    }
+    if search.max_rate < search.start {
+        return Err("--max-rate is below --start-rate, there's nothing to search".into());
+    }
 
-    println!(r"
-        ___              __  _     
-       /   |  __________/ /_(_)____
-      / /| | / ___/ ___/ __/ / ___/
-     / ___ |/ /  / /__/ /_/ / /__  
-    /_/  |_/_/   \___/\__/_/\___/
-    ");
-    println!("duration: {} seconds", duration);
-    println!("endpoint: {}", endpoint);
-    println!("templates: {}", data_file);
+    let quiet = matches.get_flag("quiet") || config.json;
+    if !quiet {
+        print_config(&config);
+        println!();
+    }
+    let report = search.run(&config, &generator, quiet).await?;
+    if let Some(path) = &config.output {
+        report.write(path)?;
+    }
+    match config.json {
+        true => println!("{}", serde_json::to_string(&report)?),
+        false => {
+            report.print();
+            if let Some(path) = &config.output {
+                println!("results written to {}", path);
+            }
+        }
+    }
+    Ok(())
+}
 
-    let stats_clone = stats.clone();
-    let endpoint_clone = endpoint.clone();
-    let schema_clone = schema.clone();
+async fn run_matrix(matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let varies = matches
+        .get_many::<String>("vary")
+        .unwrap()
+        .map(|spec| Vary::parse(spec))
+        .collect::<Result<Vec<_>, _>>()?;
+    let command = cli::matrix_command();
+    for vary in &varies {
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(vary.flag.as_str()) && arg.get_id() != "vary")
+            .ok_or_else(|| format!("--vary {}: no such option", vary.flag))?;
+        if matches.value_source(arg.get_id().as_str()) == Some(clap::parser::ValueSource::CommandLine) {
+            return Err(format!("--vary {}: --{} is given as well, leave it to the matrix", vary.flag, vary.flag).into());
+        }
+    }
+    // each run is parsed from the flags the matrix was given, with one value
+    // of each --vary in its place
+    let base = command_line(&command, matches)?;
+    let mut runs = Vec::new();
+    for values in matrix::combinations(&varies) {
+        let mut args = vec!["arctic matrix".to_string()];
+        args.extend(base.iter().cloned());
+        for (flag, value) in &values {
+            let switch = command.get_arguments().any(|arg| arg.get_long() == Some(flag.as_str()) && matches!(arg.get_action(), clap::ArgAction::SetTrue));
+            match (switch, value.as_str()) {
+                (false, _) => args.push(format!("--{}={}", flag, value)),
+                (true, "true") => args.push(format!("--{}", flag)),
+                (true, "false") => {}
+                (true, _) => return Err(format!("--vary {}: a switch, its values are true and false", flag).into()),
+            }
+        }
+        let label = matrix::label(&values);
+        let cell = command.clone().mut_arg("vary", |vary| vary.required(false)).try_get_matches_from(args).map_err(|e| format!("{}: {}", label, e))?;
+        let config = cell_config(LoadConfig::from_matches(&cell).map_err(|e| format!("{}: {}", label, e))?, runs.len() + 1);
+        runs.push((values, config));
+    }
 
-    let is_running = Arc::new(AtomicBool::new(true));
-    let spinner_is_running = is_running.clone();
-
-    let spinner_handle = tokio::spawn(async move {
-        let spinner_frames = ["|", "/", "-", "\\"];
-        let mut i = 0;
-        
-<<<<<<< HEAD
-        print!("\n");
-        while spinner_is_running.load(Ordering::SeqCst) {
-            print!("\rrunning... {}", spinner_frames[i]);
-=======
-        while spinner_is_running.load(Ordering::SeqCst) {
-            print!("\rRunning... {}", spinner_frames[i]);
->>>>>>> 7f395587592cb3204ce44fe11b968f5dfd790e5f
+    let json = matches.get_flag("json");
+    let quiet = matches.get_flag("quiet") || json;
+    let mut cells = Vec::new();
+    let count = runs.len();
+    for (number, (values, config)) in runs.into_iter().enumerate() {
+        if !quiet {
+            print!("run {}/{}: {} ... ", number + 1, count, matrix::label(&values));
             stdout().flush().ok();
-            i = (i+1)%spinner_frames.len();
-            sleep(Duration::from_millis(150)).await; 
-        }
-<<<<<<< HEAD
-        print!("\rFinished!     \n");
-=======
-        print!("\rDone.     \n");
->>>>>>> 7f395587592cb3204ce44fe11b968f5dfd790e5f
-        stdout().flush().ok();
-    });
-
-    let load_test_handle = tokio::spawn(async move {
-        while start_time.elapsed().as_secs() < duration {
-            let random_data = generate_random_data (&schema_clone);
-            let requests_start = Instant::now();
-
-            match send_data(&endpoint_clone, random_data).await {
-                Ok(_) => {
-                    stats_clone.success_count.fetch_add(1, Ordering::SeqCst);
+        }
+        // a run that fails is marked in the table, the ones after it still go
+        let cell = match run_cell(&config).await {
+            Ok((report, written)) => {
+                let cell = Cell::new(values, &report);
+                if !quiet {
+                    println!("{:.1} rps, {:.2}% errors, p99 {:.2}ms", cell.requests_per_second, cell.error_rate, cell.p99_ms);
+                    if !report.thresholds_passed() {
+                        println!("  thresholds failed");
+                    }
+                    written.iter().for_each(|line| println!("  {}", line));
                 }
-                Err(e) => {
-                    stats_clone.error_count.fetch_add(1, Ordering::SeqCst);
-                    eprintln!("error sending data: {}", e);
+                cell
+            }
+            Err(e) => {
+                if !quiet {
+                    println!("failed: {}", e);
                 }
+                Cell::failed(values, e.to_string())
             }
+        };
+        cells.push(cell);
+    }
+    let report = MatrixReport { vary: varies, cells };
+    if let Some(path) = matches.get_one::<String>("output") {
+        report.write(path)?;
+    }
+    match json {
+        true => println!("{}", serde_json::to_string(&report)?),
+        false => {
+            report.print_table();
+            if let Some(path) = matches.get_one::<String>("output") {
+                println!("results written to {}", path);
+            }
+        }
+    }
+    // a failed run first, then the exit codes of a single run
+    let failed = report.cells.iter().filter(|cell| cell.error.is_some()).count();
+    if failed > 0 {
+        return Err(format!("{} of {} runs failed", failed, report.cells.len()).into());
+    }
+    if report.cells.iter().any(|cell| cell.stop_reason == Some(StopReason::ErrorRate)) {
+        std::process::exit(ABORTED_EXIT_CODE);
+    }
+    if !report.cells.iter().all(|cell| cell.thresholds_passed) {
+        std::process::exit(THRESHOLDS_FAILED_EXIT_CODE);
+    }
+    Ok(())
+}
 
-            let duration = requests_start.elapsed().as_millis() as u64;
-            stats_clone.total_duration.fetch_add(duration, Ordering::SeqCst);
-            stats_clone.total_requests.fetch_add(1, Ordering::SeqCst);
+// the flags given on the command line, but for the --vary ones
+fn command_line(command: &clap::Command, matches: &clap::ArgMatches) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut args = Vec::new();
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if id == "vary" || matches.value_source(id) != Some(clap::parser::ValueSource::CommandLine) {
+            continue;
         }
-    });
+        let flag = format!("--{}", arg.get_long().unwrap_or(id));
+        if !arg.get_action().takes_values() {
+            args.push(flag);
+            continue;
+        }
+        for value in matches.get_raw(id).into_iter().flatten() {
+            let value = value.to_str().ok_or_else(|| format!("{}: not valid utf-8", flag))?;
+            // = keeps values that start with a dash from reading as flags
+            args.push(format!("{}={}", flag, value));
+        }
+    }
+    Ok(args)
+}
 
-    load_test_handle.await?;
-    is_running.store(false, Ordering::SeqCst);
-    spinner_handle.await?;
+// a cell's files of its own, report.html becoming report-3.html for the
+// third; --output is the matrix's table
+fn cell_config(mut config: LoadConfig, number: usize) -> LoadConfig {
+    let own = |path: &String| cell_path(path, number);
+    config.output = None;
+    config.report = config.report.as_ref().map(own);
+    config.junit = config.junit.as_ref().map(own);
+    config.timeseries = config.timeseries.as_ref().map(own);
+    config.log_requests = config.log_requests.as_ref().map(own);
+    config.capture = config.capture.as_ref().map(own);
+    config.sample_dir = own(&config.sample_dir);
+    if let Some(checkpoint) = &mut config.checkpoint {
+        checkpoint.path = own(&checkpoint.path);
+    }
+    config
+}
 
-    stats.print_summary();
-    Ok(())
+// the number goes before all of the extensions, capture.tar.gz becoming
+// capture-3.tar.gz
+fn cell_path(path: &str, number: usize) -> String {
+    let path = std::path::Path::new(path);
+    let file = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = match file.split_once('.') {
+        Some((stem, extensions)) if !stem.is_empty() => format!("{}-{}.{}", stem, number, extensions),
+        _ => format!("{}-{}", file, number),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
 }
 
-fn read_json_file(path: &str) -> Result<Value, Box<dyn std::error::Error>>{
-    let data = fs::read_to_string(path)?;
-    let json: Value = serde_json::from_str(&data)?;
-    Ok(json)
+// one run of the matrix, with the --report, --junit and --history a single
+// run writes; what's written, for the progress lines
+async fn run_cell(config: &LoadConfig) -> Result<(Report, Vec<String>), Box<dyn std::error::Error>> {
+    let generator = generator(config)?;
+    let runner = Runner::new(config.clone(), generator);
+    let stats = runner.stats();
+    let report = runner.run().await?;
+    let mut written = Vec::new();
+    if let Some(path) = &config.report {
+        html::write(path, &report, &stats)?;
+        written.push(format!("html report written to {}", path));
+    }
+    if let Some(path) = &config.junit {
+        junit::write(path, &report)?;
+        written.push(format!("junit report written to {}", path));
+    }
+    // the run happened either way, a history that can't take it doesn't fail the cell
+    if let Some(path) = &config.history {
+        match history::record(path, &report) {
+            Ok(id) => written.push(format!("run {} recorded in {}", id, path)),
+            Err(e) => warn!(path, error = %e, "run not recorded"),
+        }
+    }
+    Ok((report, written))
 }
 
-fn generate_random_data(schema: &Value) -> Value {
-    let mut rng = rand::thread_rng();
+// the live status line under the banner, redrawn in place until the run
+// is over, or a line of its own every PLAIN_STATUS_EVERY without a terminal:
+// requests so far, rps and error % over the last STATUS_WINDOW, and the time
+// left when the run has a duration
+async fn status(stats: Arc<LoadTestStats>, config: LoadConfig, pause: Arc<Pause>, is_running: Arc<AtomicBool>) {
+    let frames = ["|", "/", "-", "\\"];
+    let warmup_start = stats.start_time();
+    let mut window_start = warmup_start;
+    let mut samples = VecDeque::new();
+    let mut i = 0;
+    let plain = logging::plain();
+    let mut printed = Instant::now();
+
+    println!();
+    logging::spinner(!plain);
+    while is_running.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        // the counters start over once a warm-up is done
+        if stats.start_time() != window_start {
+            window_start = stats.start_time();
+            samples.clear();
+        }
+        let total = stats.total_requests.load(Ordering::SeqCst);
+        let errors = stats.error_count.load(Ordering::SeqCst);
+        samples.push_back((now, total, errors));
+        while samples.front().is_some_and(|(at, _, _)| now.duration_since(*at) > STATUS_WINDOW) {
+            samples.pop_front();
+        }
+        let (since, total_then, errors_then) = samples[0];
+        let (requests, failed) = (total - total_then, errors - errors_then);
+        let window = now.duration_since(since).as_secs_f64();
+        let rps = if window > 0.0 { requests as f64 / window } else { 0.0 };
+        let error_rate = match requests {
+            0 => 0.0,
+            n => failed as f64 / n as f64 * 100.0,
+        };
 
-    match schema {
-        Value::Object(map) => {
-            let mut random_map = Map::new();
-            for(key, value) in map {
-                random_map.insert(key.clone(), generate_random_data(value));
+        let elapsed = stats.start_time().elapsed();
+        let line = if config.warmup.is_some() && stats.start_time() == warmup_start {
+            format!("warming up [{}]", clock(elapsed))
+        } else {
+            let mut sent = match config.requests {
+                Some(limit) => format!("{}/{} requests", total, limit),
+                None => format!("{} requests", total),
+            };
+            if config.adaptive.is_some() {
+                sent = format!("{}, {} workers", sent, stats.concurrency.current());
+            }
+            let time = match config.duration {
+                Some(secs) => format!(
+                    "{} elapsed, {} left",
+                    clock(elapsed),
+                    clock(Duration::from_secs(secs).saturating_sub(elapsed))
+                ),
+                None => format!("{} elapsed", clock(elapsed)),
+            };
+            format!("{}, {:.1} rps, {:.2}% errors, {}", sent, rps, error_rate, time)
+        };
+        if plain {
+            if now.duration_since(printed) >= PLAIN_STATUS_EVERY {
+                printed = now;
+                let state = if pause.is_paused() { "paused, " } else { "" };
+                println!("{}{}", state, line);
             }
-            Value::Object(random_map)
+        } else {
+            let state = if pause.is_paused() { "paused" } else { frames[i] };
+            // erase to the end of the line, the last one may have been longer
+            print!("\r{} {}\x1b[K", state, line);
+            stdout().flush().ok();
+            i = (i + 1) % frames.len();
         }
-        Value::String(_) => Value::String(rng.gen::<u32>().to_string()),
-        Value::Number(n) if n.is_i64() => Value::Number(rng.gen::<i64>().into()),
-        Value::Number(n) if n.is_f64() => {
-            let num = rng.gen::<f64>();
-            Value::Number(Number::from_f64(num).unwrap_or_else(|| Number::from(0)))
-        },
-        Value::Bool(_) => Value::Bool(rng.gen()),
-        Value::Array(arr) => {
-            let mut random_arr = Vec::new();
-            if !arr.is_empty() {
-                for _ in 0..rng.gen_range(1..5) {
-                    random_arr.push(generate_random_data(&arr[0]));
+        sleep(STATUS_REFRESH).await;
+    }
+    logging::spinner(false);
+    match plain {
+        true => println!("Finished!"),
+        false => println!("\r\x1b[KFinished!"),
+    }
+    stdout().flush().ok();
+}
+
+// SIGUSR1 pauses the workers and resumes them, SIGUSR2 prints the summary
+// so far; `dump` is off while the tui owns the terminal, and under --json,
+// where stdout is the one json report
+#[cfg(unix)]
+async fn control(pause: Arc<Pause>, stats: Arc<LoadTestStats>, dump: bool) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut toggle = signal(SignalKind::user_defined1())?;
+    let mut snapshot = signal(SignalKind::user_defined2())?;
+    loop {
+        tokio::select! {
+            Some(()) = toggle.recv() => match pause.toggle() {
+                true => info!("paused, SIGUSR1 again resumes"),
+                false => info!("resumed"),
+            },
+            Some(()) = snapshot.recv() => {
+                if dump {
+                    println!();
+                    stats.print_summary();
                 }
             }
-            Value::Array(random_arr)
         }
-        val => val.clone(),
     }
 }
 
-async fn send_data(endpoint: &str, data: Value) -> Result<(), Error> {
-    let client = reqwest::Client::new();
-    let response = client
-        .post(endpoint)
-        .json(&data)
-        .send()
-        .await?;
+#[cfg(not(unix))]
+async fn control(_pause: Arc<Pause>, _stats: Arc<LoadTestStats>, _dump: bool) -> std::io::Result<()> {
+    Ok(())
+}
 
-    response.error_for_status()?;
+fn dry_run(runner: &Runner, count: u64, output: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut text = String::new();
+    for sample in runner.dry_run(count)? {
+        text.push_str(&serde_json::to_string_pretty(&sample)?);
+        text.push('\n');
+    }
+    match output {
+        Some(path) => std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))?,
+        None => print!("{}", text),
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+// the banner and what the run is about to do
+fn print_config(config: &LoadConfig) {
+    println!(r"
+        ___              __  _     
+       /   |  __________/ /_(_)____
+      / /| | / ___/ ___/ __/ / ___/
+     / ___ |/ /  / /__/ /_/ / /__  
+    /_/  |_/_/   \___/\__/_/\___/
+    ");
+    if let Some(duration) = config.duration {
+        println!("duration: {} seconds", duration);
+    }
+    if let Some(warmup) = config.warmup {
+        println!("warmup: {:?}", warmup);
+    }
+    if let Some(requests) = config.requests {
+        println!("requests: {}", requests);
+    }
+    println!("drain timeout: {:?}", config.drain_timeout);
+    if let Some(expected) = &config.expect_status {
+        println!("expected status: {}", expected);
+    }
+    if let Some(shard) = config.shard {
+        println!("shard: {} (seed {})", shard, config.seed);
+    }
+    let (recorded, targets): (Vec<_>, Vec<_>) = config.endpoints.iter().partition(|t| t.replay.is_some());
+    for target in &targets {
+        match config.endpoints.len() {
+            1 => println!("endpoint: {}", target.label()),
+            _ => println!("endpoint: {} (weight {})", target.label(), target.weight),
+        }
+    }
+    if !recorded.is_empty() {
+        println!("har: {} recorded requests", recorded.len());
+    }
+    match &config.corpus {
+        Some(path) => println!("corpus: {} ({})", path, config.corpus_order),
+        None if config.stdin => println!("payloads: stdin, one json document per line"),
+        None if config.data.is_empty() => {}
+        None => {
+            let templates: Vec<String> = config.data.iter().map(ToString::to_string).collect();
+            println!("templates: {}", templates.join(", "));
+        }
+    }
+    if let Some(feed) = &config.feed {
+        println!("feed: {} ({:?})", feed, config.feed_order);
+    }
+    if let Some(script) = &config.script {
+        let hooks = [(script.has_generate(), "generate"), (script.has_before_request(), "before_request"), (script.has_check(), "check")];
+        let hooks: Vec<&str> = hooks.iter().filter(|(defined, _)| *defined).map(|(_, hook)| *hook).collect();
+        println!("script: {} ({})", script.path(), hooks.join(", "));
+    }
+    if config.method != "POST" {
+        println!("method: {}", config.method);
+    }
+    if let Some(protobuf) = &config.protobuf {
+        println!("body format: Protobuf ({})", protobuf.message);
+    } else if let Some(random) = &config.random_bytes {
+        println!("body: {} ({})", random, random.content_type);
+    } else if let Some(xml) = &config.xml {
+        println!("body format: Xml (<{}>, attributes prefixed {:?})", xml.root, xml.attribute_prefix);
+    } else if config.body_format != BodyFormat::Json {
+        println!("body format: {:?}", config.body_format);
+    }
+    if let Some(padding) = &config.padding {
+        println!("body size: about {} bytes, padded in \"{}\"", padding.size, padding.field);
+    }
+    if let Some(stream) = &config.stream_body {
+        println!("streamed bodies: {}", stream);
+    }
+    if let Some(compress) = config.compress {
+        println!("compression: {}", compress);
+    }
+    if let Some(graphql) = &config.graphql {
+        println!("graphql: {}", graphql.path);
+    }
+    if let Some(grpc) = &config.grpc {
+        match grpc.protos.is_empty() {
+            true => println!("grpc: {} (server reflection)", grpc.method),
+            false => println!("grpc: {} ({})", grpc.method, grpc.protos.join(", ")),
+        }
+    }
+    if config.cookies {
+        println!("cookies: a jar per virtual user");
+    }
+    if let Some(refresh) = config.dns_refresh {
+        println!("dns refresh: every {:?}", refresh);
+    }
+    if let Some(checkpoint) = &config.checkpoint {
+        println!("checkpoint: {} every {:?}", checkpoint.path, checkpoint.interval);
+    }
+    if let Some(setup) = &config.global_setup {
+        println!("global setup: {} {}", setup.method, setup.url);
+    }
+    if let Some(teardown) = &config.teardown {
+        println!("teardown: {} {}", teardown.method, teardown.url);
+    }
+    if let Some(setup) = &config.setup {
+        let names: Vec<_> = setup.extract.iter().map(|extract| extract.name.as_str()).collect();
+        println!("setup: {} {} per virtual user, extracting {}", setup.method, setup.url, if names.is_empty() { "nothing".to_string() } else { names.join(", ") });
+    }
+    if let Some(scenario) = &config.scenario {
+        println!("scenario: {}, {} request steps per pass", scenario.path, scenario.requests);
+    }
+    if let Some(fingerprints) = &config.fingerprints {
+        let per = match fingerprints.rotate {
+            Rotate::Request => "request",
+            Rotate::User => "virtual user",
+        };
+        println!("fingerprints: {} entries from {}, one per {}", fingerprints.entries, fingerprints.path, per);
+    }
+    if let Some(param) = &config.cache_bust {
+        println!("cache busting: a random {} query parameter and no-cache headers", param);
+    }
+    if let Some(schema) = &config.payload_schema {
+        let invalid = match schema.invalid {
+            Invalid::Skip => "skipped",
+            Invalid::Fix => "fixed up where possible",
+        };
+        println!("payload schema: {}, invalid bodies {}", schema.path, invalid);
+    }
+    if !config.metrics.is_empty() {
+        let metrics: Vec<String> = config.metrics.iter().map(|m| format!("{}={}", m.name, m.path)).collect();
+        println!("response metrics: {}", metrics.join(", "));
+    }
+    match &config.otlp {
+        Some(otlp) => println!("traces: traceparent headers, spans exported to {}", otlp),
+        None if config.traceparent => println!("traces: traceparent headers"),
+        None => {}
+    }
+    if let Some(proxy) = &config.proxy {
+        println!("proxy: {}", proxy);
+    }
+    if let Some(version) = config.http_version {
+        println!("http version: {}", version);
+    }
+    if let Some(cacert) = &config.tls.cacert {
+        println!("ca certificates: {}", cacert);
+    }
+    if let Some(cert) = &config.tls.cert {
+        println!("client certificate: {}", cert);
+    }
+    if config.tls.insecure {
+        println!("tls verification: off");
+    }
+    if config.tls.probe_full_handshakes && config.timing {
+        println!("tls resumption: off, the timing probes do full handshakes only");
+    }
+    for resolve in &config.tls.resolve {
+        println!("resolve: {}", resolve);
+    }
+    if let Some(host_override) = &config.tls.host_override {
+        println!("host override: {}", host_override);
+    }
+    if let Some(family) = config.tls.family {
+        println!("ip family: {}", family);
+    }
+    println!("seed: {}", config.seed);
+    if let Some(adaptive) = &config.adaptive {
+        println!("concurrency: adaptive, {} to start with, up to {}, for a p95 of {:?}", adaptive.start, adaptive.max, adaptive.target_p95);
+        return;
+    }
+    match (&config.stages, config.profile.unit) {
+        (Some(stages), _) if config.preset.is_some() => {
+            println!("profile: {} (stages {})", config.preset.as_deref().unwrap_or_default(), stages);
+        }
+        (Some(stages), unit) => println!("stages: {} ({:?})", stages, unit),
+        (None, StageUnit::Rps) => println!("rate: {} rps", config.profile.peak()),
+        (None, StageUnit::Concurrency) => println!("concurrency: {}", config.workers()),
+    }
+}
+
+// the summary and files every load test ends with, then the exit status
+fn finish(config: &LoadConfig, report: &Report, stats: &LoadTestStats) -> Result<(), Box<dyn std::error::Error>> {
+    if !config.json {
+        stats.print_summary();
+        if let Some(proxy) = &report.proxy {
+            println!("via proxy:           {}", proxy);
+        }
+        threshold::print_results(&report.thresholds);
+    }
+
+    let mut written = Vec::new();
+    if let Some(path) = &config.output {
+        report.write(path, config.format)?;
+        written.push(format!("results written to {}", path));
+    }
+    if let Some(path) = &config.report {
+        html::write(path, report, stats)?;
+        written.push(format!("html report written to {}", path));
+    }
+    if let Some(path) = &config.junit {
+        junit::write(path, report)?;
+        written.push(format!("junit report written to {}", path));
+    }
+    // not worth losing the exit status over
+    if let Some(path) = &config.history {
+        match history::record(path, report) {
+            Ok(id) => written.push(format!("run {} recorded in {} under config {}", id, path, history::config_key(report))),
+            Err(e) => warn!(path, error = %e, "run not recorded"),
+        }
+    }
+    match config.json {
+        true => println!("{}", serde_json::to_string(report)?),
+        false => written.iter().for_each(|line| println!("{}", line)),
+    }
+    if report.stop_reason == Some(StopReason::ErrorRate) {
+        std::process::exit(ABORTED_EXIT_CODE);
+    }
+    if !report.thresholds_passed() {
+        std::process::exit(THRESHOLDS_FAILED_EXIT_CODE);
+    }
+    Ok(())
+}
+
+fn run_compare(matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let baseline = compare::load(matches.get_one::<String>("baseline").unwrap())?;
+    let current = compare::load(matches.get_one::<String>("current").unwrap())?;
+    let changes = compare::compare(&baseline, &current, *matches.get_one::<f64>("max-regression").unwrap())?;
+    compare::print_table(&changes);
+    if changes.iter().any(|c| c.regressed) {
+        std::process::exit(REGRESSION_EXIT_CODE);
+    }
+    Ok(())
+}
+
+fn run_history(matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let db = matches.get_one::<String>("db").unwrap();
+    let ids: Vec<i64> = matches.get_many::<i64>("compare").unwrap_or_default().copied().collect();
+    let (baseline, current) = match ids.as_slice() {
+        [] => {
+            let runs = history::list(db, matches.get_one::<String>("config-key").map(String::as_str), *matches.get_one::<usize>("last").unwrap())?;
+            if runs.is_empty() {
+                println!("no runs recorded in {}", db);
+            } else {
+                history::print_table(&runs);
+            }
+            return Ok(());
+        }
+        [current] => (history::previous(db, *current)?, *current),
+        [baseline, current, ..] => (*baseline, *current),
+    };
+    println!("run {} against run {}", current, baseline);
+    let changes = compare::compare(&history::report(db, baseline)?, &history::report(db, current)?, *matches.get_one::<f64>("max-regression").unwrap())?;
+    compare::print_table(&changes);
+    if changes.iter().any(|c| c.regressed) {
+        std::process::exit(REGRESSION_EXIT_CODE);
+    }
+    Ok(())
+}
+
+async fn run_sse(matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let config = SseConfig::from_matches(matches)?;
+    let stats = Arc::new(SseStats::new());
+
+    println!("duration: {} seconds", config.duration);
+    println!("endpoint: {}", config.endpoint);
+    println!("streams: {}", config.connections);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let report = sse::run(config.clone(), stats.clone(), stop).await?;
+    stats.print_summary();
+
+    if let Some(path) = &config.output {
+        report.write(path)?;
+        println!("results written to {}", path);
+    }
+    Ok(())
+}
+
+async fn run_ws(matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let config = WsConfig::from_matches(matches)?;
+    let generator = Generator::from_file(&config.data, config.seed)?;
+    let stats = Arc::new(WsStats::new());
+
+    println!("duration: {} seconds", config.duration);
+    println!("endpoint: {}", config.endpoint);
+    println!("templates: {}", config.data);
+    println!("seed: {}", config.seed);
+    println!("connections: {}", config.connections);
+    println!("rate: {} messages/s", config.rate);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let report = ws::run(config.clone(), generator, stats.clone(), stop).await?;
+    stats.print_summary();
+
+    if let Some(path) = &config.output {
+        report.write(path)?;
+        println!("results written to {}", path);
+    }
+    Ok(())
+}
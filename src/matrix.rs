@@ -0,0 +1,191 @@
+use serde::Serialize;
+use std::fmt::Write as _;
+
+use crate::report::{OutputFormat, Report};
+use crate::stats::StopReason;
+
+// --vary flag=v1,v2,...: a run option and the values the matrix tries it at
+#[derive(Debug, Clone, Serialize)]
+pub struct Vary {
+    pub flag: String,
+    pub values: Vec<String>,
+}
+
+impl Vary {
+    // "concurrency=10,50,100"; the flag may keep its leading dashes
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let usage = || format!("--vary {}: expected flag=value,value,... e.g. concurrency=10,50", spec);
+        let (flag, values) = spec.split_once('=').ok_or_else(usage)?;
+        let flag = flag.trim().trim_start_matches("--").to_string();
+        let values: Vec<String> = values.split(',').map(|value| value.trim().to_string()).collect();
+        if flag.is_empty() || values.iter().any(String::is_empty) {
+            return Err(usage());
+        }
+        Ok(Vary { flag, values })
+    }
+}
+
+// every combination of the values, the first --vary changing slowest
+pub fn combinations(varies: &[Vary]) -> Vec<Vec<(String, String)>> {
+    varies.iter().fold(vec![Vec::new()], |cells, vary| {
+        cells
+            .iter()
+            .flat_map(|cell| {
+                vary.values.iter().map(move |value| {
+                    let mut cell = cell.clone();
+                    cell.push((vary.flag.clone(), value.clone()));
+                    cell
+                })
+            })
+            .collect()
+    })
+}
+
+// "concurrency=10 body-size=1KB"
+pub fn label(values: &[(String, String)]) -> String {
+    values.iter().map(|(flag, value)| format!("{}={}", flag, value)).collect::<Vec<_>>().join(" ")
+}
+
+// one run of the matrix, boiled down to what the table compares
+#[derive(Debug, Serialize)]
+pub struct Cell {
+    pub values: Vec<(String, String)>,
+    pub total_requests: u64,
+    pub failed_requests: u64,
+    pub requests_per_second: f64,
+    // percent of the requests
+    pub error_rate: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<StopReason>,
+    pub thresholds_passed: bool,
+    // the run failed outright, the numbers are all zero
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Cell {
+    pub fn new(values: Vec<(String, String)>, report: &Report) -> Self {
+        let error_rate = match report.total_requests {
+            0 => 0.0,
+            n => report.failed_requests as f64 / n as f64 * 100.0,
+        };
+        Cell {
+            values,
+            total_requests: report.total_requests,
+            failed_requests: report.failed_requests,
+            requests_per_second: report.requests_per_second,
+            error_rate,
+            p50_ms: report.latency_ms.p50,
+            p90_ms: report.latency_ms.p90,
+            p99_ms: report.latency_ms.p99,
+            max_ms: report.latency_ms.max,
+            stop_reason: report.stop_reason,
+            thresholds_passed: report.thresholds_passed(),
+            error: None,
+        }
+    }
+
+    pub fn failed(values: Vec<(String, String)>, error: String) -> Self {
+        Cell {
+            values,
+            total_requests: 0,
+            failed_requests: 0,
+            requests_per_second: 0.0,
+            error_rate: 0.0,
+            p50_ms: 0.0,
+            p90_ms: 0.0,
+            p99_ms: 0.0,
+            max_ms: 0.0,
+            stop_reason: None,
+            thresholds_passed: false,
+            error: Some(error),
+        }
+    }
+}
+
+// `arctic matrix`: the runs of every combination of the --vary values, one
+// after the other, side by side
+#[derive(Debug, Serialize)]
+pub struct MatrixReport {
+    pub vary: Vec<Vary>,
+    pub cells: Vec<Cell>,
+}
+
+impl MatrixReport {
+    pub fn print_table(&self) {
+        let widths: Vec<usize> = self
+            .vary
+            .iter()
+            .map(|vary| vary.values.iter().map(String::len).chain([vary.flag.len()]).max().unwrap_or(0))
+            .collect();
+        let mut header = String::new();
+        for (vary, width) in self.vary.iter().zip(&widths) {
+            let _ = write!(header, "{:<width$} ", vary.flag, width = width);
+        }
+        println!("\n===== Matrix Summary =====");
+        println!("{}{:>10} {:>10} {:>8} {:>10} {:>10} {:>10}", header, "requests", "rps", "errors", "p50", "p90", "p99");
+        for cell in &self.cells {
+            let mut row = String::new();
+            for ((_, value), width) in cell.values.iter().zip(&widths) {
+                let _ = write!(row, "{:<width$} ", value, width = width);
+            }
+            if let Some(error) = &cell.error {
+                println!("{}failed: {}", row, error);
+                continue;
+            }
+            println!(
+                "{}{:>10} {:>10.2} {:>7.2}% {:>8.2}ms {:>8.2}ms {:>8.2}ms",
+                row, cell.total_requests, cell.requests_per_second, cell.error_rate, cell.p50_ms, cell.p90_ms, cell.p99_ms
+            );
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let contents = match OutputFormat::from_path(path) {
+            OutputFormat::Json => serde_json::to_string_pretty(self)?,
+            OutputFormat::Csv => self.to_csv(),
+        };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    // a row per run, the varied flags first
+    fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        let flags: Vec<&str> = self.vary.iter().map(|vary| vary.flag.as_str()).collect();
+        let _ = writeln!(
+            csv,
+            "{},total_requests,failed_requests,requests_per_second,error_rate,p50_ms,p90_ms,p99_ms,max_ms,error",
+            flags.join(",")
+        );
+        for cell in &self.cells {
+            let values: Vec<String> = cell.values.iter().map(|(_, value)| csv_field(value)).collect();
+            let _ = writeln!(
+                csv,
+                "{},{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{}",
+                values.join(","),
+                cell.total_requests,
+                cell.failed_requests,
+                cell.requests_per_second,
+                cell.error_rate,
+                cell.p50_ms,
+                cell.p90_ms,
+                cell.p99_ms,
+                cell.max_ms,
+                csv_field(cell.error.as_deref().unwrap_or_default())
+            );
+        }
+        csv
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
@@ -0,0 +1,123 @@
+use arctic::checks::Checks;
+use arctic::report::{OutputFormat, ShardSummary};
+use arctic::stats::{LoadTestStats, StopReason};
+use arctic::LoadConfig;
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::Duration;
+
+use crate::cli;
+
+// what merging reads of a shard's report
+#[derive(Deserialize)]
+struct ShardReport {
+    duration_secs: f64,
+    shard: Option<ShardSummary>,
+    metadata: Metadata,
+    config: Seeded,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    args: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Seeded {
+    seed: u64,
+}
+
+// `arctic merge shard*.json`: the --output reports of every --shard of one
+// run added up into the stats of the whole run, with the config its flags
+// give without --shard, writing where the merge's own flags say
+pub fn merge(matches: &ArgMatches) -> Result<(LoadConfig, LoadTestStats), Box<dyn std::error::Error>> {
+    // the one the others have to agree with, and how many shards it's one of
+    let mut first: Option<(String, ShardReport, u64)> = None;
+    // by index, with the file each came from
+    let mut shards = BTreeMap::new();
+    for path in matches.get_many::<String>("reports").unwrap() {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut report: ShardReport = serde_json::from_str(&text).map_err(|e| format!("{}: not a json report: {}", path, e))?;
+        let shard = report.shard.take().ok_or_else(|| format!("{}: not the report of a --shard run", path))?;
+        let duration = Duration::from_secs_f64(report.duration_secs.max(0.0));
+        match &first {
+            None => first = Some((path.clone(), report, shard.count)),
+            Some((first_path, first, count)) => {
+                if shard.count != *count {
+                    return Err(format!("{}: shard {}/{}, but {} is a shard of {}", path, shard.index, shard.count, first_path, count).into());
+                }
+                if report.config.seed != first.config.seed {
+                    return Err(format!("{}: seed {}, {} has {}; the shards of a run share --seed", path, report.config.seed, first_path, first.config.seed).into());
+                }
+            }
+        }
+        if let Some((other, _, _)) = shards.get(&shard.index) {
+            return Err(format!("{}: shard {}/{} again, {} is already that one", path, shard.index, shard.count, other).into());
+        }
+        shards.insert(shard.index, (path.clone(), shard, duration));
+    }
+    let (first_path, first, count) = first.ok_or("no reports to merge")?;
+    let missing: Vec<String> = (1..=count).filter(|index| !shards.contains_key(index)).map(|index| format!("{}/{}", index, count)).collect();
+    if !missing.is_empty() {
+        return Err(format!("shard {} missing, the merged report needs all {}", missing.join(", "), count).into());
+    }
+
+    let mut config = whole_run(&first.metadata.args).map_err(|e| format!("{}: the run's flags: {}", first_path, e))?;
+    config.output = matches.get_one::<String>("output").cloned();
+    config.format = match matches.get_one::<String>("format") {
+        Some(format) => format.parse()?,
+        None => config.output.as_deref().map(OutputFormat::from_path).unwrap_or_default(),
+    };
+    config.report = matches.get_one::<String>("report").cloned();
+    config.junit = matches.get_one::<String>("junit").cloned();
+    config.history = matches.get_one::<String>("history").cloned();
+    config.json = matches.get_flag("json");
+
+    let stats = LoadTestStats::new(Checks::new(config.assertions.clone()), &config.metrics, &config.endpoint_labels(), &config.tag_labels(), &config.template_labels(), &config.chaos_labels());
+    // only the first reason sticks: of those the shards stopped for, the one
+    // that says most about the run, a tripped breaker before all else
+    let reasons: Vec<Option<StopReason>> = shards.values().map(|(_, shard, _)| shard.stats.stop_reason).collect();
+    let precedence = [StopReason::ErrorRate, StopReason::Interrupted, StopReason::InputClosed, StopReason::RequestLimit, StopReason::Duration];
+    if let Some(reason) = precedence.into_iter().find(|reason| reasons.contains(&Some(*reason))) {
+        stats.stop(reason);
+    }
+    for (_, shard, _) in shards.values() {
+        stats.merge(&shard.stats);
+    }
+    // the shards ran side by side, the run took as long as the longest
+    stats.backdate(shards.values().map(|(_, _, duration)| *duration).max().unwrap_or_default());
+    if !config.json {
+        println!("merged {} shards, seed {}", count, first.config.seed);
+        // the report holds one reason, the others are only said here
+        if reasons.iter().any(|reason| *reason != reasons[0]) {
+            for ((index, _), reason) in shards.iter().zip(&reasons) {
+                let reason = reason.map_or_else(|| "no reason recorded".to_string(), |reason| reason.to_string());
+                println!("  shard {}/{} stopped: {}", index, count, reason);
+            }
+        }
+    }
+    Ok((config, stats))
+}
+
+// the config of a shard's command line without its --shard
+fn whole_run(args: &[String]) -> Result<LoadConfig, Box<dyn std::error::Error>> {
+    let mut whole = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--shard" => {
+                args.next();
+            }
+            arg if arg.starts_with("--shard=") => {}
+            arg => whole.push(arg.to_string()),
+        }
+    }
+    let matches = cli::command().try_get_matches_from(whole)?;
+    match matches.subcommand() {
+        Some(("run" | "openapi", matches)) => LoadConfig::from_matches(matches),
+        Some((other, _)) => Err(format!("arctic {} doesn't run shards", other).into()),
+        None => LoadConfig::from_matches(&matches),
+    }
+}
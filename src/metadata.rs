@@ -0,0 +1,121 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+
+use crate::config::LoadConfig;
+
+// flags whose values are credentials, left out of the recorded command line
+// the way the config leaves them out of the report
+const SECRET_FLAGS: &[&str] = &["--bearer", "--basic", "--oauth-client-secret", "--proxy-user", "--sign"];
+// header flags keep the header name and lose the value
+const HEADER_FLAGS: &[&str] = &["-H", "--header", "--api-key-header"];
+const HIDDEN: &str = "<hidden>";
+
+// where, when and with what a run happened, so a report archived from ci
+// says so itself
+#[derive(Debug, Clone, Serialize)]
+pub struct RunMetadata {
+    pub arctic_version: &'static str,
+    // the command line, credentials hidden
+    pub args: Vec<String>,
+    // sha-256 of the resolved config as the report shows it
+    pub config_hash: String,
+    // git describe of the repository the first --data template is in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_git: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub cpus: usize,
+    pub started_at: String,
+    pub finished_at: String,
+}
+
+impl RunMetadata {
+    // for a run that ends now after `duration_secs`
+    pub fn capture(config: &LoadConfig, duration_secs: f64) -> Self {
+        let finished = Utc::now();
+        let started = finished - chrono::Duration::milliseconds((duration_secs * 1000.0) as i64);
+        let config_json = serde_json::to_vec(config).unwrap_or_default();
+        RunMetadata {
+            arctic_version: env!("CARGO_PKG_VERSION"),
+            args: redact(env::args()),
+            config_hash: Sha256::digest(&config_json).iter().map(|b| format!("{:02x}", b)).collect(),
+            template_git: config.data.first().and_then(|spec| git_describe(Path::new(&spec.path))),
+            hostname: hostname(),
+            os: env::consts::OS,
+            arch: env::consts::ARCH,
+            cpus: thread::available_parallelism().map_or(1, |cpus| cpus.get()),
+            started_at: timestamp(started),
+            finished_at: timestamp(finished),
+        }
+    }
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn redact(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut redacted = Vec::new();
+    // the flag the next argument is the value of
+    let mut pending: Option<bool> = None;
+    for arg in args {
+        if let Some(header) = pending.take() {
+            redacted.push(hide(&arg, header));
+            continue;
+        }
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+        let secret = SECRET_FLAGS.contains(&flag);
+        let header = HEADER_FLAGS.contains(&flag);
+        match value {
+            Some(value) if secret || header => redacted.push(format!("{}={}", flag, hide(value, header))),
+            None if secret || header => {
+                pending = Some(header);
+                redacted.push(arg);
+            }
+            // -H'Name: value'
+            _ if arg.starts_with("-H") && arg.len() > 2 => redacted.push(format!("-H{}", hide(&arg[2..], true))),
+            _ => redacted.push(arg),
+        }
+    }
+    redacted
+}
+
+fn hide(value: &str, header: bool) -> String {
+    match value.split_once(':').filter(|_| header) {
+        Some((name, _)) => format!("{}: {}", name, HIDDEN),
+        None => HIDDEN.to_string(),
+    }
+}
+
+fn git_describe(template: &Path) -> Option<String> {
+    let dir = template.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["describe", "--always", "--dirty", "--tags"])
+        .output()
+        .ok()?;
+    let described = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !described.is_empty()).then_some(described)
+}
+
+fn hostname() -> Option<String> {
+    let name = env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| Command::new("hostname").output().ok().map(|output| String::from_utf8_lossy(&output.stdout).into_owned()))?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::jsonpath;
+use crate::timeseries::TimeSeries;
+
+// --metric name=$.path: a number the responses report about the server,
+// such as its queue depth, kept per second next to the latency
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricSpec {
+    pub name: String,
+    pub path: String,
+}
+
+impl MetricSpec {
+    // "queue_depth=$.queue.depth"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let usage = || format!("--metric {}: expected name=jsonpath e.g. queue_depth=$.queue_depth", spec);
+        let (name, path) = spec.split_once('=').ok_or_else(usage)?;
+        let (name, path) = (name.trim(), path.trim());
+        if name.is_empty() || path.is_empty() {
+            return Err(usage());
+        }
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') {
+            return Err(format!("--metric {}: names are letters, digits, _, - and .", spec));
+        }
+        Ok(MetricSpec { name: name.to_string(), path: path.to_string() })
+    }
+}
+
+// the values seen of one metric
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Aggregate {
+    pub fn add(&mut self, value: f64) {
+        self.merge(&Aggregate { count: 1, sum: value, min: value, max: value });
+    }
+
+    pub fn merge(&mut self, other: &Aggregate) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn summary(&self) -> MetricSummary {
+        let mean = match self.count {
+            0 => 0.0,
+            n => self.sum / n as f64,
+        };
+        MetricSummary { count: self.count, min: self.min, mean, max: self.max }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricSummary {
+    // responses the value was found in
+    pub count: u64,
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    specs: Vec<MetricSpec>,
+    totals: Vec<Mutex<Aggregate>>,
+}
+
+impl Metrics {
+    pub fn new(specs: &[MetricSpec]) -> Self {
+        Metrics { specs: specs.to_vec(), totals: specs.iter().map(|_| Mutex::default()).collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    // the values a json response body reports, over the run and in the
+    // second it's in; a number, or a string of one, anything else is left out
+    pub fn record(&self, json: &Value, timeseries: &TimeSeries) {
+        for (spec, total) in self.specs.iter().zip(&self.totals) {
+            let value = match jsonpath::select(json, &spec.path) {
+                Some(Value::Number(n)) => n.as_f64(),
+                Some(Value::String(s)) => s.trim().parse::<f64>().ok().filter(|v| v.is_finite()),
+                _ => None,
+            };
+            if let Some(value) = value {
+                total.lock().unwrap().add(value);
+                timeseries.record_metric(&spec.name, value);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<Aggregate> {
+        self.totals.iter().map(|total| *total.lock().unwrap()).collect()
+    }
+
+    pub fn merge(&self, other: &[Aggregate]) {
+        for (total, other) in self.totals.iter().zip(other) {
+            total.lock().unwrap().merge(other);
+        }
+    }
+
+    // by name, those never found included with a count of 0
+    pub fn summaries(&self) -> BTreeMap<String, MetricSummary> {
+        self.specs
+            .iter()
+            .zip(&self.totals)
+            .map(|(spec, total)| (spec.name.clone(), total.lock().unwrap().summary()))
+            .collect()
+    }
+}
@@ -0,0 +1,223 @@
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use crate::stats::LoadTestStats;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+// the share of the cpus arctic can use above which it's likely the limit
+// rather than the target
+const BUSY_CPU: f64 = 0.9;
+// how late the sampler's own timer may fire on average before the runtime
+// counts as starved, and requests as held up before they're sent
+const STARVED_LAG: Duration = Duration::from_millis(10);
+// the kernel's clock ticks per second in /proc, USER_HZ
+#[cfg(target_os = "linux")]
+const TICKS_PER_SEC: f64 = 100.0;
+
+// arctic's own cpu, memory, sockets and tasks over the measured window, read
+// once a second, so a run the load generator couldn't keep up with says so
+#[derive(Debug, Default)]
+pub struct ResourceMonitor {
+    samples: Mutex<Samples>,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    // when sampling started and the cpu seconds used by then
+    first: Option<(Instant, Option<f64>)>,
+    last: Option<(Instant, Option<f64>)>,
+    cpu_peak: Option<f64>,
+    memory_peak: Option<u64>,
+    sockets_peak: Option<u64>,
+    tasks_peak: u64,
+    lag_max: Duration,
+    lag_total: Duration,
+    lags: u32,
+}
+
+// what the report shows of it
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceSummary {
+    pub cpus: usize,
+    pub runtime_workers: usize,
+    // percent of one cpu: 200 is two of them kept busy; none where /proc
+    // isn't there to read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_mean_percent: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_peak_percent: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_peak_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sockets_peak: Option<u64>,
+    pub tasks_peak: u64,
+    // how late the runtime woke a timer, a sign of workers too busy to send
+    // requests on time
+    pub scheduling_lag_mean_ms: f64,
+    pub scheduling_lag_max_ms: f64,
+    // the load generator was likely the bottleneck, and why
+    pub saturated: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl ResourceMonitor {
+    fn sample(&self, lag: Option<Duration>) {
+        let now = Instant::now();
+        let cpu = cpu_secs();
+        let mut samples = self.samples.lock().unwrap();
+        if let (Some((then, Some(before))), Some(cpu)) = (samples.last, cpu) {
+            let wall = now.duration_since(then).as_secs_f64();
+            if wall > 0.0 {
+                let percent = (cpu - before) / wall * 100.0;
+                samples.cpu_peak = Some(samples.cpu_peak.map_or(percent, |peak| peak.max(percent)));
+            }
+        }
+        if samples.first.is_none() {
+            samples.first = Some((now, cpu));
+        }
+        samples.last = Some((now, cpu));
+        if let Some(memory) = memory_bytes() {
+            samples.memory_peak = Some(samples.memory_peak.map_or(memory, |peak| peak.max(memory)));
+        }
+        if let Some(sockets) = open_sockets() {
+            samples.sockets_peak = Some(samples.sockets_peak.map_or(sockets, |peak| peak.max(sockets)));
+        }
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            samples.tasks_peak = samples.tasks_peak.max(runtime.metrics().num_alive_tasks() as u64);
+        }
+        if let Some(lag) = lag {
+            samples.lag_max = samples.lag_max.max(lag);
+            samples.lag_total += lag;
+            samples.lags += 1;
+        }
+    }
+
+    // none until there's been a window to measure
+    pub fn summary(&self) -> Option<ResourceSummary> {
+        let samples = self.samples.lock().unwrap();
+        let ((first, first_cpu), (last, last_cpu)) = samples.first.zip(samples.last)?;
+        let wall = last.duration_since(first).as_secs_f64();
+        if wall <= 0.0 {
+            return None;
+        }
+        let cpus = thread::available_parallelism().map_or(1, |cpus| cpus.get());
+        let runtime_workers = tokio::runtime::Handle::try_current().map_or(cpus, |runtime| runtime.metrics().num_workers());
+        let cpu_mean = first_cpu.zip(last_cpu).map(|(first, last)| (last - first) / wall * 100.0);
+        let lag_mean = match samples.lags {
+            0 => Duration::ZERO,
+            n => samples.lag_total / n,
+        };
+
+        let mut warnings = Vec::new();
+        // the runtime's workers and the cpus, whichever is fewer, are all it can use
+        let usable = cpus.min(runtime_workers.max(1));
+        if let Some(mean) = cpu_mean.filter(|mean| *mean >= usable as f64 * 100.0 * BUSY_CPU) {
+            warnings.push(format!(
+                "arctic kept {:.0}% of the {} {} it can use busy on average, the load generator itself is likely the bottleneck",
+                mean / usable as f64,
+                usable,
+                if usable == 1 { "cpu" } else { "cpus" }
+            ));
+        }
+        if lag_mean >= STARVED_LAG {
+            warnings.push(format!(
+                "timers fired {:.1}ms late on average, requests were likely held up in arctic before they were sent",
+                lag_mean.as_secs_f64() * 1000.0
+            ));
+        }
+        Some(ResourceSummary {
+            cpus,
+            runtime_workers,
+            cpu_mean_percent: cpu_mean,
+            cpu_peak_percent: samples.cpu_peak.or(cpu_mean),
+            memory_peak_bytes: samples.memory_peak,
+            sockets_peak: samples.sockets_peak,
+            tasks_peak: samples.tasks_peak,
+            scheduling_lag_mean_ms: lag_mean.as_secs_f64() * 1000.0,
+            scheduling_lag_max_ms: samples.lag_max.as_secs_f64() * 1000.0,
+            saturated: !warnings.is_empty(),
+            warnings,
+        })
+    }
+}
+
+// samples into stats.resources until finished
+pub struct Monitor {
+    stats: Arc<LoadTestStats>,
+    task: JoinHandle<()>,
+}
+
+impl Monitor {
+    pub fn start(stats: Arc<LoadTestStats>) -> Self {
+        stats.resources.sample(None);
+        let sampling = stats.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let due = Instant::now() + SAMPLE_INTERVAL;
+                tokio::time::sleep(SAMPLE_INTERVAL).await;
+                sampling.resources.sample(Some(Instant::now().saturating_duration_since(due)));
+            }
+        });
+        Monitor { stats, task }
+    }
+
+    // a last sample for the end of the run
+    pub fn finish(self) {
+        self.task.abort();
+        self.stats.resources.sample(None);
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// user and system time of the whole process, all threads
+#[cfg(target_os = "linux")]
+fn cpu_secs() -> Option<f64> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // the command name in parentheses may hold spaces, the fields follow it
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    // utime and stime, fields 14 and 15 counting the pid as 1
+    let ticks = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+    Some(ticks as f64 / TICKS_PER_SEC)
+}
+
+#[cfg(target_os = "linux")]
+fn memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.trim_start_matches("VmRSS:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(target_os = "linux")]
+fn open_sockets() -> Option<u64> {
+    let entries = std::fs::read_dir("/proc/self/fd").ok()?;
+    let sockets = entries
+        .filter_map(Result::ok)
+        .filter(|entry| std::fs::read_link(entry.path()).is_ok_and(|target| target.to_string_lossy().starts_with("socket:")))
+        .count();
+    Some(sockets as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cpu_secs() -> Option<f64> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn memory_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_sockets() -> Option<u64> {
+    None
+}
@@ -0,0 +1,393 @@
+use reqwest::Url;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::target::{Operation, Target};
+use crate::template::Template;
+
+const METHODS: &[&str] = &["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+// how deep $refs and nested schemas are followed, recursive schemas stop here
+const MAX_DEPTH: usize = 10;
+// numbers without a minimum or maximum are drawn from this span
+const DEFAULT_SPAN: f64 = 1000.0;
+// share of nulls for nullable fields
+const NULL_PROB: f64 = 0.1;
+
+// an operation picked with --operation: its operationId or "METHOD /path",
+// with an optional :weight
+#[derive(Debug, Clone)]
+pub struct Selector {
+    pub name: String,
+    pub weight: f64,
+}
+
+impl Selector {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, weight) = match spec.rsplit_once(':') {
+            Some((name, weight)) if weight.parse::<f64>().is_ok() => (name, weight.parse().unwrap()),
+            _ => (spec, 1.0),
+        };
+        if !(weight > 0.0 && f64::is_finite(weight)) {
+            return Err(format!("--operation {}: the weight must be positive", spec));
+        }
+        Ok(Selector { name: name.trim().to_string(), weight })
+    }
+
+    fn matches(&self, id: &str, method: &str, path: &str) -> bool {
+        match self.name.split_once(' ') {
+            Some((m, p)) => m.eq_ignore_ascii_case(method) && p.trim() == path,
+            None => self.name == id,
+        }
+    }
+}
+
+// one target per selected operation of an openapi 3 or swagger 2 document
+// in yaml or json, all of them when none are selected; path and required
+// query parameters become placeholders and json request bodies templates
+pub fn load(path: &str, base_url: Option<&str>, selected: &[Selector]) -> Result<Vec<Target>, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    // yaml is a superset of json
+    let doc: Value = serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+    if doc.get("openapi").is_none() && doc.get("swagger").is_none() {
+        return Err(format!("{}: not an openapi document, it has no openapi or swagger version", path).into());
+    }
+    let base = match base_url {
+        Some(url) => url.to_string(),
+        None => server_url(&doc).map_err(|e| format!("{}: {}", path, e))?,
+    };
+    Url::parse(&base).map_err(|e| format!("{}: invalid base url {}: {}", path, base, e))?;
+
+    let mut operations = Vec::new();
+    for (route, item) in doc.get("paths").and_then(Value::as_object).into_iter().flatten() {
+        let item = resolve(&doc, item);
+        let shared = item.get("parameters").and_then(Value::as_array).cloned().unwrap_or_default();
+        for method in METHODS {
+            let Some(operation) = item.get(*method) else { continue };
+            let method = method.to_uppercase();
+            let id = match operation.get("operationId").and_then(Value::as_str) {
+                Some(id) => id.to_string(),
+                None => format!("{} {}", method, route),
+            };
+            let own = operation.get("parameters").and_then(Value::as_array).cloned().unwrap_or_default();
+            operations.push((id, method, route.as_str(), operation, parameters(&doc, &shared, &own)));
+        }
+    }
+    if operations.is_empty() {
+        return Err(format!("{}: no operations under paths", path).into());
+    }
+
+    let weights: Vec<Option<f64>> = match selected {
+        [] => vec![Some(1.0); operations.len()],
+        _ => {
+            for selector in selected {
+                if !operations.iter().any(|(id, method, route, ..)| selector.matches(id, method, route)) {
+                    let known: Vec<_> = operations.iter().map(|(id, ..)| id.as_str()).collect();
+                    return Err(format!("{}: no operation {}, the spec has {}", path, selector.name, known.join(", ")).into());
+                }
+            }
+            operations
+                .iter()
+                .map(|(id, method, route, ..)| {
+                    selected.iter().find(|s| s.matches(id, method, route)).map(|s| s.weight)
+                })
+                .collect()
+        }
+    };
+
+    let mut targets = Vec::new();
+    for ((id, method, route, operation, params), weight) in operations.into_iter().zip(weights) {
+        let Some(weight) = weight else { continue };
+        let url = operation_url(&doc, &base, route, &params);
+        let body = request_schema(&doc, operation, &params).map(|schema| template(&doc, &schema, 0));
+        // fail on the operation rather than on a request mid-run
+        if let Some(body) = &body {
+            Template::compile(body).map_err(|e| format!("{}: {}: {}", path, id, e))?;
+        }
+        targets.push(Target {
+            url,
+            weight,
+            method: None,
+            tags: BTreeMap::from([("operation".to_string(), id.clone())]),
+            replay: None,
+            operation: Some(Operation { id, method, body }),
+        });
+    }
+    Ok(targets)
+}
+
+// the first server with its variables at their defaults, or the swagger 2
+// scheme, host and base path
+fn server_url(doc: &Value) -> Result<String, String> {
+    if let Some(server) = doc.pointer("/servers/0") {
+        let mut url = server.get("url").and_then(Value::as_str).unwrap_or_default().to_string();
+        for (name, variable) in server.get("variables").and_then(Value::as_object).into_iter().flatten() {
+            let default = variable.get("default").and_then(Value::as_str).unwrap_or_default();
+            url = url.replace(&format!("{{{}}}", name), default);
+        }
+        if !url.contains("://") {
+            return Err(format!("server url {:?} is relative, give the host with --base-url", url));
+        }
+        return Ok(url.trim_end_matches('/').to_string());
+    }
+    match doc.get("host").and_then(Value::as_str) {
+        Some(host) => {
+            let scheme = doc.pointer("/schemes/0").and_then(Value::as_str).unwrap_or("https");
+            let base_path = doc.get("basePath").and_then(Value::as_str).unwrap_or_default();
+            Ok(format!("{}://{}{}", scheme, host, base_path.trim_end_matches('/')))
+        }
+        None => Err("no servers or host, give the host with --base-url".to_string()),
+    }
+}
+
+// an operation's parameters over its path's, matched by name and location
+fn parameters(doc: &Value, shared: &[Value], own: &[Value]) -> Vec<Value> {
+    let key = |param: &Value| (param.get("name").cloned(), param.get("in").cloned());
+    let own: Vec<Value> = own.iter().map(|param| resolve(doc, param).clone()).collect();
+    let mut params: Vec<Value> = shared
+        .iter()
+        .map(|param| resolve(doc, param).clone())
+        .filter(|param| !own.iter().any(|o| key(o) == key(param)))
+        .collect();
+    params.extend(own);
+    params
+}
+
+// the path with {{...}} placeholders for its parameters, plus the required
+// query parameters; header and cookie parameters are left out
+fn operation_url(doc: &Value, base: &str, route: &str, params: &[Value]) -> String {
+    let mut path = route.to_string();
+    let mut query = Vec::new();
+    for param in params {
+        let Some(name) = param.get("name").and_then(Value::as_str) else { continue };
+        // swagger 2 puts the schema on the parameter itself
+        let schema = resolve(doc, param.get("schema").unwrap_or(param));
+        match param.get("in").and_then(Value::as_str) {
+            Some("path") => path = path.replace(&format!("{{{}}}", name), &placeholder(schema)),
+            Some("query") if param.get("required").and_then(Value::as_bool).unwrap_or(false) => {
+                query.push(format!("{}={}", name, placeholder(schema)))
+            }
+            _ => {}
+        }
+    }
+    match query.is_empty() {
+        true => format!("{}{}", base, path),
+        false => format!("{}{}?{}", base, path, query.join("&")),
+    }
+}
+
+// a url placeholder for a parameter value: its example, one of its enum
+// values or a random one of its type
+fn placeholder(schema: &Value) -> String {
+    if let Some(example) = schema.get("example") {
+        return match example {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array).filter(|values| !values.is_empty()) {
+        let values: Vec<_> = values
+            .iter()
+            .map(|value| value.as_str().map_or(value.to_string(), str::to_string))
+            .collect();
+        return format!("{{{{enum {}}}}}", values.join(" "));
+    }
+    let (min, max) = bounds(schema, 1.0, DEFAULT_SPAN);
+    match (kind(schema).0.as_deref(), schema.get("format").and_then(Value::as_str)) {
+        (_, Some("uuid")) => "{{uuid}}".to_string(),
+        (Some("integer"), _) => format!("{{{{int {} {}}}}}", min as i64, max as i64),
+        (Some("number"), _) => format!("{{{{float {} {}}}}}", min, max),
+        _ => "{{string 8}}".to_string(),
+    }
+}
+
+// the json request body schema: the openapi 3 requestBody or the swagger 2
+// body parameter
+fn request_schema(doc: &Value, operation: &Value, params: &[Value]) -> Option<Value> {
+    if let Some(body) = operation.get("requestBody") {
+        let content = resolve(doc, body).get("content")?.as_object()?;
+        let (_, media) = content
+            .iter()
+            .find(|(media_type, _)| media_type.as_str() == "application/json")
+            .or_else(|| content.iter().find(|(media_type, _)| media_type.contains("json")))?;
+        return media.get("schema").cloned();
+    }
+    params
+        .iter()
+        .find(|param| param.get("in").and_then(Value::as_str) == Some("body"))
+        .and_then(|param| param.get("schema").cloned())
+}
+
+// a payload template that draws values the schema allows
+fn template(doc: &Value, schema: &Value, depth: usize) -> Value {
+    if depth > MAX_DEPTH {
+        return Value::Null;
+    }
+    let schema = resolve(doc, schema);
+    let (kind, nullable) = kind(schema);
+    let nullable = nullable || schema.get("nullable").and_then(Value::as_bool).unwrap_or(false);
+    match value_template(doc, schema, kind.as_deref(), depth) {
+        value if nullable => json!({"$nullable": {"prob": NULL_PROB, "value": value}}),
+        value => value,
+    }
+}
+
+fn value_template(doc: &Value, schema: &Value, kind: Option<&str>, depth: usize) -> Value {
+    if let Some(example) = schema.get("example").or_else(|| schema.get("const")) {
+        return json!({"$enum": [example]});
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array).filter(|values| !values.is_empty()) {
+        return json!({"$enum": values});
+    }
+    if let Some(parts) = schema.get("allOf").and_then(Value::as_array) {
+        return template(doc, &merge(doc, parts, depth), depth + 1);
+    }
+    if let Some(first) = ["oneOf", "anyOf"].iter().find_map(|key| schema.get(*key).and_then(|s| s.get(0))) {
+        return template(doc, first, depth + 1);
+    }
+
+    let format = schema.get("format").and_then(Value::as_str);
+    match kind {
+        Some("object") | None if schema.get("properties").is_some() => {
+            let required: Vec<&str> = schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            let mut fields = Map::new();
+            for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+                // a $ref cycle runs out at MAX_DEPTH, leave such fields out
+                let value = template(doc, property, depth + 1);
+                let value = match required.contains(&name.as_str()) {
+                    true => value,
+                    false => json!({"$optional": {"value": value}}),
+                };
+                fields.insert(name.clone(), value);
+            }
+            Value::Object(fields)
+        }
+        Some("object") => json!({}),
+        Some("array") => {
+            let items = schema.get("items").map_or(json!(""), |items| template(doc, items, depth + 1));
+            let mut options = Map::from_iter([("items".to_string(), items)]);
+            for (key, option) in [("minItems", "min"), ("maxItems", "max")] {
+                if let Some(n) = schema.get(key).and_then(Value::as_u64) {
+                    options.insert(option.to_string(), n.into());
+                }
+            }
+            if schema.get("uniqueItems").and_then(Value::as_bool).unwrap_or(false) {
+                options.insert("unique".to_string(), true.into());
+            }
+            json!({"$array": options})
+        }
+        Some("integer") => {
+            let (min, max) = bounds(schema, 0.0, DEFAULT_SPAN);
+            json!({"$int": {"min": min.ceil() as i64, "max": max.floor() as i64}})
+        }
+        Some("number") => {
+            let (min, max) = bounds(schema, 0.0, DEFAULT_SPAN);
+            json!({"$float": {"min": min, "max": max}})
+        }
+        Some("boolean") => json!(true),
+        Some("null") => Value::Null,
+        _ => match format {
+            Some("date-time") => json!({"$date": {}}),
+            Some("date") => json!({"$date": {"format": "%Y-%m-%d"}}),
+            Some("uuid") => json!("{{uuid}}"),
+            Some("email") => json!("{{string 8}}@example.com"),
+            _ => string_template(schema),
+        },
+    }
+}
+
+// a pattern when the generator can draw from it, else the length limits
+fn string_template(schema: &Value) -> Value {
+    if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+        let regex = json!({"$regex": pattern});
+        if Template::compile(&regex).is_ok() {
+            return regex;
+        }
+    }
+    let mut options = Map::new();
+    for (key, option) in [("minLength", "min_len"), ("maxLength", "max_len")] {
+        if let Some(n) = schema.get(key).and_then(Value::as_u64) {
+            options.insert(option.to_string(), n.into());
+        }
+    }
+    match options.is_empty() {
+        true => json!(""),
+        false => json!({"$string": options}),
+    }
+}
+
+// the allOf parts as one object schema
+fn merge(doc: &Value, parts: &[Value], depth: usize) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    let mut merged = Map::new();
+    for part in parts {
+        let part = resolve(doc, part);
+        let part = match part.get("allOf").and_then(Value::as_array) {
+            Some(nested) if depth < MAX_DEPTH => merge(doc, nested, depth + 1),
+            _ => part.clone(),
+        };
+        let Value::Object(part) = part else { continue };
+        for (key, value) in part {
+            match (key.as_str(), value) {
+                ("properties", Value::Object(props)) => properties.extend(props),
+                ("required", Value::Array(names)) => required.extend(names),
+                (_, value) => {
+                    merged.insert(key, value);
+                }
+            }
+        }
+    }
+    merged.insert("type".to_string(), "object".into());
+    merged.insert("properties".to_string(), Value::Object(properties));
+    merged.insert("required".to_string(), Value::Array(required));
+    Value::Object(merged)
+}
+
+// the schema's type and whether null is allowed, openapi 3.1 lists them
+fn kind(schema: &Value) -> (Option<String>, bool) {
+    match schema.get("type") {
+        Some(Value::String(kind)) => (Some(kind.clone()), false),
+        Some(Value::Array(kinds)) => (
+            kinds.iter().filter_map(Value::as_str).find(|kind| *kind != "null").map(str::to_string),
+            kinds.iter().any(|kind| kind == "null"),
+        ),
+        _ => (None, false),
+    }
+}
+
+// minimum and maximum, exclusive ones nudged in, with `span` filling in a
+// missing end
+fn bounds(schema: &Value, default_min: f64, span: f64) -> (f64, f64) {
+    let number = |key: &str| schema.get(key).and_then(Value::as_f64);
+    let exclusive = |key: &str| schema.get(key).and_then(Value::as_bool).unwrap_or(false);
+    let min = number("exclusiveMinimum")
+        .map(|n| n + 1.0)
+        .or_else(|| number("minimum").map(|n| if exclusive("exclusiveMinimum") { n + 1.0 } else { n }));
+    let max = number("exclusiveMaximum")
+        .map(|n| n - 1.0)
+        .or_else(|| number("maximum").map(|n| if exclusive("exclusiveMaximum") { n - 1.0 } else { n }));
+    match (min, max) {
+        (Some(min), Some(max)) if min <= max => (min, max),
+        (Some(min), Some(_)) => (min, min),
+        (Some(min), None) => (min, min + span),
+        (None, Some(max)) => ((max - span).max(default_min).min(max), max),
+        (None, None) => (default_min, span),
+    }
+}
+
+// follows local $refs like #/components/schemas/User, up to MAX_DEPTH
+fn resolve<'a>(doc: &'a Value, mut value: &'a Value) -> &'a Value {
+    for _ in 0..MAX_DEPTH {
+        let Some(reference) = value.get("$ref").and_then(Value::as_str) else { break };
+        let Some(target) = reference.strip_prefix('#').and_then(|pointer| doc.pointer(pointer)) else { break };
+        value = target;
+    }
+    value
+}
@@ -0,0 +1,182 @@
+use chrono::{SecondsFormat, Utc};
+use rand::Rng;
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+use crate::feed::Record;
+use crate::setup::Vars;
+
+const ALNUM: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+const HEX: &[u8] = b"0123456789abcdef";
+
+// text with {{...}} placeholders resolved per request, e.g.
+// /users/{{uuid}}/orders?limit={{int 1 50}}
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    segments: Vec<Segment>,
+}
+
+// what placeholders can refer to besides the rng
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Context<'a> {
+    // of the request, unique across workers and shards of the run
+    pub sequence: u64,
+    pub seed: u64,
+    // the --feed row of this request
+    pub record: Option<Record<'a>>,
+    // what the virtual user's --setup extracted
+    pub user: Option<&'a Vars>,
+    // what --global-setup extracted
+    pub run: Option<&'a Vars>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Uuid,
+    Seq,
+    // the wall clock when the request is rendered
+    NowIso,
+    NowUnix,
+    Int(RangeInclusive<i64>),
+    Float(f64, f64),
+    Chars(usize, &'static [u8]),
+    Enum(Vec<String>),
+    // {{csv.user_id}}
+    Column(String),
+    // {{vu.token}}
+    User(String),
+    // {{run.tenant_id}}
+    Run(String),
+}
+
+impl Pattern {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| format!("unclosed {{{{ in: {}", text))?;
+            segments.push(placeholder(rest[start + 2..start + end].trim())?);
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        Ok(Pattern { segments })
+    }
+
+    // the feed columns the pattern refers to
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|s| match s {
+            Segment::Column(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    // plain text renders without touching the rng, so seeded payload
+    // streams don't shift for endpoints without placeholders
+    pub fn render<R: Rng + ?Sized>(&self, rng: &mut R, context: &Context) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Uuid => push_uuid(&mut out, rng),
+                Segment::Seq => {
+                    let _ = write!(out, "{}", context.sequence);
+                }
+                Segment::NowIso => out.push_str(&Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+                Segment::NowUnix => {
+                    let _ = write!(out, "{}", Utc::now().timestamp());
+                }
+                Segment::Int(range) => {
+                    let _ = write!(out, "{}", rng.gen_range(range.clone()));
+                }
+                Segment::Float(min, max) => {
+                    let value = if min == max { *min } else { rng.gen_range(*min..*max) };
+                    let _ = write!(out, "{}", value);
+                }
+                Segment::Chars(len, charset) => {
+                    for _ in 0..*len {
+                        out.push(charset[rng.gen_range(0..charset.len())] as char);
+                    }
+                }
+                Segment::Enum(values) => out.push_str(&values[rng.gen_range(0..values.len())]),
+                Segment::Column(name) => {
+                    out.push_str(context.record.and_then(|r| r.get(name)).unwrap_or_default())
+                }
+                Segment::User(name) => out.push_str(context.user.and_then(|vars| vars.get(name)).unwrap_or_default()),
+                Segment::Run(name) => out.push_str(context.run.and_then(|vars| vars.get(name)).unwrap_or_default()),
+            }
+        }
+        out
+    }
+}
+
+fn placeholder(spec: &str) -> Result<Segment, String> {
+    let mut words = spec.split_whitespace();
+    let name = words.next().ok_or("empty {{}} placeholder")?;
+    let args: Vec<&str> = words.collect();
+    let number = |i: usize, default: &str| -> Result<f64, String> {
+        args.get(i)
+            .copied()
+            .unwrap_or(default)
+            .parse()
+            .map_err(|_| format!("invalid number in {{{{{}}}}}", spec))
+    };
+
+    match name {
+        "uuid" => Ok(Segment::Uuid),
+        "seq" => Ok(Segment::Seq),
+        "now_iso" => Ok(Segment::NowIso),
+        "now_unix" => Ok(Segment::NowUnix),
+        "int" => {
+            let integer = |i: usize, default: i64| match args.get(i) {
+                Some(arg) => arg.parse().map_err(|_| format!("invalid integer in {{{{{}}}}}", spec)),
+                None => Ok(default),
+            };
+            let (min, max) = (integer(0, 0)?, integer(1, i64::MAX)?);
+            if min > max {
+                return Err(format!("min above max in {{{{{}}}}}", spec));
+            }
+            Ok(Segment::Int(min..=max))
+        }
+        "float" => {
+            let (min, max) = (number(0, "0")?, number(1, "1")?);
+            if !min.is_finite() || !max.is_finite() || min > max {
+                return Err(format!("invalid range in {{{{{}}}}}", spec));
+            }
+            Ok(Segment::Float(min, max))
+        }
+        "string" => Ok(Segment::Chars(number(0, "12")? as usize, ALNUM)),
+        "hex" => Ok(Segment::Chars(number(0, "16")? as usize, HEX)),
+        "enum" if !args.is_empty() => Ok(Segment::Enum(args.iter().map(|a| a.to_string()).collect())),
+        "enum" => Err("{{enum}} needs at least one value".to_string()),
+        other => match other.split_once('.') {
+            Some(("csv", column)) if !column.is_empty() && args.is_empty() => Ok(Segment::Column(column.to_string())),
+            Some(("vu", var)) if !var.is_empty() && args.is_empty() => Ok(Segment::User(var.to_string())),
+            Some(("run", var)) if !var.is_empty() && args.is_empty() => Ok(Segment::Run(var.to_string())),
+            _ => Err(format!(
+                "unknown placeholder {{{{{}}}}}, expected uuid, seq, now_iso, now_unix, int, float, string, hex, enum, csv.<column>, vu.<name> or run.<name>",
+                other
+            )),
+        },
+    }
+}
+
+// random (version 4) uuid
+fn push_uuid<R: Rng + ?Sized>(out: &mut String, rng: &mut R) {
+    let mut bytes: [u8; 16] = rng.gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    for (i, byte) in bytes.iter().enumerate() {
+        if matches!(i, 4 | 6 | 8 | 10) {
+            out.push('-');
+        }
+        let _ = write!(out, "{:02x}", byte);
+    }
+}
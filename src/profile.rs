@@ -0,0 +1,257 @@
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::units::{parse_duration, serialize_secs};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StageUnit {
+    Concurrency,
+    Rps,
+}
+
+impl FromStr for StageUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "concurrency" | "vus" => Ok(StageUnit::Concurrency),
+            "rps" | "rate" => Ok(StageUnit::Rps),
+            other => Err(format!("unknown stage unit: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stage {
+    #[serde(rename = "duration_secs", serialize_with = "serialize_secs")]
+    pub duration: Duration,
+    pub target: f64,
+}
+
+// how much load to apply at any point of the run: either a number of active
+// workers or a request rate, linearly ramped between stage targets
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadProfile {
+    pub unit: StageUnit,
+    pub stages: Vec<Stage>,
+}
+
+impl LoadProfile {
+    pub fn constant(unit: StageUnit, target: f64) -> Self {
+        LoadProfile {
+            unit,
+            stages: vec![Stage { duration: Duration::ZERO, target }],
+        }
+    }
+
+    // "30s:10,2m:100,30s:0", each stage ramps from the previous target
+    pub fn parse(spec: &str, unit: StageUnit) -> Result<Self, String> {
+        let stages = spec
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|stage| {
+                let (duration, target) = stage
+                    .trim()
+                    .split_once(':')
+                    .ok_or_else(|| format!("expected <duration>:<target>, got: {}", stage))?;
+                let target: f64 = target
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid stage target: {}", target))?;
+                if target < 0.0 {
+                    return Err(format!("stage target must not be negative: {}", target));
+                }
+                Ok(Stage { duration: parse_duration(duration)?, target })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if stages.is_empty() {
+            return Err("no stages given".to_string());
+        }
+        Ok(LoadProfile { unit, stages })
+    }
+
+    // the part of the load one of `count` distributed workers applies: the
+    // rate is split evenly, worker counts are spread as whole workers
+    pub fn share(&self, index: usize, count: usize) -> Self {
+        let stages = self
+            .stages
+            .iter()
+            .map(|stage| {
+                let target = match self.unit {
+                    StageUnit::Rps => stage.target / count as f64,
+                    StageUnit::Concurrency => {
+                        let total = stage.target.round() as usize;
+                        ((total + count - 1 - index) / count) as f64
+                    }
+                };
+                Stage { duration: stage.duration, target }
+            })
+            .collect();
+        LoadProfile { unit: self.unit, stages }
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.stages.iter().map(|s| s.duration).sum()
+    }
+
+    pub fn peak(&self) -> f64 {
+        self.stages.iter().map(|s| s.target).fold(0.0, f64::max)
+    }
+
+    // offset at which the n-th request is due, found by inverting the
+    // integral of the (piecewise linear) rate; None if it is never due
+    pub fn offset_of_request(&self, n: f64) -> Option<Duration> {
+        let mut remaining = n;
+        let mut from = 0.0;
+        let mut stage_start = Duration::ZERO;
+        for stage in &self.stages {
+            let d = stage.duration.as_secs_f64();
+            let area = (from + stage.target) / 2.0 * d;
+            if area > 0.0 && remaining <= area {
+                let slope = (stage.target - from) / d;
+                let tau = if slope.abs() < f64::EPSILON {
+                    remaining / from
+                } else {
+                    (-from + (from * from + 2.0 * slope * remaining).max(0.0).sqrt()) / slope
+                };
+                return Some(stage_start + Duration::from_secs_f64(tau.clamp(0.0, d)));
+            }
+            remaining -= area;
+            from = stage.target;
+            stage_start += stage.duration;
+        }
+
+        match from > 0.0 {
+            true => Some(stage_start + Duration::from_secs_f64(remaining / from)),
+            false => None,
+        }
+    }
+
+    // index of the stage `elapsed` falls in, the last one once they're over
+    pub fn stage_at(&self, elapsed: Duration) -> usize {
+        let mut stage_end = Duration::ZERO;
+        for (index, stage) in self.stages.iter().enumerate() {
+            stage_end += stage.duration;
+            if elapsed < stage_end {
+                return index;
+            }
+        }
+        self.stages.len().saturating_sub(1)
+    }
+
+    // after the last stage the final target is held
+    pub fn target_at(&self, elapsed: Duration) -> f64 {
+        let mut from = 0.0;
+        let mut stage_start = Duration::ZERO;
+        for stage in &self.stages {
+            let stage_end = stage_start + stage.duration;
+            if elapsed < stage_end {
+                let progress = (elapsed - stage_start).as_secs_f64() / stage.duration.as_secs_f64();
+                return from + (stage.target - from) * progress;
+            }
+            from = stage.target;
+            stage_start = stage_end;
+        }
+        from
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetKind {
+    // a jump from a tenth of the target to the target and back
+    Spike,
+    // a short ramp, then the target held for the whole duration
+    Soak,
+}
+
+// --profile, "spike:1000rps:10s" or "soak:50rps:4h"; a target without
+// "rps" is a number of workers
+#[derive(Debug, Clone, Copy)]
+pub struct Preset {
+    pub kind: PresetKind,
+    pub unit: StageUnit,
+    pub target: f64,
+    pub duration: Duration,
+}
+
+// how long the spike preset sits at its baseline on either side
+const SPIKE_BASELINE: Duration = Duration::from_secs(30);
+const SPIKE_RAMP: Duration = Duration::from_secs(1);
+const SOAK_RAMP: Duration = Duration::from_secs(60);
+
+impl Preset {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.trim().splitn(3, ':');
+        let (Some(kind), Some(target), Some(duration)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(format!("expected <spike|soak>:<target>:<duration>, got: {}", spec));
+        };
+        let kind = match kind {
+            "spike" => PresetKind::Spike,
+            "soak" => PresetKind::Soak,
+            other => return Err(format!("unknown profile: {}, expected spike or soak", other)),
+        };
+        let (target, unit) = match target.strip_suffix("rps") {
+            Some(rate) => (rate, StageUnit::Rps),
+            None => (target.strip_suffix("vus").unwrap_or(target), StageUnit::Concurrency),
+        };
+        let target: f64 = target.parse().map_err(|_| format!("invalid profile target: {}", target))?;
+        if target <= 0.0 {
+            return Err(format!("profile target must be positive: {}", target));
+        }
+        Ok(Preset { kind, unit, target, duration: parse_duration(duration)? })
+    }
+
+    // the preset as a --stages spec
+    pub fn stages(&self) -> String {
+        let stage = |duration: Duration, target: f64| format!("{}ms:{}", duration.as_millis(), target);
+        match self.kind {
+            PresetKind::Spike => {
+                let baseline = match self.unit {
+                    StageUnit::Rps => self.target / 10.0,
+                    StageUnit::Concurrency => (self.target / 10.0).round().max(1.0),
+                };
+                let hold = SPIKE_BASELINE - SPIKE_RAMP;
+                [
+                    stage(SPIKE_RAMP, baseline),
+                    stage(hold, baseline),
+                    stage(SPIKE_RAMP, self.target),
+                    stage(self.duration.saturating_sub(SPIKE_RAMP), self.target),
+                    stage(SPIKE_RAMP, baseline),
+                    stage(hold, baseline),
+                ]
+                .join(",")
+            }
+            PresetKind::Soak => {
+                let ramp = SOAK_RAMP.min(self.duration / 10);
+                [stage(ramp, self.target), stage(self.duration - ramp, self.target)].join(",")
+            }
+        }
+    }
+}
+
+// hands out send slots following a rate profile, shared by all workers
+#[derive(Debug)]
+pub struct Pacer {
+    start: Instant,
+    issued: AtomicU64,
+}
+
+impl Pacer {
+    pub fn new(start: Instant) -> Self {
+        Pacer {
+            start,
+            issued: AtomicU64::new(0),
+        }
+    }
+
+    pub fn next_slot(&self, profile: &LoadProfile) -> Option<Instant> {
+        let n = self.issued.fetch_add(1, Ordering::SeqCst);
+        profile
+            .offset_of_request(n as f64)
+            .map(|offset| self.start + offset)
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt::Write as _;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::stats::LoadTestStats;
+
+const BUCKETS_SECS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub async fn serve(port: u16, stats: Arc<LoadTestStats>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let stats = stats.clone();
+            tokio::spawn(async move {
+                let _ = respond(stream, &stats).await;
+            });
+        }
+    });
+    Ok(())
+}
+
+async fn respond(mut stream: TcpStream, stats: &LoadTestStats) -> std::io::Result<()> {
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status, content_type, body) = if path == "/metrics" {
+        ("200 OK", "text/plain; version=0.0.4", render(stats))
+    } else {
+        ("404 Not Found", "text/plain", "not found\n".to_string())
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+pub fn render(stats: &LoadTestStats) -> String {
+    let mut out = String::new();
+    let counter = |out: &mut String, name: &str, help: &str, value: u64| {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter\n{} {}", name, help, name, name, value);
+    };
+
+    counter(&mut out, "arctic_requests_total", "Requests completed.", stats.total_requests.load(Ordering::SeqCst));
+    counter(&mut out, "arctic_errors_total", "Requests that failed.", stats.error_count.load(Ordering::SeqCst));
+    counter(&mut out, "arctic_retries_total", "Retried attempts.", stats.retries.load(Ordering::SeqCst));
+    counter(
+        &mut out,
+        "arctic_graphql_errors_total",
+        "Entries in graphql response errors arrays.",
+        stats.graphql_errors.load(Ordering::SeqCst),
+    );
+    counter(&mut out, "arctic_checks_passed_total", "Assertions that passed.", stats.checks_passed.load(Ordering::SeqCst));
+    counter(&mut out, "arctic_checks_failed_total", "Assertions that failed.", stats.checks_failed.load(Ordering::SeqCst));
+
+    let _ = writeln!(out, "# HELP arctic_responses_total Responses by status code.\n# TYPE arctic_responses_total counter");
+    for (code, count) in stats.status_counts() {
+        let _ = writeln!(out, "arctic_responses_total{{code=\"{}\"}} {}", code, count);
+    }
+
+    let _ = writeln!(out, "# HELP arctic_grpc_responses_total Responses by grpc-status.\n# TYPE arctic_grpc_responses_total counter");
+    for (code, count) in stats.grpc_status_counts() {
+        let _ = writeln!(out, "arctic_grpc_responses_total{{code=\"{}\"}} {}", code, count);
+    }
+
+    let _ = writeln!(out, "# HELP arctic_transport_errors_total Requests that got no response.\n# TYPE arctic_transport_errors_total counter");
+    for (kind, count) in stats.transport_error_counts() {
+        let _ = writeln!(out, "arctic_transport_errors_total{{kind=\"{}\"}} {}", kind, count);
+    }
+
+    let latency = &stats.latency;
+    let _ = writeln!(out, "# HELP arctic_response_seconds Response time.\n# TYPE arctic_response_seconds histogram");
+    for le in BUCKETS_SECS {
+        let count = latency.count_le((le * 1_000_000.0) as u64);
+        let _ = writeln!(out, "arctic_response_seconds_bucket{{le=\"{}\"}} {}", le, count);
+    }
+    let _ = writeln!(out, "arctic_response_seconds_bucket{{le=\"+Inf\"}} {}", latency.count());
+    let _ = writeln!(out, "arctic_response_seconds_sum {}", latency.sum() as f64 / 1_000_000.0);
+    let _ = writeln!(out, "arctic_response_seconds_count {}", latency.count());
+
+    out
+}
@@ -0,0 +1,104 @@
+use arctic::runner::Probe;
+use arctic::setup::Vars;
+use arctic::{Generator, LoadConfig};
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+commands:
+  next [n]    show the next payload, or the next n
+  show <seq>  show the payload of request <seq>
+  send        send the payload shown last and print the exchange
+  run         start the full run with the flags arctic repl was given
+  quit        leave
+the templates are read again before each payload, edit them in between";
+
+// how the session ended
+pub enum Exit {
+    Quit,
+    // with what the --global-setup extracted, the run doesn't do it again
+    Run(Vars),
+}
+
+// arctic repl: payloads and single requests on demand, until quit or run;
+// the --teardown runs on the way out unless the run is still to come
+pub async fn run(config: LoadConfig, generator: Generator) -> Result<Exit, Box<dyn std::error::Error>> {
+    let paths: Vec<String> = config.data.iter().map(|spec| spec.path.clone()).collect();
+    let probe = Probe::start(config, generator).await?;
+    let exit = session(&probe, &paths).await;
+    if !matches!(exit, Ok(Exit::Run(_))) {
+        probe.finish().await;
+    }
+    exit
+}
+
+async fn session(probe: &Probe, paths: &[String]) -> Result<Exit, Box<dyn std::error::Error>> {
+    println!("{}", HELP);
+    // the request the payload shown last belongs to
+    let mut shown: Option<u64> = None;
+    let stdin = io::stdin();
+    loop {
+        print!("arctic> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            println!();
+            return Ok(Exit::Quit);
+        }
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else { continue };
+        let arg = words.next();
+        match command {
+            "next" | "n" => {
+                let count = match arg.map(str::parse::<u64>) {
+                    None => 1,
+                    Some(Ok(count)) if count > 0 => count,
+                    _ => {
+                        println!("next takes how many payloads to show, e.g. next 3");
+                        continue;
+                    }
+                };
+                reload(probe, paths);
+                for _ in 0..count {
+                    let sequence = shown.map_or(0, |shown| shown + 1);
+                    shown = Some(sequence);
+                    print_preview(probe, sequence)?;
+                }
+            }
+            "show" => match arg.map(str::parse::<u64>) {
+                Some(Ok(sequence)) => {
+                    reload(probe, paths);
+                    shown = Some(sequence);
+                    print_preview(probe, sequence)?;
+                }
+                _ => println!("show takes the number of a request, e.g. show 12"),
+            },
+            "send" | "s" => {
+                reload(probe, paths);
+                let sequence = *shown.get_or_insert(0);
+                match probe.send(sequence).await {
+                    Ok(exchange) => print!("{}", exchange),
+                    Err(e) => println!("request {} failed: {}", sequence, e),
+                }
+            }
+            "run" => return Ok(Exit::Run(probe.run_vars())),
+            "help" | "?" => println!("{}", HELP),
+            "quit" | "exit" | "q" => return Ok(Exit::Quit),
+            other => println!("unknown command {}, try help", other),
+        }
+    }
+}
+
+// a template that no longer compiles keeps the one from before
+fn reload(probe: &Probe, paths: &[String]) {
+    for path in paths {
+        if let Err(e) = probe.generator().reload(path) {
+            println!("{}, keeping the template from before", e);
+        }
+    }
+}
+
+fn print_preview(probe: &Probe, sequence: u64) -> Result<(), Box<dyn std::error::Error>> {
+    println!("# request {}", sequence);
+    println!("{}", serde_json::to_string_pretty(&probe.preview(sequence))?);
+    Ok(())
+}
@@ -0,0 +1,541 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+
+use crate::adaptive::AdaptiveSummary;
+use crate::config::LoadConfig;
+use crate::metadata::RunMetadata;
+use crate::metrics::MetricSummary;
+use crate::monitor::ResourceSummary;
+use crate::failure::ErrorBucket;
+use crate::histogram::Histogram;
+use crate::stats::{megabytes_per_sec, GroupStats, LoadTestStats, StatsSnapshot, StopReason};
+use crate::threshold::ThresholdResult;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &str) -> Self {
+        if path.to_ascii_lowercase().ends_with(".csv") {
+            OutputFormat::Csv
+        } else {
+            OutputFormat::Json
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            other => Err(format!("unknown output format: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencySummary {
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl LatencySummary {
+    pub fn new(latency: &Histogram) -> Self {
+        let ms = |us: u64| us as f64 / 1000.0;
+        LatencySummary {
+            min: ms(latency.min()),
+            mean: latency.mean() / 1000.0,
+            p50: ms(latency.quantile(0.50)),
+            p90: ms(latency.quantile(0.90)),
+            p95: ms(latency.quantile(0.95)),
+            p99: ms(latency.quantile(0.99)),
+            max: ms(latency.max()),
+        }
+    }
+}
+
+// response body sizes in bytes
+#[derive(Debug, Serialize)]
+pub struct SizeSummary {
+    pub min: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p99: u64,
+    pub max: u64,
+    pub total: u64,
+}
+
+impl SizeSummary {
+    // none when no response was read
+    pub fn new(sizes: &Histogram) -> Option<Self> {
+        (sizes.count() > 0).then(|| SizeSummary {
+            min: sizes.min(),
+            mean: sizes.mean(),
+            p50: sizes.quantile(0.50),
+            p99: sizes.quantile(0.99),
+            max: sizes.max(),
+            total: sizes.sum(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupSummary {
+    pub name: String,
+    pub requests: u64,
+    pub errors: u64,
+    pub timeouts: u64,
+    pub latency_ms: LatencySummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_size_bytes: Option<SizeSummary>,
+    pub status_codes: BTreeMap<u16, u64>,
+}
+
+impl GroupSummary {
+    pub fn new(group: &GroupStats) -> Self {
+        GroupSummary {
+            name: group.name.clone(),
+            requests: group.requests.load(Ordering::SeqCst),
+            errors: group.errors.load(Ordering::SeqCst),
+            timeouts: group.timeouts.load(Ordering::SeqCst),
+            latency_ms: LatencySummary::new(&group.latency),
+            response_size_bytes: SizeSummary::new(&group.response_size),
+            status_codes: group.status_counts(),
+        }
+    }
+}
+
+// one --timing phase; dns, connect and tls count probe connections
+#[derive(Debug, Serialize)]
+pub struct PhaseSummary {
+    pub phase: &'static str,
+    pub samples: u64,
+    pub latency_ms: LatencySummary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompressionSummary {
+    pub encoding: String,
+    pub uncompressed_bytes: u64,
+    pub compressed_bytes: u64,
+    // compressed size as a fraction of the original
+    pub ratio: f64,
+}
+
+// responses over a connection opened for them and over a reused one
+#[derive(Debug, Serialize)]
+pub struct ConnectionSummary {
+    pub opened: u64,
+    pub reused: u64,
+}
+
+// full handshakes of the load, and the sessions the --timing probes offered
+// and had resumed; the latencies of both are the tls and tls_resumed phases
+#[derive(Debug, Serialize)]
+pub struct TlsSummary {
+    pub handshakes: u64,
+    pub probe_sessions_offered: u64,
+    pub probe_sessions_resumed: u64,
+}
+
+// --dns-refresh lookups, see LoadTestStats
+#[derive(Debug, Serialize)]
+pub struct DnsSummary {
+    pub refresh_secs: f64,
+    pub lookups: u64,
+    pub changes: u64,
+}
+
+// the requests in flight when the run ended, see LoadTestStats
+#[derive(Debug, Serialize)]
+pub struct DrainSummary {
+    pub in_flight: u64,
+    pub finished: u64,
+    pub drain_secs: f64,
+    pub timeout_secs: f64,
+}
+
+// with --shard, which one of how many this run was and its raw counters,
+// what `arctic merge` adds up; the percentiles of the shards can't be
+// averaged, their histograms can
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShardSummary {
+    // counting from 1, the way --shard takes it
+    pub index: u64,
+    pub count: u64,
+    pub stats: StatsSnapshot,
+}
+
+// bodies that didn't match the --validate-payloads schema
+#[derive(Debug, Serialize)]
+pub struct InvalidPayloads {
+    pub invalid: u64,
+    pub fixed: u64,
+    pub skipped: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first: Option<String>,
+}
+
+// attempts that were redirected and the hops they took
+#[derive(Debug, Serialize)]
+pub struct RedirectSummary {
+    pub redirected: u64,
+    pub hops: u64,
+    pub mean_hops: f64,
+    pub max_hops: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckSummary {
+    pub name: String,
+    pub passed: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub duration_secs: f64,
+    pub stop_reason: Option<StopReason>,
+    pub total_requests: u64,
+    pub successful_requests: u64,
+    pub failed_requests: u64,
+    // of the failed ones, those that ran out of --timeout; they're left
+    // out of latency_ms
+    pub timeouts: u64,
+    // in flight when the run ended and still going after --drain-timeout,
+    // counted neither as successful nor as failed
+    pub abandoned_requests: u64,
+    pub retries: u64,
+    // --setup requests, see LoadTestStats
+    pub setups: u64,
+    pub setup_failures: u64,
+    // see LoadTestStats
+    pub throttled: u64,
+    pub throttle_backoff_secs: f64,
+    pub graphql_errors: u64,
+    // see LoadTestStats
+    pub cache_hits: u64,
+    // with --validate-payloads, see LoadTestStats
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invalid_payloads: Option<InvalidPayloads>,
+    pub requests_per_second: f64,
+    // request and response body bytes, and their rates in MB/s
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub sent_mb_per_sec: f64,
+    pub received_mb_per_sec: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_size_bytes: Option<SizeSummary>,
+    // with --compress
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connections: Option<ConnectionSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<DnsSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drain: Option<DrainSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirects: Option<RedirectSummary>,
+    // with a rate this is response time, from the scheduled send
+    pub latency_ms: LatencySummary,
+    // from the actual send, only with a rate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_time_ms: Option<LatencySummary>,
+    // pauses between a virtual user's requests, with --think-time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub think_time_ms: Option<LatencySummary>,
+    pub status_codes: BTreeMap<u16, u64>,
+    pub status_classes: BTreeMap<String, u64>,
+    pub transport_errors: BTreeMap<String, u64>,
+    pub grpc_status: BTreeMap<String, u64>,
+    // failed requests by cause, most frequent first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ErrorBucket>,
+    // set when traffic went through --proxy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    // responses per negotiated http version
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub protocols: BTreeMap<String, u64>,
+    pub endpoints: Vec<GroupSummary>,
+    // per endpoint tag and per stage
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<GroupSummary>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<GroupSummary>,
+    // the requests --chaos broke, left out of every other figure
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chaos: Vec<GroupSummary>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub phases: Vec<PhaseSummary>,
+    pub checks_passed: u64,
+    pub checks_failed: u64,
+    pub checks: Vec<CheckSummary>,
+    // --metric values by name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub response_metrics: BTreeMap<String, MetricSummary>,
+    pub thresholds: Vec<ThresholdResult>,
+    // with --adaptive, the concurrency second by second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adaptive: Option<AdaptiveSummary>,
+    // arctic's own cpu, memory and so on during the run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_generator: Option<ResourceSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shard: Option<ShardSummary>,
+    pub metadata: RunMetadata,
+    pub config: LoadConfig,
+}
+
+impl Report {
+    pub fn new(stats: &LoadTestStats, config: &LoadConfig) -> Self {
+        let total = stats.total_requests.load(Ordering::SeqCst);
+        let duration_secs = stats.start_time().elapsed().as_secs_f64();
+        let bytes_sent = stats.bytes_sent.load(Ordering::SeqCst);
+        let bytes_received = stats.bytes_received.load(Ordering::SeqCst);
+
+        let mut report = Report {
+            duration_secs,
+            stop_reason: stats.stop_reason(),
+            total_requests: total,
+            successful_requests: stats.success_count.load(Ordering::SeqCst),
+            failed_requests: stats.error_count.load(Ordering::SeqCst),
+            timeouts: stats.timeouts.load(Ordering::SeqCst),
+            abandoned_requests: stats.abandoned.load(Ordering::SeqCst),
+            retries: stats.retries.load(Ordering::SeqCst),
+            setups: stats.setups.load(Ordering::SeqCst),
+            setup_failures: stats.setup_failures.load(Ordering::SeqCst),
+            throttled: stats.throttled.load(Ordering::SeqCst),
+            throttle_backoff_secs: stats.throttle_backoff_us.load(Ordering::SeqCst) as f64 / 1_000_000.0,
+            graphql_errors: stats.graphql_errors.load(Ordering::SeqCst),
+            cache_hits: stats.cache_hits.load(Ordering::SeqCst),
+            invalid_payloads: config.payload_schema.as_ref().map(|_| {
+                let invalid = stats.invalid_payloads.load(Ordering::SeqCst);
+                let fixed = stats.fixed_payloads.load(Ordering::SeqCst);
+                InvalidPayloads { invalid, fixed, skipped: invalid - fixed, first: stats.invalid_payload.get().cloned() }
+            }),
+            requests_per_second: total as f64 / duration_secs,
+            bytes_sent,
+            bytes_received,
+            sent_mb_per_sec: megabytes_per_sec(bytes_sent, duration_secs),
+            received_mb_per_sec: megabytes_per_sec(bytes_received, duration_secs),
+            response_size_bytes: SizeSummary::new(&stats.response_size),
+            compression: config.compress.map(|encoding| {
+                let uncompressed_bytes = stats.uncompressed_bytes.load(Ordering::SeqCst);
+                let compressed_bytes = stats.compressed_bytes.load(Ordering::SeqCst);
+                CompressionSummary {
+                    encoding: encoding.to_string(),
+                    uncompressed_bytes,
+                    compressed_bytes,
+                    ratio: match uncompressed_bytes {
+                        0 => 0.0,
+                        n => compressed_bytes as f64 / n as f64,
+                    },
+                }
+            }),
+            connections: {
+                let opened = stats.connections_opened.load(Ordering::SeqCst);
+                let reused = stats.connections_reused.load(Ordering::SeqCst);
+                (opened + reused > 0).then_some(ConnectionSummary { opened, reused })
+            },
+            tls: {
+                let handshakes = stats.tls_handshakes.load(Ordering::SeqCst);
+                let probe_sessions_offered = stats.probe_resumption_offered.load(Ordering::SeqCst);
+                (handshakes + probe_sessions_offered > 0).then(|| TlsSummary {
+                    handshakes,
+                    probe_sessions_offered,
+                    probe_sessions_resumed: stats.phases.tls_resumed.count(),
+                })
+            },
+            dns: config.dns_refresh.map(|refresh| DnsSummary {
+                refresh_secs: refresh.as_secs_f64(),
+                lookups: stats.dns_lookups.load(Ordering::SeqCst),
+                changes: stats.dns_changes.load(Ordering::SeqCst),
+            }),
+            drain: {
+                let (drained, abandoned) = (stats.drained.load(Ordering::SeqCst), stats.abandoned.load(Ordering::SeqCst));
+                (drained + abandoned > 0).then(|| DrainSummary {
+                    in_flight: drained + abandoned,
+                    finished: drained,
+                    drain_secs: stats.drain_us.load(Ordering::SeqCst) as f64 / 1_000_000.0,
+                    timeout_secs: config.drain_timeout.as_secs_f64(),
+                })
+            },
+            redirects: (stats.redirects.count() > 0).then(|| {
+                let redirects = &stats.redirects;
+                RedirectSummary {
+                    redirected: redirects.count(),
+                    hops: (redirects.mean() * redirects.count() as f64).round() as u64,
+                    mean_hops: redirects.mean(),
+                    max_hops: redirects.max(),
+                }
+            }),
+            latency_ms: LatencySummary::new(&stats.latency),
+            service_time_ms: (stats.service_time.count() > 0).then(|| LatencySummary::new(&stats.service_time)),
+            think_time_ms: (stats.think_time.count() > 0).then(|| LatencySummary::new(&stats.think_time)),
+            status_codes: stats.status_counts(),
+            status_classes: stats.status_class_counts(),
+            transport_errors: stats.transport_error_counts(),
+            grpc_status: stats.grpc_status_counts(),
+            errors: stats.errors.top(usize::MAX),
+            proxy: config.proxy.as_ref().map(|proxy| proxy.to_string()),
+            protocols: stats.protocol_counts(),
+            endpoints: stats.endpoints.iter().map(GroupSummary::new).collect(),
+            tags: stats.tags.iter().map(GroupSummary::new).collect(),
+            templates: stats.templates.iter().map(GroupSummary::new).collect(),
+            chaos: stats.chaos.iter().map(GroupSummary::new).collect(),
+            phases: stats
+                .phases
+                .all()
+                .into_iter()
+                .filter(|(_, phase)| phase.count() > 0)
+                .map(|(phase, latency)| PhaseSummary {
+                    phase,
+                    samples: latency.count(),
+                    latency_ms: LatencySummary::new(latency),
+                })
+                .collect(),
+            checks_passed: stats.checks_passed.load(Ordering::SeqCst),
+            checks_failed: stats.checks_failed.load(Ordering::SeqCst),
+            checks: stats
+                .checks
+                .iter()
+                .map(|check| CheckSummary {
+                    name: check.assertion.to_string(),
+                    passed: check.passed.load(Ordering::SeqCst),
+                    failed: check.failed.load(Ordering::SeqCst),
+                })
+                .collect(),
+            response_metrics: stats.metrics.summaries(),
+            thresholds: Vec::new(),
+            adaptive: stats.concurrency.summary(),
+            load_generator: stats.resources.summary(),
+            shard: config.shard.map(|shard| ShardSummary { index: shard.index + 1, count: shard.count, stats: stats.snapshot() }),
+            metadata: RunMetadata::capture(config, duration_secs),
+            config: config.clone(),
+        };
+        report.thresholds = config.thresholds.iter().map(|t| t.evaluate(&report)).collect();
+        report
+    }
+
+    pub fn thresholds_passed(&self) -> bool {
+        self.thresholds.iter().all(|t| t.passed)
+    }
+
+    pub fn write(&self, path: &str, format: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
+        let contents = match format {
+            OutputFormat::Json => serde_json::to_string_pretty(self)?,
+            OutputFormat::Csv => self.to_csv(),
+        };
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    // one header row and one value row, so several runs can be concatenated
+    fn to_csv(&self) -> String {
+        let l = &self.latency_ms;
+        let mut columns: Vec<(String, String)> = vec![
+            ("endpoint".into(), self.config.endpoint_urls().join(" ")),
+            ("duration_secs".into(), format!("{:.3}", self.duration_secs)),
+            ("total_requests".into(), self.total_requests.to_string()),
+            ("successful_requests".into(), self.successful_requests.to_string()),
+            ("failed_requests".into(), self.failed_requests.to_string()),
+            ("timeouts".into(), self.timeouts.to_string()),
+            ("abandoned_requests".into(), self.abandoned_requests.to_string()),
+            ("retries".into(), self.retries.to_string()),
+            ("setups".into(), self.setups.to_string()),
+            ("setup_failures".into(), self.setup_failures.to_string()),
+            ("throttled".into(), self.throttled.to_string()),
+            ("throttle_backoff_secs".into(), format!("{:.3}", self.throttle_backoff_secs)),
+            ("graphql_errors".into(), self.graphql_errors.to_string()),
+            ("cache_hits".into(), self.cache_hits.to_string()),
+            ("tls_handshakes".into(), self.tls.as_ref().map(|t| t.handshakes.to_string()).unwrap_or_default()),
+            ("tls_probe_sessions_resumed".into(), self.tls.as_ref().map(|t| t.probe_sessions_resumed.to_string()).unwrap_or_default()),
+            ("dns_lookups".into(), self.dns.as_ref().map(|d| d.lookups.to_string()).unwrap_or_default()),
+            ("dns_changes".into(), self.dns.as_ref().map(|d| d.changes.to_string()).unwrap_or_default()),
+            ("drain_secs".into(), self.drain.as_ref().map(|d| format!("{:.3}", d.drain_secs)).unwrap_or_default()),
+            ("requests_per_second".into(), format!("{:.3}", self.requests_per_second)),
+            ("bytes_sent".into(), self.bytes_sent.to_string()),
+            ("bytes_received".into(), self.bytes_received.to_string()),
+            ("sent_mb_per_sec".into(), format!("{:.3}", self.sent_mb_per_sec)),
+            ("received_mb_per_sec".into(), format!("{:.3}", self.received_mb_per_sec)),
+            ("response_size_mean_bytes".into(), self.response_size_bytes.as_ref().map(|s| format!("{:.0}", s.mean)).unwrap_or_default()),
+            ("response_size_max_bytes".into(), self.response_size_bytes.as_ref().map(|s| s.max.to_string()).unwrap_or_default()),
+            ("latency_min_ms".into(), format!("{:.3}", l.min)),
+            ("latency_mean_ms".into(), format!("{:.3}", l.mean)),
+            ("latency_p50_ms".into(), format!("{:.3}", l.p50)),
+            ("latency_p90_ms".into(), format!("{:.3}", l.p90)),
+            ("latency_p95_ms".into(), format!("{:.3}", l.p95)),
+            ("latency_p99_ms".into(), format!("{:.3}", l.p99)),
+            ("latency_max_ms".into(), format!("{:.3}", l.max)),
+            ("checks_passed".into(), self.checks_passed.to_string()),
+            ("checks_failed".into(), self.checks_failed.to_string()),
+        ];
+        if let Some(resources) = &self.load_generator {
+            let optional = |value: Option<String>| value.unwrap_or_default();
+            columns.push(("client_cpu_mean_percent".into(), optional(resources.cpu_mean_percent.map(|cpu| format!("{:.1}", cpu)))));
+            columns.push(("client_memory_peak_bytes".into(), optional(resources.memory_peak_bytes.map(|bytes| bytes.to_string()))));
+            columns.push(("client_saturated".into(), resources.saturated.to_string()));
+        }
+        if let Some(adaptive) = &self.adaptive {
+            columns.push(("adaptive_final_concurrency".into(), adaptive.final_concurrency.to_string()));
+            columns.push(("adaptive_mean_concurrency".into(), format!("{:.1}", adaptive.mean_concurrency)));
+            columns.push(("adaptive_capacity_rps".into(), adaptive.capacity_rps.map(|rps| format!("{:.3}", rps)).unwrap_or_default()));
+        }
+        if let Some(payloads) = &self.invalid_payloads {
+            columns.push(("invalid_payloads".into(), payloads.invalid.to_string()));
+            columns.push(("fixed_payloads".into(), payloads.fixed.to_string()));
+        }
+        for (name, metric) in &self.response_metrics {
+            columns.push((format!("metric_{}_min", name), metric.min.to_string()));
+            columns.push((format!("metric_{}_mean", name), format!("{:.3}", metric.mean)));
+            columns.push((format!("metric_{}_max", name), metric.max.to_string()));
+        }
+        if !self.thresholds.is_empty() {
+            let failed = self.thresholds.iter().filter(|t| !t.passed).count();
+            columns.push(("thresholds_failed".into(), failed.to_string()));
+        }
+        for (code, count) in &self.status_codes {
+            columns.push((format!("status_{}", code), count.to_string()));
+        }
+        for (code, count) in &self.grpc_status {
+            columns.push((format!("grpc_{}", code.to_ascii_lowercase()), count.to_string()));
+        }
+        for (protocol, count) in &self.protocols {
+            columns.push((format!("protocol_{}", protocol.to_ascii_lowercase().replace(['/', '.'], "")), count.to_string()));
+        }
+        for (kind, count) in &self.transport_errors {
+            columns.push((format!("transport_{}", kind.replace(' ', "_")), count.to_string()));
+        }
+
+        let header: Vec<String> = columns.iter().map(|(k, _)| csv_field(k)).collect();
+        let values: Vec<String> = columns.iter().map(|(_, v)| csv_field(v)).collect();
+        format!("{}\n{}\n", header.join(","), values.join(","))
+    }
+}
+
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,90 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+// entries buffered before workers start waiting on the writer
+const CHANNEL_CAPACITY: usize = 4096;
+
+#[derive(Debug, Serialize)]
+pub struct LogEntry {
+    pub timestamp_ms: u64,
+    pub sequence: u64,
+    pub method: String,
+    pub url: String,
+    pub request_body: Value,
+    pub status: Option<u16>,
+    pub latency_ms: f64,
+    // only with a rate, where latency_ms counts from the scheduled send time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_time_ms: Option<f64>,
+    // with --traceparent, to find the request in the server's traces
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    // the values the --header templates got, ids to look for in server logs
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    // what --chaos did to the request, which is then in no totals
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chaos: Option<String>,
+    pub error: Option<String>,
+    pub response_body: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestLog {
+    tx: mpsc::Sender<LogEntry>,
+    body_limit: usize,
+}
+
+impl RequestLog {
+    // the writer task finishes once every RequestLog clone has been dropped
+    pub async fn create(
+        path: &str,
+        body_limit: usize,
+    ) -> std::io::Result<(Self, JoinHandle<std::io::Result<()>>)> {
+        let file = File::create(path).await?;
+        let (tx, mut rx) = mpsc::channel::<LogEntry>(CHANNEL_CAPACITY);
+
+        let writer = tokio::spawn(async move {
+            let mut out = BufWriter::new(file);
+            while let Some(entry) = rx.recv().await {
+                let mut line = serde_json::to_vec(&entry).map_err(std::io::Error::other)?;
+                line.push(b'\n');
+                out.write_all(&line).await?;
+            }
+            out.flush().await
+        });
+
+        Ok((RequestLog { tx, body_limit }, writer))
+    }
+
+    pub async fn log(&self, mut entry: LogEntry) {
+        if let Some(body) = entry.response_body.as_mut() {
+            truncate(body, self.body_limit);
+        }
+        let _ = self.tx.send(entry).await;
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn truncate(body: &mut String, limit: usize) {
+    if body.len() <= limit {
+        return;
+    }
+    let mut end = limit;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    body.truncate(end);
+}
@@ -0,0 +1,1976 @@
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore};
+use reqwest::cookie::Jar;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AGE, CONTENT_ENCODING, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Client, Error, Method, StatusCode};
+use serde_json::{json, Value};
+use tonic::{Code, Status};
+use tracing::{debug, info, trace, warn};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::task::JoinSet;
+use tokio::time::sleep;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use hyper::client::connect::HttpInfo;
+
+use crate::adaptive::Controller;
+use crate::auth::{Auth, TokenProvider};
+use crate::body::{self, BodyFormat};
+use crate::breaker::Breaker;
+use crate::capture::{self, Capture, Exchange};
+use crate::chaos::{self, ChaosKind};
+use crate::checkpoint::Checkpoints;
+use crate::checks::{Checks, Observed};
+use crate::client::{self, ConnectionTracker};
+use crate::config::LoadConfig;
+use crate::dns::{DnsRefresh, Refresher};
+use crate::failure::{ExpectedStatus, TransportError};
+use crate::generator::Generator;
+use crate::graphql;
+use crate::influx::Influx;
+use crate::input::{self, Input};
+use crate::grpc::{self, GrpcClient};
+use crate::monitor::Monitor;
+use crate::placeholder::Context;
+use crate::profile::{LoadProfile, Pacer, StageUnit};
+use crate::report::Report;
+use crate::requestlog::{now_ms, LogEntry, RequestLog};
+use crate::sample::{Received, Sample, Samples, Sent};
+use crate::schema::{Checked, Schema};
+use crate::script::Prepared;
+use crate::scenario::{Last, Scenario, Step};
+use crate::setup::{Extract, Users, Vars, SETUP_RETRY};
+use crate::stats::{GroupStats, LoadTestStats, StopReason};
+use crate::statsd::Statsd;
+use crate::target::Targets;
+use crate::timing;
+use crate::trace::{Span, SpanExporter, TraceContext};
+use crate::watch;
+
+// how often an idle worker re-checks the load profile
+const IDLE_POLL: Duration = Duration::from_millis(50);
+
+struct Shared {
+    config: LoadConfig,
+    generator: Generator,
+    stats: Arc<LoadTestStats>,
+    // where what --chaos broke counts below the request level, nobody
+    // reads it; the requests themselves count in stats.chaos
+    chaos_stats: LoadTestStats,
+    targets: Targets,
+    client: Client,
+    // with --cookies, one client per worker so every virtual user keeps
+    // its own session, in its own jar
+    sessions: Vec<Client>,
+    jars: Vec<Arc<Jar>>,
+    // with --dns-refresh, the client each worker built when it last saw the
+    // records change, in place of the one it started with; shared with the
+    // warm-up, which sees the same changes
+    rebuilt: Arc<Vec<Mutex<Option<Client>>>>,
+    // with --setup, what each virtual user's setup extracted; shared with
+    // the warm-up so users log in once
+    users: Option<Arc<Users>>,
+    // what --global-setup extracted
+    run_vars: Arc<Vars>,
+    // one per target, set for --grpc runs
+    grpc: Option<Vec<GrpcClient>>,
+    tokens: Option<Arc<TokenProvider>>,
+    connections: Arc<ConnectionTracker>,
+    // with --dns-refresh, what tells the workers the records changed
+    dns: Option<Arc<DnsRefresh>>,
+    pacer: Pacer,
+    // end of the run (or the warm-up) for duration bound runs
+    deadline: Option<Instant>,
+    breaker: Option<Breaker>,
+    sequence: AtomicU64,
+    // the numbers the warm-up took, the measured run's go on from there so
+    // no payload goes out twice, but its --requests count from its first
+    warmed: u64,
+    // with --data -, where the payloads come from; shared with the warm-up
+    input: Option<Arc<Input>>,
+    log: Option<RequestLog>,
+    samples: Option<Samples>,
+    capture: Option<Capture>,
+    spans: Option<SpanExporter>,
+    stop: Arc<AtomicBool>,
+    pause: Arc<Pause>,
+    // --chaos duplicates still waiting to go out again; the ones not sent by
+    // the end of the run are dropped, the drain waits for those on the way
+    redeliveries: Mutex<JoinSet<()>>,
+}
+
+// one load test run; grab `stats()`, `stop_handle()` and `pause_handle()`
+// before `run()` to watch, hold or cut short a run from elsewhere
+pub struct Runner {
+    config: LoadConfig,
+    generator: Generator,
+    stats: Arc<LoadTestStats>,
+    stop: Arc<AtomicBool>,
+    pause: Arc<Pause>,
+    // what a --global-setup already done extracted, it isn't run again
+    run_vars: Option<Vars>,
+}
+
+// holds every worker before its next request for as long as it's set; the
+// clock keeps running, so a duration or a stage ends on time regardless
+#[derive(Debug, Default)]
+pub struct Pause {
+    paused: AtomicBool,
+    // rate slots from before the last resume are dropped rather than sent
+    // in one burst
+    resumed: Mutex<Option<Instant>>,
+}
+
+impl Pause {
+    // pauses a running run or resumes a paused one, true when now paused
+    pub fn toggle(&self) -> bool {
+        let paused = !self.paused.fetch_xor(true, Ordering::SeqCst);
+        if !paused {
+            *self.resumed.lock().unwrap() = Some(Instant::now());
+        }
+        paused
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    fn missed(&self, slot: Instant) -> bool {
+        self.resumed.lock().unwrap().is_some_and(|resumed| slot < resumed)
+    }
+}
+
+impl Runner {
+    pub fn new(mut config: LoadConfig, generator: Generator) -> Self {
+        // the generator's seed is the one payloads actually come from
+        config.seed = generator.seed();
+        let stats = LoadTestStats::new(Checks::new(config.assertions.clone()), &config.metrics, &config.endpoint_labels(), &config.tag_labels(), &config.template_labels(), &config.chaos_labels());
+        Runner {
+            config,
+            generator,
+            stats: Arc::new(stats),
+            stop: Arc::new(AtomicBool::new(false)),
+            pause: Arc::new(Pause::default()),
+            run_vars: None,
+        }
+    }
+
+    // the --global-setup ran before, in arctic repl, and these are its values
+    pub fn after_global_setup(mut self, run_vars: Vars) -> Self {
+        self.run_vars = Some(run_vars);
+        self
+    }
+
+    pub fn stats(&self) -> Arc<LoadTestStats> {
+        self.stats.clone()
+    }
+
+    // setting it stops every worker after its in-flight request
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    pub fn pause_handle(&self) -> Arc<Pause> {
+        self.pause.clone()
+    }
+
+    // the first `count` requests a run would send, with every placeholder
+    // filled in, as {"method", "url", "body"} objects
+    pub fn dry_run(&self, count: u64) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+        let targets = Targets::new(self.config.endpoints.clone(), &self.config.method)?;
+        self.generator.check_columns(targets.columns().chain(header_columns(&self.config)))?;
+        check_padding(&self.generator, &self.config)?;
+        check_protobuf(&self.generator, &self.config)?;
+        // with --data -, no more than stdin has lines for
+        let mut stdin = self.config.stdin.then(|| input::payloads(io::stdin().lock()));
+        let samples = (0..count).map_while(|sequence| {
+            let payload = match &mut stdin {
+                Some(payloads) => Some(payloads.next()?),
+                None => None,
+            };
+            // every sample a user of its own
+            let caller = Caller { user: sequence as usize, vars: None, run: &Vars::default(), target: None };
+            Some(preview(&self.generator, &targets, &self.config, sequence, payload, caller))
+        });
+        Ok(samples.collect())
+    }
+
+    // runs until the configured duration or request count is reached or the
+    // stop handle is set
+    pub async fn run(self) -> Result<Report, Box<dyn std::error::Error>> {
+        let Runner { config, generator, stats, stop, pause, run_vars } = self;
+        run(config.clone(), generator, stats.clone(), stop, pause, run_vars).await?;
+        Ok(Report::new(&stats, &config))
+    }
+}
+
+// one request at a time outside of a run, for arctic repl: the client,
+// auth, setup and rendering of a run, with each exchange handed back; the
+// setups run once as it starts, the --teardown when it's done with
+pub struct Probe {
+    shared: Arc<Shared>,
+}
+
+impl Probe {
+    pub async fn start(config: LoadConfig, generator: Generator) -> Result<Self, Box<dyn std::error::Error>> {
+        let unsupported = [
+            (config.grpc.is_some(), "--grpc"),
+            (config.scenario.is_some(), "--scenario"),
+            (config.stdin, "--data -"),
+            (!config.chaos.is_empty(), "--chaos"),
+        ];
+        if let Some((_, flag)) = unsupported.iter().find(|(given, _)| *given) {
+            return Err(format!("{} can't be tried one request at a time", flag).into());
+        }
+        let targets = Targets::new(config.endpoints.clone(), &config.method)?;
+        generator.check_columns(targets.columns().chain(header_columns(&config)))?;
+        if let Some(setup) = &config.setup {
+            generator.check_columns(setup.columns())?;
+        }
+        check_padding(&generator, &config)?;
+        check_protobuf(&generator, &config)?;
+        let client = client::build(&config, None, None)?;
+        let tokens = match &config.auth {
+            Some(Auth::OAuth2(oauth)) => Some(Arc::new(TokenProvider::new(oauth.clone(), client::build_bare(&config)?))),
+            _ => None,
+        };
+        let run_vars = match &config.global_setup {
+            Some(setup) => setup
+                .run(&client, &generator, 0, &Vars::default())
+                .await
+                .map_err(|e| format!("global setup failed: {}", e))?,
+            None => Vars::default(),
+        };
+        let stats = LoadTestStats::new(Checks::new(config.assertions.clone()), &config.metrics, &config.endpoint_labels(), &config.tag_labels(), &config.template_labels(), &config.chaos_labels());
+        let probe = Probe {
+            shared: Arc::new(Shared {
+                connections: Arc::new(ConnectionTracker::new(&config.pool)),
+                // a single virtual user
+                users: config.setup.as_ref().map(|_| Arc::new(Users::new(1))),
+                config,
+                generator,
+                stats: Arc::new(stats),
+                chaos_stats: scratch_stats(),
+                targets,
+                client,
+                sessions: Vec::new(),
+                jars: Vec::new(),
+                rebuilt: Arc::default(),
+                run_vars: Arc::new(run_vars),
+                grpc: None,
+                tokens,
+                dns: None,
+                pacer: Pacer::new(Instant::now()),
+                deadline: None,
+                breaker: None,
+                sequence: AtomicU64::new(0),
+                warmed: 0,
+                input: None,
+                log: None,
+                samples: Some(Samples::new(1)),
+                capture: None,
+                spans: None,
+                stop: Arc::new(AtomicBool::new(false)),
+                pause: Arc::default(),
+                redeliveries: Mutex::default(),
+            }),
+        };
+        let shared = &probe.shared;
+        if let Some((setup, users)) = shared.config.setup.as_ref().zip(shared.users.as_ref()) {
+            match setup.run(&shared.client, &shared.generator, 0, &shared.run_vars).await {
+                Ok(vars) => users.set(0, vars),
+                Err(e) => {
+                    probe.finish().await;
+                    return Err(format!("setup failed: {}", e).into());
+                }
+            }
+        }
+        Ok(probe)
+    }
+
+    // what the --global-setup extracted, for a run that follows
+    pub fn run_vars(&self) -> Vars {
+        (*self.shared.run_vars).clone()
+    }
+
+    // runs the --teardown
+    pub async fn finish(self) {
+        let shared = &self.shared;
+        if let Some(teardown) = &shared.config.teardown {
+            match teardown.run(&shared.client, &shared.generator, 0, &shared.run_vars).await {
+                Ok(_) => debug!("teardown done"),
+                Err(e) => warn!(error = %e, "teardown failed"),
+            }
+        }
+    }
+
+    pub fn generator(&self) -> &Generator {
+        &self.shared.generator
+    }
+
+    // the request `sequence` of a run, as --dry-run shows it, with the
+    // variables send fills it with
+    pub fn preview(&self, sequence: u64) -> Value {
+        let shared = &self.shared;
+        let caller = Caller {
+            user: 0,
+            vars: shared.users.as_ref().and_then(|users| users.get(0)),
+            run: &shared.run_vars,
+            target: None,
+        };
+        preview(&shared.generator, &shared.targets, &shared.config, sequence, None, caller)
+    }
+
+    // sends the request `sequence` of a run, the exchange as
+    // --sample-responses writes it
+    pub async fn send(&self, sequence: u64) -> Result<String, Box<dyn std::error::Error>> {
+        let shared = &self.shared;
+        let outcome = send_one(shared, 0, sequence, None, None, None).await;
+        let sample = shared.samples.as_ref().and_then(|samples| samples.take().pop());
+        match sample {
+            Some(sample) => Ok(sample.to_text(shared.config.keep_secrets)),
+            None => Err(outcome.error.unwrap_or_else(|| "no exchange to show".to_string()).into()),
+        }
+    }
+}
+
+// a request as --dry-run shows it, {"method", "url", "body"}
+fn preview(generator: &Generator, targets: &Targets, config: &LoadConfig, sequence: u64, payload: Option<Value>, caller: Caller) -> Value {
+    let request = render(generator, targets, config, sequence, payload, caller);
+    let (method, url) = match &config.grpc {
+        Some(grpc) => ("GRPC".to_string(), format!("{}/{}", request.url.trim_end_matches('/'), grpc.method)),
+        None => (targets.method(request.target).to_string(), request.url),
+    };
+    let mut sample = json!({ "method": method, "url": url });
+    if !request.headers.is_empty() {
+        let headers: serde_json::Map<_, _> = request
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), json!(String::from_utf8_lossy(value.as_bytes()))))
+            .collect();
+        sample["headers"] = Value::Object(headers);
+    }
+    match &config.random_bytes {
+        Some(random) if has_body(targets.method(request.target)) => {
+            let mut rng = request.rng;
+            sample["body"] = json!(format!("{} random bytes ({})", random.size(&mut rng), random.content_type));
+        }
+        _ if config.grpc.is_some() || has_body(targets.method(request.target)) => {
+            let mut body = request.body;
+            // as a run would send it, and what's wrong with it
+            let checked = config.payload_schema.as_ref().map(|schema| schema.apply(&mut body));
+            sample["body"] = body;
+            match checked {
+                Some(Checked::Fixed(errors)) => sample["fixed"] = json!(errors),
+                Some(Checked::Invalid(errors)) => sample["invalid"] = json!(errors),
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+    sample
+}
+
+async fn run(
+    config: LoadConfig,
+    generator: Generator,
+    stats: Arc<LoadTestStats>,
+    stop: Arc<AtomicBool>,
+    pause: Arc<Pause>,
+    run_vars: Option<Vars>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (log, log_writer) = match &config.log_requests {
+        Some(path) => {
+            let (log, writer) = RequestLog::create(path, config.log_body_limit).await?;
+            (Some(log), Some(writer))
+        }
+        None => (None, None),
+    };
+    let (capture, capture_writer) = match &config.capture {
+        Some(path) => {
+            let (capture, writer) = Capture::create(path, config.capture_rate, client::default_headers(&config)?, config.keep_secrets).map_err(|e| format!("{}: {}", path, e))?;
+            (Some(capture), Some((path.clone(), writer)))
+        }
+        None => (None, None),
+    };
+    let grpc = match &config.grpc {
+        Some(grpc) => {
+            let headers = client::header_pairs(&config);
+            let mut clients = Vec::new();
+            for target in &config.endpoints {
+                clients.push(GrpcClient::connect(&target.url, grpc, config.timeout, &headers).await?);
+            }
+            Some(clients)
+        }
+        None => None,
+    };
+    let targets = Targets::new(config.endpoints.clone(), &config.method)?;
+    generator.check_columns(targets.columns().chain(header_columns(&config)))?;
+    if let Some(setup) = &config.setup {
+        generator.check_columns(setup.columns())?;
+    }
+    check_padding(&generator, &config)?;
+    check_protobuf(&generator, &config)?;
+    let dns = config.dns_refresh.map(|refresh| DnsRefresh::new(refresh, config.workers(), stats.clone()));
+    let client = client::build(&config, dns.as_ref(), None)?;
+    // the run starts once it's done, the stats don't see it
+    let run_vars = match (run_vars, &config.global_setup) {
+        (Some(vars), _) => Arc::new(vars),
+        (None, Some(setup)) => {
+            let vars = setup
+                .run(&client, &generator, 0, &Vars::default())
+                .await
+                .map_err(|e| format!("global setup failed: {}", e))?;
+            debug!("global setup done");
+            stats.restart();
+            Arc::new(vars)
+        }
+        (None, None) => Arc::default(),
+    };
+    // however what's after it ends, the --teardown runs once it's done
+    let teardown = config.teardown.clone().map(|teardown| (teardown, client.clone(), generator.clone(), run_vars.clone()));
+    let run = async {
+        // warm-up requests log the virtual users in for the measured run
+        let jars: Vec<Arc<Jar>> = match config.cookies && config.grpc.is_none() {
+            true => (0..config.workers()).map(|_| Arc::default()).collect(),
+            false => Vec::new(),
+        };
+        let sessions = jars.iter().map(|jar| client::build(&config, dns.as_ref(), Some(jar))).collect::<Result<Vec<_>, _>>()?;
+        let rebuilt: Arc<Vec<Mutex<Option<Client>>>> = match dns {
+            Some(_) => Arc::new((0..config.workers()).map(|_| Mutex::default()).collect()),
+            None => Arc::default(),
+        };
+        let tokens = match &config.auth {
+            Some(Auth::OAuth2(oauth)) => Some(Arc::new(TokenProvider::new(oauth.clone(), client::build_bare(&config)?))),
+            _ => None,
+        };
+        let connections = Arc::new(ConnectionTracker::new(&config.pool));
+        if let Some(count) = config.pool.preconnect {
+            let clients: Vec<&Client> = match sessions.is_empty() {
+                true => vec![&client],
+                false => sessions.iter().collect(),
+            };
+            let opened = client::preconnect(&clients, &origins(&targets, &config), count, &connections).await;
+            info!(opened, "connections opened ahead of the run");
+            stats.restart();
+        }
+        let input = config.stdin.then(|| Arc::new(Input::start()));
+        let users = config.setup.as_ref().map(|_| Arc::new(Users::new(config.workers())));
+
+        // the same connections and tokens as the measured run, but results go
+        // to stats nobody reads
+        let mut warmed = 0;
+        if let Some(warmup) = config.warmup.filter(|w| !w.is_zero()) {
+            let warmup_stats = LoadTestStats::new(Checks::new(config.assertions.clone()), &config.metrics, &config.endpoint_labels(), &config.tag_labels(), &config.template_labels(), &config.chaos_labels());
+            let shared = Arc::new(Shared {
+                config: warmup_config(&config),
+                generator: generator.clone(),
+                stats: Arc::new(warmup_stats),
+                chaos_stats: scratch_stats(),
+                targets: targets.clone(),
+                client: client.clone(),
+                sessions: sessions.clone(),
+                jars: jars.clone(),
+                rebuilt: rebuilt.clone(),
+                users: users.clone(),
+                run_vars: run_vars.clone(),
+                grpc: grpc.clone(),
+                tokens: tokens.clone(),
+                connections: connections.clone(),
+                dns: dns.clone(),
+                pacer: Pacer::new(Instant::now()),
+                deadline: Some(Instant::now() + warmup),
+                breaker: None,
+                sequence: AtomicU64::new(0),
+                warmed: 0,
+                input: input.clone(),
+                log: None,
+                samples: None,
+                capture: None,
+                spans: None,
+                stop: stop.clone(),
+                pause: pause.clone(),
+                redeliveries: Mutex::default(),
+            });
+            run_workers(shared.clone()).await?;
+            warmed = shared.sequence.load(Ordering::SeqCst);
+            debug!(?warmup, warmed, "warm-up done");
+            stats.restart();
+        }
+
+        // kept alive until the run is over
+        let _watchers = match config.watch {
+            true if config.data.is_empty() => return Err("--watch needs a --data template".into()),
+            true => config
+                .data
+                .iter()
+                .map(|spec| watch::watch_template(generator.clone(), &spec.path))
+                .collect::<Result<Vec<_>, _>>()?,
+            false => Vec::new(),
+        };
+        // probes would time a path that proxied traffic doesn't take
+        let probes = match config.timing && config.proxy.is_none() {
+            true => timing::start_probes(&targets, &stats, &config.tls)?,
+            false => Vec::new(),
+        };
+        let statsd = match &config.statsd {
+            Some(statsd) => Some(Statsd::start(statsd, stats.clone()).await?),
+            None => None,
+        };
+        let checkpoints = Checkpoints::start(&config, stats.clone());
+        let monitor = Monitor::start(stats.clone());
+        let controller = config.adaptive.map(|adaptive| Controller::start(adaptive, stats.clone()));
+        let refresher = dns.clone().map(Refresher::start);
+        let influx = match &config.influx {
+            Some(influx) => Some(Influx::start(influx, client::build_bare(&config)?, stats.clone()).await?),
+            None => None,
+        };
+        let (spans, span_exporter) = match &config.otlp {
+            Some(endpoint) => {
+                let (spans, exporter) = SpanExporter::start(endpoint, client::build_bare(&config)?);
+                (Some(spans), Some(exporter))
+            }
+            None => (None, None),
+        };
+        let start_time = stats.start_time();
+        let shared = Arc::new(Shared {
+            client,
+            sessions,
+            jars,
+            rebuilt,
+            users,
+            run_vars,
+            grpc,
+            targets,
+            tokens,
+            connections,
+            dns,
+            pacer: Pacer::new(start_time),
+            deadline: config.duration.map(|secs| start_time + Duration::from_secs(secs)),
+            breaker: config.abort_on_error_rate.map(|rate| Breaker::new(rate, config.abort_window)),
+            samples: config.sample_responses.map(Samples::new),
+            capture,
+            config,
+            generator,
+            stats,
+            chaos_stats: scratch_stats(),
+            sequence: AtomicU64::new(warmed),
+            warmed,
+            input,
+            log,
+            spans,
+            stop,
+            pause,
+            redeliveries: Mutex::default(),
+        });
+        debug!(workers = shared.config.workers(), "run started");
+        let workers = run_workers(shared.clone()).await;
+        debug!(requests = shared.stats.total_requests.load(Ordering::SeqCst), "run finished");
+        workers?;
+        monitor.finish();
+        if let Some(refresher) = refresher {
+            refresher.finish();
+        }
+        if let Some(controller) = controller {
+            controller.finish();
+        }
+        for probe in probes {
+            probe.abort();
+        }
+        if let Some(statsd) = statsd {
+            statsd.finish(&shared.stats).await;
+        }
+        if let Some(influx) = influx {
+            influx.finish(&shared.stats).await;
+        }
+        if let Some(checkpoints) = checkpoints {
+            checkpoints.finish().await;
+        }
+        if let Some(samples) = &shared.samples {
+            let dir = &shared.config.sample_dir;
+            let written = samples.write(dir, shared.config.keep_secrets).map_err(|e| format!("{}: {}", dir, e))?;
+            info!(written, dir = %dir, "response samples saved");
+        }
+
+        // the workers are done, dropping the last handle closes the log and
+        // span channels
+        drop(shared);
+        if let Some(writer) = log_writer {
+            writer.await??;
+        }
+        if let Some((path, writer)) = capture_writer {
+            let written = writer.await??;
+            info!(written, path = %path, "exchanges captured");
+        }
+        if let Some(exporter) = span_exporter {
+            exporter.await?;
+        }
+        Ok::<_, Box<dyn std::error::Error>>(())
+    }
+    .await;
+    if let Some((teardown, client, generator, run_vars)) = teardown {
+        match teardown.run(&client, &generator, 0, &run_vars).await {
+            Ok(_) => debug!("teardown done"),
+            Err(e) => warn!(error = %e, "teardown failed"),
+        }
+    }
+    run
+}
+
+fn scratch_stats() -> LoadTestStats {
+    LoadTestStats::new(Checks::new(Vec::new()), &[], &[], &[], &[], &[])
+}
+
+async fn run_workers(shared: Arc<Shared>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut workers = JoinSet::new();
+    for id in 0..shared.config.workers() {
+        workers.spawn(worker(id, shared.clone()));
+    }
+    // until the run ends, or the workers all stop on their own
+    while !ended(&shared) {
+        tokio::select! {
+            joined = workers.join_next() => match joined {
+                Some(joined) => joined?,
+                None => break,
+            },
+            _ = sleep(IDLE_POLL) => {}
+        }
+    }
+
+    // the requests sent by then get --drain-timeout to finish, chaos
+    // redeliveries included
+    let stats = &shared.stats;
+    let draining = stats.in_flight.load(Ordering::SeqCst);
+    let drain_start = Instant::now();
+    // the delayed duplicates, once no worker is left to add to them
+    let mut redeliveries = JoinSet::new();
+    let drained = tokio::time::timeout(shared.config.drain_timeout, async {
+        while let Some(joined) = workers.join_next().await {
+            joined?;
+        }
+        redeliveries = std::mem::take(&mut *shared.redeliveries.lock().unwrap());
+        while redeliveries.join_next().await.is_some() {}
+        Ok::<_, tokio::task::JoinError>(())
+    })
+    .await;
+    let abandoned = match drained {
+        Ok(joined) => {
+            joined?;
+            0
+        }
+        // dropping a request in flight doesn't record it, as a success or
+        // a failure
+        Err(_) => {
+            let abandoned = stats.in_flight.load(Ordering::SeqCst);
+            let mut unclaimed = std::mem::take(&mut *shared.redeliveries.lock().unwrap());
+            // the workers and delayed duplicates still going, aborted along
+            // with the requests
+            let aborted = workers.len() + redeliveries.len() + unclaimed.len();
+            warn!(abandoned, aborted, timeout = ?shared.config.drain_timeout, "requests still in flight after the drain timeout, abandoning them");
+            workers.shutdown().await;
+            redeliveries.shutdown().await;
+            unclaimed.shutdown().await;
+            abandoned
+        }
+    };
+    if draining > 0 {
+        stats.drained.fetch_add(draining.saturating_sub(abandoned), Ordering::SeqCst);
+        stats.abandoned.fetch_add(abandoned, Ordering::SeqCst);
+        stats.drain_us.fetch_add(drain_start.elapsed().as_micros() as u64, Ordering::SeqCst);
+        debug!(draining, abandoned, "in-flight requests drained");
+    }
+    Ok(())
+}
+
+// warm-up traffic holds the level the measured run starts at, at least one
+// worker or request per second for profiles that ramp up from zero
+fn warmup_config(config: &LoadConfig) -> LoadConfig {
+    let mut warmup = config.clone();
+    let start = config.profile.target_at(Duration::ZERO).max(1.0);
+    warmup.profile = LoadProfile::constant(config.profile.unit, start);
+    warmup.stages = None;
+    warmup.adaptive = None;
+    warmup.duration = None;
+    warmup.requests = None;
+    warmup
+}
+
+// the workers that send at `elapsed` into a concurrency run
+fn concurrency(shared: &Shared, elapsed: Duration) -> usize {
+    match shared.config.adaptive {
+        Some(_) => shared.stats.concurrency.current(),
+        None => shared.config.profile.target_at(elapsed).round() as usize,
+    }
+}
+
+async fn worker(id: usize, shared: Arc<Shared>) {
+    let stats = &shared.stats;
+    let profile = &shared.config.profile;
+    let start_time = stats.start_time();
+    let deadline = shared.deadline;
+    // when the worker last came back from a Retry-After, rate slots from
+    // before it are skipped rather than sent in one burst
+    let mut backed_off: Option<Instant> = None;
+    // with --data - and a rate, the line read before the slot is taken and
+    // when it came; the request is due no earlier, a wait on stdin isn't
+    // the request's latency
+    let mut pending: Option<(Value, Instant)> = None;
+
+    loop {
+        if shared.stop.load(Ordering::SeqCst) {
+            stats.stop(StopReason::Interrupted);
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            stats.stop(StopReason::Duration);
+        }
+        // whichever bound hits first stops every worker
+        if stats.stop_reason().is_some() {
+            break;
+        }
+        if shared.pause.is_paused() {
+            sleep(IDLE_POLL).await;
+            continue;
+        }
+
+        // a virtual user runs its setup once it's first needed, and again
+        // after a pause while it keeps failing
+        if let Some((setup, users)) = shared.config.setup.as_ref().zip(shared.users.as_ref()) {
+            let active = profile.unit == StageUnit::Rps || id < concurrency(&shared, start_time.elapsed());
+            if active && users.get(id).is_none() {
+                let client = client_for(&shared, id);
+                match setup.run(&client, &shared.generator, id as u64, &shared.run_vars).await {
+                    Ok(vars) => {
+                        stats.setups.fetch_add(1, Ordering::SeqCst);
+                        users.set(id, vars);
+                    }
+                    Err(e) => {
+                        stats.setup_failures.fetch_add(1, Ordering::SeqCst);
+                        warn!(user = id, error = %e, "setup failed, trying again");
+                        wait_until(&shared, Instant::now() + SETUP_RETRY).await;
+                    }
+                }
+                continue;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+
+        // with a rate, when the request should have gone out
+        let scheduled = match profile.unit {
+            StageUnit::Concurrency => {
+                if id >= concurrency(&shared, elapsed) {
+                    sleep(IDLE_POLL).await;
+                    continue;
+                }
+                None
+            }
+            StageUnit::Rps => {
+                if let Some(input) = shared.input.as_ref().filter(|_| pending.is_none() && shared.config.scenario.is_none()) {
+                    match next_payload(&shared, input).await {
+                        Some(payload) => pending = Some((payload, Instant::now())),
+                        None => {
+                            stats.stop(StopReason::InputClosed);
+                            break;
+                        }
+                    }
+                }
+                match shared.pacer.next_slot(profile) {
+                    Some(slot) if backed_off.is_some_and(|at| slot < at) => continue,
+                    Some(slot) if deadline.is_none_or(|deadline| slot < deadline) => {
+                        // slots can be seconds ahead, don't hold up a run that
+                        // ran out of requests or stdin in the meantime
+                        while Instant::now() < slot && stats.stop_reason().is_none() {
+                            tokio::time::sleep_until(slot.min(Instant::now() + IDLE_POLL).into()).await;
+                        }
+                        Some(pending.as_ref().map_or(slot, |(_, read)| slot.max(*read)))
+                    }
+                    // nothing more for this worker, the others may still have
+                    // slots before the deadline
+                    _ => {
+                        if let Some(deadline) = deadline {
+                            wait_until(&shared, deadline).await;
+                        }
+                        stats.stop(StopReason::Duration);
+                        break;
+                    }
+                }
+            }
+        };
+
+        // another worker may have ended the run while this one waited for its slot
+        if stats.stop_reason().is_some() {
+            break;
+        }
+        if scheduled.is_some_and(|slot| shared.pause.is_paused() || shared.pause.missed(slot)) {
+            tokio::task::yield_now().await;
+            continue;
+        }
+        let retry_after = match &shared.config.scenario {
+            Some(scenario) => run_scenario(&shared, id, scenario).await,
+            None => {
+                let Some(sequence) = next_sequence(&shared) else { break };
+                let payload = match (pending.take(), &shared.input) {
+                    (Some((payload, _)), _) => Some(payload),
+                    (None, Some(input)) => match next_payload(&shared, input).await {
+                        Some(payload) => Some(payload),
+                        None => {
+                            stats.stop(StopReason::InputClosed);
+                            break;
+                        }
+                    },
+                    (None, None) => None,
+                };
+                send_one(&shared, id, sequence, scheduled, payload, None).await.retry_after
+            }
+        };
+        if let Some(wait) = retry_after {
+            back_off(&shared, stats, wait).await;
+            backed_off = Some(Instant::now());
+        }
+        if let Some(think) = shared.config.think_time {
+            let pause = jittered(think, shared.config.think_jitter);
+            // not past the end of the run, and counted as long as it lasted
+            let slept = Instant::now();
+            wait_until(&shared, slept + pause).await;
+            stats.think_time.record(slept.elapsed().as_micros() as u64);
+        }
+    }
+}
+
+// counts a request in stats.in_flight until dropped, sent or abandoned
+struct InFlight<'a>(&'a AtomicU64);
+
+impl<'a> InFlight<'a> {
+    fn start(stats: &'a LoadTestStats) -> Self {
+        stats.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(&stats.in_flight)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// the next request's number, none once --requests have gone out
+fn next_sequence(shared: &Shared) -> Option<u64> {
+    let n = shared.sequence.fetch_add(1, Ordering::SeqCst);
+    let numbered = |n: u64| match shared.config.shard {
+        Some(shard) => shard.index + n * shard.count,
+        None => n,
+    };
+    if shared.config.requests.is_some_and(|limit| numbered(n - shared.warmed) >= limit) {
+        shared.stats.stop(StopReason::RequestLimit);
+        return None;
+    }
+    Some(numbered(n))
+}
+
+// sleeps until `until` unless the run ends first, stopped, out of time, out
+// of input or cut short by the breaker; false when it did
+async fn wait_until(shared: &Shared, until: Instant) -> bool {
+    loop {
+        if ended(shared) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= until {
+            return true;
+        }
+        sleep((until - now).min(IDLE_POLL)).await;
+    }
+}
+
+// true once the run is over, stopped or out of time
+fn ended(shared: &Shared) -> bool {
+    if shared.stop.load(Ordering::SeqCst) {
+        shared.stats.stop(StopReason::Interrupted);
+    }
+    if shared.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        shared.stats.stop(StopReason::Duration);
+    }
+    shared.stats.stop_reason().is_some()
+}
+
+// waits for the next stdin line, none when stdin closed or the run ended
+// in the meantime
+async fn next_payload(shared: &Shared, input: &Input) -> Option<Value> {
+    loop {
+        if ended(shared) {
+            return None;
+        }
+        if let Ok(payload) = tokio::time::timeout(IDLE_POLL, input.next()).await {
+            return payload;
+        }
+    }
+}
+
+// what a pass over the --scenario has so far
+struct Pass {
+    vars: Vars,
+    last: Last,
+    // the longest Retry-After on the way, waited out after the pass
+    retry_after: Option<Duration>,
+}
+
+// one pass of a virtual user over the --scenario, its variables starting
+// out as what its --setup extracted
+async fn run_scenario(shared: &Arc<Shared>, worker: usize, scenario: &Scenario) -> Option<Duration> {
+    let mut pass = Pass {
+        vars: shared.users.as_ref().and_then(|users| users.get(worker)).cloned().unwrap_or_default(),
+        last: Last::default(),
+        retry_after: None,
+    };
+    run_steps(shared, worker, scenario, &scenario.steps, &mut pass).await;
+    pass.retry_after
+}
+
+// false once the pass should end early: the run is over, or a value the
+// steps after need wasn't in a response
+fn run_steps<'a>(shared: &'a Arc<Shared>, worker: usize, scenario: &'a Scenario, steps: &'a [Step], pass: &'a mut Pass) -> BoxFuture<'a, bool> {
+    Box::pin(async move {
+        for step in steps {
+            while shared.pause.is_paused() && !ended(shared) {
+                sleep(IDLE_POLL).await;
+            }
+            if ended(shared) {
+                return false;
+            }
+            let carry_on = match step {
+                Step::Request { target, extract } => send_step(shared, worker, *target, extract, pass).await,
+                // not past the end of the run
+                Step::Sleep(pause) => wait_until(shared, Instant::now() + *pause).await,
+                Step::If { condition, then, otherwise } => {
+                    let branch = if condition.holds(&pass.last) { then } else { otherwise };
+                    run_steps(shared, worker, scenario, branch, pass).await
+                }
+                Step::Loop { times, until, steps } => {
+                    let mut carry_on = true;
+                    let mut passes = 0;
+                    while passes < *times && carry_on {
+                        carry_on = run_steps(shared, worker, scenario, steps, pass).await;
+                        passes += 1;
+                        if until.as_ref().is_some_and(|until| until.holds(&pass.last)) {
+                            break;
+                        }
+                    }
+                    // the steps after carry on regardless, their ifs can tell
+                    if let Some(until) = until.as_ref().filter(|until| carry_on && !until.holds(&pass.last)) {
+                        let message = format!("{}: {} passes of a loop and still not {}", scenario.path, times, until.text);
+                        report_error(&shared.stats, &scenario.path, "scenario loop", &message);
+                    }
+                    carry_on
+                }
+            };
+            if !carry_on {
+                return false;
+            }
+        }
+        true
+    })
+}
+
+// a request step, counted like any other request, its response what the
+// conditions after it look at
+async fn send_step(shared: &Arc<Shared>, worker: usize, target: usize, extract: &[Extract], pass: &mut Pass) -> bool {
+    let Some(sequence) = next_sequence(shared) else { return false };
+    let outcome = send_one(shared, worker, sequence, None, None, Some(StepRequest { target, vars: &pass.vars })).await;
+    pass.retry_after = pass.retry_after.max(outcome.retry_after);
+    let json = outcome.response_body.as_deref().and_then(|body| serde_json::from_str::<Value>(body).ok());
+    let headers = outcome.headers.unwrap_or_default();
+    for extract in extract {
+        match extract.value(&headers, json.as_ref()) {
+            Some(value) => pass.vars.set(&extract.name, value),
+            // a failed request is counted already
+            None if outcome.failed => return false,
+            None => {
+                let label = shared.targets.targets[target].label();
+                let message = format!("{}: no {} in the response for {}", label, extract.from, extract.name);
+                report_error(&shared.stats, &label, "scenario extract", &message);
+                return false;
+            }
+        }
+    }
+    pass.last = Last { status: outcome.status, json };
+    true
+}
+
+// what became of one request, whichever protocol sent it
+#[derive(Default)]
+struct Outcome {
+    status: Option<u16>,
+    // when something looks at them, see send_http
+    headers: Option<HeaderMap>,
+    response_body: Option<String>,
+    // body bytes, when the response was read
+    response_size: Option<u64>,
+    error: Option<String>,
+    failed: bool,
+    // ran out of --timeout, so its latency is only the timeout
+    timed_out: bool,
+    // how long to hold off with --respect-retry-after
+    retry_after: Option<Duration>,
+}
+
+// `think` moved by a uniform amount within +/- `jitter`, at least zero
+fn jittered(think: Duration, jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return think;
+    }
+    let offset = rand::thread_rng().gen_range(-jitter.as_secs_f64()..=jitter.as_secs_f64());
+    Duration::from_secs_f64((think.as_secs_f64() + offset).max(0.0))
+}
+
+// what request `sequence` sends; the rng carries on for anything else the
+// request needs
+struct Rendered {
+    target: usize,
+    // the --data template the body came from
+    template: Option<usize>,
+    url: String,
+    body: Value,
+    // the --header templates filled in
+    headers: Vec<(HeaderName, HeaderValue)>,
+    rng: StdRng,
+}
+
+// a payload of every shape the template makes has to fit the --message
+fn check_protobuf(generator: &Generator, config: &LoadConfig) -> Result<(), String> {
+    match &config.protobuf {
+        Some(protobuf) => generator
+            .samples()
+            .iter()
+            // nothing generated, e.g. bodies from stdin
+            .filter(|body| !body.is_null())
+            .try_for_each(|body| protobuf.encode(body).map(|_| ())),
+        None => Ok(()),
+    }
+}
+
+// a template either makes objects or it doesn't, the first body tells
+fn check_padding(generator: &Generator, config: &LoadConfig) -> Result<(), String> {
+    match &config.padding {
+        Some(padding) => generator.samples().iter_mut().try_for_each(|body| padding.pad(body)),
+        None => Ok(()),
+    }
+}
+
+// one http request as it goes out, through every retry
+struct HttpRequest<'a> {
+    target: usize,
+    sequence: u64,
+    // where the responses, errors and bytes are counted
+    stats: &'a LoadTestStats,
+    chaos: Option<ChaosKind>,
+    method: &'a Method,
+    url: &'a str,
+    // from a --script before_request
+    headers: &'a [(HeaderName, HeaderValue)],
+    data: &'a Value,
+    file: Option<&'a [u8]>,
+    // the body itself, with --body-bytes
+    bytes: Option<&'a [u8]>,
+    // `data` encoded once for every attempt, with --message
+    protobuf: Option<&'a [u8]>,
+    trace: Option<&'a TraceContext>,
+}
+
+// whom a request goes out for: the virtual user, whose variables fill the
+// {{vu.<name>}} placeholders, and the run's for the {{run.<name>}} ones
+struct Caller<'a> {
+    user: usize,
+    vars: Option<&'a Vars>,
+    run: &'a Vars,
+    // a --scenario step's, otherwise one is picked
+    target: Option<usize>,
+}
+
+// a --scenario request step: where it goes, and the variables of the pass so far
+struct StepRequest<'a> {
+    target: usize,
+    vars: &'a Vars,
+}
+
+// `payload`, read off stdin, stands in for the generated one
+fn render(
+    generator: &Generator,
+    targets: &Targets,
+    config: &LoadConfig,
+    sequence: u64,
+    payload: Option<Value>,
+    caller: Caller,
+) -> Rendered {
+    let mut rng = generator.rng(sequence);
+    let target = caller.target.unwrap_or_else(|| targets.pick(&mut rng));
+    let context = Context { user: caller.vars, run: Some(caller.run), ..generator.context(sequence, &mut rng) };
+    let mut url = targets.url(target, &mut rng, &context);
+    if let Some(host_override) = &config.tls.host_override {
+        url = host_override.rewrite(&url);
+    }
+    let generate = |rng: &mut StdRng| {
+        let (template, mut body) = match payload {
+            Some(payload) => (None, payload),
+            None => generator.generate_with(&context, rng),
+        };
+        if let Some(padding) = &config.padding {
+            // checked up front, see check_padding
+            let _ = padding.pad(&mut body);
+        }
+        (template, body)
+    };
+    let (template, body) = match (&targets.targets[target].replay, &config.graphql) {
+        (Some(replay), _) => (None, replay.body.as_deref().map(recorded_body).unwrap_or_default()),
+        // nothing to generate, send_one draws the bytes
+        (None, _) if config.random_bytes.is_some() => (None, Value::Null),
+        (None, _) if targets.targets[target].operation.is_some() => {
+            (None, targets.body(target).map(|body| body.generate(&mut rng, &context)).unwrap_or_default())
+        }
+        (None, Some(graphql)) => {
+            let (template, variables) = generate(&mut rng);
+            (template, graphql.envelope(variables))
+        }
+        (None, None) => generate(&mut rng),
+    };
+    // after the body, so its stream is the same with or without them
+    let mut headers: Vec<_> = config
+        .header_templates
+        .iter()
+        .filter_map(|header| {
+            let value = header.pattern.render(&mut rng, &context);
+            match HeaderValue::try_from(value) {
+                Ok(value) => Some((header.name.clone(), value)),
+                Err(_) => {
+                    debug!(header = %header.name, "rendered header value isn't valid, left out");
+                    None
+                }
+            }
+        })
+        .collect();
+    // a templated header of the same name wins
+    if let Some(fingerprints) = &config.fingerprints {
+        let set = fingerprints.pick(caller.user, &mut rng);
+        let set: Vec<_> = set.iter().filter(|(name, _)| !headers.iter().any(|(templated, _)| templated == name)).cloned().collect();
+        headers.extend(set);
+    }
+    // last, so nothing else drawn changes with it
+    if let Some(param) = &config.cache_bust {
+        url = cache_bust(&url, param, rng.gen());
+    }
+    Rendered { target, template, url, body, headers, rng }
+}
+
+// the url with ?param=<random> added, or &param=<random> when it has a
+// query already
+fn cache_bust(url: &str, param: &str, value: u64) -> String {
+    let (url, fragment) = match url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (url, None),
+    };
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let mut busted = format!("{}{}{}={:016x}", url, separator, param, value);
+    if let Some(fragment) = fragment {
+        busted.push('#');
+        busted.push_str(fragment);
+    }
+    busted
+}
+
+// scheme://host:port/ of every endpoint, as requests address them
+fn origins(targets: &Targets, config: &LoadConfig) -> Vec<String> {
+    let origins = targets.origins(config.tls.host_override.as_ref());
+    origins.iter().map(|url| format!("{}/", url.origin().ascii_serialization())).collect()
+}
+
+// the client `worker` sends with: the one it rebuilt, its session's or the
+// shared one
+fn client_for(shared: &Shared, worker: usize) -> Client {
+    let rebuilt = shared.rebuilt.get(worker).and_then(|client| client.lock().unwrap().clone());
+    rebuilt.unwrap_or_else(|| shared.sessions.get(worker).unwrap_or(&shared.client).clone())
+}
+
+// a fresh client for `worker`, with the cookies of its session
+fn rebuild(shared: &Shared, worker: usize) {
+    let Some(slot) = shared.rebuilt.get(worker) else { return };
+    match client::build(&shared.config, shared.dns.as_ref(), shared.jars.get(worker)) {
+        Ok(client) => *slot.lock().unwrap() = Some(client),
+        Err(e) => warn!(worker, error = %e, "couldn't build a client for the changed dns records"),
+    }
+}
+
+// the feed columns the header templates refer to
+fn header_columns(config: &LoadConfig) -> impl Iterator<Item = &str> {
+    config.header_templates.iter().flat_map(|header| header.pattern.columns())
+}
+
+// the tag groups a request to `target` counts under right now
+fn tag_groups(shared: &Shared, target: usize) -> impl Iterator<Item = &GroupStats> {
+    let tags = shared.targets.tags(target).iter().copied();
+    // stage groups follow the endpoint tags
+    let profile = &shared.config.profile;
+    let stage = (profile.stages.len() > 1)
+        .then(|| shared.targets.tag_labels().len() + profile.stage_at(shared.stats.start_time().elapsed()));
+    tags.chain(stage).filter_map(|index| shared.stats.tags.get(index))
+}
+
+// a body that doesn't match --validate-payloads is counted and, unless it
+// could be fixed up, not sent
+fn payload_ok(schema: &Schema, stats: &LoadTestStats, body: &mut Value) -> bool {
+    let (errors, fixed) = match schema.apply(body) {
+        Checked::Valid => return true,
+        Checked::Fixed(errors) => (errors, true),
+        Checked::Invalid(errors) => (errors, false),
+    };
+    let errors = errors.join("; ");
+    debug!(errors = %errors, fixed, "payload doesn't match the schema");
+    stats.invalid_payloads.fetch_add(1, Ordering::SeqCst);
+    if fixed {
+        stats.fixed_payloads.fetch_add(1, Ordering::SeqCst);
+    }
+    let _ = stats.invalid_payload.set(errors);
+    fixed
+}
+
+// latency counts from the scheduled send time when there is one, so a
+// stalled server shows up as the queueing it causes rather than as fewer
+// requests; the time the server took alone is kept as service time.
+// Its retry_after is a Retry-After the worker should wait out before its
+// next request
+async fn send_one(
+    shared: &Arc<Shared>,
+    worker: usize,
+    sequence: u64,
+    scheduled: Option<Instant>,
+    payload: Option<Value>,
+    step: Option<StepRequest<'_>>,
+) -> Outcome {
+    let stats = &shared.stats;
+    let Rendered { target, template, url, body, headers: rendered, mut rng } = render(
+        &shared.generator,
+        &shared.targets,
+        &shared.config,
+        sequence,
+        payload,
+        Caller {
+            user: worker,
+            vars: step.as_ref().map(|step| step.vars).or_else(|| shared.users.as_ref().and_then(|users| users.get(worker))),
+            run: &shared.run_vars,
+            target: step.as_ref().map(|step| step.target),
+        },
+    );
+    let method = shared.targets.method(target);
+    let script = shared.config.script.as_ref().filter(|script| script.has_before_request() && shared.grpc.is_none());
+    let Prepared { method, url, body: mut random_data, headers } = match script {
+        Some(script) => script.before_request(method, url, body),
+        None => Prepared { method: method.clone(), url, body, headers: Vec::new() },
+    };
+    if let Some(schema) = shared.config.payload_schema.as_ref().filter(|_| has_body(&method) || shared.grpc.is_some()) {
+        if !payload_ok(schema, stats, &mut random_data) {
+            return Outcome::default();
+        }
+    }
+    // a header the script set wins over the template's
+    let headers: Vec<_> = rendered
+        .iter()
+        .filter(|(name, _)| !headers.iter().any(|(set, _)| set == name))
+        .cloned()
+        .chain(headers.iter().cloned())
+        .collect();
+    // the records changed since the worker's last request: it goes on with
+    // a client of its own, whose connections go where the names point now;
+    // over h2 a Connection: close wouldn't let go of the old ones
+    if shared.dns.as_ref().is_some_and(|dns| dns.changed_for(worker)) {
+        rebuild(shared, worker);
+    }
+    let endpoint = &url;
+    let file = shared.config.file.as_ref().map(|part| {
+        let mut bytes = vec![0u8; part.size];
+        rng.fill_bytes(&mut bytes);
+        bytes
+    });
+    let bytes = shared.config.random_bytes.as_ref().map(|random| random.generate(&mut rng));
+    // drawn after the file part and body bytes, so they're the same with or without
+    let chaos = chaos::pick(&shared.config.chaos, &mut rng).filter(|&index| {
+        !shared.config.chaos[index].needs_body() || (has_body(&method) && shared.config.body_format != BodyFormat::Multipart)
+    });
+    let kind = chaos.map(|index| shared.config.chaos[index].kind);
+    // the first of a duplicated pair is an ordinary request
+    let broken = kind.filter(|kind| !matches!(kind, ChaosKind::Duplicate(_)));
+    // a run that ends in the meantime doesn't send it
+    if let Some(ChaosKind::Delay(delay)) = kind {
+        if !wait_until(shared, Instant::now() + delay).await {
+            return Outcome::default();
+        }
+    }
+    let trace = shared.config.traceparent.then(TraceContext::random);
+    let span_start = SystemTime::now();
+    let requests_start = Instant::now();
+    let latency_start = scheduled.unwrap_or(requests_start);
+
+    let _in_flight = InFlight::start(stats);
+    let outcome = match &shared.grpc {
+        Some(clients) => send_grpc(shared, &clients[target], endpoint, &random_data, latency_start).await,
+        None => {
+            let client = client_for(shared, worker);
+            let request = HttpRequest {
+                target,
+                sequence,
+                stats: if broken.is_some() { &shared.chaos_stats } else { stats },
+                chaos: broken,
+                method: &method,
+                url: endpoint,
+                data: &random_data,
+                headers: &headers,
+                file: file.as_deref(),
+                bytes: bytes.as_deref(),
+                protobuf: None,
+                trace: trace.as_ref(),
+            };
+            send_http(shared, &client, &request, latency_start).await
+        }
+    };
+    if let Some((spans, context)) = shared.spans.as_ref().zip(trace) {
+        spans
+            .export(Span {
+                context,
+                method: method.to_string(),
+                url: endpoint.clone(),
+                start: span_start,
+                end: SystemTime::now(),
+                status: outcome.status,
+                error: outcome.error.clone(),
+            })
+            .await;
+    }
+
+    let service_time = requests_start.elapsed();
+    let latency = latency_start.elapsed();
+    if let Some((index, ChaosKind::Duplicate(after))) = chaos.zip(kind) {
+        let copy = Duplicate {
+            index,
+            worker,
+            target,
+            sequence,
+            method: method.clone(),
+            url: endpoint.clone(),
+            data: random_data.clone(),
+            headers: headers.clone(),
+            file: file.clone(),
+            bytes: bytes.clone(),
+            trace,
+        };
+        match after {
+            Some(after) => {
+                let mut redeliveries = shared.redeliveries.lock().unwrap();
+                // the ones done already
+                while redeliveries.try_join_next().is_some() {}
+                let shared = shared.clone();
+                // dropped if the run ends first
+                redeliveries.spawn(async move {
+                    if wait_until(&shared, Instant::now() + after).await {
+                        copy.send(&shared).await;
+                    }
+                });
+            }
+            None => copy.send(shared).await,
+        }
+    }
+
+    match chaos.filter(|_| broken.is_some()).and_then(|index| stats.chaos.get(index)) {
+        Some(group) => match outcome.timed_out {
+            true => group.record_timeout(),
+            false => group.record(latency, outcome.failed, outcome.status),
+        },
+        None => {
+            record_outcome(shared, target, template, latency, &outcome);
+            if scheduled.is_some() && !outcome.timed_out {
+                stats.service_time.record(service_time.as_micros() as u64);
+            }
+            trace!(sequence, url = %endpoint, status = ?outcome.status, latency_ms = latency.as_secs_f64() * 1000.0, "request done");
+        }
+    }
+
+    if let Some(log) = &shared.log {
+        let sends_body = shared.grpc.is_some() || has_body(&method);
+        let (method, url) = match &shared.config.grpc {
+            Some(grpc) => ("GRPC".to_string(), format!("{}/{}", endpoint.trim_end_matches('/'), grpc.method)),
+            None => (method.to_string(), endpoint.clone()),
+        };
+        log.log(LogEntry {
+            timestamp_ms: now_ms(),
+            sequence,
+            method,
+            url,
+            request_body: match sends_body {
+                true => random_data,
+                false => Value::Null,
+            },
+            status: outcome.status,
+            latency_ms: latency.as_secs_f64() * 1000.0,
+            service_time_ms: scheduled.map(|_| service_time.as_secs_f64() * 1000.0),
+            trace_id: trace.map(|context| context.trace_id()),
+            headers: rendered
+                .iter()
+                .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+                .collect(),
+            chaos: broken.map(|kind| kind.to_string()),
+            error: outcome.error.clone(),
+            response_body: outcome.response_body.clone(),
+        })
+        .await;
+    }
+    match broken {
+        Some(_) => Outcome { retry_after: None, ..outcome },
+        None => outcome,
+    }
+}
+
+// a request that counts, into the run's stats and those of its endpoint,
+// tags and --data template
+fn record_outcome(shared: &Shared, target: usize, template: Option<usize>, latency: Duration, outcome: &Outcome) {
+    let stats = &shared.stats;
+    let record = |group: &GroupStats| match outcome.timed_out {
+        true => group.record_timeout(),
+        false => group.record(latency, outcome.failed, outcome.status),
+    };
+    match outcome.timed_out {
+        true => stats.record_timeout(),
+        false => stats.record_request(latency, outcome.failed),
+    }
+    record(&stats.endpoints[target]);
+    let template = template.and_then(|index| stats.templates.get(index));
+    for group in tag_groups(shared, target).chain(template) {
+        record(group);
+        if let Some(size) = outcome.response_size {
+            group.response_size.record(size);
+        }
+    }
+    if let Some(size) = outcome.response_size {
+        stats.record_response_size(target, size);
+    }
+    if shared.breaker.as_ref().is_some_and(|breaker| breaker.record(outcome.failed)) {
+        stats.stop(StopReason::ErrorRate);
+    }
+}
+
+// the second of a --chaos duplicate pair, what the first sent once more
+struct Duplicate {
+    // of the --chaos
+    index: usize,
+    worker: usize,
+    target: usize,
+    sequence: u64,
+    method: Method,
+    url: String,
+    data: Value,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    file: Option<Vec<u8>>,
+    bytes: Option<Vec<u8>>,
+    trace: Option<TraceContext>,
+}
+
+impl Duplicate {
+    async fn send(self, shared: &Shared) {
+        let Some(group) = shared.stats.chaos.get(self.index) else { return };
+        let client = client_for(shared, self.worker);
+        let copy = HttpRequest {
+            target: self.target,
+            sequence: self.sequence,
+            stats: &shared.chaos_stats,
+            chaos: Some(shared.config.chaos[self.index].kind),
+            method: &self.method,
+            url: &self.url,
+            data: &self.data,
+            headers: &self.headers,
+            file: self.file.as_deref(),
+            bytes: self.bytes.as_deref(),
+            protobuf: None,
+            trace: self.trace.as_ref(),
+        };
+        let again = Instant::now();
+        let repeated = send_http(shared, &client, &copy, again).await;
+        group.record(again.elapsed(), repeated.failed, repeated.status);
+    }
+}
+
+async fn send_http(shared: &Shared, client: &Client, request: &HttpRequest<'_>, latency_start: Instant) -> Outcome {
+    let stats = request.stats;
+    let graphql = shared.config.graphql.is_some();
+    let endpoint = request.url;
+    let mut sent = None;
+    let encoded = match &shared.config.protobuf {
+        Some(protobuf) if has_body(request.method) => match protobuf.encode(request.data) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                report_error(stats, endpoint, "protobuf encoding", &e);
+                return Outcome { error: Some(e), failed: true, ..Outcome::default() };
+            }
+        },
+        _ => None,
+    };
+    let request = &HttpRequest { protobuf: encoded.as_deref(), ..*request };
+
+    // decided up front, the body is kept as it came for it
+    let capture = captures(shared, request);
+    match send_with_retries(shared, client, request, &mut sent).await {
+        Ok(response) => {
+            let status = response.status();
+            let retry_after = shared.config.retry_after.and_then(|max| retry_after(&response).map(|after| after.min(max)));
+            stats.record_status(status.as_u16());
+            stats.record_protocol(response.version());
+            if is_cache_hit(response.headers()) {
+                stats.cache_hits.fetch_add(1, Ordering::SeqCst);
+            }
+            if let Some(new) = shared.connections.is_new(&response) {
+                let counter = if new { &stats.connections_opened } else { &stats.connections_reused };
+                counter.fetch_add(1, Ordering::SeqCst);
+                if new && response.url().scheme() == "https" {
+                    stats.tls_handshakes.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            // only a script check, the samples and a scenario's extracts look at them
+            let scenario = shared.config.scenario.is_some();
+            let headers = (shared.samples.is_some() || capture.is_some() || shared.config.script.as_ref().is_some_and(|s| s.has_check()) || scenario)
+                .then(|| response.headers().clone());
+            let version = response.version();
+            let addrs = response.extensions().get::<HttpInfo>().map(|info| (info.local_addr(), info.remote_addr()));
+            // read in full either way, for the byte count and so the
+            // connection goes back to the pool
+            let downloading = Instant::now();
+            let (bytes, body_error, timed_out) = match response.bytes().await {
+                Ok(bytes) => (Some(bytes), None, false),
+                Err(e) => {
+                    let kind = TransportError::classify(&e);
+                    stats.record_transport_error(kind);
+                    report_error(stats, endpoint, &kind.to_string(), &e.to_string());
+                    (None, Some(e.to_string()), kind == TransportError::Timeout)
+                }
+            };
+            let response_size = bytes.as_ref().map(|b| b.len() as u64);
+            let captured_body = capture.and(bytes.as_ref()).map(|b| b.to_vec());
+            stats.bytes_received.fetch_add(response_size.unwrap_or(0), Ordering::SeqCst);
+            let response_body = match stats.checks.needs_body() || !stats.metrics.is_empty() || shared.log.is_some() || shared.samples.is_some() || graphql || scenario {
+                true => bytes.map(|b| String::from_utf8_lossy(&b).into_owned()),
+                false => None,
+            };
+            if shared.config.timing {
+                timing::record(&stats.phases.download, downloading.elapsed());
+            }
+            let json = match stats.checks.needs_body() || !stats.metrics.is_empty() || graphql {
+                true => response_body.as_deref().and_then(|b| serde_json::from_str::<Value>(b).ok()),
+                false => None,
+            };
+            if !stats.checks.is_empty() {
+                record_checks(stats, status.as_u16(), headers.as_ref(), response_body.as_deref(), json.as_ref(), latency_start);
+            }
+            if let Some(json) = json.as_ref().filter(|_| !stats.metrics.is_empty()) {
+                stats.metrics.record(json, &stats.timeseries);
+            }
+
+            let graphql_errors = json.as_ref().map(graphql::error_count).unwrap_or(0);
+            stats.graphql_errors.fetch_add(graphql_errors as u64, Ordering::SeqCst);
+
+            let failed = match &shared.config.expect_status {
+                Some(expected) => !expected.allows(status.as_u16()),
+                None => status.is_client_error() || status.is_server_error(),
+            };
+            if failed {
+                let message = match &shared.config.expect_status {
+                    Some(expected) => format!("{} returned {}, not one of --expect-status {}", endpoint, status, expected),
+                    None => format!("{} returned {}", endpoint, status),
+                };
+                report_error(stats, endpoint, &status.to_string(), &message);
+            } else if graphql_errors > 0 {
+                let message = format!("{} returned {} graphql errors", endpoint, graphql_errors);
+                report_error(stats, endpoint, "graphql errors", &message);
+            }
+            let failed = failed || graphql_errors > 0 || body_error.is_some();
+            if let Some((capture, sent)) = capture.zip(sent.clone()) {
+                let response = capture::Response { version, status, headers: headers.clone().unwrap_or_default(), body: captured_body };
+                capture
+                    .record(Exchange {
+                        sequence: request.sequence,
+                        latency_ms: latency_start.elapsed().as_secs_f64() * 1000.0,
+                        failed,
+                        local_addr: addrs.map(|(local, _)| local),
+                        remote_addr: addrs.map(|(_, remote)| remote),
+                        request: sent,
+                        response: Some(response),
+                        error: body_error.clone(),
+                    })
+                    .await;
+            }
+            if let Some((samples, sent)) = samples(shared, request).zip(sent) {
+                let received = Received {
+                    status: status.to_string(),
+                    version: format!("{:?}", version),
+                    headers: headers.clone().unwrap_or_default(),
+                    body: response_body.clone(),
+                };
+                samples.offer(sample(request, sent, latency_start, failed, Ok(received)));
+            }
+            Outcome {
+                status: Some(status.as_u16()),
+                headers,
+                response_body,
+                response_size,
+                failed,
+                error: body_error,
+                timed_out,
+                retry_after,
+            }
+        }
+        Err(e) => {
+            let kind = TransportError::classify(&e);
+            stats.record_transport_error(kind);
+            report_error(stats, endpoint, &kind.to_string(), &e.to_string());
+            if let Some((capture, sent)) = capture.zip(sent.clone()) {
+                capture
+                    .record(Exchange {
+                        sequence: request.sequence,
+                        latency_ms: latency_start.elapsed().as_secs_f64() * 1000.0,
+                        failed: true,
+                        local_addr: None,
+                        remote_addr: None,
+                        request: sent,
+                        response: None,
+                        error: Some(e.to_string()),
+                    })
+                    .await;
+            }
+            if let Some((samples, sent)) = samples(shared, request).zip(sent) {
+                samples.offer(sample(request, sent, latency_start, true, Err(e.to_string())));
+            }
+            Outcome {
+                error: Some(e.to_string()),
+                failed: true,
+                timed_out: kind == TransportError::Timeout,
+                ..Outcome::default()
+            }
+        }
+    }
+}
+
+// what --chaos broke on purpose would crowd out the real failures
+fn samples<'a>(shared: &'a Shared, request: &HttpRequest) -> Option<&'a Samples> {
+    shared.samples.as_ref().filter(|_| request.chaos.is_none())
+}
+
+// whether --capture keeps this exchange, one in --capture-rate of them
+fn captures<'a>(shared: &'a Shared, request: &HttpRequest) -> Option<&'a Capture> {
+    shared.capture.as_ref().filter(|capture| request.chaos.is_none() && capture.wants())
+}
+
+fn sample(request: &HttpRequest, sent: Sent, latency_start: Instant, failed: bool, response: Result<Received, String>) -> Sample {
+    Sample {
+        sequence: request.sequence,
+        request: sent,
+        latency_ms: latency_start.elapsed().as_secs_f64() * 1000.0,
+        failed,
+        response,
+    }
+}
+
+// grpc-status stands in for the http status, in checks as well
+async fn send_grpc(
+    shared: &Shared,
+    client: &GrpcClient,
+    endpoint: &str,
+    data: &Value,
+    latency_start: Instant,
+) -> Outcome {
+    let stats = &shared.stats;
+    let config = &shared.config;
+    let message = match client.message(data) {
+        Ok(message) => message,
+        Err(e) => {
+            report_error(stats, endpoint, "grpc encoding", &e);
+            return Outcome { error: Some(e), failed: true, ..Outcome::default() };
+        }
+    };
+
+    let mut attempt = 0;
+    let result = loop {
+        let token = match &shared.tokens {
+            Some(tokens) => match tokens.token().await {
+                Ok(token) => Some(token),
+                Err(e) => break Err(Status::unauthenticated(format!("token: {}", e))),
+            },
+            None => None,
+        };
+        let result = client.call(message.clone(), token.as_deref()).await;
+        let retryable = matches!(&result, Err(status) if grpc::is_retryable(status.code()));
+        if !retryable || attempt >= config.retries {
+            break result;
+        }
+
+        debug!(url = endpoint, attempt = attempt + 1, "retrying");
+        // the run ended during the back-off, this attempt is the last
+        if !wait_until(shared, Instant::now() + config.retry_backoff * 2u32.saturating_pow(attempt)).await {
+            break result;
+        }
+        attempt += 1;
+        stats.retries.fetch_add(1, Ordering::SeqCst);
+    };
+
+    let (code, json, error) = match result {
+        Ok(json) => (Code::Ok, Some(json), None),
+        Err(status) => (status.code(), None, Some(status.message().to_string())),
+    };
+    stats.record_grpc_status(code);
+
+    let response_body = json.as_ref().map(|json| json.to_string());
+    if !stats.checks.is_empty() {
+        record_checks(stats, code as u16, None, response_body.as_deref(), json.as_ref(), latency_start);
+    }
+    if let Some(json) = json.as_ref().filter(|_| !stats.metrics.is_empty()) {
+        stats.metrics.record(json, &stats.timeseries);
+    }
+
+    let failed = code != Code::Ok;
+    if failed {
+        let message = format!("{} returned {} {}", endpoint, grpc::code_name(code), error.as_deref().unwrap_or_default());
+        report_error(stats, endpoint, &format!("grpc {}", grpc::code_name(code)), &message);
+    }
+    Outcome {
+        status: Some(code as u16),
+        headers: None,
+        response_body: response_body.filter(|_| shared.log.is_some()),
+        response_size: None,
+        error,
+        failed,
+        timed_out: code == Code::DeadlineExceeded,
+        retry_after: None,
+    }
+}
+
+// the first error of each kind is logged as it happens, the rest only at
+// debug level; the summary counts them all
+fn report_error(stats: &LoadTestStats, url: &str, kind: &str, message: &str) {
+    match stats.errors.record(kind, message) {
+        true => warn!(url, kind, error = message, "request failed, more like it are counted in the summary"),
+        false => debug!(url, kind, error = message, "request failed"),
+    }
+}
+
+fn record_checks(
+    stats: &LoadTestStats,
+    status: u16,
+    headers: Option<&HeaderMap>,
+    body: Option<&str>,
+    json: Option<&Value>,
+    latency_start: Instant,
+) {
+    let observed = Observed {
+        status,
+        headers,
+        body,
+        json,
+        latency: latency_start.elapsed(),
+    };
+    let (passed, failed) = stats.checks.run(&observed);
+    stats.checks_passed.fetch_add(passed, Ordering::SeqCst);
+    stats.checks_failed.fetch_add(failed, Ordering::SeqCst);
+}
+
+// `sent` ends up with the last attempt as it went out, with --sample-responses
+async fn send_with_retries(
+    shared: &Shared,
+    client: &Client,
+    request: &HttpRequest<'_>,
+    sent: &mut Option<Sent>,
+) -> Result<reqwest::Response, Error> {
+    let config = &shared.config;
+    let mut attempt = 0;
+    loop {
+        let sending = Instant::now();
+        let result = send_data(shared, client, request, sent).await;
+        if result.as_ref().is_ok_and(is_throttled) {
+            request.stats.throttled.fetch_add(1, Ordering::SeqCst);
+        }
+        let retryable = match &result {
+            Ok(response) => is_retryable(response.status(), config.expect_status.as_ref()),
+            Err(_) => true,
+        };
+        if !retryable || attempt >= config.retries {
+            // headers of the attempt that counts
+            if config.timing && result.is_ok() {
+                timing::record(&request.stats.phases.ttfb, sending.elapsed());
+            }
+            return result;
+        }
+
+        debug!(url = request.url, attempt = attempt + 1, "retrying");
+        let told = config.retry_after.zip(result.as_ref().ok().and_then(retry_after));
+        match told {
+            Some((max, after)) => back_off(shared, request.stats, after.min(max)).await,
+            None => {
+                wait_until(shared, Instant::now() + config.retry_backoff * 2u32.saturating_pow(attempt)).await;
+            }
+        }
+        // the run ended during the back-off, this attempt is the last
+        if ended(shared) {
+            return result;
+        }
+        attempt += 1;
+        request.stats.retries.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+// a status --expect-status lists is a success, not something to try again
+fn is_retryable(status: StatusCode, expected: Option<&ExpectedStatus>) -> bool {
+    if expected.is_some_and(|expected| expected.allows(status.as_u16())) {
+        return false;
+    }
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+// an Age above 0, or one of the X-Cache headers cdns and proxies add saying
+// HIT; the response didn't come from the origin just now
+fn is_cache_hit(headers: &HeaderMap) -> bool {
+    if headers.get(AGE).and_then(|age| age.to_str().ok()).and_then(|age| age.trim().parse::<u64>().ok()).is_some_and(|age| age > 0) {
+        return true;
+    }
+    ["x-cache", "x-cache-status", "cf-cache-status", "x-proxy-cache"].iter().any(|name| {
+        headers.get_all(*name).iter().any(|value| value.to_str().is_ok_and(|value| value.to_ascii_uppercase().contains("HIT")))
+    })
+}
+
+fn is_throttled(response: &reqwest::Response) -> bool {
+    match response.status() {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::SERVICE_UNAVAILABLE => response.headers().contains_key(RETRY_AFTER),
+        _ => false,
+    }
+}
+
+// the Retry-After of a 429 or 503, in seconds or as an http date
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    if !matches!(response.status(), StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE) {
+        return None;
+    }
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => {
+            let at = DateTime::parse_from_rfc2822(value).ok()?;
+            Some((at.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+        }
+    }
+}
+
+// waits out a Retry-After, though not past the end of the run
+async fn back_off(shared: &Shared, stats: &LoadTestStats, wait: Duration) {
+    let started = Instant::now();
+    wait_until(shared, started + wait).await;
+    stats.throttle_backoff_us.fetch_add(started.elapsed().as_micros() as u64, Ordering::SeqCst);
+}
+
+async fn send_data(
+    shared: &Shared,
+    client: &Client,
+    http: &HttpRequest<'_>,
+    sent: &mut Option<Sent>,
+) -> Result<reqwest::Response, Error> {
+    let HttpRequest { target, stats, method, url, data, headers, file, trace, .. } = *http;
+    let request = client.request(method.clone(), url);
+    let mut request = match (&shared.targets.targets[target].replay, shared.config.body_format) {
+        (Some(replay), _) => {
+            let request = replay
+                .headers
+                .iter()
+                .fold(request, |request, h| request.header(&h.name, &h.value));
+            match &replay.body {
+                Some(body) => request.body(body.clone()),
+                None => request,
+            }
+        }
+        _ if !has_body(method) => request,
+        (None, BodyFormat::Json) => match shared.config.compress {
+            Some(compress) => {
+                let json = serde_json::to_vec(data).unwrap_or_default();
+                let compressed = compress.encode(&json);
+                stats.uncompressed_bytes.fetch_add(json.len() as u64, Ordering::SeqCst);
+                stats.compressed_bytes.fetch_add(compressed.len() as u64, Ordering::SeqCst);
+                request
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_ENCODING, compress.to_string())
+                    .body(compressed)
+            }
+            None => request.json(data),
+        },
+        (None, BodyFormat::Form) => request.form(&body::flatten(data)),
+        (None, BodyFormat::Xml) => {
+            // always set along with the format
+            let xml = shared.config.xml.as_ref().map(|xml| xml.render(data)).unwrap_or_default();
+            request.header(CONTENT_TYPE, "application/xml").body(xml)
+        }
+        (None, BodyFormat::Bytes) => {
+            let content_type = shared.config.random_bytes.as_ref().map_or("application/octet-stream", |random| &random.content_type);
+            request.header(CONTENT_TYPE, content_type).body(http.bytes.unwrap_or_default().to_vec())
+        }
+        (None, BodyFormat::Protobuf) => request
+            .header(CONTENT_TYPE, "application/x-protobuf")
+            .body(http.protobuf.unwrap_or_default().to_vec()),
+        (None, BodyFormat::Multipart) => {
+            let file = shared.config.file.as_ref().zip(file);
+            request.multipart(body::multipart(data, file))
+        }
+    };
+    if let Some(tokens) = &shared.tokens {
+        request = request.bearer_auth(tokens.token().await?);
+    }
+    if let Some(trace) = trace {
+        request = request.header("traceparent", trace.traceparent());
+    }
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    let mut request = request.build()?;
+    // signed as sent, the server should turn it away for what it is
+    if http.chaos == Some(ChaosKind::Malformed) {
+        chaos::break_body(&mut request, ChaosKind::Malformed);
+    }
+    if let Some(sign) = &shared.config.sign {
+        let body = request.body().and_then(|body| body.as_bytes()).unwrap_or_default();
+        let value = sign.value(body);
+        request.headers_mut().insert(sign.header.clone(), value);
+    }
+    if let Some(Auth::SigV4(signer)) = &shared.config.auth {
+        signer.sign(&mut request, Utc::now());
+    }
+    // streamed multipart bodies have no length up front and aren't counted
+    let sent_bytes = request.body().and_then(|body| body.as_bytes()).map_or(0, |bytes| bytes.len());
+    stats.bytes_sent.fetch_add(sent_bytes as u64, Ordering::SeqCst);
+    if shared.samples.is_some() || shared.capture.is_some() {
+        *sent = Some(Sent {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers: request.headers().clone(),
+            body: request.body().map_or(Some(Vec::new()), |body| body.as_bytes().map(<[u8]>::to_vec)),
+        });
+    }
+    if http.chaos == Some(ChaosKind::Abort) {
+        chaos::break_body(&mut request, ChaosKind::Abort);
+    }
+    if let Some(stream) = &shared.config.stream_body {
+        stream.apply(&mut request);
+    }
+    let (response, hops) = client::execute(client, request).await;
+    if hops > 0 {
+        stats.redirects.record(hops);
+    }
+    response
+}
+
+fn has_body(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+// recorded bodies are logged as json when they are json
+fn recorded_body(body: &str) -> Value {
+    serde_json::from_str(body).unwrap_or_else(|_| Value::String(body.to_string()))
+}
@@ -0,0 +1,151 @@
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+// headers whose values are credentials, by name; the client marks the auth
+// and --api-key-header values it adds as sensitive
+const SECRET_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie", "set-cookie", "x-api-key", "api-key", "x-amz-security-token"];
+// what a credential is written as, without --keep-secrets
+pub const HIDDEN: &str = "<hidden>";
+
+pub fn is_secret(name: &HeaderName, value: &HeaderValue) -> bool {
+    value.is_sensitive() || SECRET_HEADERS.contains(&name.as_str())
+}
+
+// a request as it went out, kept until its outcome says whether to sample it
+#[derive(Debug, Clone)]
+pub struct Sent {
+    pub method: String,
+    pub url: String,
+    pub headers: HeaderMap,
+    // empty without a body, None for streamed multipart bodies
+    pub body: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub struct Sample {
+    pub sequence: u64,
+    pub request: Sent,
+    pub latency_ms: f64,
+    pub failed: bool,
+    pub response: Result<Received, String>,
+}
+
+#[derive(Debug)]
+pub struct Received {
+    pub status: String,
+    pub version: String,
+    pub headers: HeaderMap,
+    pub body: Option<String>,
+}
+
+// --sample-responses: a uniform sample of the failed exchanges and another
+// of the rest, each kept with reservoir sampling so memory stays at `limit`
+#[derive(Debug)]
+pub struct Samples {
+    limit: usize,
+    failed: Mutex<Reservoir>,
+    passed: Mutex<Reservoir>,
+}
+
+#[derive(Debug, Default)]
+struct Reservoir {
+    seen: u64,
+    kept: Vec<Sample>,
+}
+
+impl Samples {
+    pub fn new(limit: usize) -> Self {
+        Samples {
+            limit,
+            failed: Mutex::new(Reservoir::default()),
+            passed: Mutex::new(Reservoir::default()),
+        }
+    }
+
+    pub fn offer(&self, sample: Sample) {
+        let reservoir = match sample.failed {
+            true => &self.failed,
+            false => &self.passed,
+        };
+        let mut reservoir = reservoir.lock().unwrap();
+        reservoir.seen += 1;
+        if reservoir.kept.len() < self.limit {
+            reservoir.kept.push(sample);
+        } else {
+            let slot = rand::thread_rng().gen_range(0..reservoir.seen);
+            if let Some(kept) = reservoir.kept.get_mut(slot as usize) {
+                *kept = sample;
+            }
+        }
+    }
+
+    // what's kept, failures first, leaving none
+    pub fn take(&self) -> Vec<Sample> {
+        let mut taken = std::mem::take(&mut self.failed.lock().unwrap().kept);
+        taken.append(&mut self.passed.lock().unwrap().kept);
+        taken
+    }
+
+    // failures first, successes fill whatever room is left; one file per
+    // exchange named after its sequence, returns how many were written
+    pub fn write(&self, dir: &str, keep_secrets: bool) -> std::io::Result<usize> {
+        let failed = std::mem::take(&mut self.failed.lock().unwrap().kept);
+        let mut passed = std::mem::take(&mut self.passed.lock().unwrap().kept);
+        passed.truncate(self.limit.saturating_sub(failed.len()));
+        fs::create_dir_all(dir)?;
+        let mut written = 0;
+        for sample in failed.iter().chain(&passed) {
+            let name = format!("{:06}-{}.txt", sample.sequence, if sample.failed { "failed" } else { "ok" });
+            fs::write(Path::new(dir).join(name), sample.to_text(keep_secrets))?;
+            written += 1;
+        }
+        Ok(written)
+    }
+}
+
+impl Sample {
+    // the exchange the way it went over the wire, request then response,
+    // credentials hidden unless `keep_secrets`
+    pub fn to_text(&self, keep_secrets: bool) -> String {
+        let mut text = String::new();
+        let outcome = match &self.response {
+            Ok(response) => response.status.clone(),
+            Err(e) => e.clone(),
+        };
+        let _ = writeln!(text, "# sequence {}, {:.2}ms, {}", self.sequence, self.latency_ms, outcome);
+        let _ = writeln!(text, "{} {}", self.request.method, self.request.url);
+        write_headers(&mut text, &self.request.headers, keep_secrets);
+        text.push('\n');
+        match &self.request.body {
+            Some(body) => text.push_str(&String::from_utf8_lossy(body)),
+            None => text.push_str("(streamed body, not kept)"),
+        }
+        text.push_str("\n\n");
+        match &self.response {
+            Ok(response) => {
+                let _ = writeln!(text, "{} {}", response.version, response.status);
+                write_headers(&mut text, &response.headers, keep_secrets);
+                text.push('\n');
+                text.push_str(response.body.as_deref().unwrap_or("(body couldn't be read)"));
+                text.push('\n');
+            }
+            Err(e) => {
+                let _ = writeln!(text, "no response: {}", e);
+            }
+        }
+        text
+    }
+}
+
+fn write_headers(text: &mut String, headers: &HeaderMap, keep_secrets: bool) {
+    for (name, value) in headers {
+        let _ = match !keep_secrets && is_secret(name, value) {
+            true => writeln!(text, "{}: {}", name, HIDDEN),
+            false => writeln!(text, "{}: {}", name, String::from_utf8_lossy(value.as_bytes())),
+        };
+    }
+}
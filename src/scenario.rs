@@ -0,0 +1,261 @@
+use reqwest::Method;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::jsonpath;
+use crate::setup::Extract;
+use crate::target::{Operation, Target};
+use crate::template;
+use crate::units::parse_duration;
+
+// --scenario flow.yaml: every virtual user walks the same steps over and
+// over instead of sending one request at a time, e.g. create a job, poll
+// it until it's done, then fetch the result:
+//
+//   - request: POST /jobs
+//     data: job.json
+//     extract: {job: $.id}
+//   - if: status == 201
+//     then:
+//       - loop: 20
+//         until: $.state == done
+//         steps:
+//           - sleep: 500ms
+//           - request: GET /jobs/{{vu.job}}
+//       - request: GET /jobs/{{vu.job}}/result
+//
+// what a step extracts fills the {{vu.<name>}} placeholders of the steps
+// after it, for that pass of that user
+#[derive(Debug, Clone, Serialize)]
+pub struct Scenario {
+    pub path: String,
+    // how many request steps it has, each one an endpoint of the run
+    pub requests: usize,
+    #[serde(skip)]
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    // the endpoint it sends to, and the variables taken from its response
+    Request { target: usize, extract: Vec<Extract> },
+    Sleep(Duration),
+    If { condition: Condition, then: Vec<Step>, otherwise: Vec<Step> },
+    // at most `times` passes over `steps`, fewer once `until` holds after one
+    Loop { times: u64, until: Option<Condition>, steps: Vec<Step> },
+}
+
+// "status == 201" or "$.state != done"
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub text: String,
+    subject: Subject,
+    op: Op,
+    value: Value,
+}
+
+#[derive(Debug, Clone)]
+enum Subject {
+    Status,
+    Path(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+// the last response of the pass, what the conditions look at
+#[derive(Debug, Default)]
+pub struct Last {
+    pub status: Option<u16>,
+    pub json: Option<Value>,
+}
+
+impl Scenario {
+    // the steps and the endpoints their requests go to; a /path is
+    // relative to `base`, the single --endpoint, a data file to the
+    // scenario's own directory
+    pub fn load(path: &str, base: Option<&str>) -> Result<(Self, Vec<Target>), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let root: Value = serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        let steps = match &root {
+            Value::Object(object) => object.get("steps").ok_or_else(|| format!("{}: expected a list of steps", path))?,
+            steps => steps,
+        };
+        let mut targets = Vec::new();
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        let steps = parse_steps(steps, "steps", base, dir, &mut targets).map_err(|e| format!("{}: {}", path, e))?;
+        if targets.is_empty() {
+            return Err(format!("{}: no request steps", path));
+        }
+        Ok((Scenario { path: path.to_string(), requests: targets.len(), steps }, targets))
+    }
+}
+
+fn parse_steps(value: &Value, at: &str, base: Option<&str>, dir: &Path, targets: &mut Vec<Target>) -> Result<Vec<Step>, String> {
+    let steps = value.as_array().ok_or_else(|| format!("{}: expected a list of steps", at))?;
+    steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| parse_step(step, &format!("{}[{}]", at, index), base, dir, targets))
+        .collect()
+}
+
+fn parse_step(value: &Value, at: &str, base: Option<&str>, dir: &Path, targets: &mut Vec<Target>) -> Result<Step, String> {
+    let step = value.as_object().ok_or_else(|| format!("{}: expected request, sleep, if or loop", at))?;
+    let kind = ["request", "sleep", "if", "loop"]
+        .into_iter()
+        .find(|kind| step.contains_key(*kind))
+        .ok_or_else(|| format!("{}: expected request, sleep, if or loop", at))?;
+    let allowed: &[&str] = match kind {
+        "request" => &["request", "data", "body", "extract"],
+        "sleep" => &["sleep"],
+        "if" => &["if", "then", "else"],
+        _ => &["loop", "until", "steps"],
+    };
+    if let Some(key) = step.keys().find(|key| !allowed.contains(&key.as_str())) {
+        return Err(format!("{}: unexpected {} in a {} step", at, key, kind));
+    }
+    match kind {
+        "request" => parse_request(step, at, base, dir, targets),
+        "sleep" => {
+            let pause = match &step["sleep"] {
+                Value::String(pause) => pause.clone(),
+                Value::Number(secs) => secs.to_string(),
+                _ => return Err(format!("{}: expected a duration to sleep, e.g. 500ms", at)),
+            };
+            Ok(Step::Sleep(parse_duration(&pause).map_err(|e| format!("{}: {}", at, e))?))
+        }
+        "if" => Ok(Step::If {
+            condition: parse_condition(&step["if"], at)?,
+            then: parse_steps(step.get("then").ok_or_else(|| format!("{}: an if needs then steps", at))?, &format!("{}.then", at), base, dir, targets)?,
+            otherwise: match step.get("else") {
+                Some(steps) => parse_steps(steps, &format!("{}.else", at), base, dir, targets)?,
+                None => Vec::new(),
+            },
+        }),
+        _ => {
+            let times = step["loop"]
+                .as_u64()
+                .filter(|&times| times > 0)
+                .ok_or_else(|| format!("{}: loop is the most passes over its steps, a positive number", at))?;
+            Ok(Step::Loop {
+                times,
+                until: step.get("until").map(|until| parse_condition(until, at)).transpose()?,
+                steps: parse_steps(step.get("steps").ok_or_else(|| format!("{}: a loop needs steps", at))?, &format!("{}.steps", at), base, dir, targets)?,
+            })
+        }
+    }
+}
+
+// "GET /jobs/{{vu.job}}", its body from a data file or inline
+fn parse_request(step: &Map<String, Value>, at: &str, base: Option<&str>, dir: &Path, targets: &mut Vec<Target>) -> Result<Step, String> {
+    let usage = || format!("{}: expected request: METHOD url, e.g. GET /jobs/{{{{vu.job}}}}", at);
+    let spec = step["request"].as_str().ok_or_else(usage)?;
+    let (method, url) = spec.trim().split_once(char::is_whitespace).ok_or_else(usage)?;
+    let (method, url) = (method.to_ascii_uppercase(), url.trim());
+    Method::from_bytes(method.as_bytes()).map_err(|_| format!("{}: invalid http method {}", at, method))?;
+    let url = match (url.starts_with("http://") || url.starts_with("https://"), base) {
+        (true, _) => url.to_string(),
+        (false, Some(base)) => format!("{}/{}", base.trim_end_matches('/'), url.trim_start_matches('/')),
+        (false, None) => return Err(format!("{}: {} needs a single --endpoint, the base url of the scenario's paths", at, url)),
+    };
+    let body = match (step.get("data"), step.get("body")) {
+        (Some(_), Some(_)) => return Err(format!("{}: data and body can't both be given", at)),
+        (Some(Value::String(path)), None) => Some(template::read(&dir.join(path).to_string_lossy())?),
+        (Some(_), None) => return Err(format!("{}: data is the path of a json template", at)),
+        (None, body) => body.cloned(),
+    };
+    let extract = match step.get("extract") {
+        Some(Value::Object(extract)) => extract
+            .iter()
+            .map(|(name, from)| match from.as_str().map(str::trim) {
+                Some(from) if !from.is_empty() => Ok(Extract { name: name.clone(), from: from.to_string() }),
+                _ => Err(format!("{}: extract {}: expected $.path or header:<name>", at, name)),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(format!("{}: extract is a map of name: $.path or name: header:<name>", at)),
+        None => Vec::new(),
+    };
+    let target = targets.len();
+    targets.push(Target {
+        url,
+        weight: 1.0,
+        method: None,
+        tags: Default::default(),
+        replay: None,
+        operation: Some(Operation { id: format!("step {}", target + 1), method, body }),
+    });
+    Ok(Step::Request { target, extract })
+}
+
+fn parse_condition(value: &Value, at: &str) -> Result<Condition, String> {
+    let text = value.as_str().ok_or_else(|| format!("{}: expected a condition such as status == 201", at))?.trim();
+    let usage = || format!("{}: {}: expected status or $.path, then ==, !=, <, <=, > or >=, then a value", at, text);
+    let (subject, op, value) = [("==", Op::Eq), ("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)]
+        .into_iter()
+        .find_map(|(token, op)| text.split_once(token).map(|(subject, value)| (subject.trim(), op, value.trim())))
+        .ok_or_else(usage)?;
+    let subject = match subject {
+        "status" => Subject::Status,
+        path if path.starts_with('$') => Subject::Path(path.to_string()),
+        _ => return Err(usage()),
+    };
+    if value.is_empty() {
+        return Err(usage());
+    }
+    // a bare word is a string, quoted or not
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.trim_matches('\'').to_string()));
+    Ok(Condition { text: text.to_string(), subject, op, value })
+}
+
+impl Condition {
+    // nothing to look at, no response or no such field, only holds for !=
+    pub fn holds(&self, last: &Last) -> bool {
+        let actual = match &self.subject {
+            Subject::Status => last.status.map(Value::from),
+            Subject::Path(path) => last.json.as_ref().and_then(|json| jsonpath::select(json, path)).cloned(),
+        };
+        match actual {
+            Some(actual) => compare(&actual, self.op, &self.value),
+            None => self.op == Op::Ne,
+        }
+    }
+}
+
+// numbers, or strings of them, by value; anything else only equals or not
+fn compare(actual: &Value, op: Op, expected: &Value) -> bool {
+    let number = |value: &Value| match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    };
+    let text = |value: &Value| match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    match (number(actual), number(expected)) {
+        (Some(a), Some(b)) => match op {
+            Op::Eq => a == b,
+            Op::Ne => a != b,
+            Op::Lt => a < b,
+            Op::Le => a <= b,
+            Op::Gt => a > b,
+            Op::Ge => a >= b,
+        },
+        _ => match op {
+            Op::Eq => text(actual) == text(expected),
+            Op::Ne => text(actual) != text(expected),
+            _ => false,
+        },
+    }
+}
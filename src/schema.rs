@@ -0,0 +1,539 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use tracing::warn;
+
+// a payload nested deeper than this is only checked down to here, and a
+// chain of $refs longer than this is taken for a cycle
+const MAX_DEPTH: usize = 32;
+// keywords the checks go by, and those that hold schemas to go into
+const CHECKED: &[&str] = &[
+    "type", "enum", "const", "minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum", "multipleOf", "minLength", "maxLength",
+    "pattern", "minItems", "maxItems", "uniqueItems", "minProperties", "maxProperties", "required", "$ref",
+];
+const SUBSCHEMA: &[&str] = &["items", "additionalItems", "additionalProperties", "not"];
+const SUBSCHEMA_LISTS: &[&str] = &["prefixItems", "allOf", "anyOf", "oneOf"];
+const SUBSCHEMA_MAPS: &[&str] = &["properties", "patternProperties", "$defs", "definitions"];
+// annotations, nothing in them to check
+const ANNOTATIONS: &[&str] = &[
+    "$schema", "$id", "$anchor", "$comment", "title", "description", "default", "examples", "deprecated", "readOnly", "writeOnly",
+];
+// errors kept per payload, the rest only counted
+const MAX_ERRORS: usize = 8;
+
+// what becomes of a body that doesn't match the --validate-payloads schema
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Invalid {
+    // not sent
+    Skip,
+    // numbers clamped, strings and arrays cut to length, unknown properties
+    // dropped and so on; sent if that's enough, skipped if not
+    Fix,
+}
+
+impl Invalid {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "skip" => Ok(Invalid::Skip),
+            "fix" => Ok(Invalid::Fix),
+            _ => Err(format!("--invalid-payloads {}: expected skip or fix", name)),
+        }
+    }
+}
+
+// what became of a payload
+#[derive(Debug, PartialEq)]
+pub enum Checked {
+    Valid,
+    // invalid, and why, but fine once fixed up
+    Fixed(Vec<String>),
+    // not to be sent, and why
+    Invalid(Vec<String>),
+}
+
+// --validate-payloads: a json schema every body is held against before it's
+// sent, so a template that generates what the api doesn't accept shows up as
+// such instead of as server errors. The keywords a generated payload can get
+// wrong are checked: type, enum, const, the numeric bounds, multipleOf,
+// lengths, pattern, items, prefixItems, uniqueItems, properties, required,
+// additionalProperties, patternProperties, allOf, anyOf, oneOf, not and local
+// $refs; format and the rest are left alone, with a warning naming them,
+// and a $ref that doesn't lead to a schema fails the load
+#[derive(Debug, Clone, Serialize)]
+pub struct Schema {
+    pub path: String,
+    pub invalid: Invalid,
+    #[serde(skip)]
+    root: Value,
+    #[serde(skip)]
+    patterns: HashMap<String, Regex>,
+}
+
+impl Schema {
+    pub fn load(path: &str, invalid: Invalid) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let root: Value = serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        if !root.is_object() && !root.is_boolean() {
+            return Err(format!("{}: a json schema is an object", path));
+        }
+        let mut patterns = HashMap::new();
+        let mut unchecked = BTreeSet::new();
+        collect(&root, &root, "#", &mut patterns, &mut unchecked).map_err(|e| format!("{}: {}", path, e))?;
+        if !unchecked.is_empty() {
+            let keywords: Vec<&str> = unchecked.iter().map(String::as_str).collect();
+            warn!(schema = path, keywords = keywords.join(", "), "payloads aren't checked against these schema keywords");
+        }
+        Ok(Schema { path: path.to_string(), invalid, root, patterns })
+    }
+
+    // the payload checked and, with --invalid-payloads fix, fixed up in place
+    pub fn apply(&self, value: &mut Value) -> Checked {
+        let errors = self.validate(value);
+        if errors.is_empty() {
+            return Checked::Valid;
+        }
+        if self.invalid == Invalid::Fix {
+            let mut fixed = value.clone();
+            self.fix(&mut fixed);
+            if self.validate(&fixed).is_empty() {
+                *value = fixed;
+                return Checked::Fixed(errors);
+            }
+        }
+        Checked::Invalid(errors)
+    }
+
+    // what's wrong with the payload, by where in it: "$.age: 120 is above the
+    // maximum of 99"; empty if nothing is
+    pub fn validate(&self, value: &Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        self.check(&self.root, value, "$", 0, &mut errors);
+        errors
+    }
+
+    // the mechanical repairs; what's still wrong afterwards is left to
+    // validate to find
+    pub fn fix(&self, value: &mut Value) {
+        self.repair(&self.root, value, 0);
+    }
+
+    // load made sure every $ref leads somewhere
+    fn resolve<'a>(&'a self, mut schema: &'a Value) -> &'a Value {
+        for _ in 0..MAX_DEPTH {
+            let Some(reference) = schema.get("$ref").and_then(Value::as_str) else { break };
+            let Some(target) = reference.strip_prefix('#').and_then(|pointer| self.root.pointer(pointer)) else { break };
+            schema = target;
+        }
+        schema
+    }
+
+    fn check(&self, schema: &Value, value: &Value, at: &str, depth: usize, errors: &mut Vec<String>) {
+        if depth > MAX_DEPTH || errors.len() >= MAX_ERRORS {
+            return;
+        }
+        let schema = self.resolve(schema);
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return errors.push(format!("{}: not allowed", at)),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        // this level's own, nested ones go straight into errors
+        let mut messages = Vec::new();
+
+        if let Some(kinds) = schema.get("type") {
+            let kinds: Vec<&str> = match kinds {
+                Value::String(kind) => vec![kind.as_str()],
+                Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !kinds.is_empty() && !kinds.iter().any(|kind| is_type(value, kind)) {
+                return errors.push(format!("{}: {} where {} is expected", at, describe(value), kinds.join(" or ")));
+            }
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            if !allowed.contains(value) {
+                messages.push(format!("{} is not one of the enum values", short(value)));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                messages.push(format!("{} is not {}", short(value), short(expected)));
+            }
+        }
+
+        match value {
+            Value::Number(n) => {
+                let n = n.as_f64().unwrap_or_default();
+                let bound = |name: &str| schema.get(name).and_then(Value::as_f64);
+                if let Some(min) = bound("minimum").filter(|min| n < *min) {
+                    messages.push(format!("{} is below the minimum of {}", n, min));
+                }
+                if let Some(max) = bound("maximum").filter(|max| n > *max) {
+                    messages.push(format!("{} is above the maximum of {}", n, max));
+                }
+                if let Some(min) = bound("exclusiveMinimum").filter(|min| n <= *min) {
+                    messages.push(format!("{} is not above the exclusive minimum of {}", n, min));
+                }
+                if let Some(max) = bound("exclusiveMaximum").filter(|max| n >= *max) {
+                    messages.push(format!("{} is not below the exclusive maximum of {}", n, max));
+                }
+                if let Some(step) = bound("multipleOf").filter(|step| *step > 0.0) {
+                    let ratio = n / step;
+                    if (ratio - ratio.round()).abs() > 1e-9 {
+                        messages.push(format!("{} is not a multiple of {}", n, step));
+                    }
+                }
+            }
+            Value::String(s) => {
+                let length = s.chars().count() as u64;
+                if let Some(min) = schema.get("minLength").and_then(Value::as_u64).filter(|min| length < *min) {
+                    messages.push(format!("{} characters, fewer than the minLength of {}", length, min));
+                }
+                if let Some(max) = schema.get("maxLength").and_then(Value::as_u64).filter(|max| length > *max) {
+                    messages.push(format!("{} characters, more than the maxLength of {}", length, max));
+                }
+                if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                    if self.patterns.get(pattern).is_some_and(|regex| !regex.is_match(s)) {
+                        messages.push(format!("{} doesn't match the pattern {}", short(value), pattern));
+                    }
+                }
+            }
+            Value::Array(items) => {
+                let count = items.len() as u64;
+                if let Some(min) = schema.get("minItems").and_then(Value::as_u64).filter(|min| count < *min) {
+                    messages.push(format!("{} items, fewer than the minItems of {}", count, min));
+                }
+                if let Some(max) = schema.get("maxItems").and_then(Value::as_u64).filter(|max| count > *max) {
+                    messages.push(format!("{} items, more than the maxItems of {}", count, max));
+                }
+                if schema.get("uniqueItems").and_then(Value::as_bool).unwrap_or(false) {
+                    if let Some(index) = (1..items.len()).find(|&i| items[..i].contains(&items[i])) {
+                        messages.push(format!("item {} repeats an earlier one", index));
+                    }
+                }
+                for (index, item) in items.iter().enumerate() {
+                    if let Some(item_schema) = item_schema(schema, index) {
+                        self.check(item_schema, item, &format!("{}[{}]", at, index), depth + 1, errors);
+                    }
+                }
+            }
+            Value::Object(object) => {
+                let count = object.len() as u64;
+                if let Some(min) = schema.get("minProperties").and_then(Value::as_u64).filter(|min| count < *min) {
+                    messages.push(format!("{} properties, fewer than the minProperties of {}", count, min));
+                }
+                if let Some(max) = schema.get("maxProperties").and_then(Value::as_u64).filter(|max| count > *max) {
+                    messages.push(format!("{} properties, more than the maxProperties of {}", count, max));
+                }
+                for name in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        messages.push(format!("required property {} is missing", name));
+                    }
+                }
+                for (name, property) in object {
+                    let at = property_path(at, name);
+                    match self.property_schemas(schema, name) {
+                        Some(schemas) => {
+                            for property_schema in schemas {
+                                self.check(property_schema, property, &at, depth + 1, errors);
+                            }
+                        }
+                        None => errors.push(format!("{}: not an allowed property", at)),
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        errors.extend(messages.into_iter().map(|message| format!("{}: {}", at, message)));
+        for sub in schema.get("allOf").and_then(Value::as_array).into_iter().flatten() {
+            self.check(sub, value, at, depth + 1, errors);
+        }
+        if let Some(subs) = schema.get("anyOf").and_then(Value::as_array) {
+            if !subs.iter().any(|sub| self.matches(sub, value, depth)) {
+                errors.push(format!("{}: matches none of the anyOf schemas", at));
+            }
+        }
+        if let Some(subs) = schema.get("oneOf").and_then(Value::as_array) {
+            let matching = subs.iter().filter(|sub| self.matches(sub, value, depth)).count();
+            if matching != 1 {
+                errors.push(format!("{}: matches {} of the oneOf schemas instead of one", at, matching));
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.matches(not, value, depth) {
+                errors.push(format!("{}: matches the schema under not", at));
+            }
+        }
+    }
+
+    fn matches(&self, schema: &Value, value: &Value, depth: usize) -> bool {
+        let mut errors = Vec::new();
+        self.check(schema, value, "$", depth + 1, &mut errors);
+        errors.is_empty()
+    }
+
+    // the schemas a property is held to; none if it isn't allowed at all
+    fn property_schemas<'a>(&'a self, schema: &'a Map<String, Value>, name: &str) -> Option<Vec<&'a Value>> {
+        let mut schemas: Vec<&Value> = schema.get("properties").and_then(|properties| properties.get(name)).into_iter().collect();
+        if let Some(Value::Object(patterns)) = schema.get("patternProperties") {
+            for (pattern, property) in patterns {
+                if self.patterns.get(pattern).is_some_and(|regex| regex.is_match(name)) {
+                    schemas.push(property);
+                }
+            }
+        }
+        if !schemas.is_empty() {
+            return Some(schemas);
+        }
+        match schema.get("additionalProperties") {
+            Some(Value::Bool(false)) => None,
+            Some(additional) => Some(vec![additional]),
+            None => Some(Vec::new()),
+        }
+    }
+
+    fn repair(&self, schema: &Value, value: &mut Value, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let Value::Object(schema) = self.resolve(schema) else { return };
+        let kinds: Vec<&str> = match schema.get("type") {
+            Some(Value::String(kind)) => vec![kind.as_str()],
+            Some(Value::Array(kinds)) => kinds.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+
+        // "42" for a number and the like
+        if !kinds.is_empty() && !kinds.iter().any(|kind| is_type(value, kind)) {
+            if let Some(converted) = kinds.iter().find_map(|kind| convert(value, kind)) {
+                *value = converted;
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            *value = expected.clone();
+        }
+        if let Some(first) = schema.get("enum").and_then(Value::as_array).filter(|allowed| !allowed.contains(value)).and_then(|a| a.first()) {
+            *value = first.clone();
+        }
+
+        match value {
+            Value::Number(n) => {
+                let integer = kinds.contains(&"integer") && !kinds.contains(&"number");
+                let mut x = n.as_f64().unwrap_or_default();
+                let bound = |name: &str| schema.get(name).and_then(Value::as_f64);
+                if let Some(min) = bound("minimum") {
+                    x = x.max(min);
+                }
+                if let Some(max) = bound("maximum") {
+                    x = x.min(max);
+                }
+                // only integers have a next value to move to
+                if integer {
+                    x = x.round();
+                    if let Some(min) = bound("exclusiveMinimum").filter(|min| x <= *min) {
+                        x = min.floor() + 1.0;
+                    }
+                    if let Some(max) = bound("exclusiveMaximum").filter(|max| x >= *max) {
+                        x = max.ceil() - 1.0;
+                    }
+                }
+                if x != n.as_f64().unwrap_or_default() || integer {
+                    *value = number(x, integer);
+                }
+            }
+            Value::String(s) => {
+                if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                    if s.chars().count() as u64 > max {
+                        *s = s.chars().take(max as usize).collect();
+                    }
+                }
+            }
+            Value::Array(items) => {
+                if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                    items.truncate(max as usize);
+                }
+                if schema.get("uniqueItems").and_then(Value::as_bool).unwrap_or(false) {
+                    let mut unique: Vec<Value> = Vec::with_capacity(items.len());
+                    for item in items.drain(..) {
+                        if !unique.contains(&item) {
+                            unique.push(item);
+                        }
+                    }
+                    *items = unique;
+                }
+                for (index, item) in items.iter_mut().enumerate() {
+                    if let Some(item_schema) = item_schema(schema, index) {
+                        self.repair(item_schema, item, depth + 1);
+                    }
+                }
+            }
+            Value::Object(object) => {
+                object.retain(|name, _| self.property_schemas(schema, name).is_some());
+                let names: Vec<String> = object.keys().cloned().collect();
+                for name in names {
+                    for property_schema in self.property_schemas(schema, &name).unwrap_or_default() {
+                        if let Some(property) = object.get_mut(&name) {
+                            self.repair(property_schema, property, depth + 1);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        for sub in schema.get("allOf").and_then(Value::as_array).into_iter().flatten() {
+            self.repair(sub, value, depth + 1);
+        }
+    }
+}
+
+// the schema of the items at `index`: prefixItems, or items as a list as
+// older drafts have it, then items
+fn item_schema(schema: &Map<String, Value>, index: usize) -> Option<&Value> {
+    let tuple = schema.get("prefixItems").or_else(|| schema.get("items").filter(|items| items.is_array()));
+    match tuple.and_then(Value::as_array) {
+        Some(tuple) if index < tuple.len() => Some(&tuple[index]),
+        Some(_) => schema.get("additionalItems").or_else(|| schema.get("items").filter(|items| !items.is_array())),
+        None => schema.get("items"),
+    }
+}
+
+fn is_type(value: &Value, kind: &str) -> bool {
+    match kind {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
+
+fn convert(value: &Value, kind: &str) -> Option<Value> {
+    match (kind, value) {
+        ("number" | "integer", Value::String(s)) => s.trim().parse::<f64>().ok().filter(|n| n.is_finite()).map(|n| number(n, kind == "integer")),
+        ("integer", Value::Number(n)) => n.as_f64().map(|n| number(n, true)),
+        ("string", Value::Number(_) | Value::Bool(_)) => Some(Value::String(value.to_string())),
+        ("boolean", Value::String(s)) => s.parse::<bool>().ok().map(Value::Bool),
+        _ => None,
+    }
+}
+
+fn number(n: f64, integer: bool) -> Value {
+    match integer {
+        true => Value::from(n.round() as i64),
+        false => serde_json::Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null),
+    }
+}
+
+fn describe(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+// the value as it reads in a message, cut short
+fn short(value: &Value) -> String {
+    let text = value.to_string();
+    match text.char_indices().nth(40) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+fn property_path(at: &str, name: &str) -> String {
+    let mut path = at.to_string();
+    match name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') && !name.is_empty() {
+        true => {
+            let _ = write!(path, ".{}", name);
+        }
+        false => {
+            let _ = write!(path, "[{}]", Value::String(name.to_string()));
+        }
+    }
+    path
+}
+
+// goes through the schema at `at` and the ones nested in it: every pattern
+// and patternProperties key compiled once up front, every $ref followed to
+// where it leads, and the keywords the checks don't know of into `unchecked`
+fn collect(schema: &Value, root: &Value, at: &str, patterns: &mut HashMap<String, Regex>, unchecked: &mut BTreeSet<String>) -> Result<(), String> {
+    let Value::Object(object) = schema else { return Ok(()) };
+    for (keyword, value) in object {
+        let keyword = keyword.as_str();
+        let nested = format!("{}/{}", at, keyword);
+        match keyword {
+            "pattern" => {
+                if let Value::String(pattern) = value {
+                    compile(pattern, patterns)?;
+                }
+            }
+            "$ref" => follow(value, root, &nested)?,
+            _ if SUBSCHEMA.contains(&keyword) => match value {
+                Value::Array(items) => {
+                    for (index, item) in items.iter().enumerate() {
+                        collect(item, root, &format!("{}/{}", nested, index), patterns, unchecked)?;
+                    }
+                }
+                _ => collect(value, root, &nested, patterns, unchecked)?,
+            },
+            _ if SUBSCHEMA_LISTS.contains(&keyword) => {
+                for (index, item) in value.as_array().into_iter().flatten().enumerate() {
+                    collect(item, root, &format!("{}/{}", nested, index), patterns, unchecked)?;
+                }
+            }
+            _ if SUBSCHEMA_MAPS.contains(&keyword) => {
+                for (name, item) in value.as_object().into_iter().flatten() {
+                    if keyword == "patternProperties" {
+                        compile(name, patterns)?;
+                    }
+                    collect(item, root, &format!("{}/{}", nested, name), patterns, unchecked)?;
+                }
+            }
+            _ if CHECKED.contains(&keyword) || ANNOTATIONS.contains(&keyword) => {}
+            _ => {
+                unchecked.insert(keyword.to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+// a $ref has to be a local one that, through any $refs it leads to, ends at
+// a schema; anything else would let every payload through
+fn follow(reference: &Value, root: &Value, at: &str) -> Result<(), String> {
+    let start = reference.as_str().ok_or_else(|| format!("{}: a $ref is a string", at))?;
+    let mut reference = start;
+    for _ in 0..MAX_DEPTH {
+        let pointer = reference
+            .strip_prefix('#')
+            .ok_or_else(|| format!("{}: $ref {} is not in this file, only local ones (#/...) are followed", at, reference))?;
+        let target = root.pointer(pointer).ok_or_else(|| format!("{}: $ref {} points at nothing", at, reference))?;
+        match target.get("$ref") {
+            Some(next) => reference = next.as_str().ok_or_else(|| format!("{}: $ref {} leads to a $ref that isn't a string", at, reference))?,
+            None => return Ok(()),
+        }
+    }
+    Err(format!("{}: $ref {} goes round in a cycle", at, start))
+}
+
+fn compile(pattern: &str, patterns: &mut HashMap<String, Regex>) -> Result<(), String> {
+    if !patterns.contains_key(pattern) {
+        let regex = Regex::new(pattern).map_err(|e| format!("invalid pattern {}: {}", pattern, e))?;
+        patterns.insert(pattern.to_string(), regex);
+    }
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use rand::Rng;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Method;
+use rhai::serde::{from_dynamic, to_dynamic};
+use rhai::{CallFnOptions, Dynamic, Engine, Scope, AST};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, warn};
+
+use crate::checks::Observed;
+use crate::placeholder::Context;
+
+const HOOKS: [&str; 3] = ["generate", "before_request", "check"];
+// what one hook call may run to, a runaway loop fails it rather than
+// holding up its worker for good
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+// --script: a rhai file defining any of
+//   generate(ctx)        -> the body, ctx has sequence, seed and the body
+//                           the template made, if any
+//   before_request(req)  -> req with method, url, headers and body changed
+//   check(res)           -> true or false, or a string saying what failed;
+//                           res has status, headers, body, json and latency_ms
+// a hook that throws or runs past MAX_OPERATIONS leaves the payload or
+// request as it was, and fails the check; only the hooks run, the file's
+// top level never does
+pub struct Script {
+    path: String,
+    engine: Engine,
+    ast: AST,
+    hooks: [bool; 3],
+    // per hook, whether an error has been logged yet
+    warned: [AtomicBool; 3],
+}
+
+// what before_request gets and hands back
+#[derive(Serialize, Deserialize)]
+struct ScriptRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: Value,
+}
+
+// a request as before_request left it, headers on top of the usual ones
+pub struct Prepared {
+    pub method: Method,
+    pub url: String,
+    pub body: Value,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+#[derive(Serialize)]
+struct ScriptResponse<'a> {
+    status: u16,
+    headers: BTreeMap<String, String>,
+    body: Option<&'a str>,
+    json: Option<&'a Value>,
+    latency_ms: f64,
+}
+
+#[derive(Serialize)]
+struct ScriptContext<'a> {
+    sequence: u64,
+    seed: u64,
+    body: &'a Value,
+}
+
+impl Script {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        // scripts can't reach the request's rng, so these don't follow --seed
+        engine.register_fn("random", || rand::thread_rng().gen::<f64>());
+        engine.register_fn("random_int", |min: i64, max: i64| match min < max {
+            true => rand::thread_rng().gen_range(min..=max),
+            false => min,
+        });
+        let ast = engine.compile(&source).map_err(|e| format!("{}: {}", path, e))?;
+        let hooks = HOOKS.map(|hook| ast.iter_functions().any(|f| f.name == hook && f.params.len() == 1));
+        if !hooks.iter().any(|&defined| defined) {
+            return Err(format!("{}: defines none of generate(ctx), before_request(req) or check(res)", path).into());
+        }
+        Ok(Script {
+            path: path.to_string(),
+            engine,
+            ast,
+            hooks,
+            warned: Default::default(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn has_generate(&self) -> bool {
+        self.hooks[0]
+    }
+
+    pub fn has_before_request(&self) -> bool {
+        self.hooks[1]
+    }
+
+    pub fn has_check(&self) -> bool {
+        self.hooks[2]
+    }
+
+    // the body generate(ctx) returns in place of `body`
+    pub fn generate(&self, context: &Context, body: Value) -> Value {
+        let ctx = ScriptContext { sequence: context.sequence, seed: context.seed, body: &body };
+        match self.call(0, &ctx).and_then(|result| from_dynamic::<Value>(&result).map_err(|e| e.to_string())) {
+            Ok(generated) => generated,
+            Err(e) => {
+                self.report(0, &e);
+                body
+            }
+        }
+    }
+
+    // nothing returned leaves the request as it was, as does a method or a
+    // header that isn't valid
+    pub fn before_request(&self, method: &Method, url: String, body: Value) -> Prepared {
+        let request = ScriptRequest { method: method.to_string(), url, headers: BTreeMap::new(), body };
+        let result = self.call(1, &request).and_then(|result| match result.is_unit() {
+            true => Ok(None),
+            false => from_dynamic::<ScriptRequest>(&result).map(Some).map_err(|e| e.to_string()),
+        });
+        let request = match result {
+            Ok(changed) => changed.unwrap_or(request),
+            Err(e) => {
+                self.report(1, &e);
+                request
+            }
+        };
+        let method = Method::from_bytes(request.method.as_bytes()).unwrap_or_else(|_| {
+            self.report(1, &format!("invalid http method: {}", request.method));
+            method.clone()
+        });
+        let headers = request
+            .headers
+            .iter()
+            .filter_map(|(name, value)| match (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+                (Ok(name), Ok(value)) => Some((name, value)),
+                _ => {
+                    self.report(1, &format!("invalid header: {}: {}", name, value));
+                    None
+                }
+            })
+            .collect();
+        Prepared { method, url: request.url, body: request.body, headers }
+    }
+
+    // whether the response passes check(res); nothing returned is a pass
+    pub fn check(&self, observed: &Observed) -> bool {
+        let response = ScriptResponse {
+            status: observed.status,
+            headers: observed
+                .headers
+                .into_iter()
+                .flatten()
+                .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+                .collect(),
+            body: observed.body,
+            json: observed.json,
+            latency_ms: observed.latency.as_secs_f64() * 1000.0,
+        };
+        let result = self.call(2, &response);
+        match result {
+            Ok(result) if result.is_unit() => true,
+            Ok(result) if result.is_bool() => result.as_bool().unwrap_or(false),
+            Ok(result) if result.is_string() => {
+                debug!(path = self.path, reason = %result, "script check failed");
+                false
+            }
+            Ok(result) => {
+                self.report(2, &format!("expected true, false or a string, got {}", result.type_name()));
+                false
+            }
+            Err(e) => {
+                self.report(2, &e);
+                false
+            }
+        }
+    }
+
+    fn call(&self, hook: usize, argument: &impl Serialize) -> Result<Dynamic, String> {
+        let argument = to_dynamic(argument).map_err(|e| e.to_string())?;
+        let options = CallFnOptions::new().eval_ast(false);
+        self.engine
+            .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, HOOKS[hook], (argument,))
+            .map_err(|e| e.to_string())
+    }
+
+    // once per hook, a broken script shouldn't flood the output
+    fn report(&self, hook: usize, error: &str) {
+        match self.warned[hook].swap(true, Ordering::SeqCst) {
+            false => warn!(path = self.path, hook = HOOKS[hook], error, "script hook failed"),
+            true => debug!(path = self.path, hook = HOOKS[hook], error, "script hook failed"),
+        }
+    }
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Script").field("path", &self.path).finish()
+    }
+}
@@ -0,0 +1,169 @@
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Method};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::generator::Generator;
+use crate::jsonpath;
+use crate::placeholder::{Context, Pattern};
+use crate::template::Template;
+use crate::tls::HostOverride;
+
+// wait before a virtual user tries its setup again
+pub const SETUP_RETRY: Duration = Duration::from_secs(1);
+
+// a request sent around the load rather than as part of it, never counted
+// in the stats: --setup each virtual user sends once before its first one,
+// e.g. a login, with what --setup-extract pulls out of the response filling
+// the {{vu.<name>}} placeholders of that user's requests; --global-setup
+// once before the run, its values filling {{run.<name>}}; --teardown once
+// after it, e.g. to delete what --global-setup created
+#[derive(Debug, Clone, Serialize)]
+pub struct Setup {
+    pub method: String,
+    pub url: String,
+    // template of the request body, rendered with the user's number as its
+    // sequence so a sequential --feed gives every user its own row
+    pub data: Option<String>,
+    pub extract: Vec<Extract>,
+    #[serde(skip)]
+    pattern: Pattern,
+    #[serde(skip)]
+    body: Option<Template>,
+    // --host-header and --sni, the way the load's requests get them
+    #[serde(skip)]
+    host_override: Option<HostOverride>,
+}
+
+// name=$.json.path, or name=header:<name> for a response header
+#[derive(Debug, Clone, Serialize)]
+pub struct Extract {
+    pub name: String,
+    pub from: String,
+}
+
+impl Setup {
+    // "POST https://api/login", or just the url for a POST; `flag` is the
+    // option it came from, for the errors
+    pub fn parse(flag: &str, spec: &str, data: Option<String>, extract: &[String]) -> Result<Self, String> {
+        let (method, url) = match spec.trim().split_once(char::is_whitespace) {
+            Some((method, url)) => (method.to_ascii_uppercase(), url.trim().to_string()),
+            None => ("POST".to_string(), spec.trim().to_string()),
+        };
+        Method::from_bytes(method.as_bytes()).map_err(|_| format!("--{} {}: invalid http method {}", flag, spec, method))?;
+        let pattern = Pattern::parse(&url).map_err(|e| format!("--{} {}: {}", flag, spec, e))?;
+        let body = match &data {
+            Some(path) => Some(Template::load(path)?),
+            None => None,
+        };
+        let extract = extract
+            .iter()
+            .map(|spec| match spec.split_once('=') {
+                Some((name, from)) if !name.trim().is_empty() && !from.trim().is_empty() => Ok(Extract {
+                    name: name.trim().to_string(),
+                    from: from.trim().to_string(),
+                }),
+                _ => Err(format!("--{}-extract {}: expected name=$.path or name=header:<name>", flag, spec)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Setup { method, url, data, extract, pattern, body, host_override: None })
+    }
+
+    pub fn host_override(mut self, host_override: Option<HostOverride>) -> Self {
+        self.host_override = host_override;
+        self
+    }
+
+    // the feed columns the url and body refer to
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.pattern.columns().chain(self.body.iter().flat_map(Template::columns))
+    }
+
+    // sends the request as the `sequence`-th one (the user's number, 0 for
+    // the global ones) and extracts its variables; anything but a 2xx or a
+    // value missing from the response fails it
+    pub async fn run(&self, client: &Client, generator: &Generator, sequence: u64, run: &Vars) -> Result<Vars, String> {
+        let mut rng = generator.rng(sequence);
+        let context = Context { run: Some(run), ..generator.context(sequence, &mut rng) };
+        let mut url = self.pattern.render(&mut rng, &context);
+        if let Some(host_override) = &self.host_override {
+            url = host_override.rewrite(&url);
+        }
+        let method = Method::from_bytes(self.method.as_bytes()).expect("checked in parse");
+        let mut request = client.request(method, &url);
+        if let Some(body) = &self.body {
+            request = request.json(&body.generate(&mut rng, &context));
+        }
+        let response = request.send().await.map_err(|e| format!("{} {}: {}", self.method, url, e))?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let text = response.text().await.map_err(|e| format!("{} {}: {}", self.method, url, e))?;
+        if !status.is_success() {
+            return Err(format!("{} {} returned {}", self.method, url, status));
+        }
+        let json: Option<Value> = serde_json::from_str(&text).ok();
+        let mut vars = Vec::new();
+        for extract in &self.extract {
+            let value = extract.value(&headers, json.as_ref());
+            let value = value.ok_or_else(|| format!("{} {}: no {} in the response for {}", self.method, url, extract.from, extract.name))?;
+            vars.push((extract.name.clone(), value));
+        }
+        Ok(Vars(vars))
+    }
+}
+
+impl Extract {
+    // from the response of a setup request or a --scenario step
+    pub fn value(&self, headers: &HeaderMap, json: Option<&Value>) -> Option<String> {
+        match self.from.strip_prefix("header:") {
+            Some(name) => headers.get(name.trim()).and_then(|value| value.to_str().ok()).map(str::to_string),
+            None => json.and_then(|json| jsonpath::select(json, &self.from)).map(|value| match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            }),
+        }
+    }
+}
+
+// the variables a setup request extracted
+#[derive(Debug, Clone, Default)]
+pub struct Vars(Vec<(String, String)>);
+
+impl Vars {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(var, _)| var == name).map(|(_, value)| value.as_str())
+    }
+
+    // replaces a value of the same name
+    pub fn set(&mut self, name: &str, value: String) {
+        match self.0.iter_mut().find(|(var, _)| var == name) {
+            Some((_, old)) => *old = value,
+            None => self.0.push((name.to_string(), value)),
+        }
+    }
+}
+
+// every virtual user's variables, set by its first successful setup and
+// kept from the warm-up into the measured run
+#[derive(Debug)]
+pub struct Users {
+    vars: Vec<OnceLock<Vars>>,
+}
+
+impl Users {
+    pub fn new(count: usize) -> Self {
+        Users { vars: (0..count).map(|_| OnceLock::new()).collect() }
+    }
+
+    pub fn get(&self, user: usize) -> Option<&Vars> {
+        self.vars.get(user).and_then(OnceLock::get)
+    }
+
+    pub fn set(&self, user: usize, vars: Vars) {
+        if let Some(cell) = self.vars.get(user) {
+            let _ = cell.set(vars);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE, HOST};
+use reqwest::Request;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+// streamed bodies can't be hashed before they're sent
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+#[derive(Clone)]
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+// --aws-sigv4 region/service: every request signed with aws signature
+// version 4, credentials looked up once at startup the way the aws cli does
+#[derive(Debug, Clone)]
+pub struct SigV4 {
+    pub region: String,
+    pub service: String,
+    credentials: Credentials,
+}
+
+impl SigV4 {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (region, service) = spec
+            .split_once('/')
+            .filter(|(region, service)| !region.is_empty() && !service.is_empty() && !service.contains('/'))
+            .ok_or_else(|| format!("--aws-sigv4 {}: expected region/service, e.g. us-east-1/execute-api", spec))?;
+        Ok(SigV4 {
+            region: region.to_string(),
+            service: service.to_string(),
+            credentials: Credentials::load()?,
+        })
+    }
+
+    // adds x-amz-date, x-amz-content-sha256, the session token if there is
+    // one, and the authorization header over the request as built
+    pub fn sign(&self, request: &mut Request, now: DateTime<Utc>) {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = &amz_date[..8];
+        let payload = match request.body() {
+            None => hex(&Sha256::digest(b"")),
+            Some(body) => body.as_bytes().map_or(UNSIGNED_PAYLOAD.to_string(), |bytes| hex(&Sha256::digest(bytes))),
+        };
+
+        let headers = request.headers_mut();
+        headers.insert("x-amz-date", header(&amz_date));
+        headers.insert("x-amz-content-sha256", header(&payload));
+        if let Some(token) = &self.credentials.session_token {
+            headers.insert("x-amz-security-token", header(token));
+        }
+
+        // the host header is added on the way out, sign what it will be
+        let url = request.url();
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => String::new(),
+        };
+        let mut signed = vec![(HOST.as_str().to_string(), host)];
+        for name in request.headers().keys() {
+            if name == CONTENT_TYPE || name.as_str().starts_with("x-amz-") {
+                let values: Vec<_> = request
+                    .headers()
+                    .get_all(name)
+                    .iter()
+                    .map(|value| canonical_value(&String::from_utf8_lossy(value.as_bytes())))
+                    .collect();
+                signed.push((name.as_str().to_string(), values.join(",")));
+            }
+        }
+        signed.sort();
+        let signed_headers = signed.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(";");
+        let canonical_headers: String = signed.iter().map(|(name, value)| format!("{}:{}\n", name, value)).collect();
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            request.method(),
+            self.canonical_path(url.path()),
+            canonical_query(url),
+            canonical_headers,
+            signed_headers,
+            payload
+        );
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "{}\n{}\n{}\n{}",
+            ALGORITHM,
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = [date, &self.region, &self.service, "aws4_request"]
+            .iter()
+            .fold(format!("AWS4{}", self.credentials.secret_access_key).into_bytes(), |key, part| {
+                hmac(&key, part.as_bytes())
+            });
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+            ALGORITHM, self.credentials.access_key_id, scope, signed_headers, signature
+        );
+        request.headers_mut().insert(AUTHORIZATION, header(&authorization));
+    }
+
+    // the url's path is already encoded once; every service but s3 wants
+    // each segment encoded again
+    fn canonical_path(&self, path: &str) -> String {
+        let path = if path.is_empty() { "/" } else { path };
+        match self.service.as_str() {
+            "s3" => path.to_string(),
+            _ => path.split('/').map(encode).collect::<Vec<_>>().join("/"),
+        }
+    }
+}
+
+impl Credentials {
+    // AWS_ACCESS_KEY_ID and friends, then the AWS_PROFILE (or default)
+    // profile of the shared credentials file, then of the config file; only
+    // static keys, once, none of the sdk's sso, credential_process, role
+    // assuming or metadata providers, and nothing refreshes them
+    fn load() -> Result<Self, String> {
+        if let (Ok(access_key_id), Ok(secret_access_key)) = (env::var("AWS_ACCESS_KEY_ID"), env::var("AWS_SECRET_ACCESS_KEY")) {
+            return Ok(Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: env::var("AWS_SESSION_TOKEN").ok().filter(|token| !token.is_empty()),
+            });
+        }
+        let profile = env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string());
+        let files = [
+            (aws_file("AWS_SHARED_CREDENTIALS_FILE", "credentials"), profile.clone()),
+            // the config file names every profile but the default "profile x"
+            (
+                aws_file("AWS_CONFIG_FILE", "config"),
+                match profile.as_str() {
+                    "default" => profile.clone(),
+                    _ => format!("profile {}", profile),
+                },
+            ),
+        ];
+        for (path, section) in files {
+            let Some(text) = path.and_then(|path| fs::read_to_string(path).ok()) else { continue };
+            let keys = profile_keys(&text, &section);
+            let get = |key: &str| keys.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+            if let (Some(access_key_id), Some(secret_access_key)) = (get("aws_access_key_id"), get("aws_secret_access_key")) {
+                return Ok(Credentials {
+                    access_key_id,
+                    secret_access_key,
+                    session_token: get("aws_session_token"),
+                });
+            }
+        }
+        Err(format!(
+            "--aws-sigv4: no aws credentials, set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY or add them to the {} profile",
+            profile
+        ))
+    }
+}
+
+// never print the secret
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials").field("access_key_id", &self.access_key_id).finish()
+    }
+}
+
+fn aws_file(var: &str, name: &str) -> Option<PathBuf> {
+    match env::var_os(var) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".aws").join(name)),
+    }
+}
+
+// key = value lines of one [section] of an ini file
+fn profile_keys(text: &str, section: &str) -> Vec<(String, String)> {
+    let mut current = None;
+    let mut keys = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            current = Some(name.trim().to_string());
+        } else if current.as_deref() == Some(section) {
+            if let Some((key, value)) = line.split_once('=') {
+                keys.push((key.trim().to_lowercase(), value.trim().to_string()));
+            }
+        }
+    }
+    keys
+}
+
+// sorted by name then value, both encoded
+fn canonical_query(url: &reqwest::Url) -> String {
+    let mut pairs: Vec<_> = url.query_pairs().map(|(name, value)| (encode(&name), encode(&value))).collect();
+    pairs.sort();
+    pairs.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("&")
+}
+
+// trimmed, runs of spaces down to one
+fn canonical_value(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// everything but the unreserved characters percent-encoded
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac takes any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn header(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,357 @@
+use clap::ArgMatches;
+use reqwest::header::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE};
+use reqwest::{Client, Response};
+use serde::Serialize;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::{sleep, sleep_until, timeout};
+use tracing::{debug, warn};
+
+use crate::auth::Header;
+use crate::histogram::Histogram;
+use crate::report::LatencySummary;
+use crate::stats::format_bytes;
+
+// wait before dialing again after a failed connect or a drop, unless the
+// server asked for another one with a retry: field
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// how often a reconnect wait looks for a stop
+const STOP_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SseConfig {
+    pub endpoint: String,
+    pub duration: u64,
+    pub connections: usize,
+    #[serde(rename = "connect_timeout_secs", serialize_with = "crate::units::serialize_secs")]
+    pub connect_timeout: Duration,
+    pub headers: Vec<Header>,
+    #[serde(skip)]
+    pub output: Option<String>,
+}
+
+impl SseConfig {
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(SseConfig {
+            endpoint: matches.get_one::<String>("endpoint").unwrap().clone(),
+            duration: *matches.get_one::<u64>("time").unwrap(),
+            connections: (*matches.get_one::<usize>("concurrency").unwrap()).max(1),
+            connect_timeout: matches
+                .get_one::<Duration>("timeout")
+                .copied()
+                .unwrap_or(DEFAULT_CONNECT_TIMEOUT),
+            headers: matches
+                .get_many::<String>("header")
+                .unwrap_or_default()
+                .map(|spec| Header::parse(spec))
+                .collect::<Result<_, _>>()?,
+            output: matches.get_one::<String>("output").cloned(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SseStats {
+    pub connects: AtomicU64,
+    pub connect_failures: AtomicU64,
+    // streams the server ended or that broke off before the run was over
+    pub dropped: AtomicU64,
+    pub events: AtomicU64,
+    pub bytes_received: AtomicU64,
+    // from sending the request to the stream's first event
+    pub first_event: Histogram,
+    // between consecutive events of one stream
+    pub inter_event: Histogram,
+    pub start_time: Instant,
+}
+
+impl Default for SseStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SseStats {
+    pub fn new() -> Self {
+        SseStats {
+            connects: AtomicU64::new(0),
+            connect_failures: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            events: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            first_event: Histogram::new(),
+            inter_event: Histogram::new(),
+            start_time: Instant::now(),
+        }
+    }
+
+    pub fn print_summary(&self) {
+        let elapsed = self.start_time.elapsed().as_secs_f64();
+        let events = self.events.load(Ordering::SeqCst);
+        let ms = |histogram: &Histogram, q: f64| histogram.quantile(q) as f64 / 1000.0;
+
+        println!("\n===== SSE Summary =====");
+        println!("total duration:      {:.2}s", elapsed);
+        println!("streams opened:      {}", self.connects.load(Ordering::SeqCst));
+        println!("connect failures:    {}", self.connect_failures.load(Ordering::SeqCst));
+        println!("dropped streams:     {}", self.dropped.load(Ordering::SeqCst));
+        println!("events received:     {}", events);
+        println!("events per second:   {:.2}", events as f64 / elapsed);
+        println!("bytes received:      {}", format_bytes(self.bytes_received.load(Ordering::SeqCst)));
+        for (name, histogram) in [("first event", &self.first_event), ("inter-event", &self.inter_event)] {
+            if histogram.count() > 0 {
+                println!(
+                    "{} p50/p90/p99: {:.2}ms / {:.2}ms / {:.2}ms",
+                    name,
+                    ms(histogram, 0.50),
+                    ms(histogram, 0.90),
+                    ms(histogram, 0.99)
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SseReport {
+    pub duration_secs: f64,
+    pub connects: u64,
+    pub connect_failures: u64,
+    pub dropped: u64,
+    pub events: u64,
+    pub bytes_received: u64,
+    pub events_per_second: f64,
+    pub first_event_ms: Option<LatencySummary>,
+    pub inter_event_ms: Option<LatencySummary>,
+    pub config: SseConfig,
+}
+
+impl SseReport {
+    pub fn new(stats: &SseStats, config: &SseConfig) -> Self {
+        let duration_secs = stats.start_time.elapsed().as_secs_f64();
+        let events = stats.events.load(Ordering::SeqCst);
+        let summary = |histogram: &Histogram| (histogram.count() > 0).then(|| LatencySummary::new(histogram));
+        SseReport {
+            duration_secs,
+            connects: stats.connects.load(Ordering::SeqCst),
+            connect_failures: stats.connect_failures.load(Ordering::SeqCst),
+            dropped: stats.dropped.load(Ordering::SeqCst),
+            events,
+            bytes_received: stats.bytes_received.load(Ordering::SeqCst),
+            events_per_second: events as f64 / duration_secs,
+            first_event_ms: summary(&stats.first_event),
+            inter_event_ms: summary(&stats.inter_event),
+            config: config.clone(),
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+struct Shared {
+    config: SseConfig,
+    client: Client,
+    stats: Arc<SseStats>,
+    deadline: Instant,
+    stop: Arc<AtomicBool>,
+}
+
+impl Shared {
+    fn done(&self) -> bool {
+        self.stop.load(Ordering::SeqCst) || Instant::now() >= self.deadline
+    }
+
+    // waits out a reconnect delay, cut short by the end of the run
+    async fn back_off(&self, delay: Duration) {
+        let until = (Instant::now() + delay).min(self.deadline);
+        while !self.done() && Instant::now() < until {
+            sleep(until.saturating_duration_since(Instant::now()).min(STOP_POLL)).await;
+        }
+    }
+}
+
+enum SessionEnd {
+    Finished,
+    Dropped,
+}
+
+// keeps `connections` event streams open for the whole run, reconnecting
+// dropped ones with Last-Event-ID the way a browser's EventSource does
+pub async fn run(config: SseConfig, stats: Arc<SseStats>, stop: Arc<AtomicBool>) -> Result<SseReport, Box<dyn std::error::Error>> {
+    let client = Client::builder()
+        .connect_timeout(config.connect_timeout)
+        .pool_max_idle_per_host(0)
+        .build()?;
+    let shared = Arc::new(Shared {
+        deadline: stats.start_time + Duration::from_secs(config.duration),
+        config: config.clone(),
+        client,
+        stats: stats.clone(),
+        stop,
+    });
+
+    let handles: Vec<_> = (0..config.connections)
+        .map(|_| tokio::spawn(connection(shared.clone())))
+        .collect();
+    for handle in handles {
+        handle.await?;
+    }
+    Ok(SseReport::new(&stats, &config))
+}
+
+async fn connection(shared: Arc<Shared>) {
+    let stats = &shared.stats;
+    // carries the last event id and retry delay over reconnects
+    let mut parser = EventParser::default();
+    while !shared.done() {
+        let delay = parser.retry.unwrap_or(RECONNECT_DELAY);
+        let sending = Instant::now();
+        let response = match open(&shared, parser.last_id.as_deref()).await.map_err(|e| e.to_string()) {
+            Ok(response) => response,
+            Err(e) => {
+                stats.connect_failures.fetch_add(1, Ordering::SeqCst);
+                warn!(url = %shared.config.endpoint, error = %e, "connect failed");
+                shared.back_off(delay).await;
+                continue;
+            }
+        };
+        stats.connects.fetch_add(1, Ordering::SeqCst);
+
+        if let SessionEnd::Dropped = session(&shared, response, &mut parser, sending).await {
+            stats.dropped.fetch_add(1, Ordering::SeqCst);
+            shared.back_off(parser.retry.unwrap_or(RECONNECT_DELAY)).await;
+        }
+    }
+}
+
+async fn open(shared: &Shared, last_id: Option<&str>) -> Result<Response, Box<dyn std::error::Error>> {
+    let config = &shared.config;
+    let mut request = shared
+        .client
+        .get(&config.endpoint)
+        .header(ACCEPT, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache");
+    for header in &config.headers {
+        request = request.header(&header.name, &header.value);
+    }
+    if let Some(id) = last_id {
+        request = request.header("last-event-id", id);
+    }
+    let response = timeout(config.connect_timeout, request.send())
+        .await
+        .map_err(|_| "connect timed out")??;
+    if !response.status().is_success() {
+        return Err(format!("returned {}", response.status()).into());
+    }
+    let content_type = response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    if !content_type.starts_with("text/event-stream") {
+        return Err(format!("returned content-type {:?}, not text/event-stream", content_type).into());
+    }
+    Ok(response)
+}
+
+async fn session(shared: &Shared, mut response: Response, parser: &mut EventParser, sending: Instant) -> SessionEnd {
+    let stats = &shared.stats;
+    parser.restart();
+    let mut last_event: Option<Instant> = None;
+
+    loop {
+        if shared.stop.load(Ordering::SeqCst) {
+            return SessionEnd::Finished;
+        }
+
+        tokio::select! {
+            _ = sleep_until(shared.deadline.into()) => return SessionEnd::Finished,
+            chunk = response.chunk() => match chunk {
+                Ok(Some(bytes)) => {
+                    stats.bytes_received.fetch_add(bytes.len() as u64, Ordering::SeqCst);
+                    let now = Instant::now();
+                    for _ in 0..parser.feed(&bytes) {
+                        stats.events.fetch_add(1, Ordering::SeqCst);
+                        match last_event {
+                            Some(at) => stats.inter_event.record(now.duration_since(at).as_micros() as u64),
+                            None => stats.first_event.record(now.duration_since(sending).as_micros() as u64),
+                        }
+                        last_event = Some(now);
+                    }
+                }
+                Ok(None) => {
+                    debug!(url = %shared.config.endpoint, "stream ended by the server");
+                    return SessionEnd::Dropped;
+                }
+                Err(e) => {
+                    debug!(url = %shared.config.endpoint, error = %e, "stream broke off");
+                    return SessionEnd::Dropped;
+                }
+            },
+        }
+    }
+}
+
+// the text/event-stream format: an event is the field lines up to a blank
+// line, counted when it carried data; comment lines are keep-alives
+#[derive(Debug, Default)]
+struct EventParser {
+    buffer: Vec<u8>,
+    has_data: bool,
+    last_id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl EventParser {
+    // a new stream starts with nothing half-read
+    fn restart(&mut self) {
+        self.buffer.clear();
+        self.has_data = false;
+    }
+
+    // the number of events `chunk` completed
+    fn feed(&mut self, chunk: &[u8]) -> usize {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = 0;
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n' || *b == b'\r') {
+            // a trailing \r may be the first half of a \r\n
+            if self.buffer[end] == b'\r' && end + 1 == self.buffer.len() {
+                break;
+            }
+            let skip = match (self.buffer[end], self.buffer.get(end + 1)) {
+                (b'\r', Some(b'\n')) => 2,
+                _ => 1,
+            };
+            let line: Vec<u8> = self.buffer.drain(..end + skip).take(end).collect();
+            if self.line(&String::from_utf8_lossy(&line)) {
+                events += 1;
+            }
+        }
+        events
+    }
+
+    // true when the line dispatched an event
+    fn line(&mut self, line: &str) -> bool {
+        if line.is_empty() {
+            return std::mem::take(&mut self.has_data);
+        }
+        if line.starts_with(':') {
+            return false;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "data" => self.has_data = true,
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+}
@@ -0,0 +1,1005 @@
+use reqwest::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tonic::Code;
+
+use crate::adaptive::Levels;
+use crate::checks::Checks;
+use crate::failure::{status_class, ErrorBucket, ErrorBuckets, TransportError};
+use crate::grpc;
+use crate::histogram::{Histogram, HistogramSnapshot};
+use crate::metrics::{Aggregate, MetricSpec, Metrics};
+use crate::monitor::ResourceMonitor;
+use crate::timeseries::TimeSeries;
+use crate::timing::Phases;
+
+// rows of the summary's error table
+const TOP_ERRORS: usize = 10;
+// the summary's latency histogram and the widest bar of its charts
+const HISTOGRAM_BINS: usize = 12;
+const BAR_WIDTH: usize = 40;
+
+// the http versions responses are counted by
+const PROTOCOLS: [(Version, &str); 5] = [
+    (Version::HTTP_09, "HTTP/0.9"),
+    (Version::HTTP_10, "HTTP/1.0"),
+    (Version::HTTP_11, "HTTP/1.1"),
+    (Version::HTTP_2, "HTTP/2"),
+    (Version::HTTP_3, "HTTP/3"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    Duration,
+    RequestLimit,
+    Interrupted,
+    ErrorRate,
+    InputClosed,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            StopReason::Duration => "duration elapsed",
+            StopReason::RequestLimit => "request limit reached",
+            StopReason::Interrupted => "interrupted",
+            StopReason::ErrorRate => "error rate above --abort-on-error-rate",
+            StopReason::InputClosed => "stdin closed",
+        };
+        f.write_str(reason)
+    }
+}
+
+// stats for one slice of the traffic, e.g. a single endpoint
+#[derive(Debug)]
+pub struct GroupStats {
+    pub name: String,
+    pub requests: AtomicU64,
+    pub errors: AtomicU64,
+    // failed requests that ran out of --timeout, left out of the latency
+    pub timeouts: AtomicU64,
+    pub latency: Histogram,
+    // body bytes of the responses read
+    pub response_size: Histogram,
+    status_codes: Mutex<BTreeMap<u16, u64>>,
+}
+
+impl GroupStats {
+    pub fn new(name: impl Into<String>) -> Self {
+        GroupStats {
+            name: name.into(),
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            timeouts: AtomicU64::new(0),
+            latency: Histogram::new(),
+            response_size: Histogram::new(),
+            status_codes: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn record(&self, latency: Duration, failed: bool, status: Option<u16>) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        if failed {
+            self.errors.fetch_add(1, Ordering::SeqCst);
+        }
+        self.latency.record(latency.as_micros() as u64);
+        if let Some(status) = status {
+            *self.status_codes.lock().unwrap().entry(status).or_insert(0) += 1;
+        }
+    }
+
+    // a timeout says how long --timeout is, not how long the request took
+    pub fn record_timeout(&self) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        self.errors.fetch_add(1, Ordering::SeqCst);
+        self.timeouts.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn status_counts(&self) -> BTreeMap<u16, u64> {
+        self.status_codes.lock().unwrap().clone()
+    }
+
+    pub fn latency_ms(&self, q: f64) -> f64 {
+        self.latency.quantile(q) as f64 / 1000.0
+    }
+
+    fn snapshot(&self) -> GroupSnapshot {
+        GroupSnapshot {
+            requests: self.requests.load(Ordering::SeqCst),
+            errors: self.errors.load(Ordering::SeqCst),
+            timeouts: self.timeouts.load(Ordering::SeqCst),
+            latency: self.latency.snapshot(),
+            response_size: self.response_size.snapshot(),
+            status_codes: self.status_counts(),
+        }
+    }
+
+    fn merge(&self, other: &GroupSnapshot) {
+        self.requests.fetch_add(other.requests, Ordering::SeqCst);
+        self.errors.fetch_add(other.errors, Ordering::SeqCst);
+        self.timeouts.fetch_add(other.timeouts, Ordering::SeqCst);
+        self.latency.merge(&other.latency);
+        self.response_size.merge(&other.response_size);
+        let mut codes = self.status_codes.lock().unwrap();
+        for (code, count) in &other.status_codes {
+            *codes.entry(*code).or_insert(0) += count;
+        }
+    }
+}
+
+// the counters of a run so far, sent from distributed workers to the
+// coordinator; the time series isn't included
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub total_requests: u64,
+    pub success_count: u64,
+    pub error_count: u64,
+    pub timeouts: u64,
+    pub retries: u64,
+    pub setups: u64,
+    pub setup_failures: u64,
+    pub throttled: u64,
+    pub throttle_backoff_us: u64,
+    pub graphql_errors: u64,
+    #[serde(default)]
+    pub cache_hits: u64,
+    #[serde(default)]
+    pub invalid_payloads: u64,
+    #[serde(default)]
+    pub fixed_payloads: u64,
+    pub total_duration: u64,
+    pub checks_passed: u64,
+    pub checks_failed: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub compressed_bytes: u64,
+    pub uncompressed_bytes: u64,
+    pub connections_opened: u64,
+    pub connections_reused: u64,
+    #[serde(default)]
+    pub tls_handshakes: u64,
+    #[serde(default)]
+    pub probe_resumption_offered: u64,
+    #[serde(default)]
+    pub dns_lookups: u64,
+    #[serde(default)]
+    pub dns_changes: u64,
+    #[serde(default)]
+    pub drained: u64,
+    #[serde(default)]
+    pub abandoned: u64,
+    #[serde(default)]
+    pub drain_us: u64,
+    // (passed, failed) per check
+    pub checks: Vec<(u64, u64)>,
+    #[serde(default)]
+    pub metrics: Vec<Aggregate>,
+    pub latency: HistogramSnapshot,
+    pub service_time: HistogramSnapshot,
+    pub think_time: HistogramSnapshot,
+    pub response_size: HistogramSnapshot,
+    pub redirects: HistogramSnapshot,
+    pub endpoints: Vec<GroupSnapshot>,
+    pub tags: Vec<GroupSnapshot>,
+    pub templates: Vec<GroupSnapshot>,
+    pub chaos: Vec<GroupSnapshot>,
+    pub status_codes: BTreeMap<u16, u64>,
+    pub transport_errors: Vec<u64>,
+    pub grpc_status: Vec<u64>,
+    pub protocols: Vec<u64>,
+    pub errors: Vec<ErrorBucket>,
+    // dns, connect, tls, ttfb and download
+    pub phases: Vec<HistogramSnapshot>,
+    pub stop_reason: Option<StopReason>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupSnapshot {
+    pub requests: u64,
+    pub errors: u64,
+    pub timeouts: u64,
+    pub latency: HistogramSnapshot,
+    pub response_size: HistogramSnapshot,
+    pub status_codes: BTreeMap<u16, u64>,
+}
+
+#[derive(Debug)]
+pub struct LoadTestStats {
+    pub total_requests: AtomicU64,
+    pub success_count: AtomicU64,
+    pub error_count: AtomicU64,
+    // failed requests that ran out of --timeout; they count in error_count
+    // but not in total_duration or the latency histograms
+    pub timeouts: AtomicU64,
+    pub retries: AtomicU64,
+    // --setup requests of the virtual users, those that failed are tried again
+    pub setups: AtomicU64,
+    pub setup_failures: AtomicU64,
+    // 429s, and 503s with a Retry-After, every attempt counted; and with
+    // --respect-retry-after the time workers spent waiting them out
+    pub throttled: AtomicU64,
+    pub throttle_backoff_us: AtomicU64,
+    pub graphql_errors: AtomicU64,
+    // http responses a cache answered, going by their Age and X-Cache headers
+    pub cache_hits: AtomicU64,
+    // bodies that didn't match --validate-payloads, and those of them sent
+    // fixed up; the rest weren't sent
+    pub invalid_payloads: AtomicU64,
+    pub fixed_payloads: AtomicU64,
+    // what was wrong with the first one
+    pub invalid_payload: OnceLock<String>,
+    pub total_duration: AtomicU64,
+    pub checks_passed: AtomicU64,
+    pub checks_failed: AtomicU64,
+    // request and response bodies, every attempt counted
+    pub bytes_sent: AtomicU64,
+    pub bytes_received: AtomicU64,
+    // --compress bodies as sent and as they were before
+    pub compressed_bytes: AtomicU64,
+    pub uncompressed_bytes: AtomicU64,
+    // what the http responses came over, see client::ConnectionTracker
+    pub connections_opened: AtomicU64,
+    pub connections_reused: AtomicU64,
+    // the connections opened over https, each a full handshake: reqwest's
+    // native-tls keeps no sessions to resume
+    pub tls_handshakes: AtomicU64,
+    // --timing probes that offered the server a session to resume, see
+    // phases.tls_resumed for those it took up
+    pub probe_resumption_offered: AtomicU64,
+    // with --dns-refresh, the lookups made and how often they found the
+    // records changed
+    pub dns_lookups: AtomicU64,
+    pub dns_changes: AtomicU64,
+    // requests being sent right now, and of those sent when the run ended
+    // the ones that finished within --drain-timeout, the ones given up on
+    // after it, and how long the wait took
+    pub in_flight: AtomicU64,
+    pub drained: AtomicU64,
+    pub abandoned: AtomicU64,
+    pub drain_us: AtomicU64,
+    pub checks: Checks,
+    // --metric values read from the response bodies
+    pub metrics: Metrics,
+    pub latency: Histogram,
+    // from the actual send, recorded only with a rate, where latency counts
+    // from the scheduled send time
+    pub service_time: Histogram,
+    // the pauses workers actually took, with a think time
+    pub think_time: Histogram,
+    // body bytes of the responses read, also kept per endpoint
+    pub response_size: Histogram,
+    // hops of the attempts that were redirected
+    pub redirects: Histogram,
+    pub timeseries: TimeSeries,
+    pub endpoints: Vec<GroupStats>,
+    // one group per endpoint tag and per stage, see LoadConfig::tag_labels
+    pub tags: Vec<GroupStats>,
+    // one group per --data template when there's more than one
+    pub templates: Vec<GroupStats>,
+    // one group per --chaos, whose requests count nowhere else
+    pub chaos: Vec<GroupStats>,
+    pub phases: Phases,
+    // arctic's own, see monitor
+    pub resources: ResourceMonitor,
+    // the workers sending with --adaptive
+    pub concurrency: Levels,
+    // every failed request, by cause
+    pub errors: ErrorBuckets,
+    status_codes: Box<[AtomicU64]>,
+    transport_errors: Box<[AtomicU64]>,
+    grpc_status: Box<[AtomicU64]>,
+    protocols: Box<[AtomicU64]>,
+    stop_reason: OnceLock<StopReason>,
+    start_time: RwLock<Instant>,
+}
+
+impl LoadTestStats {
+    pub fn new(checks: Checks, metrics: &[MetricSpec], endpoints: &[String], tags: &[String], templates: &[String], chaos: &[String]) -> Self {
+        LoadTestStats {
+            total_requests: AtomicU64::new(0),
+            success_count: AtomicU64::new(0),
+            error_count: AtomicU64::new(0),
+            timeouts: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            setups: AtomicU64::new(0),
+            setup_failures: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+            throttle_backoff_us: AtomicU64::new(0),
+            graphql_errors: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            invalid_payloads: AtomicU64::new(0),
+            fixed_payloads: AtomicU64::new(0),
+            invalid_payload: OnceLock::new(),
+            total_duration: AtomicU64::new(0),
+            checks_passed: AtomicU64::new(0),
+            checks_failed: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            compressed_bytes: AtomicU64::new(0),
+            uncompressed_bytes: AtomicU64::new(0),
+            connections_opened: AtomicU64::new(0),
+            connections_reused: AtomicU64::new(0),
+            tls_handshakes: AtomicU64::new(0),
+            probe_resumption_offered: AtomicU64::new(0),
+            dns_lookups: AtomicU64::new(0),
+            dns_changes: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            drained: AtomicU64::new(0),
+            abandoned: AtomicU64::new(0),
+            drain_us: AtomicU64::new(0),
+            checks,
+            metrics: Metrics::new(metrics),
+            latency: Histogram::new(),
+            service_time: Histogram::new(),
+            think_time: Histogram::new(),
+            response_size: Histogram::new(),
+            redirects: Histogram::new(),
+            timeseries: TimeSeries::new(),
+            endpoints: endpoints.iter().map(GroupStats::new).collect(),
+            tags: tags.iter().map(GroupStats::new).collect(),
+            templates: templates.iter().map(GroupStats::new).collect(),
+            chaos: chaos.iter().map(GroupStats::new).collect(),
+            phases: Phases::default(),
+            resources: ResourceMonitor::default(),
+            concurrency: Levels::default(),
+            errors: ErrorBuckets::default(),
+            status_codes: (0..600).map(|_| AtomicU64::new(0)).collect(),
+            transport_errors: TransportError::ALL.iter().map(|_| AtomicU64::new(0)).collect(),
+            grpc_status: (0..grpc::CODES).map(|_| AtomicU64::new(0)).collect(),
+            protocols: PROTOCOLS.iter().map(|_| AtomicU64::new(0)).collect(),
+            stop_reason: OnceLock::new(),
+            start_time: RwLock::new(Instant::now()),
+        }
+    }
+
+    // start of the measured window
+    pub fn start_time(&self) -> Instant {
+        *self.start_time.read().unwrap()
+    }
+
+    // moves the start of the measured window to now, once a warm-up is over
+    pub fn restart(&self) {
+        *self.start_time.write().unwrap() = Instant::now();
+        self.timeseries.restart();
+    }
+
+    // a window that ended now after `elapsed`, for stats merged from runs
+    // that are over
+    pub fn backdate(&self, elapsed: Duration) {
+        let now = Instant::now();
+        *self.start_time.write().unwrap() = now.checked_sub(elapsed).unwrap_or(now);
+    }
+
+    pub fn record_status(&self, status: u16) {
+        if let Some(counter) = self.status_codes.get(status as usize) {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub fn record_protocol(&self, version: Version) {
+        if let Some(index) = PROTOCOLS.iter().position(|(v, _)| *v == version) {
+            self.protocols[index].fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::SeqCst);
+        StatsSnapshot {
+            total_requests: load(&self.total_requests),
+            success_count: load(&self.success_count),
+            error_count: load(&self.error_count),
+            timeouts: load(&self.timeouts),
+            retries: load(&self.retries),
+            setups: load(&self.setups),
+            setup_failures: load(&self.setup_failures),
+            throttled: load(&self.throttled),
+            throttle_backoff_us: load(&self.throttle_backoff_us),
+            graphql_errors: load(&self.graphql_errors),
+            cache_hits: load(&self.cache_hits),
+            invalid_payloads: load(&self.invalid_payloads),
+            fixed_payloads: load(&self.fixed_payloads),
+            total_duration: load(&self.total_duration),
+            checks_passed: load(&self.checks_passed),
+            checks_failed: load(&self.checks_failed),
+            bytes_sent: load(&self.bytes_sent),
+            bytes_received: load(&self.bytes_received),
+            compressed_bytes: load(&self.compressed_bytes),
+            uncompressed_bytes: load(&self.uncompressed_bytes),
+            connections_opened: load(&self.connections_opened),
+            connections_reused: load(&self.connections_reused),
+            tls_handshakes: load(&self.tls_handshakes),
+            probe_resumption_offered: load(&self.probe_resumption_offered),
+            dns_lookups: load(&self.dns_lookups),
+            dns_changes: load(&self.dns_changes),
+            drained: load(&self.drained),
+            abandoned: load(&self.abandoned),
+            drain_us: load(&self.drain_us),
+            checks: self.checks.iter().map(|c| (load(&c.passed), load(&c.failed))).collect(),
+            metrics: self.metrics.snapshot(),
+            latency: self.latency.snapshot(),
+            service_time: self.service_time.snapshot(),
+            think_time: self.think_time.snapshot(),
+            response_size: self.response_size.snapshot(),
+            redirects: self.redirects.snapshot(),
+            endpoints: self.endpoints.iter().map(GroupStats::snapshot).collect(),
+            tags: self.tags.iter().map(GroupStats::snapshot).collect(),
+            templates: self.templates.iter().map(GroupStats::snapshot).collect(),
+            chaos: self.chaos.iter().map(GroupStats::snapshot).collect(),
+            status_codes: self.status_counts(),
+            transport_errors: self.transport_errors.iter().map(load).collect(),
+            grpc_status: self.grpc_status.iter().map(load).collect(),
+            protocols: self.protocols.iter().map(load).collect(),
+            errors: self.errors.snapshot(),
+            phases: self.phases.snapshot(),
+            stop_reason: self.stop_reason(),
+        }
+    }
+
+    // adds a snapshot taken from stats of the same shape, i.e. built from
+    // the same config
+    pub fn merge(&self, other: &StatsSnapshot) {
+        let add = |counter: &AtomicU64, value: u64| {
+            counter.fetch_add(value, Ordering::SeqCst);
+        };
+        add(&self.total_requests, other.total_requests);
+        add(&self.success_count, other.success_count);
+        add(&self.error_count, other.error_count);
+        add(&self.timeouts, other.timeouts);
+        add(&self.retries, other.retries);
+        add(&self.setups, other.setups);
+        add(&self.setup_failures, other.setup_failures);
+        add(&self.throttled, other.throttled);
+        add(&self.throttle_backoff_us, other.throttle_backoff_us);
+        add(&self.graphql_errors, other.graphql_errors);
+        add(&self.cache_hits, other.cache_hits);
+        add(&self.invalid_payloads, other.invalid_payloads);
+        add(&self.fixed_payloads, other.fixed_payloads);
+        add(&self.total_duration, other.total_duration);
+        add(&self.checks_passed, other.checks_passed);
+        add(&self.checks_failed, other.checks_failed);
+        add(&self.bytes_sent, other.bytes_sent);
+        add(&self.bytes_received, other.bytes_received);
+        add(&self.compressed_bytes, other.compressed_bytes);
+        add(&self.uncompressed_bytes, other.uncompressed_bytes);
+        add(&self.connections_opened, other.connections_opened);
+        add(&self.connections_reused, other.connections_reused);
+        add(&self.tls_handshakes, other.tls_handshakes);
+        add(&self.probe_resumption_offered, other.probe_resumption_offered);
+        add(&self.dns_lookups, other.dns_lookups);
+        add(&self.dns_changes, other.dns_changes);
+        add(&self.drained, other.drained);
+        add(&self.abandoned, other.abandoned);
+        // the agents drained side by side
+        self.drain_us.fetch_max(other.drain_us, Ordering::SeqCst);
+        for (check, (passed, failed)) in self.checks.iter().zip(&other.checks) {
+            add(&check.passed, *passed);
+            add(&check.failed, *failed);
+        }
+        self.metrics.merge(&other.metrics);
+        self.latency.merge(&other.latency);
+        self.service_time.merge(&other.service_time);
+        self.think_time.merge(&other.think_time);
+        self.response_size.merge(&other.response_size);
+        self.redirects.merge(&other.redirects);
+        for (group, other) in self.endpoints.iter().zip(&other.endpoints) {
+            group.merge(other);
+        }
+        for (group, other) in self.tags.iter().zip(&other.tags) {
+            group.merge(other);
+        }
+        for (group, other) in self.templates.iter().zip(&other.templates) {
+            group.merge(other);
+        }
+        for (group, other) in self.chaos.iter().zip(&other.chaos) {
+            group.merge(other);
+        }
+        for (code, count) in &other.status_codes {
+            if let Some(counter) = self.status_codes.get(*code as usize) {
+                add(counter, *count);
+            }
+        }
+        for (counter, count) in self.transport_errors.iter().zip(&other.transport_errors) {
+            add(counter, *count);
+        }
+        for (counter, count) in self.grpc_status.iter().zip(&other.grpc_status) {
+            add(counter, *count);
+        }
+        for (counter, count) in self.protocols.iter().zip(&other.protocols) {
+            add(counter, *count);
+        }
+        self.phases.merge(&other.phases);
+        self.errors.merge(&other.errors);
+        if let Some(reason) = other.stop_reason {
+            self.stop(reason);
+        }
+    }
+
+    // only the first reason sticks
+    pub fn stop(&self, reason: StopReason) {
+        let _ = self.stop_reason.set(reason);
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason.get().copied()
+    }
+
+    pub fn record_transport_error(&self, kind: TransportError) {
+        self.transport_errors[kind.index()].fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn record_grpc_status(&self, code: Code) {
+        if let Some(counter) = self.grpc_status.get(code as usize) {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub fn record_response_size(&self, endpoint: usize, bytes: u64) {
+        self.response_size.record(bytes);
+        if let Some(group) = self.endpoints.get(endpoint) {
+            group.response_size.record(bytes);
+        }
+    }
+
+    pub fn record_request(&self, latency: Duration, failed: bool) {
+        if failed {
+            self.error_count.fetch_add(1, Ordering::SeqCst);
+        } else {
+            self.success_count.fetch_add(1, Ordering::SeqCst);
+        }
+        self.total_duration.fetch_add(latency.as_millis() as u64, Ordering::SeqCst);
+        self.latency.record(latency.as_micros() as u64);
+        self.timeseries.record(Some(latency), failed);
+        self.total_requests.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // a failure like any other, except its latency is only the --timeout
+    pub fn record_timeout(&self) {
+        self.error_count.fetch_add(1, Ordering::SeqCst);
+        self.timeouts.fetch_add(1, Ordering::SeqCst);
+        self.timeseries.record(None, true);
+        self.total_requests.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn status_counts(&self) -> BTreeMap<u16, u64> {
+        self.status_codes
+            .iter()
+            .enumerate()
+            .map(|(code, count)| (code as u16, count.load(Ordering::SeqCst)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    pub fn status_class_counts(&self) -> BTreeMap<String, u64> {
+        let mut classes = BTreeMap::new();
+        for (code, count) in self.status_counts() {
+            *classes.entry(status_class(code)).or_insert(0) += count;
+        }
+        classes
+    }
+
+    pub fn transport_error_counts(&self) -> BTreeMap<String, u64> {
+        TransportError::ALL
+            .iter()
+            .map(|kind| (kind.to_string(), self.transport_errors[kind.index()].load(Ordering::SeqCst)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    // responses per negotiated http version
+    pub fn protocol_counts(&self) -> BTreeMap<String, u64> {
+        PROTOCOLS
+            .iter()
+            .zip(self.protocols.iter())
+            .map(|((_, name), count)| (name.to_string(), count.load(Ordering::SeqCst)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    pub fn grpc_status_counts(&self) -> BTreeMap<String, u64> {
+        self.grpc_status
+            .iter()
+            .enumerate()
+            .map(|(code, count)| (grpc::code_name(Code::from(code as i32)).to_string(), count.load(Ordering::SeqCst)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    // latency quantile in milliseconds
+    pub fn latency_ms(&self, q: f64) -> f64 {
+        self.latency.quantile(q) as f64 / 1000.0
+    }
+
+    // one line on what happened since `earlier`, the periodic summary of
+    // long runs
+    pub fn print_interval(&self, earlier: &StatsSnapshot, window: Duration) {
+        let requests = self.total_requests.load(Ordering::SeqCst).saturating_sub(earlier.total_requests);
+        let errors = self.error_count.load(Ordering::SeqCst).saturating_sub(earlier.error_count);
+        let latency = self.latency.since(&earlier.latency);
+        let error_rate = match requests {
+            0 => 0.0,
+            n => errors as f64 / n as f64 * 100.0,
+        };
+        println!(
+            "[{}] last {}: {} requests, {:.2} rps, {:.2}% errors, p50 {:.2}ms, p99 {:.2}ms",
+            clock(self.start_time().elapsed()),
+            clock(window),
+            requests,
+            requests as f64 / window.as_secs_f64(),
+            error_rate,
+            latency.quantile(0.50) as f64 / 1000.0,
+            latency.quantile(0.99) as f64 / 1000.0
+        );
+    }
+
+    pub fn print_summary(&self) {
+        let total = self.total_requests.load(Ordering::SeqCst);
+        let success = self.success_count.load(Ordering::SeqCst);
+        let errors = self.error_count.load(Ordering::SeqCst);
+        let total_duration = self.start_time().elapsed().as_secs_f64();
+        let avg_rps = total as f64 / total_duration;
+
+        let timeouts = self.timeouts.load(Ordering::SeqCst);
+        let completed = total.saturating_sub(timeouts);
+        let avg_response_time =
+        if completed > 0 {
+            self.total_duration.load(Ordering::SeqCst) as f64 / completed as f64
+        }
+        else {
+            0.0
+        };
+
+        println!("\n===== Load Test Summary =====");
+        println!("total duration:      {:.2}s", total_duration);
+        if let Some(reason) = self.stop_reason() {
+            println!("stopped because:     {}", reason);
+        }
+        println!("total requests:      {}", total);
+        println!("successful requests: {}", success);
+        println!("failed requests:     {}", errors);
+        if timeouts > 0 {
+            println!("timed out:           {} (failed, left out of the latencies)", timeouts);
+        }
+        let (drained, abandoned) = (self.drained.load(Ordering::SeqCst), self.abandoned.load(Ordering::SeqCst));
+        if drained + abandoned > 0 {
+            println!(
+                "drain:               {} in flight when the run ended, {} finished in {:.2}s",
+                drained + abandoned,
+                drained,
+                self.drain_us.load(Ordering::SeqCst) as f64 / 1_000_000.0
+            );
+        }
+        if abandoned > 0 {
+            println!("abandoned requests:  {} (still in flight after --drain-timeout, not counted as failed)", abandoned);
+        }
+        let retries = self.retries.load(Ordering::SeqCst);
+        if retries > 0 {
+            println!("retried attempts:    {}", retries);
+        }
+        let (setups, setup_failures) = (self.setups.load(Ordering::SeqCst), self.setup_failures.load(Ordering::SeqCst));
+        if setups + setup_failures > 0 {
+            println!("virtual user setup:  {} done, {} failed attempts", setups, setup_failures);
+        }
+        let throttled = self.throttled.load(Ordering::SeqCst);
+        if throttled > 0 {
+            let backoff = self.throttle_backoff_us.load(Ordering::SeqCst) as f64 / 1_000_000.0;
+            println!(
+                "throttled:           {} responses, {:.2}s backed off across workers, {:.2} successful requests per second",
+                throttled,
+                backoff,
+                success as f64 / total_duration
+            );
+        }
+        let graphql_errors = self.graphql_errors.load(Ordering::SeqCst);
+        if graphql_errors > 0 {
+            println!("graphql errors:      {}", graphql_errors);
+        }
+        let cache_hits = self.cache_hits.load(Ordering::SeqCst);
+        if cache_hits > 0 {
+            let responses: u64 = self.status_counts().values().sum();
+            println!(
+                "served from cache:   {} responses ({:.2}%), going by their Age and X-Cache headers",
+                cache_hits,
+                cache_hits as f64 / responses.max(1) as f64 * 100.0
+            );
+        }
+        let invalid = self.invalid_payloads.load(Ordering::SeqCst);
+        if invalid > 0 {
+            let fixed = self.fixed_payloads.load(Ordering::SeqCst);
+            println!("invalid payloads:    {} generated, {} fixed up and sent, {} skipped", invalid, fixed, invalid - fixed);
+            if let Some(example) = self.invalid_payload.get() {
+                println!("  first:             {}", example);
+            }
+        }
+        let protocols = self.protocol_counts();
+        if protocols.len() == 1 {
+            println!("protocol:            {}", protocols.keys().next().unwrap());
+        }
+        println!("requests per second: {:.2}", avg_rps);
+        let sent = self.bytes_sent.load(Ordering::SeqCst);
+        let received = self.bytes_received.load(Ordering::SeqCst);
+        if sent > 0 || received > 0 {
+            println!("bytes sent:          {} ({:.2} MB/s)", format_bytes(sent), megabytes_per_sec(sent, total_duration));
+            println!("bytes received:      {} ({:.2} MB/s)", format_bytes(received), megabytes_per_sec(received, total_duration));
+        }
+        let uncompressed = self.uncompressed_bytes.load(Ordering::SeqCst);
+        if self.response_size.count() > 0 {
+            let size = &self.response_size;
+            println!(
+                "response size:       {} min / {} avg / {} max",
+                format_bytes(size.min()),
+                format_bytes(size.mean() as u64),
+                format_bytes(size.max())
+            );
+        }
+        let opened = self.connections_opened.load(Ordering::SeqCst);
+        let reused = self.connections_reused.load(Ordering::SeqCst);
+        if opened + reused > 0 {
+            println!(
+                "connections:         {} opened, {} reused ({:.1}% of responses on a new one)",
+                opened,
+                reused,
+                opened as f64 / (opened + reused) as f64 * 100.0
+            );
+        }
+        let handshakes = self.tls_handshakes.load(Ordering::SeqCst);
+        if handshakes > 0 {
+            println!("tls handshakes:      {} full, one per https connection opened", handshakes);
+        }
+        let lookups = self.dns_lookups.load(Ordering::SeqCst);
+        if lookups > 0 {
+            println!("dns lookups:         {}, {} of them found the records changed", lookups, self.dns_changes.load(Ordering::SeqCst));
+        }
+        let offered = self.probe_resumption_offered.load(Ordering::SeqCst);
+        if offered > 0 {
+            let (full, resumed) = (&self.phases.tls, &self.phases.tls_resumed);
+            println!(
+                "tls resumption:      {} of {} probe sessions resumed, {:.2}ms p50 against {:.2}ms for a full handshake",
+                resumed.count(),
+                offered,
+                resumed.quantile(0.50) as f64 / 1000.0,
+                full.quantile(0.50) as f64 / 1000.0
+            );
+        }
+        if self.redirects.count() > 0 {
+            let redirects = &self.redirects;
+            println!(
+                "redirected:          {} attempts, {:.0} hops, {} at most",
+                redirects.count(),
+                redirects.mean() * redirects.count() as f64,
+                redirects.max()
+            );
+        }
+        if uncompressed > 0 {
+            let compressed = self.compressed_bytes.load(Ordering::SeqCst);
+            println!(
+                "compressed bodies:   {} from {} ({:.1}%)",
+                format_bytes(compressed),
+                format_bytes(uncompressed),
+                compressed as f64 / uncompressed as f64 * 100.0
+            );
+        }
+        println!("avg response time:   {:.2}ms", avg_response_time);
+        println!(
+            "latency p50/p90/p99: {:.2}ms / {:.2}ms / {:.2}ms",
+            self.latency_ms(0.50),
+            self.latency_ms(0.90),
+            self.latency_ms(0.99)
+        );
+        if self.service_time.count() > 0 {
+            let ms = |q| self.service_time.quantile(q) as f64 / 1000.0;
+            println!("service p50/p90/p99: {:.2}ms / {:.2}ms / {:.2}ms", ms(0.50), ms(0.90), ms(0.99));
+        }
+        if self.think_time.count() > 0 {
+            // a virtual user's cycle is its request plus the pause after it
+            let think = self.think_time.mean() / 1000.0;
+            let cycle = self.latency.mean() / 1000.0 + think;
+            println!("think time:          {:.2}ms mean, a request every {:.2}ms per virtual user", think, cycle);
+        }
+
+        let bins = self.latency.log_bins(HISTOGRAM_BINS);
+        if bins.len() > 1 {
+            println!("latency histogram:");
+            let peak = bins.iter().map(|(_, count)| *count).max().unwrap_or(0);
+            for (upper, count) in bins {
+                let line = format!("  <= {:>10.2}ms {:>8}  {}", upper as f64 / 1000.0, count, bar(count, peak));
+                println!("{}", line.trim_end());
+            }
+        }
+
+        if !self.phases.is_empty() {
+            println!("phases (mean / p50 / p99):");
+            for (name, phase) in self.phases.all() {
+                if phase.count() > 0 {
+                    println!(
+                        "  {:<18} {:.2}ms / {:.2}ms / {:.2}ms",
+                        name,
+                        phase.mean() / 1000.0,
+                        phase.quantile(0.50) as f64 / 1000.0,
+                        phase.quantile(0.99) as f64 / 1000.0
+                    );
+                }
+            }
+        }
+
+        let statuses = self.status_counts();
+        if !statuses.is_empty() {
+            println!("status codes:");
+            let peak = statuses.values().copied().max().unwrap_or(0);
+            for (class, count) in self.status_class_counts() {
+                println!("  {:<18} {}", class, count);
+                for (code, count) in statuses.iter().filter(|(code, _)| status_class(**code) == class) {
+                    println!("    {:<16} {:<8} {}", code, count, bar(*count, peak));
+                }
+            }
+        }
+
+        let grpc_status = self.grpc_status_counts();
+        if !grpc_status.is_empty() {
+            println!("grpc status:");
+            for (code, count) in grpc_status {
+                println!("  {:<18} {}", code, count);
+            }
+        }
+
+        if protocols.len() > 1 {
+            println!("protocols:");
+            for (protocol, count) in protocols {
+                println!("  {:<18} {}", protocol, count);
+            }
+        }
+
+        for (title, protocol) in [("transport errors:", false), ("protocol errors:", true)] {
+            let kinds: Vec<TransportError> = TransportError::ALL
+                .into_iter()
+                .filter(|kind| kind.is_protocol() == protocol)
+                .filter(|kind| self.transport_errors[kind.index()].load(Ordering::SeqCst) > 0)
+                .collect();
+            if !kinds.is_empty() {
+                println!("{}", title);
+                for kind in kinds {
+                    println!("  {:<18} {}", kind.to_string(), self.transport_errors[kind.index()].load(Ordering::SeqCst));
+                }
+            }
+        }
+
+        let errors = self.errors.top(TOP_ERRORS);
+        if !errors.is_empty() {
+            println!("top errors:");
+            for bucket in errors {
+                println!("  {:<24} {:>8}  {}", bucket.kind, bucket.count, bucket.example);
+            }
+        }
+
+        if self.endpoints.len() > 1 {
+            print_groups("endpoints", &self.endpoints);
+        }
+        if !self.tags.is_empty() {
+            print_groups("tags", &self.tags);
+        }
+        if !self.templates.is_empty() {
+            print_groups("templates", &self.templates);
+        }
+        if !self.chaos.is_empty() {
+            print_groups("chaos, left out of the totals above", &self.chaos);
+        }
+
+        if !self.checks.is_empty() {
+            println!("checks passed:       {}", self.checks_passed.load(Ordering::SeqCst));
+            println!("checks failed:       {}", self.checks_failed.load(Ordering::SeqCst));
+            for check in self.checks.iter() {
+                println!(
+                    "  {:<30} {} passed, {} failed",
+                    check.assertion.to_string(),
+                    check.passed.load(Ordering::SeqCst),
+                    check.failed.load(Ordering::SeqCst)
+                );
+            }
+        }
+
+        if let Some(adaptive) = self.concurrency.summary() {
+            println!(
+                "adaptive concurrency: {} workers at the end, {:.1} on average, {} at most, for a p95 of {:.2}ms",
+                adaptive.final_concurrency, adaptive.mean_concurrency, adaptive.peak_concurrency, adaptive.target_p95_ms
+            );
+            match adaptive.capacity_rps.zip(adaptive.capacity_concurrency) {
+                Some((rps, workers)) => println!("  capacity:          {:.2} requests per second within the target, at {} workers", rps, workers),
+                None => println!("  capacity:          no second had its p95 within the target"),
+            }
+        }
+
+        if let Some(resources) = self.resources.summary() {
+            let mut line = Vec::new();
+            if let Some((mean, peak)) = resources.cpu_mean_percent.zip(resources.cpu_peak_percent) {
+                let cpus = if resources.cpus == 1 { "cpu" } else { "cpus" };
+                line.push(format!("cpu {:.0}% mean, {:.0}% peak of {} {}", mean, peak, resources.cpus, cpus));
+            }
+            if let Some(memory) = resources.memory_peak_bytes {
+                line.push(format!("{} peak memory", format_bytes(memory)));
+            }
+            if let Some(sockets) = resources.sockets_peak {
+                line.push(format!("{} sockets", sockets));
+            }
+            line.push(format!("{} tasks", resources.tasks_peak));
+            line.push(format!("timer lag {:.1}ms max", resources.scheduling_lag_max_ms));
+            println!("load generator:      {}", line.join(", "));
+            for warning in &resources.warnings {
+                println!("  warning: {}", warning);
+            }
+        }
+
+        if !self.metrics.is_empty() {
+            println!("response metrics:");
+            for (name, metric) in self.metrics.summaries() {
+                match metric.count {
+                    0 => println!("  {:<30} not found in any response", name),
+                    count => println!(
+                        "  {:<30} min {}, mean {:.3}, max {} over {} responses",
+                        name, metric.min, metric.mean, metric.max, count
+                    ),
+                }
+            }
+        }
+    }
+}
+
+// decimal units, like the MB/s next to them
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1_000_000_000 => format!("{:.2} GB", b as f64 / 1e9),
+        b if b >= 1_000_000 => format!("{:.2} MB", b as f64 / 1e6),
+        b if b >= 1_000 => format!("{:.2} kB", b as f64 / 1e3),
+        b => format!("{} B", b),
+    }
+}
+
+pub fn megabytes_per_sec(bytes: u64, secs: f64) -> f64 {
+    match secs > 0.0 {
+        true => bytes as f64 / 1e6 / secs,
+        false => 0.0,
+    }
+}
+
+// count's share of peak as a run of '#', BAR_WIDTH at most; anything
+// above zero gets at least one
+fn bar(count: u64, peak: u64) -> String {
+    let width = match peak {
+        0 => 0,
+        _ => ((count as f64 / peak as f64 * BAR_WIDTH as f64).round() as usize).max(usize::from(count > 0)),
+    };
+    "#".repeat(width)
+}
+
+// "1:02:03"
+pub fn clock(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn print_groups(title: &str, groups: &[GroupStats]) {
+    println!("{}:", title);
+    for group in groups {
+        println!("  {}", group.name);
+        let size = match group.response_size.count() {
+            0 => String::new(),
+            _ => format!("  avg size {}", format_bytes(group.response_size.mean() as u64)),
+        };
+        let timeouts = match group.timeouts.load(Ordering::SeqCst) {
+            0 => String::new(),
+            n => format!("  timeouts {}", n),
+        };
+        println!(
+            "    requests {}  errors {}{}  p50 {:.2}ms  p99 {:.2}ms{}",
+            group.requests.load(Ordering::SeqCst),
+            group.errors.load(Ordering::SeqCst),
+            timeouts,
+            group.latency_ms(0.50),
+            group.latency_ms(0.99),
+            size
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::stats::{LoadTestStats, StatsSnapshot};
+
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+// stays under the usual 1500 byte mtu
+const MAX_DATAGRAM: usize = 1432;
+const QUANTILES: [(&str, f64); 3] = [("p50", 0.50), ("p90", 0.90), ("p99", 0.99)];
+
+// --statsd: where to push, what to call the metrics and the dogstatsd tags
+// to attach; without tags the lines are plain statsd
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsdConfig {
+    pub addr: String,
+    pub prefix: String,
+    pub tags: Vec<String>,
+}
+
+// batches the run's stats into statsd lines once a second: counters as
+// deltas, and the latency of each second as gauges so quantiles don't get
+// averaged downstream
+pub struct Statsd {
+    sink: Arc<Sink>,
+    task: JoinHandle<()>,
+}
+
+struct Sink {
+    socket: UdpSocket,
+    config: StatsdConfig,
+    // what the previous push covered
+    earlier: Mutex<StatsSnapshot>,
+}
+
+impl Statsd {
+    pub async fn start(config: &StatsdConfig, stats: Arc<LoadTestStats>) -> Result<Self, Box<dyn std::error::Error>> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket
+            .connect(&config.addr)
+            .await
+            .map_err(|e| format!("--statsd {}: {}", config.addr, e))?;
+        let sink = Arc::new(Sink {
+            socket,
+            config: config.clone(),
+            earlier: Mutex::new(stats.snapshot()),
+        });
+        let pusher = sink.clone();
+        let task = tokio::spawn(async move {
+            let start = tokio::time::Instant::now() + PUSH_INTERVAL;
+            let mut interval = tokio::time::interval_at(start, PUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                pusher.push(&stats).await;
+            }
+        });
+        Ok(Statsd { sink, task })
+    }
+
+    // pushes what the last interval left over
+    pub async fn finish(self, stats: &LoadTestStats) {
+        self.task.abort();
+        self.sink.push(stats).await;
+    }
+}
+
+impl Sink {
+    async fn push(&self, stats: &LoadTestStats) {
+        let mut earlier = self.earlier.lock().await;
+        let lines = self.lines(stats, &earlier);
+        *earlier = stats.snapshot();
+        drop(earlier);
+
+        // a collector that isn't listening mustn't disturb the run
+        for datagram in datagrams(&lines) {
+            let _ = self.socket.send(datagram.as_bytes()).await;
+        }
+    }
+
+    fn lines(&self, stats: &LoadTestStats, earlier: &StatsSnapshot) -> Vec<String> {
+        let prefix = &self.config.prefix;
+        let tags = match self.config.tags.is_empty() {
+            true => String::new(),
+            false => format!("|#{}", self.config.tags.join(",")),
+        };
+        let mut lines = Vec::new();
+        let mut line = |name: &str, value: String, kind: &str| lines.push(format!("{}.{}:{}|{}{}", prefix, name, value, kind, tags));
+
+        let requests = stats.total_requests.load(Ordering::SeqCst).saturating_sub(earlier.total_requests);
+        let errors = stats.error_count.load(Ordering::SeqCst).saturating_sub(earlier.error_count);
+        line("requests", requests.to_string(), "c");
+        line("errors", errors.to_string(), "c");
+        for (code, count) in stats.status_counts() {
+            let new = count.saturating_sub(earlier.status_codes.get(&code).copied().unwrap_or(0));
+            if new > 0 {
+                line(&format!("status.{}", code), new.to_string(), "c");
+            }
+        }
+        if requests > 0 {
+            let latency = stats.latency.since(&earlier.latency);
+            let mut gauge = |name: &str, micros: f64| line(name, format!("{:.3}", micros / 1000.0), "g");
+            gauge("latency.mean", latency.mean());
+            for (name, q) in QUANTILES {
+                gauge(&format!("latency.{}", name), latency.quantile(q) as f64);
+            }
+        }
+        lines
+    }
+}
+
+// newline separated lines packed into as few datagrams as fit
+fn datagrams(lines: &[String]) -> Vec<String> {
+    let mut datagrams: Vec<String> = Vec::new();
+    for line in lines {
+        match datagrams.last_mut() {
+            Some(last) if last.len() + 1 + line.len() <= MAX_DATAGRAM => {
+                last.push('\n');
+                last.push_str(line);
+            }
+            _ => datagrams.push(line.clone()),
+        }
+    }
+    datagrams
+}
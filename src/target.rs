@@ -0,0 +1,279 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::mock::StepRng;
+use rand::Rng;
+use reqwest::{Method, Url};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+use crate::auth::Header;
+use crate::placeholder::{Context, Pattern};
+use crate::template::Template;
+use crate::tls::HostOverride;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Target {
+    pub url: String,
+    pub weight: f64,
+    // its own http method, from --mix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    // key=value pairs its requests are also grouped under, e.g. scenario=read
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay: Option<Replay>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation: Option<Operation>,
+}
+
+// a recorded request sent as is instead of a generated payload
+#[derive(Debug, Clone, Serialize)]
+pub struct Replay {
+    pub method: String,
+    pub headers: Vec<Header>,
+    #[serde(skip)]
+    pub body: Option<String>,
+}
+
+// an operation of an openapi document, with its own method and a body
+// template made from its request schema
+#[derive(Debug, Clone, Serialize)]
+pub struct Operation {
+    pub id: String,
+    pub method: String,
+    #[serde(skip)]
+    pub body: Option<Value>,
+}
+
+impl Target {
+    // "https://host/path" or "https://host/path@3", then any number of
+    // space separated key=value tags; the spaces inside {{int 1 50}} are
+    // the url's own
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (url, tags) = split_url(spec.trim());
+        if url.is_empty() {
+            return Err("empty endpoint".into());
+        }
+        let parts = tags.split_whitespace();
+        let mut target = match url.rsplit_once('@') {
+            Some((url, weight)) if weight.parse::<f64>().is_ok() => Target::new(url, weight.parse().unwrap())?,
+            _ => Target::new(url, 1.0)?,
+        };
+        target.tags = parse_tags(parts, spec)?;
+        Ok(target)
+    }
+
+    fn new(url: &str, weight: f64) -> Result<Self, String> {
+        if !(weight > 0.0 && weight.is_finite()) {
+            return Err(format!("endpoint weight must be positive: {}", url));
+        }
+        Ok(Target { url: url.to_string(), weight, method: None, tags: BTreeMap::new(), replay: None, operation: None })
+    }
+
+    // --mix "GET /items:80,POST /items:20": a target per entry, its path
+    // relative to this one's url unless it's a url itself, each with its
+    // own method and weight and this one's tags
+    pub fn mix(&self, spec: &str) -> Result<Vec<Self>, String> {
+        let base = self.url.trim_end_matches('/');
+        spec.split(',')
+            .map(|entry| {
+                let entry = entry.trim();
+                let usage = || format!("--mix {}: expected METHOD /path[:weight], e.g. GET /items:80", entry);
+                let (request, weight) = match entry.rsplit_once(':') {
+                    Some((request, weight)) if weight.trim().parse::<f64>().is_ok() => (request, weight.trim().parse().unwrap()),
+                    _ => (entry, 1.0),
+                };
+                let (method, path) = request.trim().split_once(char::is_whitespace).ok_or_else(usage)?;
+                let (method, path) = (method.to_ascii_uppercase(), path.trim());
+                if path.is_empty() {
+                    return Err(usage());
+                }
+                Method::from_bytes(method.as_bytes()).map_err(|_| format!("--mix {}: invalid http method {}", entry, method))?;
+                let url = match path.starts_with("http://") || path.starts_with("https://") {
+                    true => path.to_string(),
+                    false => format!("{}/{}", base, path.trim_start_matches('/')),
+                };
+                let mut target = Target::new(&url, weight)?;
+                target.method = Some(method);
+                target.tags = self.tags.clone();
+                Ok(target)
+            })
+            .collect()
+    }
+
+    // "key=value", sorted by key
+    pub fn tag_labels(&self) -> Vec<String> {
+        self.tags.iter().map(|(key, value)| format!("{}={}", key, value)).collect()
+    }
+
+    // what the target's stats are reported under
+    pub fn label(&self) -> String {
+        match (&self.replay, &self.operation) {
+            (Some(replay), _) => format!("{} {}", replay.method, self.url),
+            (None, Some(operation)) => format!("{} {}", operation.method, self.url),
+            (None, None) => match &self.method {
+                Some(method) => format!("{} {}", method, self.url),
+                None => self.url.clone(),
+            },
+        }
+    }
+
+    // one "<url> [weight] [key=value ...]" per line, blank lines and #
+    // comments are skipped
+    pub fn load_file(path: &str) -> Result<Vec<Self>, Box<dyn std::error::Error>> {
+        let mut targets = Vec::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (url, rest) = split_url(line);
+            let mut parts = rest.split_whitespace().peekable();
+            let weight = match parts.next_if(|part| !part.contains('=')) {
+                Some(weight) => weight
+                    .parse()
+                    .map_err(|_| format!("invalid weight in {}: {}", path, line))?,
+                None => 1.0,
+            };
+            let mut target = Target::new(url, weight)?;
+            target.tags = parse_tags(parts, line)?;
+            targets.push(target);
+        }
+        Ok(targets)
+    }
+}
+
+// the url up to the first space outside a {{placeholder}}, and the rest
+fn split_url(spec: &str) -> (&str, &str) {
+    let bytes = spec.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"{{") {
+            depth += 1;
+            i += 2;
+        } else if depth > 0 && bytes[i..].starts_with(b"}}") {
+            depth -= 1;
+            i += 2;
+        } else if depth == 0 && bytes[i].is_ascii_whitespace() {
+            return (&spec[..i], &spec[i..]);
+        } else {
+            i += 1;
+        }
+    }
+    (spec, "")
+}
+
+fn parse_tags<'a>(parts: impl Iterator<Item = &'a str>, spec: &str) -> Result<BTreeMap<String, String>, String> {
+    parts
+        .map(|tag| match tag.split_once('=') {
+            Some((key, value)) if !key.is_empty() && !value.is_empty() => Ok((key.to_string(), value.to_string())),
+            _ => Err(format!("expected a key=value tag, got {} in: {}", tag, spec)),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct Targets {
+    pub targets: Vec<Target>,
+    patterns: Vec<Pattern>,
+    methods: Vec<Method>,
+    // the compiled body templates of openapi operations
+    bodies: Vec<Option<Template>>,
+    // `tag_labels(targets)`, and per target the indices of its tags in it
+    labels: Vec<String>,
+    tags: Vec<Vec<usize>>,
+    weights: WeightedIndex<f64>,
+}
+
+// every distinct key=value tag of `targets`, sorted
+pub fn tag_labels(targets: &[Target]) -> Vec<String> {
+    let labels: BTreeSet<String> = targets.iter().flat_map(Target::tag_labels).collect();
+    labels.into_iter().collect()
+}
+
+impl Targets {
+    // `method` is used for every target without one of its own
+    pub fn new(targets: Vec<Target>, method: &str) -> Result<Self, String> {
+        let weights = WeightedIndex::new(targets.iter().map(|t| t.weight))
+            .map_err(|e| format!("invalid endpoint weights: {}", e))?;
+        let patterns = targets
+            .iter()
+            .map(|t| Pattern::parse(&t.url))
+            .collect::<Result<_, _>>()?;
+        let methods = targets
+            .iter()
+            .map(|t| {
+                let method = match (&t.replay, &t.operation) {
+                    (Some(replay), _) => replay.method.as_str(),
+                    (None, Some(operation)) => operation.method.as_str(),
+                    (None, None) => t.method.as_deref().unwrap_or(method),
+                };
+                Method::from_bytes(method.as_bytes()).map_err(|_| format!("invalid http method: {}", method))
+            })
+            .collect::<Result<_, _>>()?;
+        let bodies = targets
+            .iter()
+            .map(|t| {
+                let body = t.operation.as_ref().and_then(|operation| operation.body.as_ref());
+                body.map(|body| Template::compile(body).map_err(|e| format!("{}: {}", t.label(), e))).transpose()
+            })
+            .collect::<Result<_, _>>()?;
+        let labels = tag_labels(&targets);
+        let tags = targets
+            .iter()
+            .map(|t| t.tag_labels().iter().filter_map(|label| labels.binary_search(label).ok()).collect())
+            .collect();
+        Ok(Targets { targets, patterns, methods, bodies, labels, tags, weights })
+    }
+
+    pub fn method(&self, index: usize) -> &Method {
+        &self.methods[index]
+    }
+
+    pub fn body(&self, index: usize) -> Option<&Template> {
+        self.bodies[index].as_ref()
+    }
+
+    pub fn tag_labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn tags(&self, index: usize) -> &[usize] {
+        &self.tags[index]
+    }
+
+    // the url of a target with its {{...}} placeholders filled in
+    pub fn url<R: Rng + ?Sized>(&self, index: usize, rng: &mut R, context: &Context) -> String {
+        self.patterns[index].render(rng, context)
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().flat_map(Pattern::columns)
+    }
+
+    // a url of each scheme, host and port the endpoints have, as requests
+    // address them
+    pub fn origins(&self, host_override: Option<&HostOverride>) -> Vec<Url> {
+        let mut origins: Vec<Url> = Vec::new();
+        for index in 0..self.targets.len() {
+            // placeholders in a host are rare, any rendering of them will do
+            let url = self.url(index, &mut StepRng::new(0, 1), &Context::default());
+            let url = host_override.map_or(url.clone(), |o| o.rewrite(&url));
+            let Ok(url) = Url::parse(&url) else { continue };
+            if !origins.iter().any(|o| o.origin() == url.origin()) {
+                origins.push(url);
+            }
+        }
+        origins
+    }
+
+    pub fn pick<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        match self.targets.len() {
+            1 => 0,
+            _ => self.weights.sample(rng),
+        }
+    }
+}
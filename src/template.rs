@@ -0,0 +1,595 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use serde_json::{Map, Number, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use crate::placeholder::{Context, Pattern};
+
+const DEFAULT_STRING_LEN: usize = 12;
+// item count of plain template arrays, [x] sends 1 to 4 copies of x
+const DEFAULT_ARRAY_LEN: RangeInclusive<usize> = 1..=4;
+// draws per wanted item before a unique array settles for fewer items
+const UNIQUE_ATTEMPTS: usize = 10;
+// base 36 digits of a u64
+const UNIQUE_LEN: usize = 13;
+const BASE36: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const DEFAULT_DATE_MIN: &str = "1970-01-01";
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+// how far * and + go in a $regex
+const DEFAULT_MAX_REPEAT: u32 = 8;
+
+// a payload template compiled once up front; plain json values are
+// randomized by type, single-key objects like {"$int": {...}} are
+// generator directives and strings with {{...}} placeholders are filled in
+#[derive(Debug, Clone)]
+pub enum Template {
+    Object(Vec<(String, Template)>),
+    Array { item: Box<Template>, len: RangeInclusive<usize>, unique: bool },
+    String,
+    Text(Pattern),
+    Int,
+    Float,
+    Bool,
+    Literal(Value),
+    IntRange(RangeInclusive<i64>),
+    FloatRange { min: f64, max: f64 },
+    Chars { len: RangeInclusive<usize>, charset: Vec<char> },
+    Enum { values: Vec<Value>, weights: Option<WeightedIndex<f64>> },
+    Nullable { prob: f64, value: Box<Template> },
+    // only meaningful as an object field, which is then left out
+    Optional { prob: f64, value: Box<Template> },
+    // counters off the request sequence, so no two requests of a run send
+    // the same value whatever worker or shard they came from
+    Seq { start: i64, step: i64 },
+    UniqueString { prefix: String },
+    // unix seconds, both ends included
+    Date { min: i64, max: i64, format: DateFormat },
+    // strings matching a pattern
+    Regex(Box<rand_regex::Regex>),
+}
+
+#[derive(Debug, Clone)]
+pub enum DateFormat {
+    // a number of seconds rather than a string
+    Unix,
+    Strftime(String),
+}
+
+impl Template {
+    // a template file, its "$include"s filled in, see read
+    pub fn load(path: &str) -> Result<Self, String> {
+        Template::compile(&read(path)?).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn compile(value: &Value) -> Result<Self, String> {
+        Ok(match value {
+            Value::Object(map) => match directive(map) {
+                Some((name, spec)) => compile_directive(name, spec)?,
+                None => Template::Object(
+                    map.iter()
+                        .map(|(key, value)| Ok((key.clone(), Template::compile(value)?)))
+                        .collect::<Result<_, String>>()?,
+                ),
+            },
+            Value::Array(items) => match items.first() {
+                Some(item) => Template::Array {
+                    item: Box::new(Template::compile(item)?),
+                    len: DEFAULT_ARRAY_LEN,
+                    unique: false,
+                },
+                None => Template::Literal(Value::Array(Vec::new())),
+            },
+            Value::String(text) if text.contains("{{") => Template::Text(Pattern::parse(text)?),
+            Value::String(_) => Template::String,
+            Value::Number(n) if n.is_i64() => Template::Int,
+            Value::Number(n) if n.is_f64() => Template::Float,
+            Value::Bool(_) => Template::Bool,
+            other => Template::Literal(other.clone()),
+        })
+    }
+
+    // the feed columns referenced anywhere in the template
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Template::Object(fields) => fields.iter().flat_map(|(_, t)| t.columns()).collect(),
+            Template::Array { item, .. } => item.columns(),
+            Template::Text(pattern) => pattern.columns().collect(),
+            Template::Nullable { value, .. } | Template::Optional { value, .. } => value.columns(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn generate<R: Rng + ?Sized>(&self, rng: &mut R, context: &Context) -> Value {
+        match self {
+            Template::Object(fields) => {
+                let mut map = Map::new();
+                for (key, template) in fields {
+                    match template {
+                        Template::Optional { prob, value } => {
+                            if rng.gen_bool(*prob) {
+                                map.insert(key.clone(), value.generate(rng, context));
+                            }
+                        }
+                        template => {
+                            map.insert(key.clone(), template.generate(rng, context));
+                        }
+                    }
+                }
+                Value::Object(map)
+            }
+            Template::Array { item, len, unique } => {
+                let len = rng.gen_range(len.clone());
+                if !unique {
+                    return Value::Array((0..len).map(|_| item.generate(rng, context)).collect());
+                }
+
+                let mut seen = HashSet::new();
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len.saturating_mul(UNIQUE_ATTEMPTS) {
+                    if items.len() == len {
+                        break;
+                    }
+                    let value = item.generate(rng, context);
+                    if seen.insert(value.to_string()) {
+                        items.push(value);
+                    }
+                }
+                Value::Array(items)
+            }
+            Template::String => Value::String(rng.gen::<u32>().to_string()),
+            Template::Text(pattern) => Value::String(pattern.render(rng, context)),
+            Template::Int => Value::Number(rng.gen::<i64>().into()),
+            Template::Float => float(rng.gen::<f64>()),
+            Template::Bool => Value::Bool(rng.gen()),
+            Template::Literal(value) => value.clone(),
+            Template::IntRange(range) => Value::Number(rng.gen_range(range.clone()).into()),
+            Template::FloatRange { min, max } => float(match min == max {
+                true => *min,
+                false => rng.gen_range(*min..*max),
+            }),
+            Template::Chars { len, charset } => {
+                let len = rng.gen_range(len.clone());
+                Value::String((0..len).map(|_| charset[rng.gen_range(0..charset.len())]).collect())
+            }
+            Template::Enum { values, weights } => {
+                let index = match weights {
+                    Some(weights) => weights.sample(rng),
+                    None => rng.gen_range(0..values.len()),
+                };
+                values[index].clone()
+            }
+            Template::Nullable { prob, value } => match rng.gen_bool(*prob) {
+                true => Value::Null,
+                false => value.generate(rng, context),
+            },
+            Template::Optional { value, .. } => value.generate(rng, context),
+            Template::Seq { start, step } => {
+                Value::Number(start.wrapping_add((context.sequence as i64).wrapping_mul(*step)).into())
+            }
+            Template::UniqueString { prefix } => Value::String(unique_string(prefix, context)),
+            Template::Regex(regex) => Value::String(rng.sample::<String, _>(regex.as_ref())),
+            Template::Date { min, max, format } => {
+                let secs = rng.gen_range(*min..=*max);
+                match format {
+                    DateFormat::Unix => Value::Number(secs.into()),
+                    // in range, see parse_date
+                    DateFormat::Strftime(format) => {
+                        Value::String(DateTime::from_timestamp(secs, 0).unwrap_or_default().format(format).to_string())
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the sequence through a bijective mix keyed by the seed, so values look
+// random and differ between runs, yet never repeat within one
+fn unique_string(prefix: &str, context: &Context) -> String {
+    let mut z = (context.sequence ^ context.seed).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    let mut digits = [b'0'; UNIQUE_LEN];
+    for digit in digits.iter_mut().rev() {
+        *digit = BASE36[(z % 36) as usize];
+        z /= 36;
+    }
+    format!("{}{}", prefix, String::from_utf8_lossy(&digits))
+}
+
+fn float(num: f64) -> Value {
+    Value::Number(Number::from_f64(num).unwrap_or_else(|| Number::from(0)))
+}
+
+// a template file with its fragments pulled in:
+//
+//   {"$include": "common/address.json", "zip": {"$regex": "[0-9]{5}"}}
+//
+// is the object in common/address.json, a path relative to the file that
+// includes it, with the keys next to the $include laid over it, down into
+// the objects both have; a list of paths lays each fragment over the ones
+// before it
+pub fn read(path: &str) -> Result<Value, String> {
+    read_files(path).map(|(value, _)| value)
+}
+
+// the template and, canonical, every file it was read from: the one at
+// `path` and the fragments it pulled in
+pub fn read_files(path: &str) -> Result<(Value, Vec<PathBuf>), String> {
+    let mut reading = Reading::default();
+    let value = read_file(Path::new(path), &mut reading)?;
+    Ok((value, reading.files))
+}
+
+#[derive(Default)]
+struct Reading {
+    // the files on the way here, to catch a cycle
+    including: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+fn read_file(path: &Path, reading: &mut Reading) -> Result<Value, String> {
+    let shown = path.display();
+    let canonical = fs::canonicalize(path).map_err(|e| format!("{}: {}", shown, e))?;
+    if reading.including.contains(&canonical) {
+        return Err(format!("{}: includes itself", shown));
+    }
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", shown, e))?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| format!("{}: {}", shown, e))?;
+    if !reading.files.contains(&canonical) {
+        reading.files.push(canonical.clone());
+    }
+    reading.including.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    // an error in an included file reads outer.json: common/address.json: ...
+    let resolved = resolve_includes(value, dir, reading).map_err(|e| format!("{}: {}", shown, e));
+    reading.including.pop();
+    resolved
+}
+
+fn resolve_includes(value: Value, dir: &Path, reading: &mut Reading) -> Result<Value, String> {
+    let mut map = match value {
+        Value::Object(map) => map,
+        Value::Array(items) => {
+            return items.into_iter().map(|item| resolve_includes(item, dir, reading)).collect::<Result<_, _>>().map(Value::Array);
+        }
+        other => return Ok(other),
+    };
+    let include = map.remove("$include");
+    let mut overrides = Map::new();
+    for (key, value) in map {
+        overrides.insert(key, resolve_includes(value, dir, reading)?);
+    }
+    let paths = match include {
+        None => return Ok(Value::Object(overrides)),
+        Some(Value::String(path)) => vec![path],
+        Some(Value::Array(paths)) if !paths.is_empty() => paths
+            .iter()
+            .map(|path| path.as_str().map(str::to_string))
+            .collect::<Option<_>>()
+            .ok_or("$include expects a path or a list of paths")?,
+        Some(_) => return Err("$include expects a path or a list of paths".to_string()),
+    };
+    let mut included: Option<Value> = None;
+    for path in &paths {
+        let fragment = read_file(&dir.join(path), reading)?;
+        included = Some(match included {
+            Some(base) => overlay(base, fragment),
+            None => fragment,
+        });
+    }
+    // a list of paths isn't empty
+    let included = included.unwrap();
+    if overrides.is_empty() {
+        return Ok(included);
+    }
+    match &included {
+        Value::Object(base) if directive(base).is_none() => Ok(overlay(included, Value::Object(overrides))),
+        _ => Err(format!("$include {}: keys can only be laid over an object", paths.join(", "))),
+    }
+}
+
+// `over` laid over `base`: key by key where both are plain objects, a
+// directive or any other value replaces what was there
+fn overlay(base: Value, over: Value) -> Value {
+    match (base, over) {
+        (Value::Object(mut base), Value::Object(over)) if directive(&base).is_none() && directive(&over).is_none() => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(old) => *old = overlay(old.take(), value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+            Value::Object(base)
+        }
+        (_, over) => over,
+    }
+}
+
+// keys that aren't a known directive are left to be generated as usual,
+// so templates can still carry fields like "$ref"
+fn directive(map: &Map<String, Value>) -> Option<(&str, &Value)> {
+    if map.len() != 1 {
+        return None;
+    }
+    let (key, spec) = map.iter().next()?;
+    match key.as_str() {
+        "$int" | "$float" | "$string" | "$enum" | "$nullable" | "$array" | "$optional" | "$seq" | "$unique_string"
+        | "$date" | "$regex" => {
+            Some((key.as_str(), spec))
+        }
+        _ => None,
+    }
+}
+
+fn compile_directive(name: &str, spec: &Value) -> Result<Template, String> {
+    // the ones that also take a bare list or pattern
+    match (name, spec) {
+        ("$enum", Value::Array(values)) => return compile_enum(values.clone(), None),
+        ("$regex", Value::String(pattern)) => return compile_regex(pattern, DEFAULT_MAX_REPEAT),
+        _ => {}
+    }
+
+    let options = spec
+        .as_object()
+        .ok_or_else(|| format!("{} expects an object of options, got {}", name, spec))?;
+    let number = |key: &str| match options.get(key) {
+        None => Ok(None),
+        Some(value) => value
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| format!("{}: {} must be a number, got {}", name, key, value)),
+    };
+    let integer = |key: &str| match options.get(key) {
+        None => Ok(None),
+        Some(value) => value
+            .as_i64()
+            .map(Some)
+            .ok_or_else(|| format!("{}: {} must be an integer, got {}", name, key, value)),
+    };
+
+    match name {
+        "$int" => {
+            let min = integer("min")?.unwrap_or(i64::MIN);
+            let max = integer("max")?.unwrap_or(i64::MAX);
+            if min > max {
+                return Err(format!("$int: min {} is above max {}", min, max));
+            }
+            Ok(Template::IntRange(min..=max))
+        }
+        "$float" => {
+            let min = number("min")?.unwrap_or(0.0);
+            let max = number("max")?.unwrap_or(1.0);
+            if !min.is_finite() || !max.is_finite() || min > max {
+                return Err(format!("$float: invalid range {}..{}", min, max));
+            }
+            Ok(Template::FloatRange { min, max })
+        }
+        "$string" => {
+            let len = |key: &str| match integer(key)? {
+                Some(n) if n < 0 => Err(format!("$string: {} can't be negative", key)),
+                n => Ok(n.map(|n| n as usize)),
+            };
+            let (min, max) = match (len("len")?, len("min_len")?, len("max_len")?) {
+                (Some(len), _, _) => (len, len),
+                (None, Some(min), Some(max)) => (min, max),
+                (None, Some(min), None) => (min, min.max(DEFAULT_STRING_LEN)),
+                (None, None, Some(max)) => (max.min(1), max),
+                (None, None, None) => (DEFAULT_STRING_LEN, DEFAULT_STRING_LEN),
+            };
+            if min > max {
+                return Err(format!("$string: min_len {} is above max_len {}", min, max));
+            }
+            let charset = match (options.get("chars"), options.get("charset")) {
+                (Some(chars), _) => chars
+                    .as_str()
+                    .ok_or_else(|| format!("$string: chars must be a string, got {}", chars))?
+                    .chars()
+                    .collect(),
+                (None, Some(charset)) => charset_chars(
+                    charset
+                        .as_str()
+                        .ok_or_else(|| format!("$string: charset must be a string, got {}", charset))?,
+                )?,
+                (None, None) => charset_chars("alnum")?,
+            };
+            if charset.is_empty() {
+                return Err("$string: chars can't be empty".to_string());
+            }
+            Ok(Template::Chars { len: min..=max, charset })
+        }
+        "$enum" => {
+            let values = match options.get("values") {
+                Some(Value::Array(values)) => values.clone(),
+                _ => return Err("$enum: expected a list of values or {\"values\": [...]}".to_string()),
+            };
+            let weights = match options.get("weights") {
+                Some(Value::Array(weights)) => Some(
+                    weights
+                        .iter()
+                        .map(|w| w.as_f64().ok_or_else(|| format!("$enum: weight must be a number, got {}", w)))
+                        .collect::<Result<Vec<_>, _>>()?,
+                ),
+                Some(other) => return Err(format!("$enum: weights must be a list, got {}", other)),
+                None => None,
+            };
+            compile_enum(values, weights)
+        }
+        "$nullable" | "$optional" => {
+            let prob = number("prob")?.unwrap_or(0.5);
+            if !(0.0..=1.0).contains(&prob) {
+                return Err(format!("{}: prob must be between 0 and 1, got {}", name, prob));
+            }
+            let value = options
+                .get("value")
+                .ok_or_else(|| format!("{}: missing value", name))?;
+            let value = Box::new(Template::compile(value)?);
+            Ok(match name {
+                "$nullable" => Template::Nullable { prob, value },
+                _ => Template::Optional { prob, value },
+            })
+        }
+        "$array" => {
+            let item = options.get("items").ok_or("$array: missing items")?;
+            let count = |key: &str| match integer(key)? {
+                Some(n) if n < 0 => Err(format!("$array: {} can't be negative", key)),
+                n => Ok(n.map(|n| n as usize)),
+            };
+            let min = count("min")?.unwrap_or(*DEFAULT_ARRAY_LEN.start());
+            let max = count("max")?.unwrap_or(min.max(*DEFAULT_ARRAY_LEN.end()));
+            if min > max {
+                return Err(format!("$array: min {} is above max {}", min, max));
+            }
+            let unique = match options.get("unique") {
+                None => false,
+                Some(Value::Bool(unique)) => *unique,
+                Some(other) => return Err(format!("$array: unique must be true or false, got {}", other)),
+            };
+            Ok(Template::Array {
+                item: Box::new(Template::compile(item)?),
+                len: min..=max,
+                unique,
+            })
+        }
+        "$seq" => {
+            let step = integer("step")?.unwrap_or(1);
+            if step == 0 {
+                return Err("$seq: step can't be 0".to_string());
+            }
+            Ok(Template::Seq { start: integer("start")?.unwrap_or(0), step })
+        }
+        "$unique_string" => {
+            let prefix = match options.get("prefix") {
+                None => String::new(),
+                Some(Value::String(prefix)) => prefix.clone(),
+                Some(other) => return Err(format!("$unique_string: prefix must be a string, got {}", other)),
+            };
+            Ok(Template::UniqueString { prefix })
+        }
+        "$regex" => {
+            let pattern = match options.get("pattern") {
+                Some(Value::String(pattern)) => pattern,
+                _ => return Err("$regex: expected a pattern or {\"pattern\": \"...\"}".to_string()),
+            };
+            let max_repeat = match integer("max_repeat")? {
+                Some(n) if !(0..=u32::MAX as i64).contains(&n) => {
+                    return Err(format!("$regex: max_repeat must be between 0 and {}", u32::MAX))
+                }
+                n => n.map_or(DEFAULT_MAX_REPEAT, |n| n as u32),
+            };
+            compile_regex(pattern, max_repeat)
+        }
+        "$date" => {
+            let bound = |key: &str, default: &str, end_of_day: bool| match options.get(key) {
+                None => parse_date(default, end_of_day),
+                Some(Value::String(date)) => parse_date(date, end_of_day),
+                Some(other) => Err(format!("$date: {} must be a date string, got {}", key, other)),
+            };
+            let min = bound("min", DEFAULT_DATE_MIN, false)?;
+            let max = bound("max", "now", true)?;
+            if min > max {
+                return Err(format!("$date: min is after max in {}", spec));
+            }
+            let format = match options.get("format") {
+                None => DateFormat::Strftime(DEFAULT_DATE_FORMAT.to_string()),
+                Some(Value::String(format)) if format == "unix" => DateFormat::Unix,
+                Some(Value::String(format)) if StrftimeItems::new(format).any(|item| item == Item::Error) => {
+                    return Err(format!("$date: invalid format {}", format))
+                }
+                Some(Value::String(format)) => DateFormat::Strftime(format.clone()),
+                Some(other) => return Err(format!("$date: format must be a string, got {}", other)),
+            };
+            Ok(Template::Date { min, max, format })
+        }
+        _ => unreachable!("not a directive: {}", name),
+    }
+}
+
+// "now", "2024-12-31", "2024-12-31T08:00:00" or rfc 3339, as unix seconds;
+// a bare date as a max takes in the whole day
+fn parse_date(text: &str, end_of_day: bool) -> Result<i64, String> {
+    let time = match text {
+        "now" => Some(Utc::now()),
+        _ => DateTime::parse_from_rfc3339(text)
+            .map(|time| time.to_utc())
+            .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S").map(|time| time.and_utc()))
+            .ok()
+            .or_else(|| {
+                let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?;
+                let time = match end_of_day {
+                    true => date.and_hms_opt(23, 59, 59),
+                    false => date.and_hms_opt(0, 0, 0),
+                };
+                Some(time?.and_utc())
+            }),
+    };
+    let time = time.ok_or_else(|| format!("$date: expected now, YYYY-MM-DD or an rfc 3339 time, got {}", text))?;
+    // years 1 to 9999, what the formats can write
+    match time.timestamp() {
+        secs if (-62_135_596_800..=253_402_300_799).contains(&secs) => Ok(secs),
+        _ => Err(format!("$date: {} is out of range", text)),
+    }
+}
+
+// a generated string is the whole value, so the ^...$ validation patterns
+// are usually written with can go
+fn compile_regex(pattern: &str, max_repeat: u32) -> Result<Template, String> {
+    let body = pattern.strip_prefix('^').unwrap_or(pattern);
+    let body = match body.strip_suffix('$') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => body,
+    };
+    // \d, \w and friends as ascii, like most validators read them, unless
+    // the pattern itself needs unicode
+    let ascii = regex_syntax::ParserBuilder::new().unicode(false).build().parse(body);
+    let hir = match ascii {
+        Ok(hir) => hir,
+        Err(_) => regex_syntax::Parser::new().parse(body).map_err(|e| format!("$regex {}: {}", pattern, e))?,
+    };
+    let regex = rand_regex::Regex::with_hir(hir, max_repeat).map_err(|e| format!("$regex {}: {}", pattern, e))?;
+    Ok(Template::Regex(Box::new(regex)))
+}
+
+// enum values are sent as written, not randomized further
+fn compile_enum(values: Vec<Value>, weights: Option<Vec<f64>>) -> Result<Template, String> {
+    if values.is_empty() {
+        return Err("$enum: needs at least one value".to_string());
+    }
+    let weights = match weights {
+        Some(weights) if weights.len() != values.len() => {
+            return Err(format!("$enum: {} weights for {} values", weights.len(), values.len()))
+        }
+        Some(weights) => Some(WeightedIndex::new(weights).map_err(|e| format!("$enum: {}", e))?),
+        None => None,
+    };
+    Ok(Template::Enum { values, weights })
+}
+
+fn charset_chars(name: &str) -> Result<Vec<char>, String> {
+    const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+    const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const DIGITS: &str = "0123456789";
+
+    let chars = match name {
+        "alnum" => format!("{}{}{}", LOWER, UPPER, DIGITS),
+        "alpha" => format!("{}{}", LOWER, UPPER),
+        "lower" => LOWER.to_string(),
+        "upper" => UPPER.to_string(),
+        "numeric" | "digits" => DIGITS.to_string(),
+        "hex" => "0123456789abcdef".to_string(),
+        other => {
+            return Err(format!(
+                "$string: unknown charset '{}', expected alnum, alpha, lower, upper, numeric or hex",
+                other
+            ))
+        }
+    };
+    Ok(chars.chars().collect())
+}
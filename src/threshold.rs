@@ -0,0 +1,251 @@
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+use crate::config::LoadConfig;
+use crate::report::{GroupSummary, LatencySummary, Report};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    Min,
+    Avg,
+    P50,
+    P90,
+    P95,
+    P99,
+    Max,
+    ErrorRate,
+    Rps,
+}
+
+impl Metric {
+    fn is_latency(self) -> bool {
+        !matches!(self, Metric::ErrorRate | Metric::Rps)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+// what a threshold is checked against: the whole run or one of its groups
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Scope {
+    Run,
+    Endpoint(String),
+    // the full key=value label or just the value
+    Tag(String),
+    Template(String),
+}
+
+// a pass/fail criterion checked once the run is over, e.g. "p95<250ms"
+// or "error_rate<=1%"; latencies are in milliseconds unless suffixed; a
+// scope in front, e.g. "tag:checkout p99<500ms", checks a group's figures
+#[derive(Debug, Clone)]
+pub struct Threshold {
+    spec: String,
+    scope: Scope,
+    metric: Metric,
+    op: Op,
+    value: f64,
+}
+
+impl Threshold {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        let (scope, criterion) = match spec.split_once(':').filter(|(kind, _)| !kind.contains(['<', '>'])) {
+            Some((kind, rest)) => {
+                let (name, criterion) = rest
+                    .trim_start()
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| format!("expected {}:<name> <metric><op><value>, got: {}", kind, spec))?;
+                let scope = match kind.trim() {
+                    "endpoint" => Scope::Endpoint(name.to_string()),
+                    "tag" => Scope::Tag(name.to_string()),
+                    "template" => Scope::Template(name.to_string()),
+                    other => return Err(format!("unknown threshold scope {}, expected endpoint, tag or template", other)),
+                };
+                (scope, criterion)
+            }
+            None => (Scope::Run, spec),
+        };
+        let at = criterion
+            .find(['<', '>'])
+            .ok_or_else(|| format!("expected <metric><op><value> like p95<250ms, got: {}", spec))?;
+        let (name, rest) = (criterion[..at].trim(), &criterion[at..]);
+        let (op, value) = match rest.as_bytes() {
+            [b'<', b'=', ..] => (Op::Le, &rest[2..]),
+            [b'>', b'=', ..] => (Op::Ge, &rest[2..]),
+            [b'<', ..] => (Op::Lt, &rest[1..]),
+            _ => (Op::Gt, &rest[1..]),
+        };
+        let metric = match name {
+            "min" => Metric::Min,
+            "avg" | "mean" => Metric::Avg,
+            "p50" | "med" => Metric::P50,
+            "p90" => Metric::P90,
+            "p95" => Metric::P95,
+            "p99" => Metric::P99,
+            "max" => Metric::Max,
+            "error_rate" => Metric::ErrorRate,
+            "rps" => Metric::Rps,
+            other => {
+                return Err(format!(
+                    "unknown threshold metric {}, expected min, avg, p50, p90, p95, p99, max, error_rate or rps",
+                    other
+                ))
+            }
+        };
+        let value = parse_value(metric, value.trim()).ok_or_else(|| format!("invalid threshold value in: {}", spec))?;
+        Ok(Threshold { spec: spec.to_string(), scope, metric, op, value })
+    }
+
+    // a scope has to name a group the run keeps
+    pub fn check_scope(&self, config: &LoadConfig) -> Result<(), String> {
+        let (labels, kind) = match &self.scope {
+            Scope::Run => return Ok(()),
+            Scope::Endpoint(_) => (config.endpoint_labels(), "endpoint"),
+            Scope::Tag(_) => (config.tag_labels(), "tag"),
+            Scope::Template(_) => (config.template_labels(), "template"),
+        };
+        match labels.iter().any(|label| self.scope.matches(label)) {
+            true => Ok(()),
+            false if labels.is_empty() => Err(format!("--threshold {}: the run has no {} groups", self.spec, kind)),
+            false => Err(format!("--threshold {}: no such {}, expected one of {}", self.spec, kind, labels.join(", "))),
+        }
+    }
+
+    pub fn actual(&self, report: &Report) -> f64 {
+        let groups = match &self.scope {
+            Scope::Run => &[][..],
+            Scope::Endpoint(_) => &report.endpoints[..],
+            Scope::Tag(_) => &report.tags[..],
+            Scope::Template(_) => &report.templates[..],
+        };
+        let figures = match &self.scope {
+            Scope::Run => Some(Figures::run(report)),
+            _ => groups.iter().find(|group| self.scope.matches(&group.name)).map(Figures::group),
+        };
+        // checked against the config, see check_scope
+        let Some(figures) = figures else { return f64::NAN };
+        let l = figures.latency;
+        match self.metric {
+            Metric::Min => l.min,
+            Metric::Avg => l.mean,
+            Metric::P50 => l.p50,
+            Metric::P90 => l.p90,
+            Metric::P95 => l.p95,
+            Metric::P99 => l.p99,
+            Metric::Max => l.max,
+            Metric::ErrorRate => match figures.requests {
+                0 => 0.0,
+                total => figures.errors as f64 / total as f64,
+            },
+            Metric::Rps => match report.duration_secs > 0.0 {
+                true => figures.requests as f64 / report.duration_secs,
+                false => 0.0,
+            },
+        }
+    }
+
+    pub fn evaluate(&self, report: &Report) -> ThresholdResult {
+        let actual = self.actual(report);
+        let passed = match self.op {
+            Op::Lt => actual < self.value,
+            Op::Le => actual <= self.value,
+            Op::Gt => actual > self.value,
+            Op::Ge => actual >= self.value,
+        };
+        let actual = match self.metric.is_latency() {
+            true => format!("{:.2}ms", actual),
+            false if self.metric == Metric::ErrorRate => format!("{:.4}", actual),
+            false => format!("{:.2}", actual),
+        };
+        ThresholdResult { threshold: self.spec.clone(), actual, passed }
+    }
+}
+
+impl Scope {
+    fn matches(&self, label: &str) -> bool {
+        match self {
+            Scope::Run => true,
+            Scope::Tag(name) => label == name || label.split_once('=').is_some_and(|(_, value)| value == name),
+            Scope::Endpoint(name) | Scope::Template(name) => label == name,
+        }
+    }
+}
+
+// the numbers a threshold can look at, for the run or a group of it
+struct Figures<'a> {
+    latency: &'a LatencySummary,
+    requests: u64,
+    errors: u64,
+}
+
+impl<'a> Figures<'a> {
+    fn run(report: &'a Report) -> Self {
+        Figures { latency: &report.latency_ms, requests: report.total_requests, errors: report.failed_requests }
+    }
+
+    fn group(group: &'a GroupSummary) -> Self {
+        Figures { latency: &group.latency_ms, requests: group.requests, errors: group.errors }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.spec)
+    }
+}
+
+impl Serialize for Threshold {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.spec)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThresholdResult {
+    pub threshold: String,
+    pub actual: String,
+    pub passed: bool,
+}
+
+// latencies take ms, s or us suffixes, error rates a % suffix
+fn parse_value(metric: Metric, value: &str) -> Option<f64> {
+    let (number, scale) = if metric.is_latency() {
+        if let Some(n) = value.strip_suffix("ms") {
+            (n, 1.0)
+        } else if let Some(n) = value.strip_suffix("us") {
+            (n, 0.001)
+        } else if let Some(n) = value.strip_suffix('s') {
+            (n, 1000.0)
+        } else {
+            (value, 1.0)
+        }
+    } else if let Some(n) = value.strip_suffix('%').filter(|_| metric == Metric::ErrorRate) {
+        (n, 0.01)
+    } else {
+        (value, 1.0)
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .map(|n| n * scale)
+}
+
+pub fn print_results(results: &[ThresholdResult]) {
+    if results.is_empty() {
+        return;
+    }
+    println!("thresholds:");
+    for result in results {
+        let outcome = if result.passed { "passed" } else { "FAILED" };
+        println!("  {:<30} {} (actual {})", result.threshold, outcome, result.actual);
+    }
+}
@@ -0,0 +1,208 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+use crate::metrics::{Aggregate, MetricSummary};
+use crate::report::csv_field;
+
+#[derive(Debug, Default)]
+struct Bucket {
+    requests: u64,
+    errors: u64,
+    latencies_us: Vec<u64>,
+    metrics: BTreeMap<String, Aggregate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Point {
+    pub timestamp: u64,
+    pub second: u64,
+    pub requests: u64,
+    pub errors: u64,
+    pub latency_mean_ms: f64,
+    pub latency_p50_ms: f64,
+    pub latency_p90_ms: f64,
+    pub latency_p99_ms: f64,
+    pub latency_max_ms: f64,
+    // the --metric values the responses of the second reported
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, MetricSummary>,
+}
+
+impl Point {
+    pub const CSV_HEADER: &'static str = "timestamp,second,requests,errors,latency_mean_ms,latency_p50_ms,latency_p90_ms,latency_p99_ms,latency_max_ms";
+
+    pub fn to_csv(&self) -> String {
+        [
+            self.timestamp.to_string(),
+            self.second.to_string(),
+            self.requests.to_string(),
+            self.errors.to_string(),
+            format!("{:.3}", self.latency_mean_ms),
+            format!("{:.3}", self.latency_p50_ms),
+            format!("{:.3}", self.latency_p90_ms),
+            format!("{:.3}", self.latency_p99_ms),
+            format!("{:.3}", self.latency_max_ms),
+        ]
+        .iter()
+        .map(|v| csv_field(v))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+// results bucketed by the second (relative to the run start) they completed in
+#[derive(Debug)]
+pub struct TimeSeries {
+    // monotonic and unix start of the run
+    start: Mutex<(Instant, u64)>,
+    open: Mutex<BTreeMap<u64, Bucket>>,
+    closed: Mutex<Vec<Point>>,
+}
+
+impl Default for TimeSeries {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSeries {
+    pub fn new() -> Self {
+        TimeSeries {
+            start: Mutex::new(now()),
+            open: Mutex::new(BTreeMap::new()),
+            closed: Mutex::new(Vec::new()),
+        }
+    }
+
+    // starts the clock over, for a measured window that begins after a
+    // warm-up; only meaningful while nothing has been recorded yet
+    pub fn restart(&self) {
+        *self.start.lock().unwrap() = now();
+    }
+
+    // no latency for a request that timed out
+    pub fn record(&self, latency: Option<Duration>, failed: bool) {
+        let second = self.start.lock().unwrap().0.elapsed().as_secs();
+        let mut open = self.open.lock().unwrap();
+        let bucket = open.entry(second).or_default();
+        bucket.requests += 1;
+        if failed {
+            bucket.errors += 1;
+        }
+        if let Some(latency) = latency {
+            bucket.latencies_us.push(latency.as_micros() as u64);
+        }
+    }
+
+    // a --metric value of a response, in the second it arrived in
+    pub fn record_metric(&self, name: &str, value: f64) {
+        let second = self.start.lock().unwrap().0.elapsed().as_secs();
+        let mut open = self.open.lock().unwrap();
+        open.entry(second).or_default().metrics.entry(name.to_string()).or_default().add(value);
+    }
+
+    // closes every bucket before the current second
+    pub fn flush(&self) {
+        let current = self.start.lock().unwrap().0.elapsed().as_secs();
+        self.close(|second| second < current)
+    }
+
+    // closes everything, used once the run is over
+    pub fn finish(&self) {
+        self.close(|_| true)
+    }
+
+    // closed points starting at index `from`, so several readers can follow along
+    pub fn points_from(&self, from: usize) -> Vec<Point> {
+        self.closed.lock().unwrap().get(from..).unwrap_or_default().to_vec()
+    }
+
+    pub fn last_points(&self, n: usize) -> Vec<Point> {
+        let closed = self.closed.lock().unwrap();
+        closed[closed.len().saturating_sub(n)..].to_vec()
+    }
+
+    fn close(&self, done: impl Fn(u64) -> bool) {
+        let mut open = self.open.lock().unwrap();
+        let seconds: Vec<u64> = open.keys().copied().filter(|s| done(*s)).collect();
+        let points: Vec<Point> = seconds
+            .into_iter()
+            .filter_map(|second| open.remove(&second).map(|bucket| (second, bucket)))
+            .map(|(second, bucket)| self.point(second, bucket))
+            .collect();
+        self.closed.lock().unwrap().extend(points);
+    }
+
+    fn point(&self, second: u64, mut bucket: Bucket) -> Point {
+        bucket.latencies_us.sort_unstable();
+        let latencies = &bucket.latencies_us;
+        let quantile = |q: f64| match latencies.len() {
+            0 => 0.0,
+            n => latencies[((q * n as f64).ceil() as usize).clamp(1, n) - 1] as f64 / 1000.0,
+        };
+        let mean = match latencies.len() {
+            0 => 0.0,
+            n => latencies.iter().sum::<u64>() as f64 / n as f64 / 1000.0,
+        };
+
+        Point {
+            timestamp: self.start.lock().unwrap().1 + second,
+            second,
+            requests: bucket.requests,
+            errors: bucket.errors,
+            latency_mean_ms: mean,
+            latency_p50_ms: quantile(0.50),
+            latency_p90_ms: quantile(0.90),
+            latency_p99_ms: quantile(0.99),
+            latency_max_ms: latencies.last().copied().unwrap_or(0) as f64 / 1000.0,
+            metrics: bucket.metrics.iter().map(|(name, aggregate)| (name.clone(), aggregate.summary())).collect(),
+        }
+    }
+}
+
+pub struct TimeSeriesWriter {
+    file: File,
+    csv: bool,
+    written: usize,
+}
+
+impl TimeSeriesWriter {
+    pub async fn create(path: &str) -> std::io::Result<Self> {
+        let csv = path.to_ascii_lowercase().ends_with(".csv");
+        let mut file = File::create(path).await?;
+        if csv {
+            file.write_all(format!("{}\n", Point::CSV_HEADER).as_bytes()).await?;
+        }
+        Ok(TimeSeriesWriter { file, csv, written: 0 })
+    }
+
+    // appends every point closed since the last call
+    pub async fn write_new(&mut self, series: &TimeSeries) -> std::io::Result<()> {
+        let points = series.points_from(self.written);
+        self.written += points.len();
+
+        let mut buf = String::new();
+        for point in &points {
+            if self.csv {
+                buf.push_str(&point.to_csv());
+            } else {
+                buf.push_str(&serde_json::to_string(point).map_err(std::io::Error::other)?);
+            }
+            buf.push('\n');
+        }
+        self.file.write_all(buf.as_bytes()).await?;
+        self.file.flush().await
+    }
+}
+
+fn now() -> (Instant, u64) {
+    let unix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    (Instant::now(), unix)
+}
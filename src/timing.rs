@@ -0,0 +1,263 @@
+use reqwest::Url;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::task::JoinHandle;
+#[cfg(any(target_os = "windows", target_vendor = "apple"))]
+use tokio_native_tls::TlsConnector;
+use tracing::warn;
+#[cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+use {
+    openssl::ssl::{SslConnector, SslSession, SslSessionCacheMode},
+    std::sync::atomic::Ordering,
+    std::sync::Mutex,
+};
+
+use crate::histogram::{Histogram, HistogramSnapshot};
+use crate::stats::LoadTestStats;
+use crate::target::Targets;
+use crate::tls::{IpFamily, TlsOptions};
+
+// how often each origin gets a fresh probe connection
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+// how long a probe waits after a full handshake for the tls 1.3 session
+// tickets, which the server sends once it's done
+#[cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+const TICKET_WAIT: Duration = Duration::from_millis(500);
+
+// where the time of a request goes. reqwest keeps connection setup to
+// itself, so dns, connect and tls are timed on probe connections opened
+// alongside the run; ttfb and download are timed on every http request
+#[derive(Debug, Default)]
+pub struct Phases {
+    pub dns: Histogram,
+    pub connect: Histogram,
+    // full handshakes, what every connection of the load does
+    pub tls: Histogram,
+    // handshakes that resumed the session of the probe before
+    pub tls_resumed: Histogram,
+    pub ttfb: Histogram,
+    pub download: Histogram,
+}
+
+impl Phases {
+    pub fn all(&self) -> [(&'static str, &Histogram); 6] {
+        [
+            ("dns", &self.dns),
+            ("connect", &self.connect),
+            ("tls", &self.tls),
+            ("tls_resumed", &self.tls_resumed),
+            ("ttfb", &self.ttfb),
+            ("download", &self.download),
+        ]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.all().iter().all(|(_, phase)| phase.count() == 0)
+    }
+
+    pub fn snapshot(&self) -> Vec<HistogramSnapshot> {
+        self.all().iter().map(|(_, phase)| phase.snapshot()).collect()
+    }
+
+    pub fn merge(&self, other: &[HistogramSnapshot]) {
+        for ((_, phase), other) in self.all().iter().zip(other) {
+            phase.merge(other);
+        }
+    }
+}
+
+pub fn record(phase: &Histogram, elapsed: Duration) {
+    phase.record(elapsed.as_micros() as u64);
+}
+
+// one probe loop per distinct scheme://host:port of the targets, recording
+// into `stats` until the handles are aborted
+pub fn start_probes(
+    targets: &Targets,
+    stats: &Arc<LoadTestStats>,
+    tls: &TlsOptions,
+) -> Result<Vec<JoinHandle<()>>, Box<dyn std::error::Error>> {
+    targets
+        .origins(tls.host_override.as_ref())
+        .into_iter()
+        .map(|origin| {
+            let probe = Probe {
+                lookup: tls.lookup_name(origin.host_str().unwrap_or_default()).to_string(),
+                resolved: tls.resolved(origin.host_str().unwrap_or_default(), origin.port_or_known_default().unwrap_or(0)),
+                family: tls.family,
+                handshaker: Handshaker::new(tls)?,
+                origin,
+            };
+            Ok(tokio::spawn(probe_loop(probe, stats.clone())))
+        })
+        .collect()
+}
+
+struct Probe {
+    origin: Url,
+    // the host to look up, the endpoints' for the sni name
+    lookup: String,
+    // a --resolve override
+    resolved: Option<IpAddr>,
+    family: Option<IpFamily>,
+    handshaker: Handshaker,
+}
+
+async fn probe_loop(probe: Probe, stats: Arc<LoadTestStats>) {
+    let mut interval = tokio::time::interval(PROBE_INTERVAL);
+    let mut warned = false;
+    loop {
+        interval.tick().await;
+        let error = match tokio::time::timeout(PROBE_TIMEOUT, probe.run(&stats)).await {
+            Ok(Ok(())) => continue,
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => format!("no connection within {:?}", PROBE_TIMEOUT),
+        };
+        // once per origin, a broken probe shouldn't drown the run's own errors
+        if !warned {
+            warn!(origin = probe.origin.origin().ascii_serialization(), error, "timing probe failed");
+            warned = true;
+        }
+    }
+}
+
+impl Probe {
+    // connects the way the client would, timing each step
+    async fn run(&self, stats: &Arc<LoadTestStats>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let phases = &stats.phases;
+        let url = &self.origin;
+        let host = url.host_str().ok_or("url without a host")?;
+        let port = url.port_or_known_default().ok_or("url without a port")?;
+        let addr = match self.resolved {
+            Some(ip) => SocketAddr::new(ip, port),
+            None => {
+                let start = Instant::now();
+                let addr = lookup_host((self.lookup.trim_matches(['[', ']']), port))
+                    .await?
+                    .find(|addr| self.family.is_none_or(|family| family.matches(&addr.ip())))
+                    .ok_or_else(|| format!("{} has no addresses", self.lookup))?;
+                record(&phases.dns, start.elapsed());
+                addr
+            }
+        };
+
+        let start = Instant::now();
+        let stream = TcpStream::connect(addr).await?;
+        record(&phases.connect, start.elapsed());
+
+        if url.scheme() == "https" {
+            self.handshaker.handshake(host.trim_matches(['[', ']']), stream, stats).await?;
+        }
+        Ok(())
+    }
+}
+
+// native-tls keeps no sessions, every probe does a full handshake
+#[cfg(any(target_os = "windows", target_vendor = "apple"))]
+struct Handshaker {
+    connector: TlsConnector,
+}
+
+#[cfg(any(target_os = "windows", target_vendor = "apple"))]
+impl Handshaker {
+    fn new(tls: &TlsOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Handshaker { connector: TlsConnector::from(tls.connector()?) })
+    }
+
+    async fn handshake(
+        &self,
+        host: &str,
+        stream: TcpStream,
+        stats: &Arc<LoadTestStats>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let start = Instant::now();
+        self.connector.connect(host, stream).await?;
+        record(&stats.phases.tls, start.elapsed());
+        Ok(())
+    }
+}
+
+// every other probe offers the session of the full handshake before it, so
+// both kinds get timed and the report shows whether the server resumes
+#[cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+#[derive(Clone)]
+struct Handshaker {
+    connector: SslConnector,
+    insecure: bool,
+    // the last session the server handed out, none with --probe-full-handshakes
+    session: Option<Arc<Mutex<Option<SslSession>>>>,
+}
+
+#[cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+impl Handshaker {
+    fn new(tls: &TlsOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut builder = tls.ssl_connector()?;
+        let session = match tls.probe_full_handshakes {
+            true => None,
+            false => {
+                let session: Arc<Mutex<Option<SslSession>>> = Arc::default();
+                let slot = session.clone();
+                builder.set_session_cache_mode(SslSessionCacheMode::CLIENT);
+                builder.set_new_session_callback(move |_, new| *slot.lock().unwrap() = Some(new));
+                Some(session)
+            }
+        };
+        Ok(Handshaker { connector: builder.build(), insecure: tls.insecure, session })
+    }
+
+    // openssl's handshake blocks, off the runtime's workers
+    async fn handshake(
+        &self,
+        host: &str,
+        stream: TcpStream,
+        stats: &Arc<LoadTestStats>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let stream = stream.into_std()?;
+        stream.set_nonblocking(false)?;
+        let (handshaker, host, stats) = (self.clone(), host.to_string(), stats.clone());
+        tokio::task::spawn_blocking(move || handshaker.blocking(&host, stream, &stats)).await?
+    }
+
+    fn blocking(
+        &self,
+        host: &str,
+        stream: std::net::TcpStream,
+        stats: &LoadTestStats,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        stream.set_read_timeout(Some(PROBE_TIMEOUT))?;
+        stream.set_write_timeout(Some(PROBE_TIMEOUT))?;
+        let mut config = self.connector.configure()?;
+        config.set_verify_hostname(!self.insecure);
+        let offered = self.session.as_ref().and_then(|session| session.lock().unwrap().take());
+        if let Some(session) = &offered {
+            // safe: the session came out of this connector's own context
+            unsafe { config.set_session(session)? };
+            stats.probe_resumption_offered.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let start = Instant::now();
+        let mut tls = config.connect(host, stream).map_err(|e| e.to_string())?;
+        let elapsed = start.elapsed();
+        match tls.ssl().session_reused() {
+            true => record(&stats.phases.tls_resumed, elapsed),
+            false => record(&stats.phases.tls, elapsed),
+        }
+
+        match (&self.session, offered) {
+            // whatever this one handed out, the next probe does a full handshake
+            (Some(session), Some(_)) => *session.lock().unwrap() = None,
+            // tls 1.3 tickets come after the handshake; a close_notify each
+            // way reads up to them without waiting for anything else
+            (Some(_), None) => {
+                tls.get_ref().set_read_timeout(Some(TICKET_WAIT))?;
+                let _ = tls.shutdown();
+                let _ = tls.shutdown();
+            }
+            (None, _) => {}
+        }
+        Ok(())
+    }
+}
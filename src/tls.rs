@@ -0,0 +1,310 @@
+use reqwest::{Certificate, ClientBuilder, Identity, Url};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+#[cfg(any(target_os = "windows", target_vendor = "apple"))]
+use tokio_native_tls::native_tls;
+
+// how https connections are verified and where hosts resolve to, for the
+// load itself, the oauth token requests and the --timing probes alike
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TlsOptions {
+    // pem file of extra trusted roots
+    pub cacert: Option<String>,
+    // pem client certificate and pkcs#8 key for mtls
+    pub cert: Option<String>,
+    pub key: Option<String>,
+    pub insecure: bool,
+    // --probe-full-handshakes, every --timing probe does a full handshake;
+    // the load's connections always do
+    pub probe_full_handshakes: bool,
+    pub resolve: Vec<Resolve>,
+    // --ipv4 or --ipv6, connect over this address family only
+    pub family: Option<IpFamily>,
+    // --host-header and --sni
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_override: Option<HostOverride>,
+}
+
+// connect to `addr` whenever `host` is asked for; the url keeps the host,
+// so it still goes out as sni and in the host header
+#[derive(Debug, Clone, Serialize)]
+pub struct Resolve {
+    pub host: String,
+    // curl's host:port:address; reqwest overrides a host on every port, so
+    // endpoints on the host have to use this one, see TlsOptions::check
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    pub addr: IpAddr,
+}
+
+// connect to the endpoints' host, an internal address or a load balancer,
+// while presenting another name in the host header and sni, e.g.
+// production's before the dns cutover; requests go out to the sni name,
+// which the clients' resolver looks up as the endpoints' host
+#[derive(Debug, Clone, Serialize)]
+pub struct HostOverride {
+    // the host of the endpoints
+    pub connect: String,
+    pub host_header: String,
+    pub sni: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl Resolve {
+    // "api.internal:10.0.0.7" or "api.internal:443:10.0.0.7", an ipv6
+    // address in brackets
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (host, rest) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("expected host:address or host:port:address, got: {}", spec))?;
+        let ip = |text: &str| text.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().ok();
+        let (port, addr) = match rest.split_once(':') {
+            Some((port, addr)) if !rest.starts_with('[') => match (port.parse::<u16>(), ip(addr)) {
+                (Ok(port), Some(addr)) => (Some(port), Some(addr)),
+                _ => (None, ip(rest)),
+            },
+            _ => (None, ip(rest)),
+        };
+        let addr = addr.ok_or_else(|| format!("invalid address in --resolve {}", spec))?;
+        Ok(Resolve { host: host.to_string(), port, addr })
+    }
+}
+
+impl HostOverride {
+    // the host header defaults to the endpoints' host and the sni to the
+    // host header's name; every endpoint has to be on the same host, the
+    // sni name can only resolve to one
+    pub fn new<'a>(host_header: Option<String>, sni: Option<String>, urls: impl IntoIterator<Item = &'a str>) -> Result<Option<Self>, String> {
+        if host_header.is_none() && sni.is_none() {
+            return Ok(None);
+        }
+        let mut connect: Option<String> = None;
+        for url in urls {
+            let parsed = Url::parse(url).map_err(|e| format!("{}: {}", url, e))?;
+            let host = parsed.host_str().ok_or_else(|| format!("{}: no host to connect to", url))?;
+            match &connect {
+                Some(other) if !other.eq_ignore_ascii_case(host) => {
+                    return Err(format!("--host-header and --sni need every endpoint on one host, got {} and {}", other, host))
+                }
+                Some(_) => {}
+                None => connect = Some(host.to_string()),
+            }
+        }
+        let connect = connect.ok_or("--host-header and --sni need an endpoint")?;
+        let host_header = host_header.unwrap_or_else(|| connect.clone());
+        let sni = match sni {
+            Some(sni) => sni,
+            // api.example.com:8443 leaves the port to the url
+            None => match host_header.rsplit_once(':') {
+                Some((name, port)) if port.parse::<u16>().is_ok() && !name.ends_with(':') => name.to_string(),
+                _ => host_header.clone(),
+            },
+        };
+        Url::parse(&format!("https://{}/", sni)).map_err(|e| format!("--sni {}: {}", sni, e))?;
+        Ok(Some(HostOverride { connect, host_header, sni }))
+    }
+
+    // the url with the sni name in place of the endpoints' host, one on
+    // another host as it is
+    pub fn rewrite(&self, url: &str) -> String {
+        let Ok(mut parsed) = Url::parse(url) else { return url.to_string() };
+        if !parsed.host_str().is_some_and(|host| host.eq_ignore_ascii_case(&self.connect)) {
+            return url.to_string();
+        }
+        match parsed.set_host(Some(&self.sni)) {
+            Ok(()) => parsed.into(),
+            Err(_) => url.to_string(),
+        }
+    }
+
+    // an explicit Host header, unless the rewritten url already gives it
+    pub fn header(&self) -> Option<&str> {
+        (!self.host_header.eq_ignore_ascii_case(&self.sni)).then_some(self.host_header.as_str())
+    }
+
+    // the name to look `host` up as, the endpoints' for the sni name
+    pub fn lookup_name<'a>(&'a self, host: &'a str) -> &'a str {
+        match host.eq_ignore_ascii_case(&self.sni) {
+            true => self.connect.trim_start_matches('[').trim_end_matches(']'),
+            false => host,
+        }
+    }
+}
+
+impl fmt::Display for HostOverride {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "connect to {}, host {}, sni {}", self.connect, self.host_header, self.sni)
+    }
+}
+
+impl fmt::Display for Resolve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}:{} -> {}", self.host, port, self.addr),
+            None => write!(f, "{} -> {}", self.host, self.addr),
+        }
+    }
+}
+
+impl IpFamily {
+    pub fn matches(self, addr: &IpAddr) -> bool {
+        match self {
+            IpFamily::Ipv4 => addr.is_ipv4(),
+            IpFamily::Ipv6 => addr.is_ipv6(),
+        }
+    }
+
+    // bound locally, this keeps the connector to addresses of the family
+    fn unspecified(self) -> IpAddr {
+        match self {
+            IpFamily::Ipv4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpFamily::Ipv6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+}
+
+impl FromStr for IpFamily {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ipv4" => Ok(IpFamily::Ipv4),
+            "ipv6" => Ok(IpFamily::Ipv6),
+            other => Err(format!("unknown ip family: {}, expected ipv4 or ipv6", other)),
+        }
+    }
+}
+
+impl fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            IpFamily::Ipv4 => "ipv4",
+            IpFamily::Ipv6 => "ipv6",
+        })
+    }
+}
+
+impl TlsOptions {
+    // a --resolve override of `host`
+    pub fn resolved(&self, host: &str, port: u16) -> Option<IpAddr> {
+        self.resolve
+            .iter()
+            .find(|r| r.host.eq_ignore_ascii_case(host) && r.port.is_none_or(|p| p == port))
+            .map(|r| r.addr)
+    }
+
+    // the name a lookup of `host` goes to, see HostOverride::lookup_name
+    pub fn lookup_name<'a>(&'a self, host: &'a str) -> &'a str {
+        self.host_override.as_ref().map_or(host, |o| o.lookup_name(host))
+    }
+
+    // overrides reqwest couldn't honour: an address of the other family, or
+    // a port other than the one an endpoint on the host uses
+    pub fn check<'a>(&self, urls: impl IntoIterator<Item = &'a str>) -> Result<(), String> {
+        let urls: Vec<Url> = urls.into_iter().filter_map(|url| Url::parse(url).ok()).collect();
+        for r in &self.resolve {
+            if let Some(family) = self.family.filter(|family| !family.matches(&r.addr)) {
+                return Err(format!("--resolve {}: not an {} address", r, family));
+            }
+            let Some(port) = r.port else { continue };
+            let other = urls.iter().find(|url| {
+                url.host_str().is_some_and(|host| host.eq_ignore_ascii_case(&r.host))
+                    && url.port_or_known_default() != Some(port)
+            });
+            if let Some(url) = other {
+                return Err(format!("--resolve {}: {} is on another port, the override covers every port of the host", r, url));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder, Box<dyn std::error::Error>> {
+        if let Some(path) = &self.cacert {
+            let pem = read(path)?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem).map_err(|e| format!("{}: {}", path, e))?);
+        }
+        if let Some((cert, key)) = self.identity()? {
+            builder = builder.identity(Identity::from_pkcs8_pem(&cert, &key).map_err(|e| format!("client certificate: {}", e))?);
+        }
+        if self.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        for r in &self.resolve {
+            // reqwest takes the port from the url
+            builder = builder.resolve(&r.host, SocketAddr::new(r.addr, 0));
+        }
+        if let Some(family) = self.family {
+            builder = builder.local_address(family.unspecified());
+        }
+        Ok(builder)
+    }
+
+    // the same settings for connections made outside reqwest
+    #[cfg(any(target_os = "windows", target_vendor = "apple"))]
+    pub fn connector(&self) -> Result<native_tls::TlsConnector, Box<dyn std::error::Error>> {
+        let mut builder = native_tls::TlsConnector::builder();
+        if let Some(path) = &self.cacert {
+            let pem = read(path)?;
+            builder.add_root_certificate(native_tls::Certificate::from_pem(&pem).map_err(|e| format!("{}: {}", path, e))?);
+        }
+        if let Some((cert, key)) = self.identity()? {
+            builder.identity(native_tls::Identity::from_pkcs8(&cert, &key).map_err(|e| format!("client certificate: {}", e))?);
+        }
+        builder.danger_accept_invalid_certs(self.insecure);
+        Ok(builder.build()?)
+    }
+
+    // the same, straight from openssl where native-tls uses it, which unlike
+    // native-tls can hand a session on to the next connection
+    #[cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+    pub fn ssl_connector(&self) -> Result<openssl::ssl::SslConnectorBuilder, Box<dyn std::error::Error>> {
+        use openssl::pkey::PKey;
+        use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+        use openssl::x509::X509;
+
+        let mut builder = SslConnector::builder(SslMethod::tls_client())?;
+        if let Some(path) = &self.cacert {
+            for cert in X509::stack_from_pem(&read(path)?).map_err(|e| format!("{}: {}", path, e))? {
+                builder.cert_store_mut().add_cert(cert)?;
+            }
+        }
+        if let Some((cert, key)) = self.identity()? {
+            let mut chain = X509::stack_from_pem(&cert).map_err(|e| format!("client certificate: {}", e))?.into_iter();
+            let leaf = chain.next().ok_or("client certificate: no certificate in the pem")?;
+            builder.set_certificate(&leaf)?;
+            for cert in chain {
+                builder.add_extra_chain_cert(cert)?;
+            }
+            let key = PKey::private_key_from_pem(&key).map_err(|e| format!("client certificate: {}", e))?;
+            builder.set_private_key(&key)?;
+        }
+        if self.insecure {
+            builder.set_verify(SslVerifyMode::NONE);
+        }
+        Ok(builder)
+    }
+
+    fn identity(&self) -> Result<Option<KeyPair>, String> {
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => Ok(Some((read(cert)?, read(key)?))),
+            (None, None) => Ok(None),
+            _ => Err("--cert and --key go together".into()),
+        }
+    }
+}
+
+// pem of a client certificate and its key
+type KeyPair = (Vec<u8>, Vec<u8>);
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("{}: {}", path, e))
+}
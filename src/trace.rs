@@ -0,0 +1,161 @@
+use rand::RngCore;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+// spans buffered before workers start waiting on the exporter
+const CHANNEL_CAPACITY: usize = 4096;
+// spans per otlp request, and how long a partial batch may wait
+const BATCH_SIZE: usize = 512;
+const BATCH_DELAY: Duration = Duration::from_secs(1);
+const SPAN_KIND_CLIENT: u8 = 3;
+const STATUS_ERROR: u8 = 2;
+
+// w3c trace context of one request: a fresh trace per request, with the
+// client span as the parent the server's spans hang off
+#[derive(Debug, Clone, Copy)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+}
+
+impl TraceContext {
+    // ids come from the thread rng, payloads stay the same with tracing on
+    pub fn random() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut context = TraceContext { trace_id: [0; 16], span_id: [0; 8] };
+        rng.fill_bytes(&mut context.trace_id);
+        rng.fill_bytes(&mut context.span_id);
+        context
+    }
+
+    pub fn trace_id(&self) -> String {
+        hex(&self.trace_id)
+    }
+
+    // value of the traceparent header, sampled
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", hex(&self.trace_id), hex(&self.span_id))
+    }
+}
+
+// the client side of one request, from the first send to the end of the body
+#[derive(Debug)]
+pub struct Span {
+    pub context: TraceContext,
+    pub method: String,
+    pub url: String,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+impl Span {
+    fn to_otlp(&self) -> Value {
+        let mut attributes = vec![
+            attribute("http.request.method", json!({ "stringValue": self.method })),
+            attribute("url.full", json!({ "stringValue": self.url })),
+        ];
+        if let Some(status) = self.status {
+            attributes.push(attribute("http.response.status_code", json!({ "intValue": status.to_string() })));
+        }
+        let failed = self.error.is_some() || self.status.is_some_and(|status| status >= 400);
+        let status = match (&self.error, failed) {
+            (Some(error), _) => json!({ "code": STATUS_ERROR, "message": error }),
+            (None, true) => json!({ "code": STATUS_ERROR }),
+            (None, false) => json!({}),
+        };
+        json!({
+            "traceId": hex(&self.context.trace_id),
+            "spanId": hex(&self.context.span_id),
+            "name": self.method,
+            "kind": SPAN_KIND_CLIENT,
+            "startTimeUnixNano": unix_nanos(self.start).to_string(),
+            "endTimeUnixNano": unix_nanos(self.end).to_string(),
+            "attributes": attributes,
+            "status": status,
+        })
+    }
+}
+
+// ships spans to an otlp/http collector as json, in batches
+#[derive(Debug, Clone)]
+pub struct SpanExporter {
+    tx: mpsc::Sender<Span>,
+}
+
+impl SpanExporter {
+    // `endpoint` is the collector's base url, spans go to /v1/traces; the
+    // task finishes once every exporter clone has been dropped
+    pub fn start(endpoint: &str, client: Client) -> (Self, JoinHandle<()>) {
+        let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+        let (tx, mut rx) = mpsc::channel::<Span>(CHANNEL_CAPACITY);
+
+        let task = tokio::spawn(async move {
+            let mut batch = Vec::new();
+            let mut warned = false;
+            let mut tick = tokio::time::interval(BATCH_DELAY);
+            loop {
+                // (channel closed, partial batch due)
+                let (closed, due) = tokio::select! {
+                    span = rx.recv() => match span {
+                        Some(span) => {
+                            batch.push(span.to_otlp());
+                            (false, false)
+                        }
+                        None => (true, true),
+                    },
+                    _ = tick.tick() => (false, true),
+                };
+                if batch.len() >= BATCH_SIZE || (due && !batch.is_empty()) {
+                    let spans = std::mem::take(&mut batch);
+                    if let Err(e) = export(&client, &url, spans).await {
+                        // once, a missing collector shouldn't drown the run's own errors
+                        if !warned {
+                            warn!(url, error = %e, "otlp export failed");
+                            warned = true;
+                        }
+                    }
+                }
+                if closed {
+                    break;
+                }
+            }
+        });
+        (SpanExporter { tx }, task)
+    }
+
+    pub async fn export(&self, span: Span) {
+        let _ = self.tx.send(span).await;
+    }
+}
+
+async fn export(client: &Client, url: &str, spans: Vec<Value>) -> Result<(), Box<dyn std::error::Error>> {
+    let body = json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [attribute("service.name", json!({ "stringValue": "arctic" }))] },
+            "scopeSpans": [{ "scope": { "name": "arctic" }, "spans": spans }],
+        }],
+    });
+    let response = client.post(url).json(&body).send().await?;
+    if !response.status().is_success() {
+        return Err(format!("collector returned {}", response.status()).into());
+    }
+    Ok(())
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,130 @@
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph, Sparkline};
+use ratatui::Frame;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use arctic::config::LoadConfig;
+use arctic::stats::LoadTestStats;
+
+const REFRESH: Duration = Duration::from_secs(1);
+const HISTORY_SECS: usize = 120;
+
+// blocking, meant to be run on its own thread; q, esc or ctrl-c stops the run
+pub fn run(
+    stats: Arc<LoadTestStats>,
+    config: LoadConfig,
+    is_running: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+) -> std::io::Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let mut last_draw: Option<Instant> = None;
+
+    let result = (|| {
+        while is_running.load(Ordering::SeqCst) {
+            if last_draw.is_none_or(|t| t.elapsed() >= REFRESH) {
+                stats.timeseries.flush();
+                terminal.draw(|frame| draw(frame, &stats, &config))?;
+                last_draw = Some(Instant::now());
+            }
+
+            if event::poll(Duration::from_millis(100))? {
+                if let Event::Key(key) = event::read()? {
+                    let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                    let quit = matches!(key.code, KeyCode::Char('q') | KeyCode::Esc);
+                    if key.kind == KeyEventKind::Press && (quit || ctrl_c) {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                }
+            }
+        }
+        Ok(())
+    })();
+
+    ratatui::restore();
+    result
+}
+
+fn draw(frame: &mut Frame, stats: &LoadTestStats, config: &LoadConfig) {
+    let [progress_area, text_area, rps_area, latency_area] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(8),
+        Constraint::Min(5),
+        Constraint::Min(5),
+    ])
+    .areas(frame.area());
+
+    let elapsed = stats.start_time().elapsed().as_secs_f64();
+    let total = stats.total_requests.load(Ordering::SeqCst);
+    let (ratio, label) = match (config.duration, config.requests) {
+        (Some(duration), _) => {
+            let duration = duration.max(1) as f64;
+            (elapsed / duration, format!("{:.0}s / {:.0}s", elapsed, duration))
+        }
+        (None, Some(requests)) => (
+            total as f64 / requests.max(1) as f64,
+            format!("{} / {} requests", total, requests),
+        ),
+        (None, None) => (0.0, format!("{:.0}s", elapsed)),
+    };
+    let progress = Gauge::default()
+        .block(Block::default().borders(Borders::ALL).title(format!(" arctic - {} ", title(config))))
+        .gauge_style(Style::default().fg(Color::Cyan))
+        .ratio(ratio.clamp(0.0, 1.0))
+        .label(label);
+    frame.render_widget(progress, progress_area);
+
+    let history = stats.timeseries.last_points(HISTORY_SECS);
+    let last = history.last();
+    let errors = stats.error_count.load(Ordering::SeqCst);
+    let error_rate = match total {
+        0 => 0.0,
+        n => errors as f64 / n as f64 * 100.0,
+    };
+
+    let lines = vec![
+        Line::from(format!("requests:        {}  ({:.1} rps avg)", total, total as f64 / elapsed.max(0.001))),
+        Line::from(format!("current rps:     {}", last.map(|p| p.requests).unwrap_or(0))),
+        Line::from(format!("errors:          {} ({:.2}%)", errors, error_rate)),
+        Line::from(format!(
+            "last second:     p50 {:.2}ms  p90 {:.2}ms  p99 {:.2}ms",
+            last.map(|p| p.latency_p50_ms).unwrap_or(0.0),
+            last.map(|p| p.latency_p90_ms).unwrap_or(0.0),
+            last.map(|p| p.latency_p99_ms).unwrap_or(0.0),
+        )),
+        Line::from(format!(
+            "overall:         p50 {:.2}ms  p90 {:.2}ms  p99 {:.2}ms",
+            stats.latency_ms(0.50),
+            stats.latency_ms(0.90),
+            stats.latency_ms(0.99),
+        )),
+        Line::from("press q to stop"),
+    ];
+    let text = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" stats "));
+    frame.render_widget(text, text_area);
+
+    let rps: Vec<u64> = history.iter().map(|p| p.requests).collect();
+    let rps = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title(" requests / second "))
+        .style(Style::default().fg(Color::Green))
+        .data(&rps[rps.len().saturating_sub(rps_area.width.saturating_sub(2) as usize)..]);
+    frame.render_widget(rps, rps_area);
+
+    let p99: Vec<u64> = history.iter().map(|p| (p.latency_p99_ms * 1000.0) as u64).collect();
+    let latency = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title(" p99 latency "))
+        .style(Style::default().fg(Color::Yellow))
+        .data(&p99[p99.len().saturating_sub(latency_area.width.saturating_sub(2) as usize)..]);
+    frame.render_widget(latency, latency_area);
+}
+
+fn title(config: &LoadConfig) -> String {
+    match config.endpoints.as_slice() {
+        [target] => target.url.clone(),
+        targets => format!("{} endpoints", targets.len()),
+    }
+}
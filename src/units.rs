@@ -0,0 +1,85 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+// "500ms", "30s", "2m", "1h", "1h30m"; a bare number is seconds
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if let Ok(secs) = s.parse::<f64>() {
+        return duration_from_secs(secs, s);
+    }
+
+    let mut total = 0.0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(|| format!("missing unit in duration: {}", s))?;
+        let value: f64 = rest[..split]
+            .parse()
+            .map_err(|_| format!("invalid duration: {}", s))?;
+        rest = &rest[split..];
+
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let multiplier = match &rest[..unit_end] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            unit => return Err(format!("unknown duration unit '{}' in: {}", unit, s)),
+        };
+        total += value * multiplier;
+        rest = &rest[unit_end..];
+    }
+
+    duration_from_secs(total, s)
+}
+
+// "512", "64k", "64KiB", "2MB", "1g"; units are binary
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let value: f64 = s[..split].parse().map_err(|_| format!("invalid size: {}", s))?;
+    let multiplier = match s[split..].trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" | "kib" => 1024.0,
+        "m" | "mb" | "mib" => 1024.0 * 1024.0,
+        "g" | "gb" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        unit => return Err(format!("unknown size unit '{}' in: {}", unit, s)),
+    };
+    Ok((value * multiplier) as usize)
+}
+
+fn duration_from_secs(secs: f64, s: &str) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("invalid duration: {}", s))
+}
+
+pub fn serialize_secs<S: serde::Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(d.as_secs_f64())
+}
+
+pub fn serialize_opt_secs<S: serde::Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+    match d {
+        Some(d) => s.serialize_some(&d.as_secs_f64()),
+        None => s.serialize_none(),
+    }
+}
+
+// config files may give durations as "500ms" style strings or plain seconds
+pub fn deserialize_opt_duration<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Secs(f64),
+        Text(String),
+    }
+
+    match Option::<Raw>::deserialize(d)? {
+        Some(Raw::Secs(secs)) => duration_from_secs(secs, &secs.to_string())
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        Some(Raw::Text(text)) => parse_duration(&text).map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
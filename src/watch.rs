@@ -0,0 +1,55 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+
+use tracing::{info, warn};
+
+use crate::generator::Generator;
+use crate::template;
+
+// reloads the --data template into `generator` whenever it or a fragment it
+// $includes changes, for as long as the returned watcher is kept. the
+// directories are watched rather than the files, editors tend to save by
+// replacing them. a fragment newly included from a directory none of the
+// files were in at the start is read, but edits to it aren't seen
+pub fn watch_template(generator: Generator, path: &str) -> Result<RecommendedWatcher, Box<dyn std::error::Error>> {
+    let (value, mut files) = template::read_files(path).map_err(|e| format!("--watch {}", e))?;
+    let mut dirs: Vec<PathBuf> = files.iter().filter_map(|file| file.parent().map(Path::to_path_buf)).collect();
+    dirs.sort();
+    dirs.dedup();
+    let path = path.to_string();
+    // one save comes as several events, only a change in what the files
+    // make up counts
+    let mut last = Ok(value);
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        if !event.paths.iter().any(|p| files.contains(p)) {
+            return;
+        }
+        match template::read_files(&path) {
+            Ok((value, read)) => {
+                if last.as_ref() == Ok(&value) {
+                    return;
+                }
+                last = Ok(value);
+                match generator.reload(&path) {
+                    Ok(()) => {
+                        files = read;
+                        info!(path, "template reloaded");
+                    }
+                    Err(e) => warn!(path, error = %e, "template doesn't load, keeping the previous one"),
+                }
+            }
+            Err(e) => {
+                if last.as_ref().err() != Some(&e) {
+                    warn!(path, error = %e, "template doesn't load, keeping the previous one");
+                }
+                last = Err(e);
+            }
+        }
+    })?;
+    for dir in &dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    }
+    Ok(watcher)
+}
@@ -0,0 +1,302 @@
+use clap::ArgMatches;
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, sleep_until, timeout};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::warn;
+
+use crate::auth::Header;
+use crate::generator::Generator;
+use crate::histogram::Histogram;
+use crate::report::LatencySummary;
+
+// wait before dialing again after a failed connect or a drop
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// sends still waiting for an echo, per connection; older ones are forgotten
+const MAX_PENDING_ECHOES: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WsConfig {
+    pub endpoint: String,
+    pub data: String,
+    pub duration: u64,
+    pub connections: usize,
+    // messages per second over all connections
+    pub rate: f64,
+    pub seed: u64,
+    #[serde(rename = "connect_timeout_secs", serialize_with = "crate::units::serialize_secs")]
+    pub connect_timeout: Duration,
+    pub headers: Vec<Header>,
+    #[serde(skip)]
+    pub output: Option<String>,
+}
+
+impl WsConfig {
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, Box<dyn std::error::Error>> {
+        let connections = *matches.get_one::<usize>("concurrency").unwrap();
+        Ok(WsConfig {
+            endpoint: matches.get_one::<String>("endpoint").unwrap().clone(),
+            data: matches.get_one::<String>("data").unwrap().clone(),
+            duration: *matches.get_one::<u64>("time").unwrap(),
+            connections: connections.max(1),
+            rate: matches.get_one::<f64>("rate").copied().unwrap_or(connections as f64),
+            seed: matches
+                .get_one::<u64>("seed")
+                .copied()
+                .unwrap_or_else(rand::random),
+            connect_timeout: matches
+                .get_one::<Duration>("timeout")
+                .copied()
+                .unwrap_or(DEFAULT_CONNECT_TIMEOUT),
+            headers: matches
+                .get_many::<String>("header")
+                .unwrap_or_default()
+                .map(|spec| Header::parse(spec))
+                .collect::<Result<_, _>>()?,
+            output: matches.get_one::<String>("output").cloned(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct WsStats {
+    pub connects: AtomicU64,
+    pub connect_failures: AtomicU64,
+    pub dropped: AtomicU64,
+    pub sent: AtomicU64,
+    pub received: AtomicU64,
+    pub send_errors: AtomicU64,
+    // send to echo, for servers that echo messages back in order
+    pub round_trip: Histogram,
+    pub start_time: Instant,
+}
+
+impl Default for WsStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WsStats {
+    pub fn new() -> Self {
+        WsStats {
+            connects: AtomicU64::new(0),
+            connect_failures: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            send_errors: AtomicU64::new(0),
+            round_trip: Histogram::new(),
+            start_time: Instant::now(),
+        }
+    }
+
+    pub fn print_summary(&self) {
+        let elapsed = self.start_time.elapsed().as_secs_f64();
+        let sent = self.sent.load(Ordering::SeqCst);
+        let ms = |q: f64| self.round_trip.quantile(q) as f64 / 1000.0;
+
+        println!("\n===== WebSocket Summary =====");
+        println!("total duration:      {:.2}s", elapsed);
+        println!("connections opened:  {}", self.connects.load(Ordering::SeqCst));
+        println!("connect failures:    {}", self.connect_failures.load(Ordering::SeqCst));
+        println!("dropped connections: {}", self.dropped.load(Ordering::SeqCst));
+        println!("messages sent:       {}", sent);
+        println!("messages received:   {}", self.received.load(Ordering::SeqCst));
+        println!("send errors:         {}", self.send_errors.load(Ordering::SeqCst));
+        println!("messages per second: {:.2}", sent as f64 / elapsed);
+        if self.round_trip.count() > 0 {
+            println!("round trip p50/p90/p99: {:.2}ms / {:.2}ms / {:.2}ms", ms(0.50), ms(0.90), ms(0.99));
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WsReport {
+    pub duration_secs: f64,
+    pub connects: u64,
+    pub connect_failures: u64,
+    pub dropped: u64,
+    pub sent: u64,
+    pub received: u64,
+    pub send_errors: u64,
+    pub messages_per_second: f64,
+    pub round_trip_ms: Option<LatencySummary>,
+    pub config: WsConfig,
+}
+
+impl WsReport {
+    pub fn new(stats: &WsStats, config: &WsConfig) -> Self {
+        let duration_secs = stats.start_time.elapsed().as_secs_f64();
+        let sent = stats.sent.load(Ordering::SeqCst);
+        WsReport {
+            duration_secs,
+            connects: stats.connects.load(Ordering::SeqCst),
+            connect_failures: stats.connect_failures.load(Ordering::SeqCst),
+            dropped: stats.dropped.load(Ordering::SeqCst),
+            sent,
+            received: stats.received.load(Ordering::SeqCst),
+            send_errors: stats.send_errors.load(Ordering::SeqCst),
+            messages_per_second: sent as f64 / duration_secs,
+            round_trip_ms: (stats.round_trip.count() > 0).then(|| LatencySummary::new(&stats.round_trip)),
+            config: config.clone(),
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+struct Shared {
+    config: WsConfig,
+    generator: Generator,
+    stats: Arc<WsStats>,
+    sequence: AtomicU64,
+    deadline: Instant,
+    stop: Arc<AtomicBool>,
+}
+
+impl Shared {
+    fn done(&self) -> bool {
+        self.stop.load(Ordering::SeqCst) || Instant::now() >= self.deadline
+    }
+}
+
+enum SessionEnd {
+    Finished,
+    Dropped,
+}
+
+// keeps `connections` sockets open for the whole run, redialing dropped ones
+pub async fn run(
+    config: WsConfig,
+    generator: Generator,
+    stats: Arc<WsStats>,
+    stop: Arc<AtomicBool>,
+) -> Result<WsReport, Box<dyn std::error::Error>> {
+    if !config.rate.is_finite() || config.rate <= 0.0 {
+        return Err("--rate must be positive".into());
+    }
+    generator.check_columns([])?;
+    // a rate past a message per nanosecond per connection rounds down to no
+    // interval at all, which tokio's interval doesn't take
+    let interval = Duration::from_secs_f64(config.connections as f64 / config.rate).max(Duration::from_nanos(1));
+    let shared = Arc::new(Shared {
+        deadline: stats.start_time + Duration::from_secs(config.duration),
+        config: config.clone(),
+        generator,
+        stats: stats.clone(),
+        sequence: AtomicU64::new(0),
+        stop,
+    });
+
+    let handles: Vec<_> = (0..config.connections)
+        .map(|_| tokio::spawn(connection(shared.clone(), interval)))
+        .collect();
+    for handle in handles {
+        handle.await?;
+    }
+    Ok(WsReport::new(&stats, &config))
+}
+
+async fn connection(shared: Arc<Shared>, interval: Duration) {
+    let stats = &shared.stats;
+    while !shared.done() {
+        let socket = match connect(&shared.config).await.map_err(|e| e.to_string()) {
+            Ok(socket) => socket,
+            Err(e) => {
+                stats.connect_failures.fetch_add(1, Ordering::SeqCst);
+                warn!(url = %shared.config.endpoint, error = %e, "connect failed");
+                sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        stats.connects.fetch_add(1, Ordering::SeqCst);
+
+        if let SessionEnd::Dropped = session(&shared, socket, interval).await {
+            stats.dropped.fetch_add(1, Ordering::SeqCst);
+            if !shared.done() {
+                sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+async fn connect(
+    config: &WsConfig,
+) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, Box<dyn std::error::Error>> {
+    let mut request = config.endpoint.as_str().into_client_request()?;
+    for header in &config.headers {
+        request
+            .headers_mut()
+            .insert(HeaderName::from_bytes(header.name.as_bytes())?, HeaderValue::from_str(&header.value)?);
+    }
+    let (socket, _) = timeout(config.connect_timeout, tokio_tungstenite::connect_async(request))
+        .await
+        .map_err(|_| "connect timed out")??;
+    Ok(socket)
+}
+
+async fn session(
+    shared: &Shared,
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    interval: Duration,
+) -> SessionEnd {
+    let stats = &shared.stats;
+    let (mut sink, mut stream) = socket.split();
+    let mut ticker = tokio::time::interval(interval);
+    let mut pending: VecDeque<Instant> = VecDeque::new();
+
+    loop {
+        if shared.stop.load(Ordering::SeqCst) {
+            let _ = sink.send(Message::Close(None)).await;
+            return SessionEnd::Finished;
+        }
+
+        tokio::select! {
+            _ = sleep_until(shared.deadline.into()) => {
+                let _ = sink.send(Message::Close(None)).await;
+                return SessionEnd::Finished;
+            }
+            _ = ticker.tick() => {
+                let sequence = shared.sequence.fetch_add(1, Ordering::SeqCst);
+                let payload = shared.generator.generate(sequence).to_string();
+                match sink.send(Message::text(payload)).await {
+                    Ok(()) => {
+                        stats.sent.fetch_add(1, Ordering::SeqCst);
+                        if pending.len() == MAX_PENDING_ECHOES {
+                            pending.pop_front();
+                        }
+                        pending.push_back(Instant::now());
+                    }
+                    Err(_) => {
+                        stats.send_errors.fetch_add(1, Ordering::SeqCst);
+                        return SessionEnd::Dropped;
+                    }
+                }
+            }
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(_) | Message::Binary(_))) => {
+                    stats.received.fetch_add(1, Ordering::SeqCst);
+                    if let Some(sent_at) = pending.pop_front() {
+                        stats.round_trip.record(sent_at.elapsed().as_micros() as u64);
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return SessionEnd::Dropped,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}